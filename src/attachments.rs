@@ -0,0 +1,423 @@
+//! Attachment handling for user messages
+//! Resolves `@image:path` references into multimodal content parts and `@path` references into inlined file context
+
+use crate::openrouter::{ContentPart, ImageUrl};
+use base64::Engine as _;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Marker after the file trigger that makes a reference an image attachment, as in `@image:shot.png`
+pub const IMAGE_MARKER: &str = "image:";
+
+/// Largest image accepted as an attachment
+pub const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Largest slice of a referenced file inlined into a message; longer files are truncated
+pub const MAX_FILE_CONTEXT_BYTES: usize = 100 * 1024;
+
+/// Paths referenced with `@path` in a user message, excluding `@image:` attachments and escaped `@@` words.
+/// `trigger` is the configured file trigger that stands in for `@`.
+pub fn extract_file_references(input: &str, trigger: char) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+
+    for word in input.split_whitespace() {
+        let Some(path) = word.strip_prefix(trigger) else {
+            continue;
+        };
+        if path.starts_with(trigger) || path.starts_with(IMAGE_MARKER) {
+            continue;
+        }
+        // Allow references at the end of a sentence or inside quotes
+        let path = path.trim_end_matches([',', '.', ';', ':', '!', '?', ')', '"', '\'']);
+        if !path.is_empty() && !paths.iter().any(|existing| existing == path) {
+            paths.push(path.to_string());
+        }
+    }
+
+    paths
+}
+
+/// Read a referenced text file relative to the working directory as a fenced context block.
+/// Returns `Ok(None)` when the path does not name a file, since `@` may also start a mention.
+/// Files outside the working directory, including through `..` or symlinks, and binary files are errors.
+pub fn load_file_context(working_dir: &str, path: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let full_path = Path::new(working_dir).join(path);
+    if !full_path.is_file() {
+        return Ok(None);
+    }
+    let resolved = fs::canonicalize(&full_path).map_err(|e| format!("Could not resolve {}: {}", full_path.display(), e))?;
+    let root = fs::canonicalize(working_dir).map_err(|e| format!("Could not resolve {}: {}", working_dir, e))?;
+    if !resolved.starts_with(&root) {
+        return Err(format!("{} is outside the working directory", path).into());
+    }
+
+    let bytes = fs::read(&resolved).map_err(|e| format!("Could not read {}: {}", full_path.display(), e))?;
+    let mut content = String::from_utf8(bytes).map_err(|_| format!("{} is not a text file", path))?;
+
+    let mut note = String::new();
+    if content.len() > MAX_FILE_CONTEXT_BYTES {
+        let mut end = MAX_FILE_CONTEXT_BYTES;
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        note = format!(" (truncated to the first {} of {} bytes)", end, content.len());
+        content.truncate(end);
+    }
+
+    Ok(Some(format!("File: {}{}\n```\n{}\n```", path, note, content.trim_end())))
+}
+
+/// Combined size of the files preloaded with `preferences.context_files` or `--context-files`
+pub const MAX_CONTEXT_FILES_BYTES: usize = 200 * 1024;
+
+/// Standing project context: one message body holding every listed file that fits the budget, and a
+/// warning for each file that is missing, unreadable or over it. Each file is also capped like `@path`.
+pub fn load_context_files(working_dir: &str, paths: &[String]) -> (Option<String>, Vec<String>) {
+    let mut blocks = Vec::new();
+    let mut warnings = Vec::new();
+    let mut total = 0;
+    for path in paths {
+        match load_file_context(working_dir, path) {
+            Ok(Some(block)) if total + block.len() > MAX_CONTEXT_FILES_BYTES => warnings.push(format!(
+                "Context file {} skipped: context files are limited to {} KB in total",
+                path,
+                MAX_CONTEXT_FILES_BYTES / 1024
+            )),
+            Ok(Some(block)) => {
+                total += block.len();
+                blocks.push(block);
+            }
+            Ok(None) => warnings.push(format!("Context file {} not found", path)),
+            Err(e) => warnings.push(format!("Context file {} skipped: {}", path, e)),
+        }
+    }
+
+    let message = (!blocks.is_empty()).then(|| {
+        format!("Project context files, kept in context for the whole session:\n\n{}", blocks.join("\n\n"))
+    });
+    (message, warnings)
+}
+
+/// Split a user message into its text and the image paths it references with the file trigger
+pub fn extract_image_references(input: &str, trigger: char) -> (String, Vec<String>) {
+    let prefix = format!("{}{}", trigger, IMAGE_MARKER);
+    let mut text_words = Vec::new();
+    let mut image_paths = Vec::new();
+
+    for word in input.split_whitespace() {
+        match word.strip_prefix(prefix.as_str()) {
+            Some(path) if !path.is_empty() => image_paths.push(path.to_string()),
+            _ => text_words.push(word),
+        }
+    }
+
+    if image_paths.is_empty() {
+        // Leave the message untouched when there is nothing to attach
+        return (input.to_string(), image_paths);
+    }
+
+    (text_words.join(" "), image_paths)
+}
+
+/// MIME type for a supported image file extension
+pub fn image_mime_type(path: &str) -> Option<&'static str> {
+    let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Read an image relative to the working directory and encode it as a data URL part
+pub fn load_image_part(working_dir: &str, path: &str) -> Result<ContentPart, Box<dyn std::error::Error>> {
+    let mime_type = image_mime_type(path)
+        .ok_or_else(|| format!("Unsupported image type: {} (use png, jpg, gif or webp)", path))?;
+
+    let full_path = Path::new(working_dir).join(path);
+    let size = fs::metadata(&full_path)
+        .map_err(|e| format!("Could not read image {}: {}", full_path.display(), e))?
+        .len();
+    if size > MAX_IMAGE_BYTES {
+        return Err(format!("Image {} is too large ({} bytes, limit {})", path, size, MAX_IMAGE_BYTES).into());
+    }
+
+    let bytes = fs::read(&full_path)?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+
+    Ok(ContentPart::ImageUrl {
+        image_url: ImageUrl {
+            url: format!("data:{};base64,{}", mime_type, encoded),
+        },
+    })
+}
+
+/// How long a directory listing is reused between keystrokes before it is read again
+const LISTING_TTL: Duration = Duration::from_secs(2);
+
+/// `(is_dir, name)` pairs sorted directories first, then by name
+type Listing = Vec<(bool, String)>;
+
+/// Directory listings shared by completions, with the time each was read
+fn listing_cache() -> &'static Mutex<HashMap<PathBuf, (Instant, Listing)>> {
+    static LISTINGS: OnceLock<Mutex<HashMap<PathBuf, (Instant, Listing)>>> = OnceLock::new();
+    LISTINGS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Entries of `dir`, from the cache when they were read less than `ttl` ago
+fn list_directory(dir: &Path, ttl: Duration) -> Option<Listing> {
+    let mut cache = listing_cache().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some((read_at, entries)) = cache.get(dir) {
+        if read_at.elapsed() < ttl {
+            return Some(entries.clone());
+        }
+    }
+
+    let mut entries: Listing = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            // file_type avoids a stat per entry; only symlinks need following to tell directories apart
+            let file_type = entry.file_type().ok()?;
+            let is_dir = file_type.is_dir() || (file_type.is_symlink() && entry.path().is_dir());
+            Some((is_dir, entry.file_name().to_string_lossy().to_string()))
+        })
+        .collect();
+    entries.sort_by(|(a_is_dir, a), (b_is_dir, b)| b_is_dir.cmp(a_is_dir).then_with(|| a.cmp(b)));
+
+    cache.retain(|_, (read_at, _)| read_at.elapsed() < LISTING_TTL);
+    cache.insert(dir.to_path_buf(), (Instant::now(), entries.clone()));
+    Some(entries)
+}
+
+/// Completions for a partially typed `@path`: entries of the directory being typed whose names start
+/// with the last segment, directories first and with a trailing `/`. Hidden entries are only offered
+/// once the typed name starts with `.`. At most `max_entries` are returned (0 = unlimited), and listings
+/// are reused for a moment so typing in a huge directory does not re-read it on every keystroke.
+pub fn complete_file_reference(working_dir: &str, partial_path: &str, max_entries: usize) -> Vec<String> {
+    let partial_path = clean_typed_path(partial_path);
+    let (dir, name_prefix) = match partial_path.rsplit_once('/') {
+        Some(("", name_prefix)) => ("/", name_prefix),
+        Some((dir, name_prefix)) => (dir, name_prefix),
+        None => ("", partial_path.as_str()),
+    };
+    let include_hidden = name_prefix.starts_with('.');
+
+    let Some(entries) = list_directory(&Path::new(working_dir).join(if dir.is_empty() { "." } else { dir }), LISTING_TTL) else {
+        return Vec::new();
+    };
+
+    let limit = if max_entries == 0 { usize::MAX } else { max_entries };
+    entries
+        .into_iter()
+        .filter(|(_, name)| name.starts_with(name_prefix) && (include_hidden || !name.starts_with('.')))
+        .take(limit)
+        .map(|(is_dir, name)| {
+            let path = match dir {
+                "" => name,
+                "/" => format!("/{}", name),
+                dir => format!("{}/{}", dir, name),
+            };
+            if is_dir { format!("{}/", path) } else { path }
+        })
+        .collect()
+}
+
+/// Collapse repeated slashes and `.` directories so completions never read `src//main.rs` or `src/./x`.
+/// The last segment is the name being typed and is kept as is, so `.` still starts a hidden name.
+fn clean_typed_path(path: &str) -> String {
+    let Some((dirs, name)) = path.rsplit_once('/') else {
+        return path.to_string();
+    };
+
+    let mut segments: Vec<&str> = dirs.split('/').filter(|segment| !segment.is_empty() && *segment != ".").collect();
+    segments.push(name);
+    let cleaned = segments.join("/");
+    if path.starts_with('/') {
+        format!("/{}", cleaned)
+    } else {
+        cleaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract_image_references() {
+        let (text, images) = extract_image_references("what is wrong in @image:shot.png and @image:ui/a.jpg", '@');
+        assert_eq!(text, "what is wrong in and");
+        assert_eq!(images, vec!["shot.png".to_string(), "ui/a.jpg".to_string()]);
+
+        let (text, images) = extract_image_references("edit  @src/main.rs please", '@');
+        assert_eq!(text, "edit  @src/main.rs please");
+        assert!(images.is_empty());
+
+        let (text, images) = extract_image_references("compare #image:a.png with @@image:b.png", '#');
+        assert_eq!(text, "compare with @@image:b.png");
+        assert_eq!(images, vec!["a.png".to_string()]);
+    }
+
+    #[test]
+    fn test_context_files_respect_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let working_dir = temp_dir.path().to_string_lossy().to_string();
+        fs::write(temp_dir.path().join("CONVENTIONS.md"), "Use snake_case\n").unwrap();
+        fs::write(temp_dir.path().join("a.md"), "a".repeat(MAX_FILE_CONTEXT_BYTES)).unwrap();
+        fs::write(temp_dir.path().join("b.md"), "b".repeat(MAX_FILE_CONTEXT_BYTES)).unwrap();
+
+        let paths: Vec<String> = ["CONVENTIONS.md", "missing.md", "a.md", "b.md"].iter().map(|p| p.to_string()).collect();
+        let (message, warnings) = load_context_files(&working_dir, &paths);
+        let message = message.unwrap();
+        assert!(message.contains("File: CONVENTIONS.md\n```\nUse snake_case\n```"));
+        assert!(message.contains("File: a.md"));
+        assert!(!message.contains("File: b.md"));
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("missing.md not found"));
+        assert!(warnings[1].contains("b.md skipped"));
+
+        assert_eq!(load_context_files(&working_dir, &[]), (None, Vec::new()));
+    }
+
+    #[test]
+    fn test_file_references_are_inlined() {
+        assert_eq!(
+            extract_file_references("compare @src/a.rs, @src/b.rs and @src/a.rs with @image:ui.png", '@'),
+            vec!["src/a.rs".to_string(), "src/b.rs".to_string()]
+        );
+        assert_eq!(extract_file_references("ask @@team about #notes.md", '#'), vec!["notes.md".to_string()]);
+        assert!(extract_file_references("ask @@team", '@').is_empty());
+
+        let temp_dir = TempDir::new().unwrap();
+        let working_dir = temp_dir.path().to_string_lossy().to_string();
+        fs::write(temp_dir.path().join("notes.md"), "hello\n").unwrap();
+        fs::write(temp_dir.path().join("big.txt"), "x".repeat(MAX_FILE_CONTEXT_BYTES + 10)).unwrap();
+        fs::write(temp_dir.path().join("blob.bin"), [0xff, 0xfe, 0x00]).unwrap();
+
+        assert_eq!(load_file_context(&working_dir, "notes.md").unwrap().unwrap(), "File: notes.md\n```\nhello\n```");
+        assert!(load_file_context(&working_dir, "big.txt").unwrap().unwrap().contains("truncated"));
+        assert!(load_file_context(&working_dir, "blob.bin").is_err());
+        assert!(load_file_context(&working_dir, "someone").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_file_references_stay_inside_the_working_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(temp_dir.path().join("secret.txt"), "outside\n").unwrap();
+        fs::write(project.join("notes.md"), "inside\n").unwrap();
+        let working_dir = project.to_string_lossy().to_string();
+
+        let outside = temp_dir.path().join("secret.txt").to_string_lossy().to_string();
+        for path in ["../secret.txt", outside.as_str(), "./../project/../secret.txt"] {
+            let error = load_file_context(&working_dir, path).unwrap_err().to_string();
+            assert!(error.contains("outside the working directory"), "{}: {}", path, error);
+        }
+        assert!(load_file_context(&working_dir, "./../project/notes.md").unwrap().unwrap().contains("inside"));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp_dir.path().join("secret.txt"), project.join("link.txt")).unwrap();
+            assert!(load_file_context(&working_dir, "link.txt").is_err());
+        }
+    }
+
+    #[test]
+    fn test_load_image_part_encodes_data_url() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("pixel.png"), [0x89, b'P', b'N', b'G']).unwrap();
+
+        let part = load_image_part(&temp_dir.path().to_string_lossy(), "pixel.png").unwrap();
+        match part {
+            ContentPart::ImageUrl { image_url } => assert_eq!(image_url.url, "data:image/png;base64,iVBORw=="),
+            _ => panic!("Expected image part"),
+        }
+
+        assert!(load_image_part(&temp_dir.path().to_string_lossy(), "notes.txt").is_err());
+    }
+
+    #[test]
+    fn test_file_reference_completion() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("src/bin")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        fs::write(dir.path().join("src/.hidden"), "").unwrap();
+        fs::write(dir.path().join(".env"), "").unwrap();
+        fs::write(dir.path().join("README.md"), "").unwrap();
+        let root = dir.path().to_str().unwrap();
+
+        assert_eq!(complete_file_reference(root, "", 0), vec!["src/", "README.md"]);
+        assert_eq!(complete_file_reference(root, "sr", 0), vec!["src/"]);
+        assert_eq!(complete_file_reference(root, "src/", 0), vec!["src/bin/", "src/main.rs"]);
+        assert_eq!(complete_file_reference(root, "src/ma", 0), vec!["src/main.rs"]);
+
+        // Repeated slashes and `.` directories never leak into completions
+        assert_eq!(complete_file_reference(root, "src//", 0), vec!["src/bin/", "src/main.rs"]);
+        assert_eq!(complete_file_reference(root, "src/./m", 0), vec!["src/main.rs"]);
+        assert_eq!(complete_file_reference(root, "./src/b", 0), vec!["src/bin/"]);
+
+        // Hidden entries appear only once the typed name starts with `.`
+        assert_eq!(complete_file_reference(root, ".", 0), vec![".env"]);
+        assert_eq!(complete_file_reference(root, "src/.", 0), vec!["src/.hidden"]);
+        assert!(complete_file_reference(root, "missing/", 0).is_empty());
+    }
+
+    #[test]
+    fn test_file_reference_completion_caps_large_directories() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("huge")).unwrap();
+        for i in 0..60 {
+            fs::write(dir.path().join(format!("huge/file{:02}.txt", i)), "").unwrap();
+        }
+        let root = dir.path().to_str().unwrap();
+
+        let first = complete_file_reference(root, "huge/", 50);
+        assert_eq!(first.len(), 50);
+        assert_eq!(first[0], "huge/file00.txt");
+        assert_eq!(complete_file_reference(root, "huge/file5", 50).len(), 10);
+        assert_eq!(complete_file_reference(root, "huge/", 0).len(), 60);
+
+        // Further keystrokes reuse the listing until it goes stale
+        let huge = dir.path().join("huge");
+        fs::write(huge.join("file99.txt"), "").unwrap();
+        let hour = Duration::from_secs(3600);
+        assert_eq!(list_directory(&huge, hour).unwrap().len(), 60);
+        assert_eq!(list_directory(&huge, Duration::ZERO).unwrap().len(), 61);
+        assert_eq!(list_directory(&huge, hour).unwrap().len(), 61);
+    }
+
+    #[test]
+    fn test_file_reference_completion_on_a_huge_directory() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("huge")).unwrap();
+        for i in 0..20_000 {
+            fs::write(dir.path().join(format!("huge/file{:05}.txt", i)), "").unwrap();
+        }
+        let root = dir.path().to_str().unwrap();
+
+        // Reading 20,000 entries takes about 125ms in a debug build (24ms in release); each keystroke after it
+        // filters the cached listing in about 7ms (2ms in release)
+        let first = complete_file_reference(root, "huge/", 50);
+        assert_eq!(first.len(), 50);
+        assert_eq!(first[0], "huge/file00000.txt");
+
+        // The next keystroke filters the cached listing instead of reading the directory again
+        let huge = dir.path().join("huge");
+        fs::write(huge.join("file20000.txt"), "").unwrap();
+        assert!(complete_file_reference(root, "huge/file2", 50).is_empty());
+
+        for partial in ["huge/f", "huge/fi", "huge/fil", "huge/file", "huge/file1", "huge/file19", "huge/file199"] {
+            assert_eq!(complete_file_reference(root, partial, 50).len(), 50);
+        }
+        assert_eq!(complete_file_reference(root, "huge/file1999", 50).len(), 10);
+        assert_eq!(list_directory(&huge, Duration::ZERO).unwrap().len(), 20_001);
+    }
+}
@@ -0,0 +1,70 @@
+//! Periodic background saving of session state and the story file
+//! The main loop publishes snapshots; a background task writes the latest one on each interval
+
+use crate::session::{SessionRecord, SessionStore};
+use crate::story::StoryLogger;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Session state captured at the end of a turn
+pub struct AutosaveSnapshot {
+    pub session: SessionRecord,
+    pub story: StoryLogger,
+}
+
+impl AutosaveSnapshot {
+    pub fn write(&self) -> Result<(), Box<dyn std::error::Error>> {
+        SessionStore::new()?.save(&self.session)?;
+        self.story.write_story_file()?;
+        Ok(())
+    }
+}
+
+/// Background saver; writes happen while holding the pending-snapshot lock so they never overlap a publish
+pub struct Autosaver {
+    pending: Arc<Mutex<Option<AutosaveSnapshot>>>,
+    task: JoinHandle<()>,
+}
+
+impl Autosaver {
+    pub fn start(interval: Duration) -> Self {
+        let pending: Arc<Mutex<Option<AutosaveSnapshot>>> = Arc::new(Mutex::new(None));
+
+        let shared = Arc::clone(&pending);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately; there is nothing to save yet
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = write_pending(&shared) {
+                    eprintln!("Warning: Autosave failed: {}", e);
+                }
+            }
+        });
+
+        Self { pending, task }
+    }
+
+    /// Replace the snapshot to be written on the next interval
+    pub fn publish(&self, snapshot: AutosaveSnapshot) {
+        if let Ok(mut pending) = self.pending.lock() {
+            *pending = Some(snapshot);
+        }
+    }
+}
+
+impl Drop for Autosaver {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn write_pending(pending: &Mutex<Option<AutosaveSnapshot>>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut pending = pending.lock().map_err(|_| "Autosave state is poisoned")?;
+    if let Some(snapshot) = pending.take() {
+        snapshot.write()?;
+    }
+    Ok(())
+}
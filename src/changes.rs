@@ -0,0 +1,192 @@
+//! Session change tracking
+//! Records which files the tools created, modified or deleted so they can be reviewed before committing
+
+use crate::tools::git;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// How a file differs from its state when the session started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn symbol(&self) -> char {
+        match self {
+            ChangeKind::Created => '+',
+            ChangeKind::Modified => '~',
+            ChangeKind::Deleted => '-',
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+
+    /// Change made by a successful file tool call, given whether its target existed beforehand
+    pub fn from_tool(tool_name: &str, existed_before: bool) -> Option<ChangeKind> {
+        match tool_name {
+            "create_file" | "write_file" if existed_before => Some(ChangeKind::Modified),
+            "create_file" | "write_file" | "create_from_template" => Some(ChangeKind::Created),
+            "delete_file" => Some(ChangeKind::Deleted),
+            _ => None,
+        }
+    }
+}
+
+/// Net file changes of the session, in the order files were first touched
+#[derive(Debug, Default)]
+pub struct ChangeTracker {
+    changes: Vec<(String, ChangeKind)>,
+    /// Most recently created or modified file that still exists
+    last_edited: Option<String>,
+    /// Content of each file as the model last wrote it, by absolute path, to diff its next rewrite against
+    written: HashMap<PathBuf, String>,
+}
+
+impl ChangeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold a new change into the file's net change since the session started
+    pub fn record(&mut self, path: &str, kind: ChangeKind) {
+        if kind == ChangeKind::Deleted {
+            if self.last_edited.as_deref() == Some(path) {
+                self.last_edited = None;
+            }
+        } else {
+            self.last_edited = Some(path.to_string());
+        }
+
+        let Some(index) = self.changes.iter().position(|(changed, _)| changed == path) else {
+            self.changes.push((path.to_string(), kind));
+            return;
+        };
+
+        let net = match (self.changes[index].1, kind) {
+            // A file created and removed again within the session leaves no trace
+            (ChangeKind::Created, ChangeKind::Deleted) => {
+                self.changes.remove(index);
+                return;
+            }
+            (ChangeKind::Created, _) => ChangeKind::Created,
+            (ChangeKind::Deleted, ChangeKind::Created | ChangeKind::Modified) => ChangeKind::Modified,
+            (_, kind) => kind,
+        };
+        self.changes[index].1 = net;
+    }
+
+    /// Make recorded paths absolute so they stay correct once the working directory changes
+    pub fn anchor_to(&mut self, working_dir: &str) {
+        let anchor = |path: &mut String| *path = Path::new(working_dir).join(&*path).to_string_lossy().to_string();
+        for (path, _) in &mut self.changes {
+            anchor(path);
+        }
+        if let Some(path) = &mut self.last_edited {
+            anchor(path);
+        }
+    }
+
+    /// Keep what the model just wrote to `path` and return what it wrote there before, if anything.
+    /// `None` content (unreadable or too large to keep) leaves nothing to compare the next write against.
+    pub fn remember_write(&mut self, path: PathBuf, content: Option<String>) -> Option<String> {
+        match content {
+            Some(content) => self.written.insert(path, content),
+            None => {
+                self.written.remove(&path);
+                None
+            }
+        }
+    }
+
+    pub fn changes(&self) -> &[(String, ChangeKind)] {
+        &self.changes
+    }
+
+    pub fn last_edited(&self) -> Option<&str> {
+        self.last_edited.as_deref()
+    }
+
+    /// List every change, optionally followed by its diff against git HEAD
+    pub fn render(&self, working_dir: &str, with_diff: bool) -> String {
+        if self.changes.is_empty() {
+            return "📝 No files changed in this session".to_string();
+        }
+
+        let mut output = format!("📝 {} files changed in this session\n", self.changes.len());
+        for (path, kind) in &self.changes {
+            output.push_str(&format!("  {} {} ({})\n", kind.symbol(), path, kind.label()));
+        }
+
+        if with_diff {
+            for (path, _) in &self.changes {
+                match git::diff_against_head(working_dir, path) {
+                    Some(diff) if !diff.trim().is_empty() => output.push_str(&format!("\n{}", diff)),
+                    Some(_) => output.push_str(&format!("\n{}: no difference from HEAD\n", path)),
+                    None => {
+                        output.push_str("\n💡 Not a git repository, diffs are unavailable\n");
+                        break;
+                    }
+                }
+            }
+        }
+
+        output.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_fold_into_net_change() {
+        let mut tracker = ChangeTracker::new();
+        tracker.record("new.rs", ChangeKind::Created);
+        tracker.record("new.rs", ChangeKind::Modified);
+        tracker.record("lib.rs", ChangeKind::Modified);
+        tracker.record("old.rs", ChangeKind::Deleted);
+        tracker.record("old.rs", ChangeKind::Created);
+        tracker.record("tmp.rs", ChangeKind::Created);
+        tracker.record("tmp.rs", ChangeKind::Deleted);
+
+        assert_eq!(
+            tracker.changes(),
+            &[
+                ("new.rs".to_string(), ChangeKind::Created),
+                ("lib.rs".to_string(), ChangeKind::Modified),
+                ("old.rs".to_string(), ChangeKind::Modified),
+            ]
+        );
+        assert_eq!(tracker.last_edited(), None);
+        tracker.record("lib.rs", ChangeKind::Modified);
+        assert_eq!(tracker.last_edited(), Some("lib.rs"));
+        assert_eq!(ChangeKind::from_tool("write_file", false), Some(ChangeKind::Created));
+        assert_eq!(ChangeKind::from_tool("read_file", true), None);
+    }
+
+    #[test]
+    fn test_anchor_to_keeps_absolute_paths() {
+        let mut tracker = ChangeTracker::new();
+        tracker.record("src/lib.rs", ChangeKind::Modified);
+        tracker.record("/etc/hosts", ChangeKind::Modified);
+        tracker.anchor_to("/work/project");
+
+        assert_eq!(
+            tracker.changes(),
+            &[
+                ("/work/project/src/lib.rs".to_string(), ChangeKind::Modified),
+                ("/etc/hosts".to_string(), ChangeKind::Modified),
+            ]
+        );
+        assert_eq!(tracker.last_edited(), Some("/etc/hosts"));
+    }
+}
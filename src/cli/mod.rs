@@ -16,9 +16,49 @@ pub struct Cli {
     #[arg(long)]
     pub model: Option<String>,
     
-    /// Enable verbose output
-    #[arg(long, short)]
-    pub verbose: bool,
+    /// Cap tokens per response for this run, overriding openrouter.max_tokens
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_tokens: Option<u32>,
+
+    /// Sampling seed for this run, overriding openrouter.seed
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+
+    /// Alternative replies to request per chat turn for this run, overriding openrouter.n
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub choices: Option<u32>,
+
+    /// End the whole session after this many seconds, saving the story so far and exiting with status 124
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+
+    /// More detail, repeatable: -v tool summaries, -vv full tool results, -vvv raw API requests and responses
+    #[arg(long, short, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Append an instruction to the system prompt for this run (repeatable)
+    #[arg(long, value_name = "TEXT")]
+    pub append_system: Vec<String>,
+
+    /// Give the model these files at session start and after /clear, in addition to preferences.context_files
+    #[arg(long, value_name = "PATH", value_delimiter = ',')]
+    pub context_files: Vec<String>,
+
+    /// Start without first checking that the API is reachable and accepts the key (offline or mock servers)
+    #[arg(long)]
+    pub skip_preflight: bool,
+
+    /// Keep the session story in memory only instead of writing story.md
+    #[arg(long)]
+    pub no_story: bool,
+
+    /// Use this config file instead of the default location
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<String>,
+
+    /// Append debug diagnostics to this file (filter with RUST_LOG)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -28,6 +68,61 @@ pub enum Commands {
         #[command(subcommand)]
         config_command: ConfigCommand,
     },
+    #[command(about = "Set up the API key, default model and project directory, then start chatting")]
+    Quickstart,
+    #[command(about = "Show version information")]
+    Version {
+        /// Also report build details, config location and model defaults
+        #[arg(long, short)]
+        verbose: bool,
+    },
+    #[command(about = "Re-run a recorded session's tool calls without the model and report divergences")]
+    Replay {
+        /// Session record (JSON), the story.md it wrote, or a session ID
+        source: String,
+        /// Directory to replay into; created if missing, and refused unless empty
+        #[arg(long)]
+        dir: String,
+    },
+    #[command(about = "Browse saved sessions")]
+    Sessions {
+        #[command(subcommand)]
+        sessions_command: SessionsCommand,
+    },
+    #[command(about = "Convert a recorded session into OpenAI or Anthropic chat JSON")]
+    Export {
+        /// Session record (JSON), the story.md it wrote, or a session ID
+        source: String,
+        /// Target message schema: openai or anthropic
+        #[arg(long, default_value = "openai")]
+        format: String,
+        /// Write to this file instead of stdout
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<String>,
+    },
+    #[command(hide = true, about = "Print a decomposition JSON schema example for debugging")]
+    Schema {
+        /// Schema name: task_decomposition, plan_action_decomposition or nested_plan
+        name: String,
+        /// Check that a saved model response parses against the schema
+        #[arg(long, value_name = "FILE")]
+        validate: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SessionsCommand {
+    #[command(about = "List saved sessions for a directory, newest first")]
+    List {
+        /// Directory whose sessions to list (default: the current directory)
+        #[arg(long)]
+        dir: Option<String>,
+    },
+    #[command(about = "Show the prompts, replies and tool calls of a session")]
+    Show {
+        /// Session ID, or enough of its start to be unique
+        id: String,
+    },
 }
 
 #[derive(Subcommand)]
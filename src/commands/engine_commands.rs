@@ -1,5 +1,7 @@
 use crate::engine::LooEngine;
-use crate::commands::registry::CommandResult;
+use crate::commands::registry::{parse_list_models_args, CommandResult};
+use crate::execution_stack::ExecutionStack;
+use crate::plan_display::render_plan;
 
 /// Clear conversation context, keeping only the system message
 pub async fn handle_clear_command(engine: &mut LooEngine) -> CommandResult {
@@ -18,21 +20,22 @@ pub async fn handle_clear_command(engine: &mut LooEngine) -> CommandResult {
 
 /// List available models with optional filtering
 pub async fn handle_list_models_command(engine: &LooEngine, args: &str) -> CommandResult {
-    let search_term = args.trim();
+    let query = parse_list_models_args(args);
+    let scope = query.scope();
     
-    match engine.openrouter_client.list_models(search_term).await {
+    match engine.openrouter_client.list_models(&query.search_term, query.filter, query.refresh).await {
         Ok(models) => {
             if models.is_empty() {
-                if search_term.is_empty() {
+                if scope.is_empty() {
                     Ok("📋 No models available".to_string())
                 } else {
-                    Ok(format!("📋 No models found matching '{}'", search_term))
+                    Ok(format!("📋 No models found{}", scope))
                 }
             } else {
-                let mut result = if search_term.is_empty() {
+                let mut result = if scope.is_empty() {
                     format!("📋 Available models ({}):\n", models.len())
                 } else {
-                    format!("📋 Models matching '{}' ({}):\n", search_term, models.len())
+                    format!("📋 Models{} ({}):\n", scope, models.len())
                 };
                 
                 let max_items = std::cmp::min(models.len(), 10);
@@ -67,7 +70,7 @@ pub async fn handle_model_command(engine: &mut LooEngine, args: &str) -> Command
     // Update the OpenRouter client with new config
     match crate::openrouter::OpenRouterClient::new(engine.config.clone()).await {
         Ok(new_client) => {
-            engine.openrouter_client = new_client;
+            engine.openrouter_client = new_client.with_session_tokens_of(&engine.openrouter_client);
             Ok(format!("✅ Model changed from '{}' to '{}'", old_model, new_model))
         }
         Err(e) => {
@@ -89,15 +92,20 @@ pub async fn handle_plan_command(engine: &mut LooEngine, request: &str) -> Comma
     use crate::commands::PlanCommand;
     let plan_cmd = PlanCommand::new();
     
-    match plan_cmd.execute(request.trim()).await {
-        Ok(result) => {
-            // Display the generated plan
-            println!("{}", result);
-            
+    match plan_cmd.generate(request.trim()).await {
+        Ok(response) => {
             // Also try to parse and push to execution stack if possible
-            match plan_cmd.parse_plan_json(&result) {
-                Ok(action_plan) => {
+            match plan_cmd.parse_plan_json(&response) {
+                Ok(mut action_plan) => {
+                    println!("🎯 Generated Action Plan:\n\n{}", action_plan);
+
+                    // Nothing touches the files until the user has approved the plan
+                    if !engine.confirm_plan(&mut action_plan) {
+                        return Ok("🚫 Plan cancelled; nothing was added to the execution stack".to_string());
+                    }
+
                     println!("\n📋 Converting plan to execution stack...");
+                    let summary = render_plan(&action_plan);
                     let request_ids = engine.push_action_plan(action_plan);
                     println!("✅ Added {} action items to execution stack", request_ids.len());
                     
@@ -111,7 +119,7 @@ pub async fn handle_plan_command(engine: &mut LooEngine, request: &str) -> Comma
                         println!("💡 Stack execution disabled. Use /stack-execute to run manually.");
                     }
                     
-                    Ok(format!("{}\n\n📊 {}", result, engine.get_stack_status()))
+                    Ok(format!("{}\n\n📊 {}", summary, engine.get_stack_status()))
                 }
                 Err(parse_err) => {
                     // If parsing fails, still push as a user prompt for decomposition
@@ -126,7 +134,7 @@ pub async fn handle_plan_command(engine: &mut LooEngine, request: &str) -> Comma
                         }
                     }
                     
-                    Ok(format!("{}\n\n📊 {}", result, engine.get_stack_status()))
+                    Ok(format!("{}\n\n📊 {}", response, engine.get_stack_status()))
                 }
             }
         }
@@ -187,18 +195,22 @@ pub async fn handle_stack_auto_command(engine: &mut LooEngine, args: &str) -> Co
 
 /// Push a user prompt to the stack
 pub async fn handle_stack_push_command(engine: &mut LooEngine, args: &str) -> CommandResult {
-    if args.trim().is_empty() {
-        return Err("Usage: /stack-push <prompt> [priority]".into());
+    let args = args.trim();
+    if args.is_empty() {
+        return Err("Usage: /stack-push <prompt> [priority 0-9]".into());
     }
-    
-    let parts: Vec<&str> = args.trim().splitn(2, ' ').collect();
-    let prompt = if parts.len() > 1 { parts[0] } else { args.trim() };
-    let priority = if parts.len() > 1 { 
-        parts[1].parse().unwrap_or(3) 
-    } else { 
-        3 
+
+    // A trailing number is the priority; anything else is part of the prompt
+    let (prompt, priority) = match args.rsplit_once(' ') {
+        Some((prompt, last)) if last.chars().all(|c| c.is_ascii_digit()) => {
+            let priority = last.parse::<u8>().ok().filter(|priority| *priority <= ExecutionStack::MAX_PRIORITY).ok_or_else(|| {
+                format!("Priority must be between 0 and {}, got {}", ExecutionStack::MAX_PRIORITY, last)
+            })?;
+            (prompt.trim(), priority)
+        }
+        _ => (args, ExecutionStack::DEFAULT_PRIORITY),
     };
-    
+
     let request_id = engine.push_user_prompt(prompt, priority);
     Ok(format!("📥 Pushed prompt to stack: {} (priority: {})", request_id, priority))
 }
\ No newline at end of file
@@ -1,9 +1,10 @@
 pub mod plan;
 pub mod registry;
 pub mod engine_commands;
+pub mod semantic_commands;
 
 pub use plan::PlanCommand;
 pub use registry::{
     init_command_registry, get_autocomplete_commands, get_command_descriptions, execute_command,
-    command_needs_engine
+    command_needs_engine, register_templates
 };
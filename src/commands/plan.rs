@@ -145,16 +145,21 @@ impl PlanCommand {
         Err("Could not extract valid JSON from response".into())
     }
 
-    pub async fn execute(&self, user_request: &str) -> Result<String, Box<dyn std::error::Error>> {
+    /// Ask the model for a plan and return its raw JSON response
+    pub async fn generate(&self, user_request: &str) -> Result<String, Box<dyn std::error::Error>> {
         // Create engine instance for LLM processing
         let working_dir = std::env::current_dir()?.to_string_lossy().to_string();
-        let mut engine = LooEngine::new(working_dir, None, false).await?;
+        let mut engine = LooEngine::new(working_dir, None, 0).await?;
         
         // Create the full prompt for plan generation
         let full_prompt = self.create_full_prompt(user_request)?;
         
         // Process the prompt through the engine to get LLM response
-        let llm_response = self.process_plan_request(&mut engine, &full_prompt).await?;
+        self.process_plan_request(&mut engine, &full_prompt).await
+    }
+
+    pub async fn execute(&self, user_request: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let llm_response = self.generate(user_request).await?;
         
         // Parse the JSON response and format the plan
         match self.parse_plan_json(&llm_response) {
@@ -181,7 +186,7 @@ impl PlanCommand {
         // Create system message for plan generation
         let system_message = Message {
             role: "system".to_string(),
-            content: "You are an expert code planning assistant. You create detailed, step-by-step action plans for coding tasks. Always respond with valid JSON only, following the exact format specified in the user's prompt.".to_string(),
+            content: "You are an expert code planning assistant. You create detailed, step-by-step action plans for coding tasks. Always respond with valid JSON only, following the exact format specified in the user's prompt.".to_string().into(),
             tool_calls: None,
             tool_call_id: None,
         };
@@ -189,7 +194,7 @@ impl PlanCommand {
         // Create user message with the plan request
         let user_message = Message {
             role: "user".to_string(),
-            content: prompt.to_string(),
+            content: prompt.to_string().into(),
             tool_calls: None,
             tool_call_id: None,
         };
@@ -207,7 +212,7 @@ impl PlanCommand {
         let assistant_message = &response.choices[0].message;
         
         // Return the content from the LLM response
-        Ok(assistant_message.content.clone())
+        Ok(assistant_message.content.text())
     }
 
     pub fn display_plan(&self, json_response: &str) -> Result<String, Box<dyn std::error::Error>> {
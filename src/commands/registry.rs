@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use crate::openrouter::ModelFilter;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::OnceLock;
 
 /// Result type for command execution
@@ -20,12 +21,15 @@ pub struct CommandInfo {
 #[derive(Debug)]
 pub struct CommandRegistry {
     commands: HashMap<String, CommandInfo>,
+    /// User-defined prompt templates from the `[templates]` config section
+    templates: HashMap<String, String>,
 }
 
 impl CommandRegistry {
     fn new() -> Self {
         Self {
             commands: HashMap::new(),
+            templates: HashMap::new(),
         }
     }
 
@@ -40,6 +44,21 @@ impl CommandRegistry {
         self.commands.insert(name.to_string(), command_info);
     }
 
+    /// Register a prompt template as a command; built-in commands cannot be shadowed
+    pub fn register_template(&mut self, name: &str, template: &str) -> Result<(), String> {
+        if self.commands.contains_key(name) && !self.templates.contains_key(name) {
+            return Err(format!("Template '{}' conflicts with a built-in command", name));
+        }
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Template name '{}' must be a single word", name));
+        }
+
+        let description = format!("Template: {}", template);
+        self.register(name, &description, handle_template_command, true);
+        self.templates.insert(name.to_string(), template.to_string());
+        Ok(())
+    }
+
     /// Get all registered commands
     pub fn get_all_commands(&self) -> Vec<&CommandInfo> {
         let mut commands: Vec<&CommandInfo> = self.commands.values().collect();
@@ -59,6 +78,11 @@ impl CommandRegistry {
 
     /// Execute a command by name
     pub fn execute_command(&self, command_name: &str, args: &str) -> Option<CommandResult> {
+        if let Some(template) = self.templates.get(command_name) {
+            let handler = self.commands.get(command_name)?.handler;
+            return Some(render_template(template, args).map_err(Into::into).and_then(|prompt| handler(&prompt)));
+        }
+
         self.commands
             .get(command_name)
             .map(|cmd_info| (cmd_info.handler)(args))
@@ -129,6 +153,89 @@ pub fn register_engine_command(name: &str, description: &str, handler: CommandHa
     });
 }
 
+/// Register prompt templates from config, reporting any that could not be added
+pub fn register_templates(templates: &BTreeMap<String, String>) -> Vec<String> {
+    with_registry_mut(|registry| {
+        templates
+            .iter()
+            .filter_map(|(name, template)| registry.register_template(name, template).err())
+            .collect()
+    })
+}
+
+/// Placeholder names in a template, in order of first appearance
+pub fn template_placeholders(template: &str) -> Vec<String> {
+    let mut placeholders: Vec<String> = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else { break };
+        let name = &after[..end];
+        if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') && !placeholders.iter().any(|p| p == name) {
+            placeholders.push(name.to_string());
+        }
+        rest = &after[end + 1..];
+    }
+
+    placeholders
+}
+
+/// Fill a template's placeholders from command arguments.
+/// `key=value` arguments fill named placeholders, remaining words fill the others in order
+/// (the last one takes everything left over). A leading `@` on file references is dropped.
+pub fn render_template(template: &str, args: &str) -> Result<String, String> {
+    let placeholders = template_placeholders(template);
+    let mut named: HashMap<String, String> = HashMap::new();
+    let mut positional: Vec<String> = Vec::new();
+
+    for word in args.split_whitespace() {
+        match word.split_once('=') {
+            Some((key, value)) if placeholders.iter().any(|p| p == key) => {
+                named.insert(key.to_string(), strip_file_marker(value).to_string());
+            }
+            _ => positional.push(strip_file_marker(word).to_string()),
+        }
+    }
+
+    if placeholders.is_empty() {
+        // Templates without placeholders take the arguments as trailing context
+        return Ok(if positional.is_empty() {
+            template.to_string()
+        } else {
+            format!("{} {}", template, positional.join(" "))
+        });
+    }
+
+    let unfilled: Vec<&String> = placeholders.iter().filter(|p| !named.contains_key(*p)).collect();
+    let mut positional = positional.into_iter();
+    for (index, placeholder) in unfilled.iter().enumerate() {
+        let value = if index + 1 == unfilled.len() {
+            positional.by_ref().collect::<Vec<_>>().join(" ")
+        } else {
+            positional.next().unwrap_or_default()
+        };
+        if value.is_empty() {
+            let usage: Vec<String> = placeholders.iter().map(|p| format!("<{}>", p)).collect();
+            return Err(format!("Missing value for {{{}}} (usage: {})", placeholder, usage.join(" ")));
+        }
+        named.insert((*placeholder).clone(), value);
+    }
+
+    let mut rendered = template.to_string();
+    for (name, value) in &named {
+        rendered = rendered.replace(&format!("{{{}}}", name), value);
+    }
+    Ok(rendered)
+}
+
+fn strip_file_marker(word: &str) -> &str {
+    match word.strip_prefix('@') {
+        Some(path) if !path.is_empty() && !path.starts_with(crate::attachments::IMAGE_MARKER) => path,
+        _ => word,
+    }
+}
+
 /// Check if a command needs engine context
 pub fn command_needs_engine(command_name: &str) -> bool {
     with_registry(|registry| {
@@ -188,8 +295,11 @@ fn handle_plan_command(args: &str) -> CommandResult {
 }
 
 // Engine commands return a special result that indicates they need engine processing
-fn handle_clear_command(_args: &str) -> CommandResult {
-    Err("ENGINE_COMMAND:clear".into())
+fn handle_clear_command(args: &str) -> CommandResult {
+    match args.trim() {
+        "" | "--keep-memory" => Err("ENGINE_COMMAND:clear".into()),
+        _ => Err("Usage: /clear [--keep-memory]".into()),
+    }
 }
 
 fn handle_model_command(args: &str) -> CommandResult {
@@ -206,6 +316,51 @@ fn handle_list_models_command(args: &str) -> CommandResult {
     Err(format!("ENGINE_COMMAND:list-models:{}", search_term).into())
 }
 
+/// Parsed `/list-models` arguments
+#[derive(Debug, PartialEq, Eq)]
+pub struct ListModelsArgs {
+    pub search_term: String,
+    pub filter: ModelFilter,
+    /// Bypass the models cache
+    pub refresh: bool,
+}
+
+/// Split `/list-models` arguments into the search term, capability filters and whether `--refresh` was given
+pub fn parse_list_models_args(args: &str) -> ListModelsArgs {
+    let mut parsed = ListModelsArgs {
+        search_term: String::new(),
+        filter: ModelFilter::default(),
+        refresh: false,
+    };
+    let mut terms = Vec::new();
+
+    for word in args.split_whitespace() {
+        match word {
+            "--refresh" => parsed.refresh = true,
+            "--tools" => parsed.filter.tools = true,
+            "--vision" => parsed.filter.vision = true,
+            _ => terms.push(word),
+        }
+    }
+
+    parsed.search_term = terms.join(" ");
+    parsed
+}
+
+impl ListModelsArgs {
+    /// Describes the search and filters for headings, e.g. ` matching 'gpt' with tool calling`
+    pub fn scope(&self) -> String {
+        let mut scope = String::new();
+        if !self.search_term.is_empty() {
+            scope.push_str(&format!(" matching '{}'", self.search_term));
+        }
+        if let Some(capabilities) = self.filter.describe() {
+            scope.push_str(&format!(" with {}", capabilities));
+        }
+        scope
+    }
+}
+
 fn handle_stack_status_command(_args: &str) -> CommandResult {
     Err("ENGINE_COMMAND:stack-status".into())
 }
@@ -224,19 +379,91 @@ fn handle_stack_auto_command(args: &str) -> CommandResult {
 
 fn handle_stack_push_command(args: &str) -> CommandResult {
     if args.trim().is_empty() {
-        Err("Usage: /stack-push <prompt> [priority]".into())
+        Err("Usage: /stack-push <prompt> [priority 0-9]".into())
     } else {
         Err(format!("ENGINE_COMMAND:stack-push:{}", args).into())
     }
 }
 
+fn handle_tools_command(_args: &str) -> CommandResult {
+    Err("ENGINE_COMMAND:tools".into())
+}
+
+fn handle_memory_command(_args: &str) -> CommandResult {
+    Err("ENGINE_COMMAND:memory".into())
+}
+
+fn handle_history_command(_args: &str) -> CommandResult {
+    Err("ENGINE_COMMAND:history".into())
+}
+
+fn handle_remember_command(args: &str) -> CommandResult {
+    if args.trim().is_empty() {
+        Err("Usage: /remember <note>".into())
+    } else {
+        Err(format!("ENGINE_COMMAND:remember:{}", args.trim()).into())
+    }
+}
+
+fn handle_compact_command(args: &str) -> CommandResult {
+    let keep = args.trim();
+    if !keep.is_empty() && keep.parse::<usize>().is_err() {
+        return Err("Usage: /compact [turns_to_keep]".into());
+    }
+    Err(format!("ENGINE_COMMAND:compact:{}", keep).into())
+}
+
+fn handle_dump_command(args: &str) -> CommandResult {
+    if args.trim().is_empty() {
+        Err("Usage: /dump <path>".into())
+    } else {
+        Err(format!("ENGINE_COMMAND:dump:{}", args.trim()).into())
+    }
+}
+
+fn handle_mode_command(args: &str) -> CommandResult {
+    Err(format!("ENGINE_COMMAND:mode:{}", args.trim()).into())
+}
+
+fn handle_changes_command(args: &str) -> CommandResult {
+    let mut words = args.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (None, _, _) | (Some("diff"), _, None) => Err(format!("ENGINE_COMMAND:changes:{}", args.trim()).into()),
+        _ => Err("Usage: /changes [diff [path]]".into()),
+    }
+}
+
+fn handle_explain_command(_args: &str) -> CommandResult {
+    Err("ENGINE_COMMAND:explain".into())
+}
+
+fn handle_cd_command(args: &str) -> CommandResult {
+    match args.trim() {
+        "" => Err("Usage: /cd <path>".into()),
+        path => Err(format!("ENGINE_COMMAND:cd:{}", path).into()),
+    }
+}
+
+fn handle_open_command(args: &str) -> CommandResult {
+    Err(format!("ENGINE_COMMAND:open:{}", args.trim()).into())
+}
+
+// Templates arrive here with their placeholders already filled in
+fn handle_template_command(prompt: &str) -> CommandResult {
+    Err(format!("ENGINE_COMMAND:template:{}", prompt).into())
+}
+
 /// Register all built-in commands
 fn register_builtin_commands() {
     with_registry_mut(|registry| {
         // Register engine commands that need engine context
-        registry.register("clear", "Clear conversation context", handle_clear_command, true);
+        registry.register("clear", "Clear conversation context (--keep-memory keeps working memory and topic)", handle_clear_command, true);
+        registry.register("compact", "Drop older messages, keeping the last few turns", handle_compact_command, true);
         registry.register("model", "Change the current LLM model", handle_model_command, true);
-        registry.register("list-models", "List all available LLM models", handle_list_models_command, true);
+        registry.register("mode", "Pin the conversation mode (planning, implementing, troubleshooting, exploring, conversational) or 'auto'", handle_mode_command, true);
+        registry.register("cd", "Change the working directory for tools and @ references", handle_cd_command, true);
+        registry.register("tools", "List the tools the model can currently call", handle_tools_command, true);
+        registry.register("list-models", "List available LLM models (--tools/--vision filter by capability, --refresh bypasses the cache)", handle_list_models_command, true);
         
         // Register plan command that needs engine context  
         registry.register("plan", "Generate detailed action plan for coding tasks", handle_plan_command, true);
@@ -246,7 +473,20 @@ fn register_builtin_commands() {
         registry.register("stack-execute", "Execute pending items in the stack", handle_stack_execute_command, true);
         registry.register("stack-clear", "Clear the execution stack", handle_stack_clear_command, true);
         registry.register("stack-auto", "Toggle automatic stack execution", handle_stack_auto_command, true);
-        registry.register("stack-push", "Push a prompt to the execution stack", handle_stack_push_command, true);
+        registry.register("stack-push", "Push a prompt to the execution stack (trailing priority 0-9; 5 and up runs before queued prompts)", handle_stack_push_command, true);
+
+        // Register project memory commands
+        registry.register("memory", "Show remembered notes and working memory", handle_memory_command, true);
+        registry.register("remember", "Add a note to the project memory", handle_remember_command, true);
+        registry.register("history", "List this directory's saved sessions (/history show <id>, /history resume <id>)", handle_history_command, true);
+
+        // Register review commands
+        registry.register("changes", "List files changed this session (/changes diff [path] to diff against git HEAD)", handle_changes_command, true);
+        registry.register("explain", "Ask the model to explain why it took the last turn's tool actions", handle_explain_command, true);
+        registry.register("open", "Open a file in $EDITOR (defaults to the file most recently edited this session)", handle_open_command, true);
+
+        // Register debugging commands
+        registry.register("dump", "Write the raw API message history to a JSON file", handle_dump_command, true);
     });
 }
 
@@ -341,4 +581,51 @@ mod tests {
         assert!(command_needs_engine("list-models"), "list-models should need engine");
         assert!(!command_needs_engine("unknown-command"), "unknown-command should not need engine");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_list_models_args() {
+        let parsed = parse_list_models_args("claude --refresh");
+        assert_eq!((parsed.search_term.as_str(), parsed.refresh), ("claude", true));
+        let parsed = parse_list_models_args("  llama  ");
+        assert_eq!((parsed.search_term.as_str(), parsed.refresh), ("llama", false));
+        let parsed = parse_list_models_args("--refresh");
+        assert_eq!((parsed.search_term.as_str(), parsed.refresh), ("", true));
+
+        let parsed = parse_list_models_args("--tools gpt --vision");
+        assert_eq!(parsed.search_term, "gpt");
+        assert_eq!(parsed.filter, ModelFilter { tools: true, vision: true });
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(
+            render_template("Review {file} for bugs and suggest fixes", "@src/main.rs").unwrap(),
+            "Review src/main.rs for bugs and suggest fixes"
+        );
+        assert_eq!(
+            render_template("Port {file} to {language}", "language=Go lib.rs").unwrap(),
+            "Port lib.rs to Go"
+        );
+        assert_eq!(
+            render_template("Explain {topic}", "error handling in tools").unwrap(),
+            "Explain error handling in tools"
+        );
+        assert_eq!(render_template("Summarize the project", "briefly").unwrap(), "Summarize the project briefly");
+        assert!(render_template("Review {file}", "").is_err());
+    }
+
+    #[test]
+    fn test_template_registration() {
+        let mut registry = CommandRegistry::new();
+        registry.register("clear", "Clear conversation context", handle_clear_command, true);
+
+        assert!(registry.register_template("clear", "Forget {thing}").is_err());
+        registry.register_template("review", "Review {file}").unwrap();
+
+        assert!(registry.command_needs_engine("review"));
+        let result = registry.execute_command("review", "@src/lib.rs").unwrap();
+        assert_eq!(result.unwrap_err().to_string(), "ENGINE_COMMAND:template:Review src/lib.rs");
+        let result = registry.execute_command("review", "").unwrap();
+        assert!(!result.unwrap_err().to_string().starts_with("ENGINE_COMMAND:"));
+    }
+}
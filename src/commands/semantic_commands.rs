@@ -0,0 +1,119 @@
+use crate::semantic_engine::SemanticEngine;
+use crate::commands::registry::{parse_list_models_args, CommandResult};
+use crate::compaction::DEFAULT_KEEP_TURNS;
+
+/// Drop older messages on demand, keeping the system prompt and recent turns
+pub async fn handle_compact_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    let keep_turns = match args.trim() {
+        "" => DEFAULT_KEEP_TURNS,
+        keep => keep.parse().map_err(|_| "Usage: /compact [turns_to_keep]")?,
+    };
+
+    Ok(engine.compact_context(keep_turns))
+}
+
+/// Change the current LLM model
+pub async fn handle_model_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    let new_model = args.trim();
+
+    if new_model.is_empty() {
+        return Err("Usage: /model <model_name>\n💡 Tip: Use /list-models to see available models".into());
+    }
+
+    engine.change_model(new_model).await.map_err(|e| e.to_string().into())
+}
+
+/// List available models with optional filtering
+pub async fn handle_list_models_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    engine.list_models(&parse_list_models_args(args)).await.map_err(|e| e.to_string().into())
+}
+
+/// Show remembered notes and working memory
+pub async fn handle_memory_command(engine: &mut SemanticEngine, _args: &str) -> CommandResult {
+    Ok(engine.memory_summary())
+}
+
+/// List the tools the model can currently call
+pub async fn handle_tools_command(engine: &mut SemanticEngine, _args: &str) -> CommandResult {
+    Ok(engine.tools_summary())
+}
+
+/// Add a durable note to the project memory
+pub async fn handle_remember_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    if args.trim().is_empty() {
+        return Err("Usage: /remember <note>".into());
+    }
+
+    let result = engine.remember(args);
+    if let Err(e) = engine.save_session() {
+        return Err(format!("Note kept for this session but could not be saved: {}", e).into());
+    }
+    Ok(result)
+}
+
+/// List this directory's saved sessions, show one, or resume one
+pub async fn handle_history_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    let mut words = args.split_whitespace();
+    let result = match (words.next(), words.next(), words.next()) {
+        (None, _, _) | (Some("list"), None, _) => engine.session_history(),
+        (Some("show"), Some(id), None) => engine.show_session(id),
+        (Some("resume"), Some(id), None) => engine.resume_session(id),
+        _ => return Err("Usage: /history [show <id> | resume <id>]".into()),
+    };
+    result.map_err(|e| e.to_string().into())
+}
+
+/// Show the conversation mode, pin one, or resume automatic detection
+pub async fn handle_mode_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    match args.trim() {
+        "" => Ok(engine.mode_summary()),
+        mode => engine.set_mode(mode).map_err(|e| e.into()),
+    }
+}
+
+/// List the files changed during this session, optionally with diffs against git HEAD
+pub async fn handle_changes_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    let mut words = args.split_whitespace();
+    match (words.next(), words.next()) {
+        (None, _) => Ok(engine.changes_report(false, None)),
+        (Some("diff"), path) => Ok(engine.changes_report(true, path)),
+        _ => Err("Usage: /changes [diff [path]]".into()),
+    }
+}
+
+/// Open a file, or the one most recently edited this session, in the user's editor
+pub async fn handle_open_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    let path = match args.trim() {
+        "" => None,
+        path => Some(path),
+    };
+    engine.open_in_editor(path).map_err(|e| e.to_string().into())
+}
+
+/// Move the session to another directory
+pub async fn handle_cd_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    engine.change_directory(args.trim()).map_err(|e| format!("Cannot change directory to {}", e).into())
+}
+
+/// Ask the model why it took the last turn's tool actions
+pub async fn handle_explain_command(engine: &mut SemanticEngine, _args: &str) -> CommandResult {
+    engine.explain_last_turn().await.map_err(|e| format!("Could not explain the last turn: {}", e).into())
+}
+
+/// Write the raw message history, including tool calls, to a JSON file
+pub async fn handle_dump_command(engine: &mut SemanticEngine, args: &str) -> CommandResult {
+    let path = args.trim();
+    if path.is_empty() {
+        return Err("Usage: /dump <path>".into());
+    }
+
+    let written = engine.dump_messages(path).map_err(|e| format!("Could not write {}: {}", path, e))?;
+    Ok(format!("💾 Wrote {} messages to {}", engine.messages.len(), written.display()))
+}
+
+/// Send a rendered prompt template through the normal conversation flow
+pub async fn handle_template_command(engine: &mut SemanticEngine, prompt: &str) -> CommandResult {
+    println!("📝 {}", prompt);
+    engine.process_conversation(prompt).await.map_err(|e| e.to_string())?;
+    Ok(String::new())
+}
@@ -0,0 +1,102 @@
+//! Manual context compaction
+//! Drops older turns from a conversation while keeping the system prompt and recent exchanges
+
+use crate::openrouter::Message;
+
+/// Turns kept by `/compact` when no count is given
+pub const DEFAULT_KEEP_TURNS: usize = 3;
+
+/// What a compaction removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionReport {
+    pub messages_removed: usize,
+    pub tokens_reclaimed: usize,
+}
+
+/// Bytes of text per token assumed by the estimates, close enough for English prose and code
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Rough token estimate (about four characters per token) for a set of messages
+pub fn estimate_tokens(messages: &[Message]) -> usize {
+    let chars: usize = messages
+        .iter()
+        .map(|message| {
+            let tool_chars: usize = message
+                .tool_calls
+                .iter()
+                .flatten()
+                .map(|call| call.function.name.len() + call.function.arguments.len())
+                .sum();
+            message.content.text().len() + tool_chars
+        })
+        .sum();
+    chars.div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Drop everything before the last `keep_turns` user turns, preserving leading system messages.
+/// Cuts only at user messages so tool calls are never separated from their results.
+pub fn compact_messages(messages: &mut Vec<Message>, keep_turns: usize) -> CompactionReport {
+    let system_count = messages.iter().take_while(|message| message.role == "system").count();
+
+    let user_positions: Vec<usize> = messages
+        .iter()
+        .enumerate()
+        .skip(system_count)
+        .filter(|(_, message)| message.role == "user")
+        .map(|(index, _)| index)
+        .collect();
+
+    if user_positions.len() <= keep_turns {
+        return CompactionReport { messages_removed: 0, tokens_reclaimed: 0 };
+    }
+
+    let cut = if keep_turns == 0 {
+        messages.len()
+    } else {
+        user_positions[user_positions.len() - keep_turns]
+    };
+
+    let removed: Vec<Message> = messages.drain(system_count..cut).collect();
+    CompactionReport {
+        messages_removed: removed.len(),
+        tokens_reclaimed: estimate_tokens(&removed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.into(),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    #[test]
+    fn test_compact_keeps_system_and_recent_turns() {
+        let mut messages = vec![
+            message("system", "You are loo"),
+            message("user", "first question"),
+            message("assistant", "first answer"),
+            message("user", "second question"),
+            message("tool", "{\"status\":\"success\"}"),
+            message("assistant", "second answer"),
+            message("user", "third question"),
+        ];
+
+        let report = compact_messages(&mut messages, 2);
+
+        assert_eq!(report.messages_removed, 2);
+        assert_eq!(report.tokens_reclaimed, estimate_tokens(&[message("user", "first question"), message("assistant", "first answer")]));
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[1].content.text(), "second question");
+        assert_eq!(messages.len(), 5);
+
+        let report = compact_messages(&mut messages, 5);
+        assert_eq!(report.messages_removed, 0);
+    }
+}
@@ -1,14 +1,27 @@
+use crate::input::Triggers;
+use crate::openrouter::cache::DEFAULT_MODELS_CACHE_TTL;
+use crate::theme::{self, ThemeRole};
+use crate::tools::git::default_protected_branches;
+use crate::tools::newlines::{LineEnding, TrailingNewline};
+use crate::tools::{DEFAULT_MAX_OUTPUT_LINES, DEFAULT_MAX_WRITE_BYTES};
 use dirs;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
     pub openrouter: OpenRouterConfig,
     pub preferences: PreferencesConfig,
     pub tools: ToolsConfig,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    /// Prompt templates invoked as `/<name> args`, e.g. `review = "Review {file} for bugs"`
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -16,13 +29,354 @@ pub struct OpenRouterConfig {
     pub api_key: Option<String>,
     pub model: String,
     pub base_url: String,
+    /// How long the cached models list stays fresh, in seconds
+    #[serde(default = "default_models_cache_ttl")]
+    pub models_cache_ttl: u64,
+    /// Send tool definitions; disable for models that reject function calling
+    #[serde(default = "default_true")]
+    pub supports_tools: bool,
+    /// Let the model batch independent tool calls into one response to save round trips
+    #[serde(default = "default_true")]
+    pub parallel_tool_calls: bool,
+    /// Cap on tokens generated per response; unset leaves the limit to the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Sampling seed for reproducible output on models that support it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Alternative replies requested per chat turn, to pick from interactively; unset means one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// Receive replies as they are generated; an incomplete stream is retried without streaming
+    #[serde(default)]
+    pub stream: bool,
+    /// Which API dialect `base_url` speaks
+    #[serde(default)]
+    pub provider: Provider,
 }
 
+/// API flavour behind `openrouter.base_url`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Provider {
+    /// openrouter.ai: API key required, OpenRouter headers and models catalog
+    #[default]
+    #[serde(rename = "openrouter")]
+    OpenRouter,
+    /// Any OpenAI-compatible server such as Ollama or LM Studio: API key optional, models list best effort
+    #[serde(rename = "openai_compatible")]
+    OpenAiCompatible,
+}
+
+impl Provider {
+    pub const NAMES: [&'static str; 2] = ["openrouter", "openai_compatible"];
+
+    pub fn from_name(name: &str) -> Option<Provider> {
+        match name {
+            "openrouter" => Some(Provider::OpenRouter),
+            "openai_compatible" => Some(Provider::OpenAiCompatible),
+            _ => None,
+        }
+    }
+
+    /// Local servers usually accept unauthenticated requests
+    pub fn requires_api_key(&self) -> bool {
+        *self == Provider::OpenRouter
+    }
+
+    /// Name used in error messages
+    pub fn label(&self) -> &'static str {
+        match self {
+            Provider::OpenRouter => "OpenRouter",
+            Provider::OpenAiCompatible => "OpenAI-compatible",
+        }
+    }
+}
+
+fn default_models_cache_ttl() -> u64 {
+    DEFAULT_MODELS_CACHE_TTL
+}
+
+/// `preferences.verbose` level that prints a one-line summary of every tool result
+pub const VERBOSE_SUMMARIES: u8 = 1;
+
+/// `preferences.verbose` level that prints full tool results, arguments and live command output
+pub const VERBOSE_TOOL_RESULTS: u8 = 2;
+
+/// `preferences.verbose` level that also prints raw API requests and responses
+pub const VERBOSE_RAW: u8 = 3;
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct PreferencesConfig {
     pub default_directory: Option<String>,
-    pub verbose: bool,
+    /// Detail level from 0 (quiet) to `VERBOSE_RAW`; `true` and `false` from older configs read as 3 and 0
+    #[serde(deserialize_with = "deserialize_verbosity")]
+    pub verbose: u8,
     pub auto_confirm: bool,
+    /// Show an activity indicator while waiting for the model
+    #[serde(default = "default_true")]
+    pub spinner: bool,
+    /// Tell the model about files changed outside loo between turns
+    #[serde(default)]
+    pub watch_files: bool,
+    /// Save session and story in the background every N seconds instead of after each turn (0 = per turn)
+    #[serde(default)]
+    pub autosave_interval_secs: u64,
+    /// Mask API keys, tokens and passwords in tool output before it reaches the model or story.md
+    #[serde(default = "default_true")]
+    pub redact_secrets: bool,
+    /// Most requests a stack execution may queue per session, including decomposed sub-requests (0 = unlimited)
+    #[serde(default = "default_max_stack_requests")]
+    pub max_stack_requests: usize,
+    /// Pause between stack requests, in milliseconds (0 = none)
+    #[serde(default = "default_stack_request_delay_ms")]
+    pub stack_request_delay_ms: u64,
+    /// Up to this many extra milliseconds, chosen at random, added to each pause between stack requests
+    #[serde(default)]
+    pub stack_request_jitter_ms: u64,
+    /// Chat messages a session accepts before it ends, across all its turns (0 = unlimited)
+    #[serde(default)]
+    pub max_turns: usize,
+    /// Tokens a session may use across all its API requests, as reported by the API, before it ends (0 = unlimited)
+    #[serde(default)]
+    pub max_session_tokens: u64,
+    /// How much of each tool result to echo: minimal, summary or full (verbose always shows full)
+    #[serde(default)]
+    pub tool_output: ToolOutput,
+    /// Name shown before assistant replies and in story.md headings (default 🤖)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assistant_name: Option<String>,
+    /// Chat prompt label and help line
+    #[serde(default)]
+    pub prompt: PromptConfig,
+    /// Write story.md into the working directory; when off the story is only kept in memory
+    #[serde(default = "default_true")]
+    pub write_story: bool,
+    /// Emoji (markdown) or text labels (plain) in story.md
+    #[serde(default)]
+    pub story_format: StoryFormat,
+    /// Most `@path` completions offered at once, so huge directories stay responsive (0 = unlimited)
+    #[serde(default = "default_autocomplete_max_entries")]
+    pub autocomplete_max_entries: usize,
+    /// Character that starts a file reference and its autocomplete (default `@`); typed twice it is literal
+    #[serde(default = "default_file_trigger")]
+    pub file_trigger: char,
+    /// Character that starts a command and its autocomplete (default `/`); typed twice it is literal
+    #[serde(default = "default_command_trigger")]
+    pub command_trigger: char,
+    /// Ask the model what each chat message intends (clear context, change model, ...) before answering it;
+    /// when off every message is a regular conversation turn and only slash commands do anything else
+    #[serde(default = "default_true")]
+    pub intent_recognition: bool,
+    /// Load `.env` from the working directory at startup; variables already set in the shell win
+    #[serde(default = "default_true")]
+    pub load_dotenv: bool,
+    /// Periodic "still running" lines on stderr during long tool calls when stderr is not a terminal: off, text or json
+    #[serde(default)]
+    pub progress_log: ProgressLogFormat,
+    /// Files, relative to the working directory, whose contents are given to the model in every session
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_files: Vec<String>,
+}
+
+/// Chat prompt text; unset values fall back to the built-in label and help line
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PromptConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Shown under the input line; an empty string hides it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+}
+
+impl PromptConfig {
+    pub const DEFAULT_LABEL: &'static str = "💬 You:";
+
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(Self::DEFAULT_LABEL)
+    }
+
+    pub fn help<'a>(&'a self, default: &'a str) -> &'a str {
+        self.help.as_deref().unwrap_or(default)
+    }
+}
+
+/// How much of a tool result is printed after the call
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolOutput {
+    /// Only the status and duration
+    #[default]
+    Minimal,
+    /// A one-line description such as `Read file src/main.rs (2048 bytes)`
+    Summary,
+    /// The raw tool result
+    Full,
+}
+
+impl ToolOutput {
+    pub const NAMES: [&'static str; 3] = ["minimal", "summary", "full"];
+
+    pub fn from_name(name: &str) -> Option<ToolOutput> {
+        match name {
+            "minimal" => Some(ToolOutput::Minimal),
+            "summary" => Some(ToolOutput::Summary),
+            "full" => Some(ToolOutput::Full),
+            _ => None,
+        }
+    }
+}
+
+/// How story.md marks tools, results and events
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StoryFormat {
+    /// Emoji such as 🔧 and ✅
+    #[default]
+    Markdown,
+    /// Text labels such as `[TOOL]` and `[OK]`, for screen readers and plain-text viewers
+    Plain,
+}
+
+impl StoryFormat {
+    pub const NAMES: [&'static str; 2] = ["markdown", "plain"];
+
+    pub fn from_name(name: &str) -> Option<StoryFormat> {
+        match name {
+            "markdown" => Some(StoryFormat::Markdown),
+            "plain" => Some(StoryFormat::Plain),
+            _ => None,
+        }
+    }
+}
+
+/// What the chat does when the model calls the `complete` tool
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompleteAction {
+    /// Show the model's wrap-up and end the turn; tool calls queued after `complete` are not run
+    #[default]
+    EndTurn,
+    /// Note the completion and let the model keep going
+    Continue,
+}
+
+impl CompleteAction {
+    pub const NAMES: [&'static str; 2] = ["end_turn", "continue"];
+
+    pub fn from_name(name: &str) -> Option<CompleteAction> {
+        match name {
+            "end_turn" => Some(CompleteAction::EndTurn),
+            "continue" => Some(CompleteAction::Continue),
+            _ => None,
+        }
+    }
+}
+
+/// How long-running tool calls report progress when no spinner can be drawn
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressLogFormat {
+    Off,
+    /// `⏳ Still running: cargo build, 45s elapsed`
+    #[default]
+    Text,
+    /// One JSON object per line, for scripts reading stderr
+    Json,
+}
+
+impl ProgressLogFormat {
+    pub const NAMES: [&'static str; 3] = ["off", "text", "json"];
+
+    pub fn from_name(name: &str) -> Option<ProgressLogFormat> {
+        match name {
+            "off" => Some(ProgressLogFormat::Off),
+            "text" => Some(ProgressLogFormat::Text),
+            "json" => Some(ProgressLogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl PreferencesConfig {
+    /// Prefix for assistant replies in the terminal
+    pub fn assistant_label(&self) -> String {
+        match &self.assistant_name {
+            Some(name) => format!("{}:", name),
+            None => "🤖".to_string(),
+        }
+    }
+
+    /// Autocomplete and reference trigger characters
+    pub fn triggers(&self) -> Triggers {
+        Triggers { file: self.file_trigger, command: self.command_trigger }
+    }
+
+    /// Tool result verbosity, raised to summaries or full output by the `verbose` level
+    pub fn tool_output_level(&self) -> ToolOutput {
+        match self.verbose {
+            level if level >= VERBOSE_TOOL_RESULTS => ToolOutput::Full,
+            VERBOSE_SUMMARIES if self.tool_output == ToolOutput::Minimal => ToolOutput::Summary,
+            _ => self.tool_output,
+        }
+    }
+}
+
+fn default_stack_request_delay_ms() -> u64 {
+    500
+}
+
+fn default_max_stack_requests() -> usize {
+    50
+}
+
+fn default_max_write_bytes() -> u64 {
+    DEFAULT_MAX_WRITE_BYTES
+}
+
+fn default_max_output_lines() -> usize {
+    DEFAULT_MAX_OUTPUT_LINES
+}
+
+fn default_max_deletes_per_turn() -> usize {
+    5
+}
+
+fn default_autocomplete_max_entries() -> usize {
+    200
+}
+
+fn default_file_trigger() -> char {
+    Triggers::default().file
+}
+
+fn default_command_trigger() -> char {
+    Triggers::default().command
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn deserialize_verbosity<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Verbosity {
+        Flag(bool),
+        Level(u8),
+    }
+    Ok(match Verbosity::deserialize(deserializer)? {
+        Verbosity::Flag(verbose) => if verbose { VERBOSE_RAW } else { 0 },
+        Verbosity::Level(level) => level.min(VERBOSE_RAW),
+    })
+}
+
+/// A verbosity level from 0 to 3, or `true`/`false` for the highest and lowest
+fn parse_verbosity(value: &str) -> Option<u8> {
+    match value {
+        "true" => Some(VERBOSE_RAW),
+        "false" => Some(0),
+        _ => value.parse().ok().filter(|level| *level <= VERBOSE_RAW),
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -31,6 +385,94 @@ pub struct ToolsConfig {
     pub commands: bool,
     pub git: bool,
     pub command_timeout: u64,
+    /// Branches where git commit/add require confirmation (honors `preferences.auto_confirm`)
+    #[serde(default = "default_protected_branches")]
+    pub git_protected_branches: Vec<String>,
+    /// Largest content `create_file`/`write_file` may write in one call
+    #[serde(default = "default_max_write_bytes")]
+    pub max_write_bytes: u64,
+    /// Lines of `run_command` stdout/stderr kept for the model, head and tail around an omission marker (0 = unlimited)
+    #[serde(default = "default_max_output_lines")]
+    pub max_output_lines: usize,
+    /// `delete_file` calls one turn may make before loo asks to confirm the rest in bulk (0 = unlimited)
+    #[serde(default = "default_max_deletes_per_turn")]
+    pub max_deletes_per_turn: usize,
+    /// Ask before `run_command` runs a command pattern (e.g. `cargo build`) for the first time in a session
+    #[serde(default = "default_true")]
+    pub confirm_commands: bool,
+    /// Line endings of files written by `create_file`/`write_file`: auto, lf or crlf
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    /// Final newline of written files: keep, ensure or strip
+    #[serde(default)]
+    pub trailing_newline: TrailingNewline,
+    /// Format files written by `create_file`/`write_file`/`create_from_template` with rustfmt, prettier, black or gofmt when installed
+    #[serde(default)]
+    pub format_on_write: bool,
+    /// Project directory, relative to the working directory, searched first for `create_from_template` templates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_dir: Option<String>,
+    /// What chat does when the model calls `complete`: end_turn or continue
+    #[serde(default)]
+    pub on_complete: CompleteAction,
+}
+
+/// Terminal colors: a preset plus optional per-role color overrides
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ThemeConfig {
+    pub preset: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selection: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dim: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            preset: "dark".to_string(),
+            prompt: None,
+            warning: None,
+            selection: None,
+            dim: None,
+            success: None,
+            error: None,
+        }
+    }
+}
+
+impl ThemeConfig {
+    pub fn role(&self, role: ThemeRole) -> Option<&str> {
+        let value = match role {
+            ThemeRole::Prompt => &self.prompt,
+            ThemeRole::Warning => &self.warning,
+            ThemeRole::Selection => &self.selection,
+            ThemeRole::Dim => &self.dim,
+            ThemeRole::Success => &self.success,
+            ThemeRole::Error => &self.error,
+        };
+        value.as_deref()
+    }
+
+    pub fn set_role(&mut self, role: ThemeRole, color: Option<String>) {
+        let slot = match role {
+            ThemeRole::Prompt => &mut self.prompt,
+            ThemeRole::Warning => &mut self.warning,
+            ThemeRole::Selection => &mut self.selection,
+            ThemeRole::Dim => &mut self.dim,
+            ThemeRole::Success => &mut self.success,
+            ThemeRole::Error => &mut self.error,
+        };
+        *slot = color;
+    }
 }
 
 impl Default for Config {
@@ -40,26 +482,108 @@ impl Default for Config {
                 api_key: None,
                 model: "meta-llama/llama-3.1-8b-instruct:free".to_string(),
                 base_url: "https://openrouter.ai/api/v1".to_string(),
+                models_cache_ttl: DEFAULT_MODELS_CACHE_TTL,
+                supports_tools: true,
+                parallel_tool_calls: true,
+                max_tokens: None,
+                seed: None,
+                n: None,
+                stream: false,
+                provider: Provider::default(),
             },
             preferences: PreferencesConfig {
                 default_directory: None,
-                verbose: false,
+                verbose: 0,
                 auto_confirm: false,
+                spinner: true,
+                watch_files: false,
+                autosave_interval_secs: 0,
+                redact_secrets: true,
+                max_stack_requests: default_max_stack_requests(),
+                stack_request_delay_ms: default_stack_request_delay_ms(),
+                stack_request_jitter_ms: 0,
+                max_turns: 0,
+                max_session_tokens: 0,
+                tool_output: ToolOutput::default(),
+                assistant_name: None,
+                prompt: PromptConfig::default(),
+                write_story: true,
+                story_format: StoryFormat::default(),
+                autocomplete_max_entries: default_autocomplete_max_entries(),
+                file_trigger: default_file_trigger(),
+                command_trigger: default_command_trigger(),
+                intent_recognition: true,
+                load_dotenv: true,
+                progress_log: ProgressLogFormat::default(),
+                context_files: Vec::new(),
             },
             tools: ToolsConfig {
                 filesystem: true,
                 commands: true,
                 git: true,
                 command_timeout: 300,
+                git_protected_branches: default_protected_branches(),
+                max_write_bytes: DEFAULT_MAX_WRITE_BYTES,
+                max_output_lines: DEFAULT_MAX_OUTPUT_LINES,
+                max_deletes_per_turn: default_max_deletes_per_turn(),
+                confirm_commands: true,
+                line_ending: LineEnding::default(),
+                trailing_newline: TrailingNewline::default(),
+                format_on_write: false,
+                template_dir: None,
+                on_complete: CompleteAction::default(),
             },
+            theme: ThemeConfig::default(),
+            templates: BTreeMap::new(),
         }
     }
 }
 
+/// A trigger character: exactly one character that cannot be part of a word or end one
+fn parse_trigger(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(trigger), None) if !trigger.is_alphanumeric() && !trigger.is_whitespace() => Some(trigger),
+        _ => None,
+    }
+}
+
+/// Strip trailing slashes from an API base URL and check it is an absolute http(s) URL
+pub fn normalize_base_url(url: &str) -> Result<String, String> {
+    let trimmed = url.trim().trim_end_matches('/');
+    let host = trimmed
+        .strip_prefix("https://")
+        .or_else(|| trimmed.strip_prefix("http://"))
+        .ok_or_else(|| format!("Invalid base URL '{}': must start with http:// or https://", url))?;
+
+    if host.is_empty() || host.starts_with('/') {
+        return Err(format!("Invalid base URL '{}': missing host", url));
+    }
+
+    Ok(trimmed.to_string())
+}
+
 pub struct ConfigManager;
 
+/// Explicit config file chosen with `--config`, used instead of the OS config directory
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
 impl ConfigManager {
+    /// Use `path` for every later load and save in this process
+    pub fn set_config_path<P: Into<PathBuf>>(path: P) -> Result<(), Box<dyn std::error::Error>> {
+        CONFIG_PATH_OVERRIDE
+            .set(path.into())
+            .map_err(|_| "Config path has already been set".into())
+    }
+
     pub fn config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+            if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            return Ok(path.clone());
+        }
+
         let config_dir = dirs::config_dir()
             .ok_or("Could not find config directory")?
             .join("loo");
@@ -68,15 +592,22 @@ impl ConfigManager {
         Ok(config_dir.join("config.toml"))
     }
     
-    pub fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
+    /// Directory holding `scaffold_project` template overrides, next to the config file
+    pub fn scaffold_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
         let config_path = Self::config_path()?;
-        
-        if !config_path.exists() {
+        Ok(config_path.parent().unwrap_or(Path::new(".")).join("scaffold"))
+    }
+
+    /// Directory holding the user's `create_from_template` templates, next to the config file
+    pub fn file_templates_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let config_path = Self::config_path()?;
+        Ok(config_path.parent().unwrap_or(Path::new(".")).join("templates"))
+    }
+
+    pub fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
+        let Some(mut config) = Self::load_config_file()? else {
             return Ok(Config::default());
-        }
-        
-        let config_content = fs::read_to_string(config_path)?;
-        let mut config: Config = toml::from_str(&config_content)?;
+        };
         
         // Override with environment variables
         if let Ok(api_key) = env::var("OPENROUTER_API_KEY") {
@@ -114,25 +645,179 @@ impl ConfigManager {
         Ok(())
     }
     
+    /// The config file as written, without environment overrides, or `None` when there is none
+    fn load_config_file() -> Result<Option<Config>, Box<dyn std::error::Error>> {
+        let config_path = Self::config_path()?;
+        
+        if !config_path.exists() {
+            tracing::debug!(path = %config_path.display(), "no config file, using defaults");
+            return Ok(None);
+        }
+        
+        tracing::debug!(path = %config_path.display(), "loading config");
+        let config_content = fs::read_to_string(config_path)?;
+        Ok(Some(toml::from_str(&config_content)?))
+    }
+
     pub fn set_config_value(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let mut config = Self::load_config()?;
+        // Environment overrides such as OPENROUTER_API_KEY are not written into the file
+        let mut config = Self::load_config_file()?.unwrap_or_default();
         
         match key {
             "openrouter.api_key" => config.openrouter.api_key = Some(value.to_string()),
             "openrouter.model" => config.openrouter.model = value.to_string(),
-            "openrouter.base_url" => config.openrouter.base_url = value.to_string(),
+            "openrouter.base_url" => config.openrouter.base_url = normalize_base_url(value)?,
+            "openrouter.models_cache_ttl" => config.openrouter.models_cache_ttl = value.parse()?,
+            "openrouter.supports_tools" => config.openrouter.supports_tools = value.parse()?,
+            "openrouter.parallel_tool_calls" => config.openrouter.parallel_tool_calls = value.parse()?,
+            "openrouter.max_tokens" => {
+                config.openrouter.max_tokens = match value.parse()? {
+                    0 => return Err("openrouter.max_tokens must be a positive number".into()),
+                    max_tokens => Some(max_tokens),
+                };
+            }
+            "openrouter.seed" => config.openrouter.seed = Some(value.parse()?),
+            "openrouter.stream" => config.openrouter.stream = value.parse()?,
+            "openrouter.n" => {
+                config.openrouter.n = match value.parse()? {
+                    0 => return Err("openrouter.n must be a positive number".into()),
+                    n => Some(n),
+                };
+            }
+            "openrouter.provider" => {
+                config.openrouter.provider = Provider::from_name(value).ok_or_else(|| {
+                    format!("Unknown provider: {} (use {})", value, Provider::NAMES.join(", "))
+                })?;
+            }
             "preferences.default_directory" => config.preferences.default_directory = Some(value.to_string()),
-            "preferences.verbose" => config.preferences.verbose = value.parse()?,
+            "preferences.verbose" => {
+                config.preferences.verbose = parse_verbosity(value)
+                    .ok_or_else(|| format!("Invalid verbosity: {} (use 0-{}, true or false)", value, VERBOSE_RAW))?;
+            }
             "preferences.auto_confirm" => config.preferences.auto_confirm = value.parse()?,
+            "preferences.spinner" => config.preferences.spinner = value.parse()?,
+            "preferences.watch_files" => config.preferences.watch_files = value.parse()?,
+            "preferences.autosave_interval_secs" => config.preferences.autosave_interval_secs = value.parse()?,
+            "preferences.redact_secrets" => config.preferences.redact_secrets = value.parse()?,
+            "preferences.intent_recognition" => config.preferences.intent_recognition = value.parse()?,
+            "preferences.max_stack_requests" => config.preferences.max_stack_requests = value.parse()?,
+            "preferences.stack_request_delay_ms" => config.preferences.stack_request_delay_ms = value.parse()?,
+            "preferences.stack_request_jitter_ms" => config.preferences.stack_request_jitter_ms = value.parse()?,
+            "preferences.max_turns" => config.preferences.max_turns = value.parse()?,
+            "preferences.max_session_tokens" => config.preferences.max_session_tokens = value.parse()?,
+            "preferences.tool_output" => {
+                config.preferences.tool_output = ToolOutput::from_name(value).ok_or_else(|| {
+                    format!("Unknown tool output level: {} (use {})", value, ToolOutput::NAMES.join(", "))
+                })?;
+            }
+            "preferences.progress_log" => {
+                config.preferences.progress_log = ProgressLogFormat::from_name(value).ok_or_else(|| {
+                    format!("Unknown progress log format: {} (use {})", value, ProgressLogFormat::NAMES.join(", "))
+                })?;
+            }
+            "preferences.autocomplete_max_entries" => config.preferences.autocomplete_max_entries = value.parse()?,
+            "preferences.file_trigger" | "preferences.command_trigger" => {
+                let trigger = parse_trigger(value).ok_or_else(|| {
+                    format!("{} must be a single punctuation or symbol character, not '{}'", key, value)
+                })?;
+                if key == "preferences.file_trigger" {
+                    config.preferences.file_trigger = trigger;
+                } else {
+                    config.preferences.command_trigger = trigger;
+                }
+                if config.preferences.file_trigger == config.preferences.command_trigger {
+                    return Err("preferences.file_trigger and preferences.command_trigger must be different characters".into());
+                }
+            }
+            "preferences.write_story" => config.preferences.write_story = value.parse()?,
+            "preferences.story_format" => {
+                config.preferences.story_format = StoryFormat::from_name(value).ok_or_else(|| {
+                    format!("Unknown story format: {} (use {})", value, StoryFormat::NAMES.join(", "))
+                })?;
+            }
+            "preferences.load_dotenv" => config.preferences.load_dotenv = value.parse()?,
+            "preferences.context_files" => {
+                config.preferences.context_files = value
+                    .split(',')
+                    .map(|path| path.trim().to_string())
+                    .filter(|path| !path.is_empty())
+                    .collect();
+            }
+            "preferences.assistant_name" => config.preferences.assistant_name = Some(value.to_string()),
+            "preferences.prompt.label" => config.preferences.prompt.label = Some(value.to_string()),
+            "preferences.prompt.help" => config.preferences.prompt.help = Some(value.to_string()),
             "tools.filesystem" => config.tools.filesystem = value.parse()?,
             "tools.commands" => config.tools.commands = value.parse()?,
             "tools.git" => config.tools.git = value.parse()?,
             "tools.command_timeout" => config.tools.command_timeout = value.parse()?,
+            "tools.max_write_bytes" => config.tools.max_write_bytes = value.parse()?,
+            "tools.max_output_lines" => config.tools.max_output_lines = value.parse()?,
+            "tools.max_deletes_per_turn" => config.tools.max_deletes_per_turn = value.parse()?,
+            "tools.line_ending" => {
+                config.tools.line_ending = LineEnding::from_name(value).ok_or_else(|| {
+                    format!("Unknown line ending: {} (use {})", value, LineEnding::NAMES.join(", "))
+                })?;
+            }
+            "tools.trailing_newline" => {
+                config.tools.trailing_newline = TrailingNewline::from_name(value).ok_or_else(|| {
+                    format!("Unknown trailing newline policy: {} (use {})", value, TrailingNewline::NAMES.join(", "))
+                })?;
+            }
+            "tools.confirm_commands" => config.tools.confirm_commands = value.parse()?,
+            "tools.format_on_write" => config.tools.format_on_write = value.parse()?,
+            "tools.template_dir" => config.tools.template_dir = Some(value.to_string()),
+            "tools.on_complete" => {
+                config.tools.on_complete = CompleteAction::from_name(value).ok_or_else(|| {
+                    format!("Unknown complete action: {} (use {})", value, CompleteAction::NAMES.join(", "))
+                })?;
+            }
+            "tools.git_protected_branches" => {
+                config.tools.git_protected_branches = value
+                    .split(',')
+                    .map(|branch| branch.trim().to_string())
+                    .filter(|branch| !branch.is_empty())
+                    .collect();
+            }
+            "theme.preset" => {
+                if !theme::PRESETS.contains(&value) {
+                    return Err(format!("Unknown theme preset: {} (use {})", value, theme::PRESETS.join(", ")).into());
+                }
+                config.theme.preset = value.to_string();
+            }
+            _ if key.starts_with("templates.") => {
+                let name = &key["templates.".len()..];
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Err(format!("Template name '{}' must be a single word", name).into());
+                }
+                // An empty value removes the template
+                if value.is_empty() {
+                    config.templates.remove(name);
+                } else {
+                    config.templates.insert(name.to_string(), value.to_string());
+                }
+            }
+            _ if key.starts_with("theme.") => {
+                let role = ThemeRole::from_name(&key["theme.".len()..])
+                    .ok_or_else(|| format!("Unknown config key: {}", key))?;
+                // "default" drops the override and falls back to the preset color
+                let color = if value == "default" {
+                    None
+                } else if theme::parse_color(value).is_some() {
+                    Some(value.to_string())
+                } else {
+                    return Err(format!("Unknown color '{}' for {}", value, key).into());
+                };
+                config.theme.set_role(role, color);
+            }
             _ => return Err(format!("Unknown config key: {}", key).into()),
         }
         
         Self::save_config(&config)?;
-        println!("✅ Updated {}: {}", key, value);
+        if key == "openrouter.api_key" {
+            println!("✅ Updated {}", key);
+        } else {
+            println!("✅ Updated {}: {}", key, value);
+        }
         Ok(())
     }
     
@@ -143,9 +828,10 @@ impl ConfigManager {
         let has_api_key = config.openrouter.api_key.is_some() 
             || env::var("OPENROUTER_API_KEY").is_ok();
         
-        if has_api_key {
+        if has_api_key || !config.openrouter.provider.requires_api_key() {
             println!("✅ Configuration is valid");
             println!("🔧 Model: {}", config.openrouter.model);
+            println!("🔧 Provider: {}", config.openrouter.provider.label());
             println!("🔧 Base URL: {}", config.openrouter.base_url);
             Ok(())
         } else {
@@ -0,0 +1,183 @@
+//! Line diffs between two versions of a file, used to show how the model's rewrites changed it
+//! The output follows the unified format of `diff -u`, so it reads like `git diff` in the terminal and story.md
+
+use crate::theme::{self, ThemeRole};
+use std::iter;
+
+/// Largest table the line matching may fill; a bigger changed region is shown removed and re-added whole
+const MAX_TABLE_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Edit script turning `old` into `new`, from the longest common subsequence of their lines
+fn line_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+    let (n, m) = (old_changed.len(), new_changed.len());
+
+    let mut ops = vec![Op::Equal; prefix];
+    if n * m > MAX_TABLE_CELLS {
+        ops.extend(iter::repeat_n(Op::Delete, n));
+        ops.extend(iter::repeat_n(Op::Insert, m));
+    } else {
+        // common[i * width + j] is the common subsequence length of old_changed[i..] and new_changed[j..]
+        let width = m + 1;
+        let mut common = vec![0u32; (n + 1) * width];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                common[i * width + j] = if old_changed[i] == new_changed[j] {
+                    common[(i + 1) * width + j + 1] + 1
+                } else {
+                    common[(i + 1) * width + j].max(common[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_changed[i] == new_changed[j] {
+                ops.push(Op::Equal);
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || common[(i + 1) * width + j] >= common[i * width + j + 1]) {
+                // Removals go first, so a replaced line reads as - then +
+                ops.push(Op::Delete);
+                i += 1;
+            } else {
+                ops.push(Op::Insert);
+                j += 1;
+            }
+        }
+    }
+    ops.extend(iter::repeat_n(Op::Equal, suffix));
+    ops
+}
+
+/// Unified diff hunks from `old` to `new` with `context` unchanged lines around each change, or `None` when
+/// both have the same lines. Line ending differences are not shown.
+pub fn unified_diff(old: &str, new: &str, context: usize) -> Option<String> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = line_ops(&old_lines, &new_lines);
+
+    // Line positions in the old and new file before each step
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for op in &ops {
+        positions.push((old_pos, new_pos));
+        if *op != Op::Insert {
+            old_pos += 1;
+        }
+        if *op != Op::Delete {
+            new_pos += 1;
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    // Changes closer together than twice the context share a hunk
+    let changes: Vec<usize> = (0..ops.len()).filter(|k| ops[*k] != Op::Equal).collect();
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &k in &changes {
+        match groups.last_mut() {
+            Some((_, last)) if k - *last <= 2 * context + 1 => *last = k,
+            _ => groups.push((k, k)),
+        }
+    }
+    if groups.is_empty() {
+        return None;
+    }
+
+    let mut diff = String::new();
+    for (first, last) in groups {
+        let start = first.saturating_sub(context);
+        let end = (last + context + 1).min(ops.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let (old_count, new_count) = (old_end - old_start, new_end - new_start);
+        // An empty range is numbered by the line before it, as in `diff -u`
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            if old_count == 0 { old_start } else { old_start + 1 },
+            old_count,
+            if new_count == 0 { new_start } else { new_start + 1 },
+            new_count
+        ));
+        for k in start..end {
+            let (old_pos, new_pos) = positions[k];
+            let line = match ops[k] {
+                Op::Equal => format!(" {}", old_lines[old_pos]),
+                Op::Delete => format!("-{}", old_lines[old_pos]),
+                Op::Insert => format!("+{}", new_lines[new_pos]),
+            };
+            diff.push_str(&line);
+            diff.push('\n');
+        }
+    }
+    Some(diff)
+}
+
+/// Lines added and removed by a diff from `unified_diff`
+pub fn diff_stats(diff: &str) -> (usize, usize) {
+    diff.lines().fold((0, 0), |(added, removed), line| match line.as_bytes().first() {
+        Some(b'+') => (added + 1, removed),
+        Some(b'-') => (added, removed + 1),
+        _ => (added, removed),
+    })
+}
+
+/// `diff` with added lines in the success color, removed lines in the error color and hunk headers dimmed
+pub fn paint_diff(diff: &str) -> String {
+    diff.lines()
+        .map(|line| match line.as_bytes().first() {
+            Some(b'+') => theme::paint(ThemeRole::Success, line),
+            Some(b'-') => theme::paint(ThemeRole::Error, line),
+            Some(b'@') => theme::paint(ThemeRole::Dim, line),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            unified_diff(old, new, 1).unwrap(),
+            "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -10,1 +10,2 @@\n j\n+k\n"
+        );
+        // With more context the two changes merge into one hunk
+        assert_eq!(unified_diff(old, new, 4).unwrap().matches("@@ -").count(), 1);
+        assert_eq!(diff_stats(&unified_diff(old, new, 3).unwrap()), (2, 1));
+
+        assert_eq!(unified_diff("", "x\n", 3).unwrap(), "@@ -0,0 +1,1 @@\n+x\n");
+        assert_eq!(unified_diff("x\ny\n", "y\n", 3).unwrap(), "@@ -1,2 +1,1 @@\n-x\n y\n");
+        assert_eq!(unified_diff("same\r\n", "same\n", 3), None);
+    }
+
+    #[test]
+    fn test_line_ops_keep_common_lines() {
+        let old = ["fn main() {", "    one();", "    two();", "}"];
+        let new = ["fn main() {", "    two();", "    three();", "}"];
+        assert_eq!(
+            line_ops(&old, &new),
+            vec![Op::Equal, Op::Delete, Op::Equal, Op::Insert, Op::Equal]
+        );
+    }
+}
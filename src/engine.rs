@@ -1,221 +1,23 @@
-use crate::config::{Config, ConfigManager};
-use crate::openrouter::{Message, OpenRouterClient};
-use crate::story::StoryLogger;
-use crate::tools::ToolExecutor;
+use crate::config::{Config, ConfigManager, ToolOutput, VERBOSE_SUMMARIES, VERBOSE_TOOL_RESULTS};
+use crate::openrouter::{Message, OpenRouterClient, OpenRouterResponse, ToolChoice};
+use crate::story::{format_duration, summarize_tool_result, StoryLogger};
+use crate::tools::error::classify as classify_tool_error;
+use crate::tools::batch::CallCoalescer;
+use crate::tools::deletes::{bulk_delete_denied, DeleteGuard};
+use crate::tools::{completion_summary, is_interrupted, ToolExecutor};
 use crate::commands::{execute_command, engine_commands};
 use crate::execution_stack::{ExecutionStack, StackRequest, StackResponse};
+use crate::input::{self, ChatAutocomplete};
+use crate::plan_display::{render_plan, ActionPlan, ActionStatus};
+use crate::theme::{self, ThemeRole};
 use crate::llm_schemas::{TaskDecompositionResponse, PlanActionDecompositionResponse, NestedPlanResponse, schema_examples, create_json_prompt};
-use serde_json::json;
 use uuid::Uuid;
-use inquire::{Text, Autocomplete};
-use std::fs;
-use std::path::Path;
+use inquire::{MultiSelect, Select};
+use std::io::IsTerminal;
+use std::time::Instant;
+use tracing::Instrument;
 
 
-#[derive(Clone)]
-struct CustomTextAutocomplete {
-    working_dir: String,
-}
-
-impl CustomTextAutocomplete {
-    fn new(working_dir: String) -> Self {
-        Self { 
-            working_dir,
-        }
-    }
-}
-
-impl Autocomplete for CustomTextAutocomplete {
-    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, inquire::CustomUserError> {
-        // Handle slash commands
-        if input.starts_with('/') {
-            let commands = vec![
-                "/clear".to_string(),
-                "/plan".to_string(),
-                "/model".to_string(),
-                "/list-models".to_string(),
-                "/stack-status".to_string(),
-                "/stack-execute".to_string(),
-                "/stack-clear".to_string(),
-                "/stack-auto".to_string(),
-                "/stack-push".to_string(),
-            ];
-            
-            let filtered: Vec<String> = commands
-                .into_iter()
-                .filter(|cmd| cmd.starts_with(input))
-                .collect();
-                
-            return Ok(filtered);
-        }
-        
-        // Handle filesystem autocomplete if '@' is present
-        if input.contains('@') {
-            let last_at = input.rfind('@').unwrap();
-            let before_at = &input[..last_at];
-            let after_at = &input[last_at + 1..];
-            
-            // Check if this is a folder path that should show contents
-            // If the path ends with '/' and we have an exact folder match, show contents
-            if after_at.ends_with('/') && !after_at.trim_end_matches('/').is_empty() {
-                let folder_contents = self.get_folder_contents(after_at);
-                
-                // If we have folder contents, show them as drilling suggestions
-                if !folder_contents.is_empty() {
-                    let drill_suggestions: Vec<String> = folder_contents
-                        .into_iter()
-                        .map(|item| format!("{}@{}{}", before_at, after_at, item))
-                        .collect();
-                        
-                    return Ok(drill_suggestions);
-                }
-            }
-            
-            // Regular filesystem autocomplete
-            let suggestions = self.get_file_suggestions(after_at);
-            
-            let full_suggestions: Vec<String> = suggestions
-                .into_iter()
-                .map(|suggestion| format!("{}@{}", before_at, suggestion))
-                .collect();
-                
-            return Ok(full_suggestions);
-        }
-        
-        // No suggestions for regular text
-        Ok(vec![])
-    }
-
-    fn get_completion(
-        &mut self,
-        _input: &str,
-        highlighted_suggestion: Option<String>,
-    ) -> Result<inquire::autocompletion::Replacement, inquire::CustomUserError> {
-        // Return partial replacement to allow continued typing
-        Ok(match highlighted_suggestion {
-            Some(suggestion) => inquire::autocompletion::Replacement::Some(suggestion),
-            None => inquire::autocompletion::Replacement::None,
-        })
-    }
-}
-
-impl CustomTextAutocomplete {
-    fn get_folder_contents(&self, folder_path: &str) -> Vec<String> {
-        // Remove trailing slash for directory access
-        let clean_path = folder_path.trim_end_matches('/');
-        let full_path = Path::new(&self.working_dir).join(clean_path);
-        let mut entries = Vec::new();
-
-        if let Ok(dir_entries) = fs::read_dir(&full_path) {
-            for entry in dir_entries.flatten() {
-                if let Ok(metadata) = entry.metadata() {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    
-                    // Skip hidden files unless specifically requested
-                    if name.starts_with('.') && !folder_path.contains("/.") {
-                        continue;
-                    }
-
-                    if metadata.is_dir() {
-                        entries.push(format!("{}/", name));
-                    } else {
-                        entries.push(name);
-                    }
-                }
-            }
-        }
-
-        // Sort: directories first, then files, both alphabetically
-        entries.sort_by(|a, b| {
-            let a_is_dir = a.ends_with('/');
-            let b_is_dir = b.ends_with('/');
-            match (a_is_dir, b_is_dir) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.cmp(b),
-            }
-        });
-
-        entries
-    }
-
-    fn get_file_suggestions(&self, partial_path: &str) -> Vec<String> {
-        if partial_path.is_empty() {
-            return self.list_directory(".");
-        }
-
-        let path = Path::new(partial_path);
-        let (dir_path, file_prefix) = if partial_path.ends_with('/') {
-            (partial_path.trim_end_matches('/').to_string(), String::new())
-        } else {
-            match path.parent() {
-                Some(parent) => {
-                    let parent_str = parent.to_string_lossy().to_string();
-                    let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-                    (parent_str, file_name)
-                }
-                None => (".".to_string(), partial_path.to_string()),
-            }
-        };
-
-        let dir_path_str = if dir_path.is_empty() { "." } else { &dir_path };
-        let entries = self.list_directory(dir_path_str);
-        
-        entries
-            .into_iter()
-            .filter(|entry| entry.starts_with(&file_prefix))
-            .collect()
-    }
-
-    fn list_directory(&self, relative_path: &str) -> Vec<String> {
-        let full_path = Path::new(&self.working_dir).join(relative_path);
-        let mut entries = Vec::new();
-
-        if let Ok(dir_entries) = fs::read_dir(&full_path) {
-            for entry in dir_entries.flatten() {
-                if let Ok(metadata) = entry.metadata() {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    
-                    // Skip hidden files unless specifically requested
-                    if name.starts_with('.') && !relative_path.contains("/.") {
-                        continue;
-                    }
-
-                    let entry_path = if relative_path == "." {
-                        if metadata.is_dir() {
-                            format!("{}/", name)
-                        } else {
-                            name
-                        }
-                    } else {
-                        let clean_relative_path = relative_path.trim_end_matches('/');
-                        if metadata.is_dir() {
-                            format!("{}/{}/", clean_relative_path, name)
-                        } else {
-                            format!("{}/{}", clean_relative_path, name)
-                        }
-                    };
-
-                    entries.push(entry_path);
-                }
-            }
-        }
-
-        // Sort: directories first, then files, both alphabetically
-        entries.sort_by(|a, b| {
-            let a_is_dir = a.ends_with('/');
-            let b_is_dir = b.ends_with('/');
-            match (a_is_dir, b_is_dir) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.cmp(b),
-            }
-        });
-
-        entries
-    }
-}
-
 pub struct LooEngine {
     pub openrouter_client: OpenRouterClient,
     pub tool_executor: ToolExecutor,
@@ -226,13 +28,15 @@ pub struct LooEngine {
     pub messages: Vec<Message>,
     pub execution_stack: ExecutionStack,
     pub auto_execute_stack: bool,
+    /// `delete_file` calls requested this turn, checked against `tools.max_deletes_per_turn`
+    delete_guard: DeleteGuard,
 }
 
 impl LooEngine {
     pub async fn new(
         working_dir: String,
         cli_model: Option<String>,
-        cli_verbose: bool,
+        cli_verbose: u8,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let mut config = ConfigManager::load_config()?;
         
@@ -242,14 +46,26 @@ impl LooEngine {
         }
         
         // Override verbose from CLI if provided
-        if cli_verbose {
-            config.preferences.verbose = true;
-        }
+        config.preferences.verbose = config.preferences.verbose.max(cli_verbose);
         
         let openrouter_client = OpenRouterClient::new(config.clone()).await?;
-        let tool_executor = ToolExecutor::new(working_dir.clone(), config.preferences.verbose);
+        let tool_executor = ToolExecutor::new(working_dir.clone(), config.preferences.verbose >= VERBOSE_TOOL_RESULTS)
+            .with_git_guard(config.tools.git_protected_branches.clone(), config.preferences.auto_confirm)
+            .with_command_approval(config.tools.confirm_commands)
+            .with_max_write_bytes(config.tools.max_write_bytes)
+            .with_max_output_lines(config.tools.max_output_lines)
+            .with_line_endings(config.tools.line_ending, config.tools.trailing_newline)
+            .with_format_on_write(config.tools.format_on_write)
+            .with_scaffold_dir(ConfigManager::scaffold_dir().ok())
+            .with_template_dirs(config.tools.template_dir.clone(), ConfigManager::file_templates_dir().ok())
+            .with_redaction(config.preferences.redact_secrets);
         let session_id = Uuid::new_v4().to_string();
-        let story_logger = StoryLogger::new(working_dir.clone(), session_id.clone());
+        let story_logger = StoryLogger::new(working_dir.clone(), session_id.clone())
+            .with_assistant_name(config.preferences.assistant_name.clone())
+            .with_file_output(config.preferences.write_story)
+            .with_format(config.preferences.story_format);
+        let mut execution_stack = ExecutionStack::new();
+        execution_stack.set_request_budget(config.preferences.max_stack_requests);
 
         Ok(Self {
             openrouter_client,
@@ -259,8 +75,9 @@ impl LooEngine {
             working_dir,
             session_id,
             messages: Vec::new(),
-            execution_stack: ExecutionStack::new(),
+            execution_stack,
             auto_execute_stack: true,
+            delete_guard: DeleteGuard::default(),
         })
     }
 
@@ -279,7 +96,7 @@ impl LooEngine {
                 Break down complex tasks into smaller, manageable steps. \
                 Always verify your work and provide clear explanations of what you're doing.",
                 self.working_dir, self.session_id
-            ),
+            ).into(),
             tool_calls: None,
             tool_call_id: None,
         };
@@ -287,7 +104,7 @@ impl LooEngine {
         // Add user message
         let user_message = Message {
             role: "user".to_string(),
-            content: user_prompt.to_string(),
+            content: user_prompt.to_string().into(),
             tool_calls: None,
             tool_call_id: None,
         };
@@ -317,10 +134,15 @@ impl LooEngine {
         let mut exit_attempts = 0;
         
         loop {
-            let user_input = Text::new("💬 You:")
-                .with_help_message("Type your message (Ctrl+C 3x to exit, Tab for autocomplete)")
-                .with_autocomplete(CustomTextAutocomplete::new(self.working_dir.clone()))
-                .prompt();
+            let user_input = input::prompt_message(
+                self.config.preferences.prompt.label(),
+                self.config.preferences.prompt.help("Type your message (Ctrl+C 3x to exit, Tab for autocomplete)"),
+                ChatAutocomplete::new(
+                    self.working_dir.clone(),
+                    self.config.preferences.autocomplete_max_entries,
+                    self.config.preferences.triggers(),
+                ),
+            );
 
             match user_input {
                 Ok(user_message) => {
@@ -332,13 +154,15 @@ impl LooEngine {
                     }
                     
                     // Handle special commands
-                    if user_message.starts_with('/') {
-                        self.handle_command(&user_message[1..]).await?;
+                    let triggers = self.config.preferences.triggers();
+                    if let Some(command_line) = input::command_line(user_message, triggers) {
+                        self.handle_command(command_line).await?;
                     } else {
+                        let user_message = input::unescape_command(user_message, triggers);
                         // Regular user message
                         let user_msg = Message {
                             role: "user".to_string(),
-                            content: user_message.to_string(),
+                            content: user_message.to_string().into(),
                             tool_calls: None,
                             tool_call_id: None,
                         };
@@ -349,6 +173,10 @@ impl LooEngine {
                         self.process_conversation_turn().await?;
                     }
                 }
+                Err(e) if input::is_end_of_input(&e) => {
+                    println!("\n👋 Input closed. Saving session story...");
+                    break;
+                }
                 Err(inquire::InquireError::OperationCanceled) => {
                     exit_attempts += 1;
                     if exit_attempts >= 3 {
@@ -380,7 +208,7 @@ impl LooEngine {
         // Generate story file at the end of session
         if let Err(e) = self.story_logger.write_story_file() {
             eprintln!("Warning: Failed to write story file: {}", e);
-        } else {
+        } else if self.story_logger.writes_file() {
             println!("📝 Session story saved to story.md");
         }
 
@@ -468,28 +296,46 @@ impl LooEngine {
     }
 
     async fn process_conversation_turn(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let turn_started = Instant::now();
+        let span = tracing::info_span!("conversation_turn", messages = self.messages.len());
+        self.delete_guard.start_turn();
+        let result = self.run_conversation_loop().instrument(span).await;
+        tracing::debug!(elapsed_ms = turn_started.elapsed().as_millis() as u64, "conversation turn finished");
+        self.story_logger.log_turn_completed(turn_started.elapsed());
+        result
+    }
+
+    async fn run_conversation_loop(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         // Main conversation processing loop
         loop {
             let response = self.openrouter_client
                 .chat_completion(self.messages.clone())
                 .await?;
 
+            if let Some(reasoning) = &response.choices[0].reasoning {
+                if self.config.preferences.verbose >= VERBOSE_SUMMARIES {
+                    println!("{}", theme::paint(ThemeRole::Dim, &format!("💭 {}", reasoning.trim())));
+                }
+                self.story_logger.log_reasoning(reasoning);
+            }
             let assistant_message = &response.choices[0].message;
             self.messages.push(assistant_message.clone());
 
             // Log assistant response if it has content
             if !assistant_message.content.is_empty() {
-                self.story_logger.log_assistant_response(&assistant_message.content);
+                self.story_logger.log_assistant_response(&assistant_message.content.text());
             }
 
             // Check if there are tool calls to execute
             if let Some(tool_calls) = &assistant_message.tool_calls {
-                if self.config.preferences.verbose || tool_calls.len() > 1 {
+                if self.config.preferences.verbose >= VERBOSE_SUMMARIES || tool_calls.len() > 1 {
                     println!("🤖 LLM making {} tool calls", tool_calls.len());
                 }
                 
+                let deletes_allowed = self.delete_guard.allow(tool_calls, &self.config, &self.story_logger);
+                let mut duplicates = CallCoalescer::new();
                 for tool_call in tool_calls {
-                    if self.config.preferences.verbose {
+                    if self.config.preferences.verbose >= VERBOSE_TOOL_RESULTS {
                         println!("  🔧 Executing: {}", tool_call.function.name);
                     } else {
                         println!("🔧 {}", tool_call.function.name);
@@ -500,32 +346,51 @@ impl LooEngine {
                         .unwrap_or(serde_json::Value::Null);
                     self.story_logger.log_tool_execution(&tool_call.function.name, &args);
                     
-                    match self.tool_executor.execute_tool_call(tool_call).await {
+                    let tool_started = Instant::now();
+                    let outcome = match duplicates.cached(tool_call) {
+                        Some(result) => {
+                            println!("  ♻️ Same as an earlier call in this response, reusing its result");
+                            Ok(result)
+                        }
+                        None if !deletes_allowed && tool_call.function.name == "delete_file" => {
+                            let path = args["path"].as_str().unwrap_or_default();
+                            Err(bulk_delete_denied(path, self.config.tools.max_deletes_per_turn).into())
+                        }
+                        None => self.tool_executor.execute_tool_call(tool_call).await,
+                    };
+                    let tool_duration = tool_started.elapsed();
+                    duplicates.record(tool_call, outcome.as_deref().ok());
+                    for pattern in self.tool_executor.take_new_approvals() {
+                        self.story_logger.log_command_approved(&pattern);
+                    }
+
+                    match outcome {
                         Ok(result) => {
-                            if self.config.preferences.verbose {
-                                println!("  ✅ Success: {}", result);
-                            } else {
-                                println!("  ✅");
+                            match self.config.preferences.tool_output_level() {
+                                ToolOutput::Full => println!("  ✅ Success in {}: {}", format_duration(tool_duration), result),
+                                ToolOutput::Summary => println!(
+                                    "  ✅ {} ({})",
+                                    summarize_tool_result(&tool_call.function.name, &result),
+                                    format_duration(tool_duration)
+                                ),
+                                ToolOutput::Minimal => println!("  ✅ {}", format_duration(tool_duration)),
                             }
 
                             // Check if the command was interrupted and log accordingly
-                            let was_interrupted = if let Ok(json_result) = serde_json::from_str::<serde_json::Value>(&result) {
-                                json_result["interrupted"].as_bool().unwrap_or(false)
-                            } else {
-                                false
-                            };
-
-                            if was_interrupted {
+                            if is_interrupted(&result) {
                                 self.story_logger.log_process_interrupted();
                             }
 
-                            // Log tool result
-                            self.story_logger.log_tool_result(&tool_call.function.name, true, &result);
+                            // Log tool result, plus the uncapped command output when verbose
+                            self.story_logger.log_tool_result(&tool_call.function.name, true, &result, tool_duration);
+                            if let Some(output) = self.tool_executor.take_full_output().filter(|_| self.config.preferences.verbose >= VERBOSE_TOOL_RESULTS) {
+                                self.story_logger.log_full_output(&output);
+                            }
                             
                             // Create tool response message
                             let tool_message = Message {
                                 role: "tool".to_string(),
-                                content: result.clone(),
+                                content: result.clone().into(),
                                 tool_calls: None,
                                 tool_call_id: Some(tool_call.id.clone()),
                             };
@@ -534,19 +399,27 @@ impl LooEngine {
                             // Check for completion
                             if tool_call.function.name == "complete" {
                                 println!("🎉 Project completed successfully!");
+                                if let Some(summary) = completion_summary(tool_call) {
+                                    println!("{} {}", self.config.preferences.assistant_label(), summary);
+                                }
                                 return Ok(());
                             }
                         }
                         Err(e) => {
-                            println!("  ❌ Error: {}", e);
+                            println!("  ❌ Error after {}: {}", format_duration(tool_duration), e);
 
                             // Log tool error
-                            self.story_logger.log_tool_result(&tool_call.function.name, false, &e.to_string());
+                            self.story_logger.log_tool_result(&tool_call.function.name, false, &e.to_string(), tool_duration);
+
+                            let tool_error = classify_tool_error(&tool_call.function.name, e.as_ref());
+                            if !tool_error.category.recoverable() {
+                                println!("  ✋ {} needs your attention ({})", tool_call.function.name, tool_error.category.as_str().replace('_', " "));
+                            }
                             
                             // Create error tool response
                             let error_message = Message {
                                 role: "tool".to_string(),
-                                content: json!({"status": "error", "message": e.to_string()}).to_string(),
+                                content: tool_error.to_json().to_string().into(),
                                 tool_calls: None,
                                 tool_call_id: Some(tool_call.id.clone()),
                             };
@@ -554,10 +427,13 @@ impl LooEngine {
                         }
                     }
                 }
+                if duplicates.coalesced > 0 {
+                    println!("♻️ Reused the result of {} repeated tool {}", duplicates.coalesced, if duplicates.coalesced == 1 { "call" } else { "calls" });
+                }
             } else {
                 // No more tool calls, LLM provided final response
                 if !assistant_message.content.is_empty() {
-                    println!("🤖 {}", assistant_message.content);
+                    println!("{} {}", self.config.preferences.assistant_label(), assistant_message.content);
                 }
                 break;
             }
@@ -582,10 +458,55 @@ impl LooEngine {
     }
 
     /// Push an action plan to the execution stack
-    pub fn push_action_plan(&mut self, plan: crate::plan_display::ActionPlan) -> Vec<String> {
+    pub fn push_action_plan(&mut self, plan: ActionPlan) -> Vec<String> {
         self.execution_stack.push_action_plan(plan, None)
     }
 
+    /// Show a generated plan's approval prompt, letting the user drop actions first. Returns false when cancelled.
+    /// `auto_confirm` approves without asking; without a terminal there is nobody to ask, so the plan is not run.
+    pub fn confirm_plan(&self, plan: &mut ActionPlan) -> bool {
+        const PROCEED: &str = "Proceed";
+        const EDIT: &str = "Edit (remove actions)";
+        const CANCEL: &str = "Cancel";
+
+        if self.config.preferences.auto_confirm {
+            return true;
+        }
+        if !std::io::stdin().is_terminal() {
+            println!("⚠️ No terminal to confirm the plan; set preferences.auto_confirm to run plans unattended");
+            return false;
+        }
+
+        loop {
+            match Select::new("Run this plan?", vec![PROCEED, EDIT, CANCEL]).prompt() {
+                Ok(PROCEED) => return true,
+                Ok(EDIT) => {
+                    let actions: Vec<String> = plan
+                        .phases
+                        .iter()
+                        .flat_map(|phase| &phase.actions)
+                        .map(|action| format!("{}. {} ({} → {})", action.id, action.title, action.tool, action.target))
+                        .collect();
+                    let Ok(removed) = MultiSelect::new("Select actions to remove:", actions).prompt() else {
+                        continue;
+                    };
+                    let ids: Vec<usize> = removed
+                        .iter()
+                        .filter_map(|label| label.split_once('.').and_then(|(id, _)| id.parse().ok()))
+                        .collect();
+                    plan.remove_actions(&ids);
+
+                    if plan.phases.is_empty() {
+                        println!("🗑️ Every action was removed");
+                        return false;
+                    }
+                    println!("\n{}\n", render_plan(plan));
+                }
+                _ => return false,
+            }
+        }
+    }
+
     /// Start the recursive execution loop
     pub async fn start_stack_execution(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if !self.auto_execute_stack {
@@ -603,15 +524,23 @@ impl LooEngine {
                 
                 // Mark request as started
                 self.execution_stack.start_processing(request.clone());
+                let plan_action = match &request {
+                    StackRequest::PlanAction { plan_id, action, .. } => Some((plan_id.clone(), action.id)),
+                    _ => None,
+                };
+                self.update_plan_progress(&plan_action, ActionStatus::InProgress);
                 
                 // Process the request
                 match self.process_stack_request(request).await {
                     Ok(response) => {
                         println!("✅ Request completed successfully");
+                        let status = if response.success { ActionStatus::Completed } else { ActionStatus::Failed };
+                        self.update_plan_progress(&plan_action, status);
                         self.execution_stack.push_response(response);
                     }
                     Err(e) => {
                         println!("❌ Request failed: {}", e);
+                        self.update_plan_progress(&plan_action, ActionStatus::Failed);
                         // Create error response
                         let error_response = StackResponse {
                             request_id,
@@ -624,8 +553,12 @@ impl LooEngine {
                     }
                 }
 
-                // Small delay to prevent overwhelming the LLM
-                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+                // Pause between requests so the LLM is not overwhelmed
+                let preferences = &self.config.preferences;
+                let delay = ExecutionStack::request_delay(preferences.stack_request_delay_ms, preferences.stack_request_jitter_ms, Uuid::new_v4().as_u128() as u64);
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
             }
         }
 
@@ -634,6 +567,16 @@ impl LooEngine {
         Ok(())
     }
 
+    /// Record a plan action's new status and redraw the plan's progress
+    fn update_plan_progress(&mut self, plan_action: &Option<(String, usize)>, status: ActionStatus) {
+        let Some((plan_id, action_id)) = plan_action else {
+            return;
+        };
+        if let Some(plan) = self.execution_stack.set_action_status(plan_id, *action_id, status) {
+            println!("\n{}\n", render_plan(plan));
+        }
+    }
+
     /// Process a single stack request
     async fn process_stack_request(&mut self, request: StackRequest) -> Result<StackResponse, Box<dyn std::error::Error>> {
         match request {
@@ -652,7 +595,7 @@ impl LooEngine {
     /// Process a user prompt request using structured JSON
     async fn process_user_prompt_request(&mut self, id: String, content: String) -> Result<StackResponse, Box<dyn std::error::Error>> {
         // Check if this is a simple request that can be executed directly
-        if self.is_executable_request(&content) {
+        if self.is_executable_request(&content) || self.budget_forces_direct() {
             return self.execute_direct_request(id, content).await;
         }
 
@@ -704,7 +647,7 @@ impl LooEngine {
     /// Process a plan action request using structured JSON
     async fn process_plan_action_request(&mut self, id: String, action: crate::plan_display::Action, context: String) -> Result<StackResponse, Box<dyn std::error::Error>> {
         // Check if this action is already executable
-        if self.is_action_executable(&action) {
+        if self.is_action_executable(&action) || self.budget_forces_direct() {
             return self.execute_plan_action(id, action).await;
         }
 
@@ -756,7 +699,7 @@ impl LooEngine {
     /// Process a nested plan request
     async fn process_nested_plan_request(&mut self, id: String, request: String, depth: u8) -> Result<StackResponse, Box<dyn std::error::Error>> {
         // For deeper recursion levels or simple tasks, try direct execution first
-        if depth >= 2 || self.is_executable_request(&request) {
+        if depth >= 2 || self.is_executable_request(&request) || self.budget_forces_direct() {
             return self.execute_direct_request(id, request).await;
         }
         
@@ -771,14 +714,14 @@ impl LooEngine {
                 2) If this requires multiple steps, break it into 2-3 specific, actionable sub-tasks. \
                 Prefer option 1 (direct execution) when possible.",
                 self.working_dir, request
-            ),
+            ).into(),
             tool_calls: None,
             tool_call_id: None,
         };
 
         let user_message = Message {
             role: "user".to_string(),
-            content: request.clone(),
+            content: request.clone().into(),
             tool_calls: None,
             tool_call_id: None,
         };
@@ -829,6 +772,18 @@ impl LooEngine {
         }
     }
 
+    /// When the request budget is nearly spent, skip decomposition so no more requests fan out
+    fn budget_forces_direct(&self) -> bool {
+        if !self.execution_stack.budget_low() {
+            return false;
+        }
+        println!(
+            "⚠️ Request budget nearly spent ({} left), executing directly without further decomposition",
+            self.execution_stack.remaining_budget().unwrap_or(0)
+        );
+        true
+    }
+
     /// Check if a request is executable without further decomposition
     fn is_executable_request(&self, request: &str) -> bool {
         let request_lower = request.to_lowercase();
@@ -882,7 +837,7 @@ impl LooEngine {
                 You MUST use the appropriate tools (create_file, create_directory, run_command, etc.) to implement this request. \
                 Do not just provide explanations - execute the actual implementation using tools.",
                 self.working_dir, request
-            ),
+            ).into(),
             tool_calls: None,
             tool_call_id: None,
         };
@@ -890,7 +845,7 @@ impl LooEngine {
         // Create user message
         let user_message = Message {
             role: "user".to_string(),
-            content: format!("Please implement this request using the available tools: {}", request),
+            content: format!("Please implement this request using the available tools: {}", request).into(),
             tool_calls: None,
             tool_call_id: None,
         };
@@ -903,6 +858,7 @@ impl LooEngine {
         self.messages = messages;
         
         let result = self.process_conversation_turn().await;
+        let summary = if result.is_ok() { self.completion_report().await } else { None };
         
         // Restore original messages
         self.messages = temp_messages;
@@ -912,7 +868,7 @@ impl LooEngine {
                 Ok(StackResponse {
                     request_id: id.clone(),
                     success: true,
-                    content: format!("Successfully executed: {}", request),
+                    content: summary.unwrap_or_else(|| format!("Successfully executed: {}", request)),
                     generated_requests: Vec::new(),
                     completed_actions: vec![id],
                 })
@@ -929,6 +885,33 @@ impl LooEngine {
         }
     }
 
+    /// Summary of the work in the current messages, from the model's `complete` call. A model that stopped
+    /// without calling it is made to, so the stack records what was done rather than a generic note.
+    async fn completion_report(&self) -> Option<String> {
+        let completed = self.messages.iter().rev()
+            .filter_map(|message| message.tool_calls.as_ref())
+            .flatten()
+            .find(|tool_call| tool_call.function.name == "complete");
+        if let Some(tool_call) = completed {
+            return completion_summary(tool_call);
+        }
+
+        let mut messages = self.messages.clone();
+        messages.push(Message {
+            role: "user".to_string(),
+            content: "Call complete with a one or two sentence summary of what was done.".into(),
+            tool_calls: None,
+            tool_call_id: None,
+        });
+        match self.openrouter_client.chat_completion_with_tool_choice(messages, ToolChoice::force("complete")).await {
+            Ok(response) => response.choices.first()?.message.tool_calls.as_ref()?.first().and_then(completion_summary),
+            Err(e) => {
+                tracing::debug!(error = %e, "no completion summary");
+                None
+            }
+        }
+    }
+
     /// Execute a plan action using tools
     async fn execute_plan_action(&mut self, id: String, action: crate::plan_display::Action) -> Result<StackResponse, Box<dyn std::error::Error>> {
         println!("⚙️ Executing plan action: {}", action.title);
@@ -950,22 +933,44 @@ impl LooEngine {
         // Create a temporary message set for decomposition
         let system_message = Message {
             role: "system".to_string(),
-            content: "You are a task decomposition expert. Break down complex requests into specific, actionable steps. Be concise and precise.".to_string(),
+            content: "You are a task decomposition expert. Break down complex requests into specific, actionable steps. Be concise and precise.".to_string().into(),
             tool_calls: None,
             tool_call_id: None,
         };
 
         let user_message = Message {
             role: "user".to_string(),
-            content: prompt.to_string(),
+            content: prompt.to_string().into(),
             tool_calls: None,
             tool_call_id: None,
         };
 
-        let temp_messages = vec![system_message, user_message];
-        
-        let response = self.openrouter_client.chat_completion(temp_messages).await?;
-        Ok(response.choices[0].message.content.clone())
+        let mut temp_messages = vec![system_message, user_message];
+
+        let response = self.openrouter_client.chat_completion(temp_messages.clone()).await?;
+        let content = Self::first_choice_text(&response);
+        if !content.trim().is_empty() {
+            return Ok(content);
+        }
+
+        // Tool-happy models sometimes answer with only a tool call; ask once more for plain JSON text
+        temp_messages.push(Message {
+            role: "user".to_string(),
+            content: "Respond with the JSON as plain text. Do not call any tools.".to_string().into(),
+            tool_calls: None,
+            tool_call_id: None,
+        });
+        let response = self.openrouter_client.chat_completion_with_tool_choice(temp_messages, ToolChoice::None).await?;
+        let content = Self::first_choice_text(&response);
+        if content.trim().is_empty() {
+            return Err("Model returned no text for the decomposition request, even after asking for JSON".into());
+        }
+        Ok(content)
+    }
+
+    /// Text of the first choice, empty when the model sent no choices or only tool calls
+    fn first_choice_text(response: &OpenRouterResponse) -> String {
+        response.choices.first().map(|choice| choice.message.content.text()).unwrap_or_default()
     }
 
     /// Parse LLM decomposition response into stack requests
@@ -1198,4 +1203,133 @@ impl LooEngine {
             completed_actions: Vec::new(),
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::path::Path;
+
+    fn tool_call(id: &str, name: &str, arguments: Value) -> Value {
+        json!({"id": id, "type": "function", "function": {"name": name, "arguments": arguments.to_string()}})
+    }
+
+    fn reply(message: Value, finish_reason: &str) -> String {
+        json!({"choices": [{"message": message, "finish_reason": finish_reason}]}).to_string()
+    }
+
+    /// An engine in `working_dir` talking to `server`, with nobody at the terminal
+    async fn test_engine(server: &mockito::ServerGuard, working_dir: &Path, configure: impl FnOnce(&mut Config)) -> LooEngine {
+        let working_dir = working_dir.to_string_lossy().to_string();
+        let mut config = Config::default();
+        config.openrouter.api_key = Some("sk-test".to_string());
+        config.openrouter.base_url = server.url();
+        config.preferences.spinner = false;
+        configure(&mut config);
+        LooEngine {
+            openrouter_client: OpenRouterClient::new(config.clone()).await.unwrap(),
+            tool_executor: ToolExecutor::new(working_dir.clone(), false).with_interactive(false),
+            story_logger: StoryLogger::new(working_dir.clone(), "test".to_string()).with_file_output(false),
+            config,
+            working_dir,
+            session_id: "test".to_string(),
+            messages: vec![Message { role: "user".to_string(), content: "Go".into(), tool_calls: None, tool_call_id: None }],
+            execution_stack: ExecutionStack::new(),
+            auto_execute_stack: false,
+            delete_guard: DeleteGuard::default().with_interactive(false),
+        }
+    }
+
+    fn tool_results(engine: &LooEngine) -> Vec<Value> {
+        engine
+            .messages
+            .iter()
+            .filter(|message| message.role == "tool")
+            .map(|message| serde_json::from_str(&message.content.text()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_deletes_past_the_limit_are_refused() {
+        let mut server = mockito::Server::new_async().await;
+        let delete = |id: &str, path: &str| tool_call(id, "delete_file", json!({"path": path}));
+        let deletes = server
+            .mock("POST", "/chat/completions")
+            .with_body(reply(json!({
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [delete("1", "a.txt"), delete("2", "b.txt"), delete("3", "c.txt")]
+            }), "tool_calls"))
+            .expect(1)
+            .create_async()
+            .await;
+        let done = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex("tool_call_id".to_string()))
+            .with_body(reply(json!({"role": "assistant", "content": "Done"}), "stop"))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let working_dir = tempfile::TempDir::new().unwrap();
+        for file in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(working_dir.path().join(file), "keep").unwrap();
+        }
+        let mut engine = test_engine(&server, working_dir.path(), |config| config.tools.max_deletes_per_turn = 2).await;
+
+        engine.process_conversation_turn().await.unwrap();
+
+        deletes.assert_async().await;
+        done.assert_async().await;
+        for file in ["a.txt", "b.txt", "c.txt"] {
+            assert!(working_dir.path().join(file).exists(), "{} was deleted", file);
+        }
+        let results = tool_results(&engine);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|result| result["message"].as_str().unwrap().contains("tools.max_deletes_per_turn")));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_calls_share_one_round_trip_and_keep_their_order() {
+        let mut server = mockito::Server::new_async().await;
+        let read = |id: &str| tool_call(id, "read_file", json!({"path": "notes.txt"}));
+        // Four calls arrive in one response because parallel tool calls are requested
+        let calls = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex(r#""parallel_tool_calls":true"#.to_string()))
+            .with_body(reply(json!({
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    read("1"),
+                    read("2"),
+                    tool_call("3", "write_file", json!({"path": "notes.txt", "content": "new"})),
+                    read("4"),
+                ]
+            }), "tool_calls"))
+            .expect(1)
+            .create_async()
+            .await;
+        let done = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex("tool_call_id".to_string()))
+            .with_body(reply(json!({"role": "assistant", "content": "Done"}), "stop"))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let working_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(working_dir.path().join("notes.txt"), "old").unwrap();
+        let mut engine = test_engine(&server, working_dir.path(), |_| {}).await;
+
+        engine.process_conversation_turn().await.unwrap();
+
+        // Four tool calls took two API round trips: the response carrying them and the one answering all four
+        calls.assert_async().await;
+        done.assert_async().await;
+        let contents = tool_results(&engine).iter().map(|result| result["content"].clone()).collect::<Vec<_>>();
+        // The write runs in its place, so only the read after it sees the new content
+        assert_eq!(contents, vec![json!("old"), json!("old"), Value::Null, json!("new")]);
+        assert_eq!(std::fs::read_to_string(working_dir.path().join("notes.txt")).unwrap(), "new");
+    }
+}
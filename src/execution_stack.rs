@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 use crate::plan_display::{ActionPlan, Action, ActionStatus};
 
 /// Represents different types of execution requests that can be stacked
@@ -9,6 +10,7 @@ pub enum StackRequest {
     UserPrompt {
         id: String,
         content: String,
+        /// 0 to `ExecutionStack::MAX_PRIORITY`; `ExecutionStack::URGENT_PRIORITY` and above jump ahead of queued requests
         priority: u8,
     },
     /// Plan action to be executed
@@ -44,6 +46,10 @@ pub struct ExecutionContext {
     pub max_depth: u8,
     pub active_plan_ids: Vec<String>,
     pub completed_action_ids: Vec<String>,
+    /// Most requests the stack may accept in a session (0 = unlimited)
+    pub max_requests: usize,
+    /// Requests accepted so far, counted against `max_requests`
+    pub requests_accepted: usize,
 }
 
 impl Default for ExecutionContext {
@@ -53,11 +59,17 @@ impl Default for ExecutionContext {
             max_depth: 5, // Prevent infinite recursion
             active_plan_ids: Vec::new(),
             completed_action_ids: Vec::new(),
+            max_requests: 0,
+            requests_accepted: 0,
         }
     }
 }
 
-/// Main execution stack for managing prompts and responses
+/// Main execution stack for managing prompts and responses.
+///
+/// Requests wait in one of two places. Prompts below `URGENT_PRIORITY` and plan actions are queued and run
+/// first in, first out. Urgent prompts and nested plans go on the priority stack, which is always drained
+/// first and runs last in, first out, so the newest urgent request is handled next.
 #[derive(Debug)]
 pub struct ExecutionStack {
     /// Queue of requests to be processed (FIFO for normal requests)
@@ -70,6 +82,8 @@ pub struct ExecutionStack {
     context: ExecutionContext,
     /// Next available ID for requests
     next_id: u64,
+    /// Plans pushed to the stack, keyed by plan ID, with live action statuses
+    plans: HashMap<String, ActionPlan>,
 }
 
 impl Default for ExecutionStack {
@@ -79,6 +93,15 @@ impl Default for ExecutionStack {
 }
 
 impl ExecutionStack {
+    /// Highest user prompt priority; larger values are clamped to it
+    pub const MAX_PRIORITY: u8 = 9;
+
+    /// Priority of prompts pushed without one
+    pub const DEFAULT_PRIORITY: u8 = 3;
+
+    /// Prompts at or above this priority go on the priority stack and run before anything queued
+    pub const URGENT_PRIORITY: u8 = 5;
+
     pub fn new() -> Self {
         Self {
             request_queue: VecDeque::new(),
@@ -86,6 +109,7 @@ impl ExecutionStack {
             history: Vec::new(),
             context: ExecutionContext::default(),
             next_id: 1,
+            plans: HashMap::new(),
         }
     }
 
@@ -96,8 +120,9 @@ impl ExecutionStack {
         id
     }
 
-    /// Push a user prompt to the stack
+    /// Push a user prompt, clamping `priority` to `MAX_PRIORITY`
     pub fn push_user_prompt(&mut self, content: String, priority: u8) -> String {
+        let priority = priority.min(Self::MAX_PRIORITY);
         let id = self.generate_id();
         let request = StackRequest::UserPrompt {
             id: id.clone(),
@@ -105,11 +130,12 @@ impl ExecutionStack {
             priority,
         };
 
-        if priority >= 5 {
+        if priority >= Self::URGENT_PRIORITY {
             self.priority_stack.push(request);
         } else {
             self.request_queue.push_back(request);
         }
+        self.context.requests_accepted += 1;
 
         println!("📥 Pushed user prompt to stack: {}", id);
         id
@@ -126,6 +152,7 @@ impl ExecutionStack {
         };
 
         self.request_queue.push_back(request);
+        self.context.requests_accepted += 1;
         println!("📥 Pushed plan action to stack: {}", id);
         id
     }
@@ -147,13 +174,13 @@ impl ExecutionStack {
 
         // Nested plans get priority to maintain execution flow
         self.priority_stack.push(nested_request);
+        self.context.requests_accepted += 1;
         println!("📥 Pushed nested plan to stack (depth {}): {}", depth, id);
         id
     }
 
-    /// Pop the next request to process (priority stack first, then queue)
+    /// Pop the next request to process: the newest entry of the priority stack, or else the oldest queued request
     pub fn pop_request(&mut self) -> Option<StackRequest> {
-        // Check priority stack first
         if let Some(request) = self.priority_stack.pop() {
             return Some(request);
         }
@@ -166,25 +193,42 @@ impl ExecutionStack {
     pub fn push_response(&mut self, response: StackResponse) {
         println!("📤 Processing response for request: {}", response.request_id);
 
+        // Requests past the depth limit are dropped before they count against the budget
+        let generated: Vec<&StackRequest> = response
+            .generated_requests
+            .iter()
+            .filter(|request| !matches!(request, StackRequest::NestedPlan { depth, .. } if *depth > self.context.max_depth))
+            .collect();
+
+        // Each response may spend at most half of the remaining budget, so fan-out shrinks as it runs out
+        let allowed = match self.remaining_budget() {
+            Some(remaining) => remaining.div_ceil(2).min(generated.len()),
+            None => generated.len(),
+        };
+        if allowed < generated.len() {
+            println!(
+                "⚠️ Request budget: keeping {} of {} generated requests ({} of {} used)",
+                allowed,
+                generated.len(),
+                self.context.requests_accepted,
+                self.context.max_requests
+            );
+        }
+
         // Add generated requests to the stack
-        for generated_request in &response.generated_requests {
+        for generated_request in generated.into_iter().take(allowed) {
             match generated_request {
-                StackRequest::UserPrompt { priority, .. } => {
-                    if *priority >= 5 {
-                        self.priority_stack.push(generated_request.clone());
-                    } else {
-                        self.request_queue.push_back(generated_request.clone());
-                    }
+                StackRequest::UserPrompt { priority, .. } if *priority >= Self::URGENT_PRIORITY => {
+                    self.priority_stack.push(generated_request.clone());
                 }
-                StackRequest::PlanAction { .. } => {
+                StackRequest::UserPrompt { .. } | StackRequest::PlanAction { .. } => {
                     self.request_queue.push_back(generated_request.clone());
                 }
-                StackRequest::NestedPlan { depth, .. } => {
-                    if *depth <= self.context.max_depth {
-                        self.priority_stack.push(generated_request.clone());
-                    }
+                StackRequest::NestedPlan { .. } => {
+                    self.priority_stack.push(generated_request.clone());
                 }
             }
+            self.context.requests_accepted += 1;
         }
 
         // Update completed actions
@@ -242,11 +286,50 @@ impl ExecutionStack {
             }
         }
 
-        self.context.active_plan_ids.push(plan_id);
+        self.context.active_plan_ids.push(plan_id.clone());
+        self.plans.insert(plan_id, plan);
         println!("📊 Added {} actions to execution stack", request_ids.len());
         request_ids
     }
 
+    /// Update the status of a plan action, returning the plan for display
+    pub fn set_action_status(&mut self, plan_id: &str, action_id: usize, status: ActionStatus) -> Option<&ActionPlan> {
+        let plan = self.plans.get_mut(plan_id)?;
+        let action = plan
+            .phases
+            .iter_mut()
+            .flat_map(|phase| phase.actions.iter_mut())
+            .find(|action| action.id == action_id)?;
+        action.status = status;
+        Some(plan)
+    }
+
+    /// Pause between two requests: `base_ms` plus up to `jitter_ms` more, with `random` choosing how much,
+    /// so several sessions sharing a rate limit do not fire in lockstep
+    pub fn request_delay(base_ms: u64, jitter_ms: u64, random: u64) -> Duration {
+        let jitter = if jitter_ms == 0 { 0 } else { jitter_ms.checked_add(1).map_or(random, |range| random % range) };
+        Duration::from_millis(base_ms.saturating_add(jitter))
+    }
+
+    /// Limit how many requests the stack accepts this session (0 = unlimited)
+    pub fn set_request_budget(&mut self, max_requests: usize) {
+        self.context.max_requests = max_requests;
+    }
+
+    /// Requests still allowed before the budget is spent, or `None` without a budget
+    pub fn remaining_budget(&self) -> Option<usize> {
+        if self.context.max_requests == 0 {
+            return None;
+        }
+        Some(self.context.max_requests.saturating_sub(self.context.requests_accepted))
+    }
+
+    /// True once three quarters of the budget is used; requests should then run without further decomposition
+    pub fn budget_low(&self) -> bool {
+        self.remaining_budget()
+            .is_some_and(|remaining| remaining * 4 <= self.context.max_requests)
+    }
+
     /// Check if the stack has any pending requests
     pub fn has_pending_requests(&self) -> bool {
         !self.request_queue.is_empty() || !self.priority_stack.is_empty()
@@ -266,7 +349,11 @@ impl ExecutionStack {
     pub fn clear_all(&mut self) {
         self.request_queue.clear();
         self.priority_stack.clear();
-        self.context = ExecutionContext::default();
+        self.plans.clear();
+        self.context = ExecutionContext {
+            max_requests: self.context.max_requests,
+            ..ExecutionContext::default()
+        };
         println!("🧹 Cleared all pending requests from stack");
     }
 
@@ -278,6 +365,7 @@ impl ExecutionStack {
             • Active plans: {}\n\
             • Completed actions: {}\n\
             • Current depth: {}/{}\n\
+            • Request budget: {}\n\
             • History entries: {}",
             self.pending_count(),
             self.request_queue.len(),
@@ -286,6 +374,10 @@ impl ExecutionStack {
             self.context.completed_action_ids.len(),
             self.context.current_depth,
             self.context.max_depth,
+            match self.context.max_requests {
+                0 => format!("{} used (unlimited)", self.context.requests_accepted),
+                max => format!("{}/{} used", self.context.requests_accepted, max),
+            },
             self.history.len()
         )
     }
@@ -318,6 +410,17 @@ mod tests {
     use super::*;
     use crate::plan_display::Phase;
 
+    #[test]
+    fn test_request_delay_stays_within_jitter() {
+        assert_eq!(ExecutionStack::request_delay(0, 0, 12345), Duration::ZERO);
+        assert_eq!(ExecutionStack::request_delay(500, 0, 12345), Duration::from_millis(500));
+        assert_eq!(ExecutionStack::request_delay(500, 200, 0), Duration::from_millis(500));
+        assert_eq!(ExecutionStack::request_delay(500, 200, 200), Duration::from_millis(700));
+        assert_eq!(ExecutionStack::request_delay(500, 200, 201), Duration::from_millis(500));
+        assert!((0..1000).all(|random| ExecutionStack::request_delay(100, 50, random * 7919) <= Duration::from_millis(150)));
+        assert_eq!(ExecutionStack::request_delay(0, u64::MAX, 42), Duration::from_millis(42));
+    }
+
     #[test]
     fn test_stack_basic_operations() {
         let mut stack = ExecutionStack::new();
@@ -353,6 +456,60 @@ mod tests {
         assert_eq!(stack.pending_count(), 0);
     }
 
+    #[test]
+    fn test_urgent_push_jumps_ahead_of_queued_requests() {
+        let mut stack = ExecutionStack::new();
+        let first = stack.push_user_prompt("Write docs".to_string(), 0);
+        let second = stack.push_user_prompt("Add tests".to_string(), ExecutionStack::URGENT_PRIORITY - 1);
+        let urgent = stack.push_user_prompt("Fix the crash".to_string(), ExecutionStack::URGENT_PRIORITY);
+        let clamped = stack.push_user_prompt("Revert the deploy".to_string(), 200);
+
+        let order: Vec<(String, u8)> = std::iter::from_fn(|| stack.pop_request())
+            .map(|request| match request {
+                StackRequest::UserPrompt { id, priority, .. } => (id, priority),
+                other => panic!("Expected UserPrompt, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            order,
+            vec![(clamped, ExecutionStack::MAX_PRIORITY), (urgent, ExecutionStack::URGENT_PRIORITY), (first, 0), (second, ExecutionStack::URGENT_PRIORITY - 1)]
+        );
+    }
+
+    #[test]
+    fn test_request_budget_caps_generated_requests() {
+        let mut stack = ExecutionStack::new();
+        stack.set_request_budget(6);
+
+        stack.push_user_prompt("Build an ambitious app".to_string(), 3);
+        let request = stack.pop_request().unwrap();
+        stack.start_processing(request);
+
+        let generated = (0..10)
+            .map(|i| StackRequest::UserPrompt { id: format!("sub_{}", i), content: format!("Step {}", i), priority: 3 })
+            .collect();
+        stack.push_response(StackResponse {
+            request_id: "req_1".to_string(),
+            success: true,
+            content: String::new(),
+            generated_requests: generated,
+            completed_actions: Vec::new(),
+        });
+
+        // 5 remaining, so at most 3 of the 10 generated requests are kept
+        assert_eq!(stack.pending_count(), 3);
+        assert_eq!(stack.remaining_budget(), Some(2));
+        assert!(!stack.budget_low());
+
+        stack.push_user_prompt("One more".to_string(), 3);
+        assert!(stack.budget_low());
+
+        let mut unlimited = ExecutionStack::new();
+        assert_eq!(unlimited.remaining_budget(), None);
+        unlimited.push_user_prompt("Anything".to_string(), 3);
+        assert!(!unlimited.budget_low());
+    }
+
     #[test]
     fn test_nested_plan_depth_limit() {
         let mut stack = ExecutionStack::new();
@@ -367,4 +524,39 @@ mod tests {
         assert!(id.is_empty());
         assert_eq!(stack.pending_count(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_plan_action_status_tracking() {
+        let mut stack = ExecutionStack::new();
+        let plan = ActionPlan {
+            title: "Plan".to_string(),
+            overview: "Overview".to_string(),
+            phases: vec![Phase {
+                name: "Phase".to_string(),
+                emoji: "🏗️".to_string(),
+                actions: vec![Action {
+                    id: 1,
+                    title: "Create file".to_string(),
+                    tool: "create_file".to_string(),
+                    target: "main.rs".to_string(),
+                    operation: "write".to_string(),
+                    purpose: "entry point".to_string(),
+                    success_criteria: "file exists".to_string(),
+                    dependencies: Vec::new(),
+                    status: ActionStatus::Pending,
+                }],
+            }],
+            expected_outcome: "Done".to_string(),
+        };
+
+        stack.push_action_plan(plan, None);
+        let plan_id = match stack.pop_request().unwrap() {
+            StackRequest::PlanAction { plan_id, .. } => plan_id,
+            _ => panic!("Expected PlanAction"),
+        };
+
+        let plan = stack.set_action_status(&plan_id, 1, ActionStatus::Completed).unwrap();
+        assert_eq!(plan.phases[0].actions[0].status, ActionStatus::Completed);
+        assert!(stack.set_action_status(&plan_id, 99, ActionStatus::Failed).is_none());
+    }
+}
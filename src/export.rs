@@ -0,0 +1,229 @@
+//! `loo export`: a recorded session's messages in another provider's chat format, for use in other agent tools
+//! Unlike story.md this keeps every message and tool call; unlike `/dump` it follows the target provider's schema
+
+use crate::openrouter::{ContentPart, Message, MessageContent};
+use crate::replay::resolve_session;
+use crate::session::SessionStore;
+use serde_json::{json, Map, Value};
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    OpenAi,
+    Anthropic,
+}
+
+impl ExportFormat {
+    pub const NAMES: [&'static str; 2] = ["openai", "anthropic"];
+
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name.to_lowercase().as_str() {
+            "openai" => Some(ExportFormat::OpenAi),
+            "anthropic" => Some(ExportFormat::Anthropic),
+            _ => None,
+        }
+    }
+}
+
+/// Chat Completions request body: loo already stores messages in this shape
+pub fn to_openai(messages: &[Message]) -> Value {
+    json!({ "messages": messages })
+}
+
+/// Messages API request body. System messages move to the top-level `system` field, tool calls become
+/// `tool_use` blocks, tool results become `tool_result` blocks in a user message, and consecutive messages
+/// with the same role are merged because the API expects user and assistant turns to alternate.
+pub fn to_anthropic(messages: &[Message]) -> Value {
+    let mut system = Vec::new();
+    let mut turns: Vec<(&str, Vec<Value>)> = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match message.role.as_str() {
+            "system" => {
+                system.push(message.content.text());
+                continue;
+            }
+            "tool" => ("user", vec![tool_result_block(message)]),
+            "assistant" => {
+                let mut blocks = content_blocks(&message.content);
+                blocks.extend(message.tool_calls.iter().flatten().map(|call| {
+                    json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.function.name,
+                        "input": tool_input(&call.function.arguments),
+                    })
+                }));
+                ("assistant", blocks)
+            }
+            _ => ("user", content_blocks(&message.content)),
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+
+        match turns.last_mut() {
+            Some((last_role, last_blocks)) if *last_role == role => last_blocks.extend(blocks),
+            _ => turns.push((role, blocks)),
+        }
+    }
+
+    let mut body = Map::new();
+    if !system.is_empty() {
+        body.insert("system".to_string(), json!(system.join("\n\n")));
+    }
+    let messages: Vec<Value> = turns.into_iter().map(|(role, content)| json!({ "role": role, "content": content })).collect();
+    body.insert("messages".to_string(), json!(messages));
+    Value::Object(body)
+}
+
+/// Text and image blocks, dropping empty text since the API rejects it
+fn content_blocks(content: &MessageContent) -> Vec<Value> {
+    let parts = match content {
+        MessageContent::Text(text) => vec![ContentPart::Text { text: text.clone() }],
+        MessageContent::Parts(parts) => parts.clone(),
+    };
+    parts
+        .into_iter()
+        .filter_map(|part| match part {
+            ContentPart::Text { text } if text.trim().is_empty() => None,
+            ContentPart::Text { text } => Some(json!({ "type": "text", "text": text })),
+            ContentPart::ImageUrl { image_url } => Some(image_block(&image_url.url)),
+        })
+        .collect()
+}
+
+fn image_block(url: &str) -> Value {
+    // Attached images are stored as `data:<media type>;base64,<data>` URLs
+    let inline = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"));
+    match inline {
+        Some((media_type, data)) => json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": media_type, "data": data },
+        }),
+        None => json!({ "type": "image", "source": { "type": "url", "url": url } }),
+    }
+}
+
+fn tool_result_block(message: &Message) -> Value {
+    let content = message.content.text();
+    let is_error = serde_json::from_str::<Value>(&content)
+        .is_ok_and(|result| result["status"] == "error");
+    let mut block = json!({
+        "type": "tool_result",
+        "tool_use_id": message.tool_call_id.clone().unwrap_or_default(),
+        "content": content,
+    });
+    if is_error {
+        block["is_error"] = json!(true);
+    }
+    block
+}
+
+/// `tool_use.input` must be an object; arguments the model garbled are kept as a string under `arguments`
+fn tool_input(arguments: &str) -> Value {
+    match serde_json::from_str::<Value>(arguments) {
+        Ok(Value::Object(input)) => Value::Object(input),
+        _ if arguments.trim().is_empty() => json!({}),
+        _ => json!({ "arguments": arguments }),
+    }
+}
+
+/// Export `source` in `format`, writing to `output` or printing to stdout
+pub fn run(source: &str, format: &str, output: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let format = ExportFormat::from_name(format).ok_or_else(|| {
+        format!("Unknown export format '{}', expected one of: {}", format, ExportFormat::NAMES.join(", "))
+    })?;
+    let store = SessionStore::new().ok();
+    let record = resolve_session(source, store.as_ref())?;
+
+    let exported = match format {
+        ExportFormat::OpenAi => to_openai(&record.messages),
+        ExportFormat::Anthropic => to_anthropic(&record.messages),
+    };
+    let exported = serde_json::to_string_pretty(&exported)?;
+
+    match output {
+        Some(path) => {
+            fs::write(path, exported).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+            println!("💾 Exported {} messages from session {} to {}", record.messages.len(), record.id, path);
+        }
+        None => println!("{}", exported),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openrouter::{ImageUrl, ToolCall, ToolCallFunction};
+
+    fn message(role: &str, content: &str) -> Message {
+        Message { role: role.to_string(), content: content.to_string().into(), tool_calls: None, tool_call_id: None }
+    }
+
+    #[test]
+    fn test_anthropic_export_shapes_tool_calls() {
+        let mut assistant = message("assistant", "Checking");
+        assistant.tool_calls = Some(vec![
+            ToolCall {
+                id: "call_1".to_string(),
+                call_type: "function".to_string(),
+                function: ToolCallFunction { name: "read_file".to_string(), arguments: r#"{"path":"a.rs"}"#.to_string() },
+            },
+            ToolCall {
+                id: "call_2".to_string(),
+                call_type: "function".to_string(),
+                function: ToolCallFunction { name: "read_file".to_string(), arguments: r#"{"path":"b.rs"}"#.to_string() },
+            },
+        ]);
+        let mut first = message("tool", r#"{"status":"success","content":"fn a() {}"}"#);
+        first.tool_call_id = Some("call_1".to_string());
+        let mut second = message("tool", r#"{"status":"error","category":"not_found"}"#);
+        second.tool_call_id = Some("call_2".to_string());
+        let messages = vec![
+            message("system", "Be brief"),
+            message("user", "Read the files"),
+            assistant,
+            first,
+            second,
+            message("assistant", "a.rs is empty"),
+        ];
+
+        let exported = to_anthropic(&messages);
+        assert_eq!(exported["system"], "Be brief");
+        let turns = exported["messages"].as_array().unwrap();
+        assert_eq!(turns.iter().map(|turn| turn["role"].as_str().unwrap()).collect::<Vec<_>>(), ["user", "assistant", "user", "assistant"]);
+        assert_eq!(turns[1]["content"][1], json!({"type": "tool_use", "id": "call_1", "name": "read_file", "input": {"path": "a.rs"}}));
+        assert_eq!(turns[2]["content"].as_array().unwrap().len(), 2);
+        assert_eq!(turns[2]["content"][0]["tool_use_id"], "call_1");
+        assert!(turns[2]["content"][0].get("is_error").is_none());
+        assert_eq!(turns[2]["content"][1]["is_error"], true);
+    }
+
+    #[test]
+    fn test_anthropic_export_images_and_arguments() {
+        let image = Message {
+            role: "user".to_string(),
+            content: MessageContent::Parts(vec![
+                ContentPart::Text { text: "What is this?".to_string() },
+                ContentPart::ImageUrl { image_url: ImageUrl { url: "data:image/png;base64,iVBORw0KGgo=".to_string() } },
+            ]),
+            tool_calls: None,
+            tool_call_id: None,
+        };
+        let exported = to_anthropic(&[image]);
+        assert_eq!(
+            exported["messages"][0]["content"][1]["source"],
+            json!({"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="})
+        );
+        assert!(exported.get("system").is_none());
+
+        assert_eq!(tool_input(""), json!({}));
+        assert_eq!(tool_input("{not json"), json!({"arguments": "{not json"}));
+        assert_eq!(ExportFormat::from_name("OpenAI"), Some(ExportFormat::OpenAi));
+        assert_eq!(ExportFormat::from_name("gemini"), None);
+    }
+}
@@ -0,0 +1,241 @@
+//! Chat prompt input
+//! Uses inquire with autocomplete on capable terminals and plain line reads everywhere else
+//!
+//! loo does no input rendering of its own: inquire draws and scrolls the line, including long unbroken
+//! pastes and wide characters, and plain mode passes the line through untouched.
+//!
+//! Raw mode is only held by inquire while a prompt is open, and raw mode turns off ISIG, so Ctrl+Z
+//! reaches the prompt as a key press instead of suspending loo with the terminal left raw. Between
+//! prompts the terminal is in its normal mode and SIGTSTP/SIGCONT need no special handling.
+
+use crate::attachments::complete_file_reference;
+use crate::commands::{get_autocomplete_commands, get_command_descriptions};
+use inquire::autocompletion::Replacement;
+use inquire::{Autocomplete, CustomUserError, InquireError, Text};
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// True for terminals that cannot interpret ANSI escapes (`TERM=dumb`)
+pub fn dumb_terminal() -> bool {
+    env::var("TERM").map(|term| term == "dumb").unwrap_or(false)
+}
+
+/// True when raw mode and cursor control are unavailable, e.g. CI logs, pipes and IDE consoles
+pub fn plain_input() -> bool {
+    dumb_terminal() || !io::stdin().is_terminal() || !io::stdout().is_terminal()
+}
+
+/// Prompt for one chat message, with autocomplete when the terminal supports it.
+/// An empty `help` shows no help line. End of input in plain mode is reported as an `UnexpectedEof` IO error.
+pub fn prompt_message<A: Autocomplete + 'static>(prompt: &str, help: &str, autocomplete: A) -> Result<String, InquireError> {
+    if !plain_input() {
+        let text = Text::new(prompt).with_autocomplete(autocomplete);
+        let text = if help.is_empty() { text } else { text.with_help_message(help) };
+        return text.prompt();
+    }
+
+    print!("{} ", prompt);
+    io::stdout().flush()?;
+    read_plain_line(&mut io::stdin().lock())
+}
+
+fn read_plain_line<R: BufRead>(reader: &mut R) -> Result<String, InquireError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Characters that start file reference and command autocomplete (`preferences.file_trigger` and
+/// `preferences.command_trigger`). Typing a trigger twice enters it literally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Triggers {
+    pub file: char,
+    pub command: char,
+}
+
+impl Default for Triggers {
+    fn default() -> Self {
+        Self { file: '@', command: '/' }
+    }
+}
+
+/// What the input typed so far is completing
+#[derive(Debug, PartialEq, Eq)]
+pub enum AutocompletePrefix<'a> {
+    /// A command name after a leading command trigger
+    Command(&'a str),
+    /// A path after the last file trigger, with the text typed before that trigger
+    File { before: &'a str, partial: &'a str },
+}
+
+/// The command or file reference being typed at the end of `input`, if any. A doubled trigger is an
+/// escaped literal and completes nothing.
+pub fn get_autocomplete_prefix(input: &str, triggers: Triggers) -> Option<AutocompletePrefix<'_>> {
+    if let Some(name) = input.strip_prefix(triggers.command) {
+        if !name.starts_with(triggers.command) {
+            return Some(AutocompletePrefix::Command(name));
+        }
+    }
+
+    let at = input.rfind(triggers.file)?;
+    let before = &input[..at];
+    // In a run of triggers pairs are literals, so only an odd run ends in a live one
+    let run = before.chars().rev().take_while(|c| *c == triggers.file).count();
+    (run % 2 == 0).then(|| AutocompletePrefix::File { before, partial: &input[at + triggers.file.len_utf8()..] })
+}
+
+/// Autocomplete for the chat prompt: registered commands with their descriptions after the command
+/// trigger, and paths under the working directory after the file trigger
+#[derive(Clone)]
+pub struct ChatAutocomplete {
+    working_dir: String,
+    max_entries: usize,
+    triggers: Triggers,
+}
+
+impl ChatAutocomplete {
+    pub fn new(working_dir: String, max_entries: usize, triggers: Triggers) -> Self {
+        Self {
+            working_dir,
+            max_entries,
+            triggers,
+        }
+    }
+
+    /// `/name  description` lines for the commands starting with `prefix`, names aligned
+    fn command_suggestions(&self, prefix: &str) -> Vec<String> {
+        let names = get_autocomplete_commands(prefix);
+        let descriptions = get_command_descriptions();
+        let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0);
+        names
+            .into_iter()
+            .map(|name| match descriptions.get(&name).filter(|description| !description.is_empty()) {
+                Some(description) => format!("{}{:<width$}  {}", self.triggers.command, name, description, width = width),
+                None => format!("{}{}", self.triggers.command, name),
+            })
+            .collect()
+    }
+}
+
+impl Autocomplete for ChatAutocomplete {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        Ok(match get_autocomplete_prefix(input, self.triggers) {
+            Some(AutocompletePrefix::Command(prefix)) => self.command_suggestions(prefix),
+            Some(AutocompletePrefix::File { before, partial }) => {
+                complete_file_reference(&self.working_dir, partial, self.max_entries)
+                    .into_iter()
+                    .map(|path| format!("{}{}{}", before, self.triggers.file, path))
+                    .collect()
+            }
+            // No suggestions for regular text or escaped triggers
+            None => Vec::new(),
+        })
+    }
+
+    fn get_completion(&mut self, input: &str, highlighted_suggestion: Option<String>) -> Result<Replacement, CustomUserError> {
+        Ok(match highlighted_suggestion {
+            // Command names have no spaces, so the description is everything after the first one
+            Some(suggestion) if matches!(get_autocomplete_prefix(input, self.triggers), Some(AutocompletePrefix::Command(_))) => {
+                Replacement::Some(suggestion.split_whitespace().next().unwrap_or_default().to_string())
+            }
+            Some(suggestion) => Replacement::Some(suggestion),
+            None => Replacement::None,
+        })
+    }
+}
+
+/// The command line of a submitted message that starts with the command trigger. A message starting with
+/// a doubled trigger is not a command; `unescape_command` turns it back into the text that was meant.
+pub fn command_line(message: &str, triggers: Triggers) -> Option<&str> {
+    message.strip_prefix(triggers.command).filter(|line| !line.starts_with(triggers.command))
+}
+
+/// `message` with a leading doubled command trigger reduced to one
+pub fn unescape_command(message: &str, triggers: Triggers) -> &str {
+    match message.strip_prefix(triggers.command) {
+        Some(rest) if rest.starts_with(triggers.command) => rest,
+        _ => message,
+    }
+}
+
+/// `text` with each doubled file trigger reduced to one, once references have been resolved
+pub fn unescape_file_triggers(text: &str, triggers: Triggers) -> String {
+    let trigger = triggers.file.to_string();
+    text.replace(&trigger.repeat(2), &trigger)
+}
+
+/// Whether an input error means stdin has closed and the session should end
+pub fn is_end_of_input(error: &InquireError) -> bool {
+    matches!(error, InquireError::IO(e) if e.kind() == io::ErrorKind::UnexpectedEof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_line_reads_until_eof() {
+        let mut reader = io::Cursor::new("fix the build\r\n");
+        assert_eq!(read_plain_line(&mut reader).unwrap(), "fix the build");
+        assert!(is_end_of_input(&read_plain_line(&mut reader).unwrap_err()));
+    }
+
+    #[test]
+    fn test_autocomplete_prefix_and_escapes() {
+        let triggers = Triggers::default();
+        assert_eq!(get_autocomplete_prefix("/hist", triggers), Some(AutocompletePrefix::Command("hist")));
+        assert_eq!(
+            get_autocomplete_prefix("edit @src/ma", triggers),
+            Some(AutocompletePrefix::File { before: "edit ", partial: "src/ma" })
+        );
+        assert_eq!(get_autocomplete_prefix("plain text", triggers), None);
+
+        // Doubled triggers are literals; a third one starts a reference again
+        assert_eq!(get_autocomplete_prefix("mail me@@", triggers), None);
+        assert_eq!(get_autocomplete_prefix("//etc/hosts", triggers), None);
+        assert_eq!(
+            get_autocomplete_prefix("@@@src", triggers),
+            Some(AutocompletePrefix::File { before: "@@", partial: "src" })
+        );
+        assert_eq!(command_line("/clear", triggers), Some("clear"));
+        assert_eq!(command_line("//etc/hosts is missing", triggers), None);
+        assert_eq!(unescape_command("//etc/hosts is missing", triggers), "/etc/hosts is missing");
+        assert_eq!(unescape_file_triggers("ping @@team about @@@x", triggers), "ping @team about @@x");
+
+        let triggers = Triggers { file: '#', command: '!' };
+        assert_eq!(get_autocomplete_prefix("/etc/ho", triggers), None);
+        assert_eq!(get_autocomplete_prefix("!mo", triggers), Some(AutocompletePrefix::Command("mo")));
+        assert_eq!(
+            get_autocomplete_prefix("a@b #sr", triggers),
+            Some(AutocompletePrefix::File { before: "a@b ", partial: "sr" })
+        );
+    }
+
+    #[test]
+    fn test_chat_autocomplete_inserts_only_the_completion() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("notes.md"), "").unwrap();
+        let mut autocomplete = ChatAutocomplete::new(temp_dir.path().to_string_lossy().to_string(), 0, Triggers::default());
+
+        assert_eq!(autocomplete.get_suggestions("read @no").unwrap(), vec!["read @notes.md"]);
+        assert!(autocomplete.get_suggestions("mail me@@no").unwrap().is_empty());
+        assert_eq!(
+            autocomplete.get_completion("read @no", Some("read @notes.md".to_string())).unwrap(),
+            Replacement::Some("read @notes.md".to_string())
+        );
+        // Command suggestions show their description, which stays out of the input
+        assert_eq!(
+            autocomplete.get_completion("/cl", Some("/clear  Clear conversation context".to_string())).unwrap(),
+            Replacement::Some("/clear".to_string())
+        );
+    }
+
+    #[test]
+    fn test_plain_line_keeps_long_and_wide_input_intact() {
+        let line = format!("{}漢字テスト e\u{301}\u{200b}", "x".repeat(5000));
+        let mut reader = io::Cursor::new(format!("{}\n", line));
+        assert_eq!(read_plain_line(&mut reader).unwrap(), line);
+    }
+}
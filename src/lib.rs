@@ -1,16 +1,31 @@
+pub mod attachments;
+pub mod autosave;
 pub mod cli;
+pub mod changes;
 pub mod commands;
+pub mod compaction;
 pub mod config;
+pub mod diff;
 pub mod engine;
 pub mod execution_stack;
+pub mod export;
+pub mod input;
 pub mod llm_intent_recognition;
 pub mod llm_schemas;
+pub mod logging;
 pub mod openrouter;
 pub mod plan_display;
 pub mod prompts;
+pub mod redaction;
+pub mod replay;
 pub mod semantic_engine;
+pub mod session;
+pub mod shutdown;
+pub mod spinner;
 pub mod story;
+pub mod theme;
 pub mod tools;
+pub mod watcher;
 
 // Re-export commonly used items
 pub use commands::{init_command_registry, execute_command, command_needs_engine};
\ No newline at end of file
@@ -59,23 +59,23 @@ Be flexible and understand natural variations in language."#;
         let messages = vec![
             Message {
                 role: "system".to_string(),
-                content: system_prompt.to_string(),
+                content: system_prompt.to_string().into(),
                 tool_calls: None,
                 tool_call_id: None,
             },
             Message {
                 role: "user".to_string(),
-                content: input.to_string(),
+                content: input.to_string().into(),
                 tool_calls: None,
                 tool_call_id: None,
             },
         ];
 
         let response = self.client.chat_completion(messages).await?;
-        let content = &response.choices[0].message.content;
+        let content = response.choices[0].message.content.text();
 
         // Parse the JSON response
-        let parsed: serde_json::Value = serde_json::from_str(content)
+        let parsed: serde_json::Value = serde_json::from_str(&content)
             .map_err(|e| format!("Failed to parse intent JSON: {} - Response: {}", e, content))?;
 
         let intent_str = parsed["intent"]
@@ -128,6 +128,30 @@ pub fn create_json_prompt(instruction: &str, schema_example: &str) -> String {
     )
 }
 
+/// Names accepted by `loo schema`, in the order they are listed
+pub const SCHEMA_NAMES: &[&str] = &["task_decomposition", "plan_action_decomposition", "nested_plan"];
+
+/// Response type and example sent to the model for a named schema
+pub fn schema_by_name(name: &str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "task_decomposition" => Some(("TaskDecompositionResponse", schema_examples::TASK_DECOMPOSITION)),
+        "plan_action_decomposition" => Some(("PlanActionDecompositionResponse", schema_examples::PLAN_ACTION_DECOMPOSITION)),
+        "nested_plan" => Some(("NestedPlanResponse", schema_examples::NESTED_PLAN)),
+        _ => None,
+    }
+}
+
+/// Check that a model response parses as the named schema's response type
+pub fn validate_response(name: &str, json: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match name {
+        "task_decomposition" => serde_json::from_str::<TaskDecompositionResponse>(json).map(|_| ())?,
+        "plan_action_decomposition" => serde_json::from_str::<PlanActionDecompositionResponse>(json).map(|_| ())?,
+        "nested_plan" => serde_json::from_str::<NestedPlanResponse>(json).map(|_| ())?,
+        _ => return Err(format!("Unknown schema: {} (use {})", name, SCHEMA_NAMES.join(", ")).into()),
+    }
+    Ok(())
+}
+
 /// Schema examples for prompt generation
 pub mod schema_examples {
     pub const TASK_DECOMPOSITION: &str = r#"{
@@ -153,8 +177,8 @@ pub mod schema_examples {
   "executable_steps": [
     {
       "step_id": "step_1",
-      "tool": "bash",
-      "operation": "run_command",
+      "tool": "run_command",
+      "operation": "list_files",
       "target": "ls -la",
       "parameters": {"working_dir": "./"},
       "validation": "Command should list directory contents",
@@ -180,7 +204,7 @@ pub mod schema_examples {
           {
             "action_id": "action_1",
             "title": "Check prerequisites",
-            "tool": "bash",
+            "tool": "run_command",
             "target": "system",
             "operation": "validate_environment",
             "parameters": null,
@@ -254,4 +278,14 @@ mod tests {
         assert!(response.is_executable);
         assert!(response.executable_action.is_some());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_schema_examples_match_response_types() {
+        for name in SCHEMA_NAMES {
+            let (_, example) = schema_by_name(name).unwrap();
+            validate_response(name, example).unwrap();
+        }
+        assert!(validate_response("nested_plan", r#"{"plan_title": "x"}"#).is_err());
+        assert!(schema_by_name("unknown").is_none());
+    }
+}
@@ -0,0 +1,28 @@
+//! Internal diagnostics
+//! `tracing` spans and events are forwarded to the `log` facade and written by env_logger, filtered by `RUST_LOG`.
+//! User-facing output keeps going to stdout/stderr directly and never passes through here.
+
+use env_logger::{Env, Target, WriteStyle};
+use std::fs::OpenOptions;
+
+/// Filter used for `--log-file` when `RUST_LOG` is unset: loo's own diagnostics plus span entry/exit
+const DEFAULT_FILE_FILTER: &str = "loo=debug,loo_cli=debug,tracing::span=debug";
+
+/// Install the logger. Without `RUST_LOG` nothing is logged unless `log_file` is given,
+/// in which case debug diagnostics are appended to that file instead of the terminal.
+pub fn init(log_file: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let default_filter = if log_file.is_some() { DEFAULT_FILE_FILTER } else { "off" };
+    let mut builder = env_logger::Builder::from_env(Env::default().default_filter_or(default_filter));
+
+    if let Some(path) = log_file {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open log file {}: {}", path, e))?;
+        builder.target(Target::Pipe(Box::new(file))).write_style(WriteStyle::Never);
+    }
+
+    builder.try_init()?;
+    Ok(())
+}
@@ -1,28 +1,93 @@
+mod attachments;
+mod autosave;
 mod cli;
+mod changes;
 mod commands;
+mod compaction;
 mod config;
+mod diff;
 mod engine;
 mod execution_stack;
+mod export;
+mod input;
 mod llm_intent_recognition;
 mod llm_schemas;
+mod logging;
 mod openrouter;
 mod plan_display;
 mod prompts;
+mod redaction;
+mod replay;
 mod semantic_engine;
+mod session;
+mod shutdown;
+mod spinner;
 mod story;
+mod theme;
 mod tools;
+mod watcher;
 
 use clap::Parser;
-use cli::{Cli, Commands, ConfigCommand};
-use config::ConfigManager;
+use cli::{Cli, Commands, ConfigCommand, SessionsCommand};
+use autosave::Autosaver;
+use commands::registry::parse_list_models_args;
+use config::{Config, ConfigManager, Provider};
+use inquire::validator::Validation;
+use inquire::{Password, PasswordDisplayMode, Select, Text};
+use openrouter::{ModelFilter, OpenRouterClient};
 use semantic_engine::SemanticEngine;
+use session::SessionStore;
+use shutdown::ShutdownHandler;
+use theme::ThemeRole;
 use llm_intent_recognition::{LLMIntentRecognizer, UserIntent};
+use std::env;
 use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::time::Duration;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    logging::init(cli.log_file.as_deref())?;
 
+    if let Some(path) = &cli.config {
+        ConfigManager::set_config_path(path)?;
+    }
+
+    // Environment variables are only set while the process is single-threaded, before the runtime starts
+    if cli.command.is_none() {
+        load_dotenv(&cli);
+    }
+
+    tokio::runtime::Runtime::new()?.block_on(run(cli))
+}
+
+/// Load `.env` from the session's working directory so `OPENROUTER_*` variables in it reach every config
+/// loaded afterwards. Only `preferences.load_dotenv` and `preferences.default_directory` are read first,
+/// and neither comes from the environment; a directory that cannot be resolved is reported by the session.
+fn load_dotenv(cli: &Cli) {
+    let Ok(config) = ConfigManager::load_config() else {
+        return;
+    };
+    if !config.preferences.load_dotenv {
+        return;
+    }
+    let Ok(working_dir) = resolve_working_dir(cli.dir.clone(), &config) else {
+        return;
+    };
+
+    let path = Path::new(&working_dir).join(".env");
+    if !path.is_file() {
+        return;
+    }
+    match dotenvy::from_path(&path) {
+        Ok(()) if cli.verbose > 0 => println!("🔑 Loaded variables from {}", path.display()),
+        Ok(()) => {}
+        Err(e) => eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: Cannot load {}: {}", path.display(), e))),
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     match cli.command {
         Some(Commands::Config { config_command }) => {
             match config_command {
@@ -39,9 +104,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
                 ConfigCommand::Validate => {
                     ConfigManager::validate_config()?;
+                    check_server_connectivity().await?;
+                }
+            }
+        }
+        Some(Commands::Quickstart) => {
+            run_quickstart(cli).await?;
+        }
+        Some(Commands::Version { verbose }) => {
+            print_version(verbose)?;
+        }
+        Some(Commands::Schema { name, validate }) => {
+            print_schema(&name, validate.as_deref())?;
+        }
+        Some(Commands::Replay { source, dir }) => {
+            let config = ConfigManager::load_config()?;
+            theme::init(&config.theme);
+            replay::run(&source, &dir, &config).await?;
+        }
+        Some(Commands::Sessions { sessions_command }) => {
+            let store = SessionStore::new()?;
+            match sessions_command {
+                SessionsCommand::List { dir } => {
+                    let dir = fs::canonicalize(dir.as_deref().unwrap_or("."))?.to_string_lossy().to_string();
+                    println!("{}", session::format_session_list(&dir, &store.list_for_dir(&dir)?, None));
+                }
+                SessionsCommand::Show { id } => {
+                    println!("{}", session::format_transcript(&store.find(&id)?));
                 }
             }
         }
+        Some(Commands::Export { source, format, output }) => {
+            export::run(&source, &format, output.as_deref())?;
+        }
         None => {
             // Start the new semantic conversation system
             start_semantic_chat(cli).await?;
@@ -51,20 +146,207 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Guided first run: create the config file, ask for the API key, default model and project directory,
+/// then start a session. Anything already configured is left alone, so running it again is harmless.
+async fn run_quickstart(mut cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    if !std::io::stdin().is_terminal() {
+        return Err("loo quickstart asks questions; in scripts use `loo config init` and `loo config set`".into());
+    }
+
+    let config_path = ConfigManager::config_path()?;
+    if config_path.exists() {
+        println!("✅ Using configuration at {}", config_path.display());
+    } else {
+        // Not init_config, whose hint to edit the file by hand is what the next steps replace
+        ConfigManager::save_config(&Config::default())?;
+        println!("✅ Created configuration at {}", config_path.display());
+    }
+
+    let config = ConfigManager::load_config()?;
+    if env::var("OPENROUTER_API_KEY").is_ok_and(|key| !key.trim().is_empty()) {
+        println!("✅ Using the API key from OPENROUTER_API_KEY");
+    } else if !config.openrouter.provider.requires_api_key() || config.openrouter.api_key.is_some() {
+        println!("✅ API key already configured");
+    } else {
+        let api_key = Password::new("OpenRouter API key:")
+            .without_confirmation()
+            .with_display_mode(PasswordDisplayMode::Masked)
+            .with_help_message("Create one at https://openrouter.ai/keys")
+            .with_validator(|key: &str| Ok(match key.trim().is_empty() {
+                true => Validation::Invalid("An API key is needed to talk to the model".into()),
+                false => Validation::Valid,
+            }))
+            .prompt()?;
+        ConfigManager::set_config_value("openrouter.api_key", api_key.trim())?;
+    }
+
+    let config = ConfigManager::load_config()?;
+    let default_directory = config.preferences.default_directory.clone();
+    if cli.model.is_some() || config.openrouter.model != Config::default().openrouter.model {
+        println!("✅ Model: {}", cli.model.as_ref().unwrap_or(&config.openrouter.model));
+    } else {
+        let current = config.openrouter.model.clone();
+        // loo works through tool calls, so only offer models that accept them
+        let filter = ModelFilter { tools: config.openrouter.provider == Provider::OpenRouter, vision: false };
+        match OpenRouterClient::new(config).await?.list_models("", filter, false).await {
+            Ok(models) if !models.is_empty() => {
+                let cursor = models.iter().position(|model| *model == current).unwrap_or(0);
+                let model = Select::new("Default model (type to filter):", models)
+                    .with_starting_cursor(cursor)
+                    .with_page_size(12)
+                    .prompt()?;
+                ConfigManager::set_config_value("openrouter.model", &model)?;
+            }
+            Ok(_) => println!("✅ Model: {}", current),
+            Err(e) => eprintln!("{}", theme::paint(ThemeRole::Warning, &format!(
+                "⚠️ Could not fetch the model list ({}); keeping {}. Change it later with `loo config set openrouter.model <id>`",
+                e, current
+            ))),
+        }
+    }
+
+    if cli.dir.is_none() && default_directory.is_none() {
+        let current = env::current_dir()?.to_string_lossy().to_string();
+        let dir = Text::new("Project directory:")
+            .with_default(&current)
+            .with_help_message("loo reads and edits files here; saved as preferences.default_directory, --dir overrides it")
+            .with_validator(|dir: &str| Ok(match Path::new(dir).is_dir() {
+                true => Validation::Valid,
+                false => Validation::Invalid("Not a directory".into()),
+            }))
+            .prompt()?;
+        let dir = fs::canonicalize(&dir)?.to_string_lossy().to_string();
+        ConfigManager::set_config_value("preferences.default_directory", &dir)?;
+        cli.dir = Some(dir);
+    }
+
+    println!();
+    start_semantic_chat(cli).await
+}
+
+/// OpenAI-compatible servers are usually local, so validation also checks that one is answering
+async fn check_server_connectivity() -> Result<(), Box<dyn std::error::Error>> {
+    let config = ConfigManager::load_config()?;
+    if config.openrouter.provider != Provider::OpenAiCompatible {
+        return Ok(());
+    }
+
+    let client = OpenRouterClient::new(config).await?;
+    match client.check_connectivity().await? {
+        Some(count) => println!("✅ Server is reachable ({} models)", count),
+        None => println!("✅ Server is reachable (models list is not in the OpenAI format)"),
+    }
+    Ok(())
+}
+
+fn print_schema(name: &str, validate: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let (type_name, example) = llm_schemas::schema_by_name(name).ok_or_else(|| {
+        format!("Unknown schema: {} (use {})", name, llm_schemas::SCHEMA_NAMES.join(", "))
+    })?;
+
+    match validate {
+        Some(path) => {
+            let response = fs::read_to_string(path)?;
+            llm_schemas::validate_response(name, &response)
+                .map_err(|e| format!("{} does not match {}: {}", path, type_name, e))?;
+            println!("✅ {} matches {}", path, type_name);
+        }
+        None => {
+            println!("# {} ({})", name, type_name);
+            println!("{}", example);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_version(verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    println!("loo {}", env!("CARGO_PKG_VERSION"));
+
+    if !verbose {
+        return Ok(());
+    }
+
+    let config = ConfigManager::load_config()?;
+    let has_api_key = config.openrouter.api_key.is_some()
+        || std::env::var("OPENROUTER_API_KEY").is_ok();
+
+    println!("🔧 Git commit: {}", env!("LOO_GIT_COMMIT"));
+    println!("🔧 Rustc: {}", env!("LOO_RUSTC_VERSION"));
+    println!("🔧 Config file: {}", ConfigManager::config_path()?.display());
+    println!("🔧 Default model: {}", config.openrouter.model);
+    println!("🔧 Base URL: {}", config.openrouter.base_url);
+    println!("🔧 API key: {}", if has_api_key { "detected" } else { "not found" });
+
+    Ok(())
+}
+
+/// Working directory for a chat: `--dir`, then `preferences.default_directory`, then the current directory.
+/// Canonicalized, so story.md and saved sessions always refer to the same absolute path.
+fn resolve_working_dir(cli_dir: Option<String>, config: &Config) -> Result<String, Box<dyn std::error::Error>> {
+    let (dir, source) = match (cli_dir, &config.preferences.default_directory) {
+        (Some(dir), _) => (dir, "--dir"),
+        (None, Some(dir)) => (dir.clone(), "preferences.default_directory"),
+        (None, None) => (".".to_string(), "current directory"),
+    };
+    match fs::canonicalize(&dir) {
+        Ok(path) if path.is_dir() => Ok(path.to_string_lossy().to_string()),
+        Ok(_) => Err(format!("{} '{}' is not a directory", source, dir).into()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(format!("{} '{}' does not exist", source, dir).into()),
+        Err(e) => Err(format!("{} '{}' cannot be opened: {}", source, dir, e).into()),
+    }
+}
+
 async fn start_semantic_chat(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     let config = ConfigManager::load_config()?;
-    
-    // Determine working directory from CLI, config, or current directory
-    let working_dir = cli.dir
-        .or_else(|| config.preferences.default_directory.clone())
-        .unwrap_or_else(|| ".".to_string());
-    
-    let working_dir = fs::canonicalize(&working_dir)?
-        .to_string_lossy()
-        .to_string();
-
-    let mut engine = SemanticEngine::new(working_dir.clone(), cli.model, cli.verbose).await?;
-    let intent_recognizer = LLMIntentRecognizer::new(engine.openrouter_client.clone());
+
+    let working_dir = match resolve_working_dir(cli.dir, &config) {
+        Ok(working_dir) => working_dir,
+        Err(e) => {
+            eprintln!("{}", theme::paint(ThemeRole::Error, &format!("❌ Cannot start a session: {}", e)));
+            std::process::exit(1);
+        }
+    };
+
+    commands::init_command_registry();
+    for warning in commands::register_templates(&config.templates) {
+        eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: {}", warning)));
+    }
+    theme::init(&config.theme);
+
+    let mut engine = SemanticEngine::new(working_dir.clone(), cli.model, cli.max_tokens, cli.seed, cli.choices, cli.verbose, cli.no_story)
+        .await?
+        .with_system_additions(cli.append_system)
+        .with_context_files(cli.context_files);
+    // Fail before the banner rather than on the first message
+    if !cli.skip_preflight {
+        if let Err(e) = engine.openrouter_client.preflight().await {
+            eprintln!("{}", theme::paint(ThemeRole::Error, &format!("❌ Cannot start a session: {}", e)));
+            eprintln!("💡 Check openrouter.api_key and openrouter.base_url with `loo config get` and fix them with `loo config set`,");
+            eprintln!("   or pass --skip-preflight to start anyway");
+            std::process::exit(1);
+        }
+    }
+    let intent_recognizer = config
+        .preferences
+        .intent_recognition
+        .then(|| LLMIntentRecognizer::new(engine.openrouter_client.clone()));
+
+    // Process managers and CI timeouts stop loo with SIGTERM, and --timeout ends the run; save like a normal exit when they do
+    let mut shutdown = ShutdownHandler::new(engine.story_logger.clone());
+    shutdown.track_processes(engine.tool_executor.background_processes());
+    if let Err(e) = shutdown.listen_for_signals() {
+        eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: Cannot handle termination signals: {}", e)));
+    }
+    if let Some(secs) = cli.timeout {
+        shutdown.exit_after(Duration::from_secs(secs));
+    }
+
+    // With an autosave interval, state is flushed in the background instead of after every turn
+    let autosaver = match config.preferences.autosave_interval_secs {
+        0 => None,
+        secs => Some(Autosaver::start(Duration::from_secs(secs))),
+    };
 
     println!("🚀 Starting LOO with Semantic Intelligence");
     println!("📁 Working directory: {}", working_dir);
@@ -72,25 +354,38 @@ async fn start_semantic_chat(cli: Cli) -> Result<(), Box<dyn std::error::Error>>
     println!();
     println!("🎯 Intelligent conversation mode activated!");
     println!("💡 Tips:");
-    println!("   • Just talk naturally - I'll understand what you want to do");
-    println!("   • Say 'clear context' to reset our conversation");
-    println!("   • Say 'change model to <name>' to switch AI models");
-    println!("   • Say 'list models' to see available models");
-    println!("   • Use @ for file path autocomplete (e.g., 'edit @src/main.rs')");
+    let (file, command) = (config.preferences.file_trigger, config.preferences.command_trigger);
+    if intent_recognizer.is_some() {
+        println!("   • Just talk naturally - I'll understand what you want to do");
+        println!("   • Say 'clear context' to reset our conversation");
+        println!("   • Say 'change model to <name>' to switch AI models");
+        println!("   • Say 'list models' to see available models");
+    } else {
+        println!("   • Use {c}clear to reset our conversation, {c}model <name> to switch AI models and {c}list-models to see them", c = command);
+    }
+    println!("   • Use {f}path to include a file's contents, with Tab autocomplete (e.g., 'edit {f}src/main.rs')", f = file);
+    println!("   • Attach images for vision models with {f}image:path (e.g., 'what is wrong here? {f}image:shot.png')", f = file);
+    println!("   • Use {c}remember <note> to keep a note across sessions, {c}memory to review it", c = command);
+    println!("   • Use {c}dump <path> to save the raw API message history for bug reports", c = command);
+    println!("   • Type {f}{f} or {c}{c} for a literal {f} or {c}", f = file, c = command);
+    if !config.templates.is_empty() {
+        let names: Vec<String> = config.templates.keys().map(|name| format!("{}{}", command, name)).collect();
+        println!("   • Your prompt templates: {}", names.join(", "));
+    }
     println!("   • Press Ctrl+C three times to exit");
     println!();
 
     // Interactive chat loop with semantic understanding
     let mut exit_attempts = 0;
-    
+    let mut turns = 0;
+    let triggers = config.preferences.triggers();
+
     loop {
-        use inquire::Text;
-        use crate::semantic_engine::CustomTextAutocomplete;
-        
-        let user_input = Text::new("💬 You:")
-            .with_help_message("Speak naturally (Ctrl+C 3x to exit, Tab for autocomplete)")
-            .with_autocomplete(CustomTextAutocomplete::new(working_dir.clone()))
-            .prompt();
+        let user_input = input::prompt_message(
+            config.preferences.prompt.label(),
+            config.preferences.prompt.help("Speak naturally (Ctrl+C 3x to exit, Tab for autocomplete)"),
+            input::ChatAutocomplete::new(engine.working_dir.clone(), config.preferences.autocomplete_max_entries, triggers),
+        );
 
         match user_input {
             Ok(user_message) => {
@@ -100,41 +395,79 @@ async fn start_semantic_chat(cli: Cli) -> Result<(), Box<dyn std::error::Error>>
                 if user_message.is_empty() {
                     continue;
                 }
+
+                // Slash commands bypass intent recognition
+                if let Some(command_line) = input::command_line(user_message, triggers) {
+                    engine.handle_command(command_line).await?;
+                    continue;
+                }
+                let user_message = input::unescape_command(user_message, triggers);
                 
                 // Recognize user intent using LLM instead of parsing commands
-                let intent = match intent_recognizer.recognize_intent(user_message).await {
-                    Ok(intent) => intent,
-                    Err(e) => {
-                        println!("⚠️ Intent recognition failed: {}, using regular conversation", e);
-                        UserIntent::RegularConversation(user_message.to_string())
-                    }
+                let intent = match intent_recognizer.as_ref().map(|recognizer| recognizer.recognize_intent(user_message)) {
+                    None => UserIntent::RegularConversation(user_message.to_string()),
+                    Some(recognition) => match recognition.await {
+                        Ok(intent) => intent,
+                        Err(e) => {
+                            println!("{}", theme::paint(ThemeRole::Warning, &format!("⚠️ Intent recognition failed: {}, using regular conversation", e)));
+                            UserIntent::RegularConversation(user_message.to_string())
+                        }
+                    },
                 };
                 
                 match intent {
                     UserIntent::ClearContext => {
-                        let result = engine.clear_context();
+                        let result = engine.clear_context(false);
                         println!("{}", result);
                     }
                     UserIntent::ChangeModel(model) => {
                         match engine.change_model(&model).await {
                             Ok(result) => println!("{}", result),
-                            Err(e) => println!("❌ {}", e),
+                            Err(e) => println!("{}", theme::paint(ThemeRole::Error, &format!("❌ {}", e))),
                         }
                     }
                     UserIntent::ListModels(search_term) => {
-                        let search = search_term.unwrap_or_default();
-                        match engine.list_models(&search).await {
+                        let query = parse_list_models_args(&search_term.unwrap_or_default());
+                        match engine.list_models(&query).await {
                             Ok(result) => println!("{}", result),
-                            Err(e) => println!("❌ {}", e),
+                            Err(e) => println!("{}", theme::paint(ThemeRole::Error, &format!("❌ {}", e))),
                         }
                     }
                     _ => {
                         // Process all other intents through semantic conversation
                         if let Err(e) = engine.process_conversation(user_message).await {
-                            println!("❌ Error: {}", e);
+                            println!("{}", theme::paint(ThemeRole::Error, &format!("❌ Error: {}", e)));
                         }
                     }
                 }
+
+                if let Some(autosaver) = &autosaver {
+                    autosaver.publish(engine.autosave_snapshot());
+                } else if let Err(e) = engine.save_session() {
+                    eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: Failed to save session: {}", e)));
+                }
+                shutdown.publish(engine.session_record());
+
+                if let Some(exhausted) = engine.openrouter_client.token_budget_exhausted() {
+                    engine.story_logger.log_guard_triggered(&format!("Session ended: {}", exhausted));
+                    println!("\n{}", theme::paint(ThemeRole::Warning, &format!("🛑 {}. Saving session story...", exhausted)));
+                    break;
+                }
+
+                turns += 1;
+                let max_turns = config.preferences.max_turns;
+                if max_turns > 0 && turns >= max_turns {
+                    engine.story_logger.log_guard_triggered(&format!("Session ended after reaching the limit of {} messages (preferences.max_turns)", max_turns));
+                    println!("\n{}", theme::paint(ThemeRole::Warning, &format!("🛑 This session has reached its limit of {} messages. Thanks for chatting! Saving session story...", max_turns)));
+                    break;
+                }
+                if max_turns > 0 && turns + 1 == max_turns {
+                    println!("{}", theme::paint(ThemeRole::Warning, "⚠️ One message left in this session"));
+                }
+            }
+            Err(e) if input::is_end_of_input(&e) => {
+                println!("\n👋 Input closed. Saving session story...");
+                break;
             }
             Err(inquire::InquireError::OperationCanceled) => {
                 exit_attempts += 1;
@@ -142,7 +475,7 @@ async fn start_semantic_chat(cli: Cli) -> Result<(), Box<dyn std::error::Error>>
                     println!("\n👋 Goodbye! Saving session story...");
                     break;
                 } else {
-                    println!("\n⚠️ Press Ctrl+C {} more time(s) to exit", 3 - exit_attempts);
+                    println!("\n{}", theme::paint(ThemeRole::Warning, &format!("⚠️ Press Ctrl+C {} more time(s) to exit", 3 - exit_attempts)));
                     continue;
                 }
             }
@@ -152,22 +485,29 @@ async fn start_semantic_chat(cli: Cli) -> Result<(), Box<dyn std::error::Error>>
                     println!("\n👋 Goodbye! Saving session story...");
                     break;
                 } else {
-                    println!("\n⚠️ Press Ctrl+C {} more time(s) to exit", 3 - exit_attempts);
+                    println!("\n{}", theme::paint(ThemeRole::Warning, &format!("⚠️ Press Ctrl+C {} more time(s) to exit", 3 - exit_attempts)));
                     continue;
                 }
             }
             Err(e) => {
-                println!("❌ Input error: {}", e);
+                println!("{}", theme::paint(ThemeRole::Error, &format!("❌ Input error: {}", e)));
                 exit_attempts = 0;
                 continue;
             }
         }
     }
 
+    // Stop periodic saving before the final write so the two never overlap
+    drop(autosaver);
+
+    if let Err(e) = engine.save_session() {
+        eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: Failed to save session: {}", e)));
+    }
+
     // Generate story file at the end of session
     if let Err(e) = engine.story_logger.write_story_file() {
-        eprintln!("Warning: Failed to write story file: {}", e);
-    } else {
+        eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: Failed to write story file: {}", e)));
+    } else if engine.story_logger.writes_file() {
         println!("📝 Session story saved to story.md");
     }
 
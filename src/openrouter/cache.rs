@@ -0,0 +1,91 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default lifetime of the cached models catalog, in seconds
+pub const DEFAULT_MODELS_CACHE_TTL: u64 = 6 * 60 * 60;
+
+/// Raw `/models` response saved alongside when and where it was fetched
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedModels {
+    pub fetched_at: DateTime<Utc>,
+    pub base_url: String,
+    pub response: String,
+}
+
+/// On-disk cache for the models catalog, shared by every session
+pub struct ModelsCache {
+    path: PathBuf,
+}
+
+impl ModelsCache {
+    /// Open the default cache under the user's cache directory
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = dirs::cache_dir()
+            .ok_or("Could not find cache directory")?
+            .join("loo")
+            .join("models.json");
+        Ok(Self::with_path(path))
+    }
+
+    pub fn with_path<P: AsRef<Path>>(path: P) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Cached response for `base_url` if it is younger than `ttl_secs`
+    pub fn load(&self, base_url: &str, ttl_secs: u64) -> Option<CachedModels> {
+        let cached = self.load_any_age(base_url)?;
+        let ttl = Duration::seconds(i64::try_from(ttl_secs).unwrap_or(i64::MAX));
+        let fresh = Utc::now().signed_duration_since(cached.fetched_at) < ttl;
+        fresh.then_some(cached)
+    }
+
+    /// Cached response for `base_url` however old it is, the fallback when the server cannot be reached
+    pub fn load_any_age(&self, base_url: &str) -> Option<CachedModels> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        let cached: CachedModels = serde_json::from_str(&content).ok()?;
+        (cached.base_url == base_url).then_some(cached)
+    }
+
+    pub fn store(&self, base_url: &str, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let cached = CachedModels {
+            fetched_at: Utc::now(),
+            base_url: base_url.to_string(),
+            response: response.to_string(),
+        };
+        fs::write(&self.path, serde_json::to_string(&cached)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_respects_ttl_and_base_url() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = ModelsCache::with_path(temp_dir.path().join("models.json"));
+
+        assert!(cache.load("https://openrouter.ai/api/v1", 60).is_none());
+
+        cache.store("https://openrouter.ai/api/v1", r#"{"data":[]}"#).unwrap();
+        let cached = cache.load("https://openrouter.ai/api/v1", 60).unwrap();
+        assert_eq!(cached.response, r#"{"data":[]}"#);
+
+        assert!(cache.load("https://api.example.com", 60).is_none());
+        assert!(cache.load("https://openrouter.ai/api/v1", 0).is_none());
+        assert!(cache.load_any_age("https://openrouter.ai/api/v1").is_some());
+        assert!(cache.load_any_age("https://api.example.com").is_none());
+    }
+}
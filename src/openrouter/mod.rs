@@ -1,28 +1,166 @@
-use std::cmp::min;
-use crate::config::Config;
+pub mod cache;
+pub mod stream;
+
+use crate::config::{normalize_base_url, Config, Provider, VERBOSE_RAW, VERBOSE_SUMMARIES};
+use crate::openrouter::cache::ModelsCache;
+use crate::openrouter::stream::{StreamAccumulator, StreamError};
+use crate::spinner::Spinner;
+use crate::tools::registry::tool_definitions;
+use crate::theme::{self, ThemeRole};
 use reqwest;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{json, Value};
 use std::env;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::Instrument;
 
 #[derive(Serialize)]
 pub struct OpenRouterRequest {
     pub model: String,
     pub messages: Vec<Message>,
+    /// Omitted entirely for models that reject the `tools` field
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<Tool>,
-    pub tool_choice: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    /// Lets the model request several tool calls in one response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Asks models that support sampling seeds for repeatable output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Number of alternative replies to generate; omitted for the usual single reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// Receive the reply as server-sent events (`openrouter.stream`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    /// Asks for token usage in the last event of a stream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<Value>,
+}
+
+/// How the model may use tools on a request
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ToolChoice {
+    /// The model decides whether to call a tool
+    #[default]
+    Auto,
+    /// The model must not call tools
+    None,
+    /// The model must call the named function
+    Function(String),
+}
+
+impl ToolChoice {
+    /// Force the model to call a specific tool, e.g. `ToolChoice::force("complete")`
+    pub fn force(name: &str) -> Self {
+        ToolChoice::Function(name.to_string())
+    }
+}
+
+impl Serialize for ToolChoice {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ToolChoice::Auto => serializer.serialize_str("auto"),
+            ToolChoice::None => serializer.serialize_str("none"),
+            ToolChoice::Function(name) => json!({"type": "function", "function": {"name": name}}).serialize(serializer),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Message {
     pub role: String,
-    pub content: String,
+    #[serde(default, deserialize_with = "deserialize_content")]
+    pub content: MessageContent,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
 }
 
+/// Message content: plain text, or a list of parts for multimodal requests
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+impl MessageContent {
+    /// Text of the message, joining text parts and skipping images
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            MessageContent::Text(text) => text.is_empty(),
+            MessageContent::Parts(parts) => parts.is_empty(),
+        }
+    }
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        MessageContent::Text(String::new())
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        MessageContent::Text(text.to_string())
+    }
+}
+
+impl fmt::Display for MessageContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// Assistant messages with only tool calls may carry `"content": null`
+fn deserialize_content<'de, D>(deserializer: D) -> Result<MessageContent, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<MessageContent>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Tool {
     #[serde(rename = "type")]
@@ -54,22 +192,184 @@ pub struct ToolCallFunction {
 #[derive(Deserialize)]
 pub struct OpenRouterResponse {
     pub choices: Vec<Choice>,
+    /// Token counts billed for the request; some OpenAI-compatible servers leave it out
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: Option<u64>,
+}
+
+impl Usage {
+    pub fn total(&self) -> u64 {
+        self.total_tokens.unwrap_or(self.prompt_tokens + self.completion_tokens)
+    }
 }
 
 #[derive(Deserialize)]
 pub struct ApiError {
     pub message: String,
-    pub code: i32,
+    /// Numeric on OpenRouter; a string or absent on other OpenAI-compatible servers
+    #[serde(default)]
+    pub code: Value,
+    /// OpenRouter puts moderation details here, e.g. `{"reasons": ["violence"], "flagged_input": "…"}`
+    #[serde(default)]
+    pub metadata: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ApiErrorBody {
+    Detailed(ApiError),
+    /// Servers such as Ollama send only the message
+    Message(String),
 }
 
 #[derive(Deserialize)]
 pub struct ErrorResponse {
-    pub error: ApiError,
+    pub error: ApiErrorBody,
 }
 
+impl ErrorResponse {
+    pub fn message(&self) -> &str {
+        match &self.error {
+            ApiErrorBody::Detailed(error) => &error.message,
+            ApiErrorBody::Message(message) => message,
+        }
+    }
+
+    /// Message with the error code when the server sent one
+    pub fn describe(&self) -> String {
+        match &self.error {
+            ApiErrorBody::Detailed(ApiError { message, code: Value::String(code), .. }) => format!("{} (code: {})", message, code),
+            ApiErrorBody::Detailed(ApiError { message, code: Value::Null, .. }) => message.clone(),
+            ApiErrorBody::Detailed(ApiError { message, code, .. }) => format!("{} (code: {})", message, code),
+            ApiErrorBody::Message(message) => message.clone(),
+        }
+    }
+
+    /// `Some(reason)` when moderation refused the request: OpenRouter's flagged-input errors carry
+    /// `metadata.reasons`, and Azure-style providers use a `content_filter` code
+    pub fn moderation(&self) -> Option<ContentFiltered> {
+        let ApiErrorBody::Detailed(error) = &self.error else {
+            return None;
+        };
+        let reasons: Vec<&str> = error.metadata["reasons"]
+            .as_array()
+            .map(|reasons| reasons.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        if !reasons.is_empty() {
+            return Some(ContentFiltered { reason: Some(reasons.join(", ")) });
+        }
+        let moderation_code = matches!(error.code.as_str(), Some("content_filter" | "content_policy_violation"));
+        (moderation_code || error.metadata.get("flagged_input").is_some())
+            .then(|| ContentFiltered { reason: Some(error.message.clone()) })
+    }
+}
+
+/// `finish_reason` of a reply withheld or cut short by the provider's content filter
+pub const CONTENT_FILTER: &str = "content_filter";
+
+/// The provider's moderation blocked the request or withheld the whole reply. Not a fault in loo or the
+/// connection, so callers can report it plainly and carry on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentFiltered {
+    /// Categories or message the provider gave, if any
+    pub reason: Option<String>,
+}
+
+impl fmt::Display for ContentFiltered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "The provider blocked this response ({})", reason),
+            None => write!(f, "The provider blocked this response"),
+        }
+    }
+}
+
+impl std::error::Error for ContentFiltered {}
+
+/// The session has used up `preferences.max_session_tokens`; no further requests are sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBudgetExhausted {
+    pub used: u64,
+    pub limit: u64,
+}
+
+impl fmt::Display for TokenBudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "This session has used {} tokens, reaching its budget of {} (preferences.max_session_tokens)", self.used, self.limit)
+    }
+}
+
+impl std::error::Error for TokenBudgetExhausted {}
+
 #[derive(Deserialize)]
+#[serde(from = "RawChoice")]
 pub struct Choice {
     pub message: Message,
+    /// `"length"` when the reply was cut off by the token limit
+    pub finish_reason: Option<String>,
+    /// Thinking that reasoning models return next to the reply (`reasoning`, or `reasoning_content` on some
+    /// OpenAI-compatible servers). Kept off `message`, so it is never sent back or shown as the answer.
+    pub reasoning: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawChoice {
+    /// Some providers leave the message out of a content-filtered choice
+    #[serde(default)]
+    message: Option<RawReply>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawReply {
+    #[serde(flatten)]
+    message: Message,
+    #[serde(default, alias = "reasoning_content")]
+    reasoning: Option<String>,
+}
+
+impl From<RawChoice> for Choice {
+    fn from(raw: RawChoice) -> Self {
+        let (message, reasoning) = match raw.message {
+            Some(reply) => (reply.message, reply.reasoning.filter(|reasoning| !reasoning.trim().is_empty())),
+            None => (empty_reply(), None),
+        };
+        Choice { message, finish_reason: raw.finish_reason, reasoning }
+    }
+}
+
+impl Choice {
+    pub fn truncated(&self) -> bool {
+        self.finish_reason.as_deref() == Some("length")
+    }
+
+    pub fn content_filtered(&self) -> bool {
+        self.finish_reason.as_deref() == Some(CONTENT_FILTER)
+    }
+
+    pub fn calls_tools(&self) -> bool {
+        has_tool_calls(&self.message)
+    }
+}
+
+fn empty_reply() -> Message {
+    Message {
+        role: "assistant".to_string(),
+        content: MessageContent::default(),
+        tool_calls: None,
+        tool_call_id: None,
+    }
 }
 
 #[derive(Deserialize)]
@@ -77,255 +377,702 @@ pub struct ModelsResponse {
     pub data: Vec<Model>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Model {
     pub id: String,
     pub name: Option<String>,
     pub description: Option<String>,
+    pub architecture: Option<ModelArchitecture>,
+    /// Request parameters the model accepts, e.g. "tools" or "response_format"
+    pub supported_parameters: Option<Vec<String>>,
+    pub context_length: Option<u64>,
+    pub pricing: Option<ModelPricing>,
+    pub top_provider: Option<TopProvider>,
+}
+
+/// Prices in USD per token, sent as decimal strings; "-1" marks a router whose price depends on the model it picks
+#[derive(Deserialize, Clone)]
+pub struct ModelPricing {
+    pub prompt: Option<String>,
+    pub completion: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TopProvider {
+    pub max_completion_tokens: Option<u64>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ModelArchitecture {
+    /// Modality summary such as "text+image->text"
+    pub modality: Option<String>,
+    pub input_modalities: Option<Vec<String>>,
+}
+
+impl Model {
+    /// Whether the model accepts image input
+    pub fn supports_vision(&self) -> bool {
+        let Some(architecture) = &self.architecture else {
+            return false;
+        };
+
+        if let Some(inputs) = &architecture.input_modalities {
+            return inputs.iter().any(|input| input == "image");
+        }
+
+        architecture
+            .modality
+            .as_deref()
+            .and_then(|modality| modality.split("->").next())
+            .map(|inputs| inputs.split('+').any(|input| input == "image"))
+            .unwrap_or(false)
+    }
+
+    /// Whether the model accepts tool definitions for function calling
+    pub fn supports_tools(&self) -> bool {
+        self.supported_parameters
+            .as_ref()
+            .is_some_and(|parameters| parameters.iter().any(|parameter| parameter == "tools"))
+    }
+}
+
+/// What loo knows about a model from the models catalog, for the features that depend on it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInfo {
+    pub id: String,
+    pub name: Option<String>,
+    /// Tokens of prompt and reply together the model accepts
+    pub context_length: Option<u64>,
+    /// Longest reply the model's main provider generates
+    pub max_completion_tokens: Option<u64>,
+    /// USD per prompt token, `None` when unknown or variable
+    pub prompt_price: Option<f64>,
+    /// USD per generated token, `None` when unknown or variable
+    pub completion_price: Option<f64>,
+    pub supports_tools: bool,
+    pub supports_vision: bool,
+}
+
+impl From<&Model> for ModelInfo {
+    fn from(model: &Model) -> Self {
+        let price = |price: Option<&String>| price.and_then(|price| price.parse::<f64>().ok()).filter(|price| *price >= 0.0);
+        let pricing = model.pricing.as_ref();
+        Self {
+            id: model.id.clone(),
+            name: model.name.clone(),
+            context_length: model.context_length,
+            max_completion_tokens: model.top_provider.as_ref().and_then(|provider| provider.max_completion_tokens),
+            prompt_price: price(pricing.and_then(|pricing| pricing.prompt.as_ref())),
+            completion_price: price(pricing.and_then(|pricing| pricing.completion.as_ref())),
+            supports_tools: model.supports_tools(),
+            supports_vision: model.supports_vision(),
+        }
+    }
+}
+
+/// Capabilities a listed model must have
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ModelFilter {
+    pub tools: bool,
+    pub vision: bool,
+}
+
+impl ModelFilter {
+    pub fn matches(&self, model: &Model) -> bool {
+        (!self.tools || model.supports_tools()) && (!self.vision || model.supports_vision())
+    }
+
+    /// Readable list of the required capabilities, or `None` when nothing is filtered
+    pub fn describe(&self) -> Option<String> {
+        let mut capabilities = Vec::new();
+        if self.tools {
+            capabilities.push("tool calling");
+        }
+        if self.vision {
+            capabilities.push("vision");
+        }
+        if capabilities.is_empty() {
+            return None;
+        }
+        Some(capabilities.join(" and "))
+    }
 }
 
 #[derive(Clone)]
 pub struct OpenRouterClient {
     client: reqwest::Client,
     config: Config,
+    /// Set once the API has rejected tool definitions for the current model
+    tools_unsupported: Arc<AtomicBool>,
+    /// Models catalog fetched this session, shared by every clone of the client
+    models: Arc<Mutex<Option<Arc<Vec<Model>>>>>,
+    /// Tokens reported in `usage` this session, shared by every clone of the client
+    session_tokens: Arc<AtomicU64>,
+}
+
+/// Appended for models without tool calling so they describe actions instead
+const NO_TOOLS_INSTRUCTION: &str = "Tool calling is not available with this model. \
+Instead of calling tools, describe the exact file changes and shell commands the user should apply, \
+using fenced code blocks with the file path or command on the line before each block.";
+
+/// Most follow-up requests made to finish one reply that keeps hitting the token limit
+const MAX_CONTINUATIONS: usize = 3;
+
+/// Tries at fetching the models list before giving up on a transient failure
+const MODELS_FETCH_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry of the models list; doubled for each further one
+const MODELS_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// How long the startup check waits for the API
+const PREFLIGHT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+const CONTINUE_INSTRUCTION: &str = "Your previous reply was cut off by the length limit. \
+Continue exactly where it stopped, without repeating anything already written.";
+
+/// A chat completion body as received
+enum ResponseBody {
+    Text(String),
+    Streamed(Result<OpenRouterResponse, StreamError>),
+}
+
+/// Collect a streamed reply; a connection dropped part-way counts as a truncated stream
+async fn read_stream(mut response: reqwest::Response) -> Result<OpenRouterResponse, StreamError> {
+    let mut accumulator = StreamAccumulator::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(bytes)) => accumulator.push_bytes(&bytes)?,
+            Ok(None) => return accumulator.into_response(),
+            Err(e) => {
+                tracing::warn!(error = %e, received_chars = accumulator.content().len(), "stream interrupted");
+                return Err(StreamError::Truncated);
+            }
+        }
+    }
+}
+
+fn has_tool_calls(message: &Message) -> bool {
+    message.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty())
+}
+
+/// Bytes of a raw response echoed in verbose mode
+const LOG_PREVIEW_BYTES: usize = 80;
+
+/// Start of a raw response for the verbose log, cut back to a character boundary so multi-byte text cannot split
+fn log_preview(text: &str) -> &str {
+    let mut end = LOG_PREVIEW_BYTES.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Whether an HTTP status is worth retrying: rate limiting or a server-side failure
+fn is_transient_status(status: u16) -> bool {
+    status == 429 || status >= 500
+}
+
+/// Backoff before retry number `retry` (starting at 1)
+fn retry_delay(retry: u32) -> std::time::Duration {
+    MODELS_RETRY_DELAY * 2u32.saturating_pow(retry.saturating_sub(1))
+}
+
+/// Whether an API error message means the model cannot accept tool definitions
+pub fn is_tools_unsupported_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("tool") && (message.contains("support") || message.contains("no endpoints found"))
 }
 
 impl OpenRouterClient {
-    pub async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
-        // Get API key from config or environment
+    pub async fn new(mut config: Config) -> Result<Self, Box<dyn std::error::Error>> {
+        config.openrouter.base_url = normalize_base_url(&config.openrouter.base_url)?;
+
+        // Get API key from config or environment; local OpenAI-compatible servers may not need one
+        let provider = config.openrouter.provider;
         let api_key = config.openrouter.api_key
             .clone()
-            .or_else(|| env::var("OPENROUTER_API_KEY").ok())
-            .ok_or("OpenRouter API key not found. Set it in config file or OPENROUTER_API_KEY environment variable")?;
+            .or_else(|| env::var("OPENROUTER_API_KEY").ok());
+        if api_key.is_none() && provider.requires_api_key() {
+            return Err("OpenRouter API key not found. Set it in config file or OPENROUTER_API_KEY environment variable".into());
+        }
 
         let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert(
-            reqwest::header::AUTHORIZATION,
-            format!("Bearer {}", api_key).parse()?,
-        );
-        // headers.insert("HTTP-Referer", "https://github.com/loo".parse()?);
-        headers.insert("X-Title", "Break CLI".parse()?);
+        if let Some(api_key) = api_key {
+            headers.insert(
+                reqwest::header::AUTHORIZATION,
+                format!("Bearer {}", api_key).parse()?,
+            );
+        }
+        if provider == Provider::OpenRouter {
+            // headers.insert("HTTP-Referer", "https://github.com/loo".parse()?);
+            headers.insert("X-Title", "Break CLI".parse()?);
+        }
 
         let client = reqwest::Client::builder()
             .default_headers(headers)
             .timeout(std::time::Duration::from_secs(config.tools.command_timeout))
             .build()?;
 
-        if config.preferences.verbose {
+        if config.preferences.verbose >= VERBOSE_SUMMARIES {
             println!("🔧 Using model: {}", config.openrouter.model);
             println!("🔧 API endpoint: {}/chat/completions", config.openrouter.base_url);
         }
 
-        Ok(Self { client, config })
+        Ok(Self {
+            client,
+            config,
+            tools_unsupported: Arc::new(AtomicBool::new(false)),
+            models: Arc::new(Mutex::new(None)),
+            session_tokens: Arc::new(AtomicU64::new(0)),
+        })
     }
 
-    pub fn get_tools(&self) -> Vec<Tool> {
-        let mut tools = Vec::new();
-
-        if self.config.tools.filesystem {
-            tools.extend(vec![
-                Tool {
-                    tool_type: "function".to_string(),
-                    function: ToolFunction {
-                        name: "create_file".to_string(),
-                        description: "Create a new file with specified content".to_string(),
-                        parameters: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": {"type": "string", "description": "File path to create"},
-                                "content": {"type": "string", "description": "File content"}
-                            },
-                            "required": ["path", "content"]
-                        }),
-                    },
-                },
-                Tool {
-                    tool_type: "function".to_string(),
-                    function: ToolFunction {
-                        name: "read_file".to_string(),
-                        description: "Read the contents of a file".to_string(),
-                        parameters: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": {"type": "string", "description": "File path to read"}
-                            },
-                            "required": ["path"]
-                        }),
-                    },
-                },
-                Tool {
-                    tool_type: "function".to_string(),
-                    function: ToolFunction {
-                        name: "write_file".to_string(),
-                        description: "Write content to an existing file".to_string(),
-                        parameters: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": {"type": "string", "description": "File path to write to"},
-                                "content": {"type": "string", "description": "Content to write"}
-                            },
-                            "required": ["path", "content"]
-                        }),
-                    },
-                },
-                Tool {
-                    tool_type: "function".to_string(),
-                    function: ToolFunction {
-                        name: "delete_file".to_string(),
-                        description: "Delete a file".to_string(),
-                        parameters: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": {"type": "string", "description": "File path to delete"}
-                            },
-                            "required": ["path"]
-                        }),
-                    },
-                },
-                Tool {
-                    tool_type: "function".to_string(),
-                    function: ToolFunction {
-                        name: "create_directory".to_string(),
-                        description: "Create a directory and any necessary parent directories".to_string(),
-                        parameters: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": {"type": "string", "description": "Directory path to create"}
-                            },
-                            "required": ["path"]
-                        }),
-                    },
-                },
-                Tool {
-                    tool_type: "function".to_string(),
-                    function: ToolFunction {
-                        name: "list_directory".to_string(),
-                        description: "List contents of a directory".to_string(),
-                        parameters: json!({
-                            "type": "object",
-                            "properties": {
-                                "path": {"type": "string", "description": "Directory path to list (defaults to current directory)"}
-                            }
-                        }),
-                    },
-                },
-                Tool {
-                    tool_type: "function".to_string(),
-                    function: ToolFunction {
-                        name: "query_context".to_string(),
-                        description: "Query project context and current state".to_string(),
-                        parameters: json!({
-                            "type": "object",
-                            "properties": {
-                                "type": {"type": "string", "enum": ["full", "directory"], "description": "Type of context query"}
-                            }
-                        }),
-                    },
-                },
-            ]);
-        }
-
-        if self.config.tools.commands {
-            tools.push(Tool {
-                tool_type: "function".to_string(),
-                function: ToolFunction {
-                    name: "run_command".to_string(),
-                    description: "Execute a shell command".to_string(),
-                    parameters: json!({
-                        "type": "object",
-                        "properties": {
-                            "command": {"type": "string", "description": "Command to execute"}
-                        },
-                        "required": ["command"]
-                    }),
-                },
-            });
-        }
+    /// Keep counting towards `preferences.max_session_tokens` from where `previous` left off, e.g. after a model switch
+    pub fn with_session_tokens_of(mut self, previous: &OpenRouterClient) -> Self {
+        self.session_tokens = Arc::clone(&previous.session_tokens);
+        self
+    }
+
+    /// Tokens used by every request of the session so far
+    pub fn session_tokens(&self) -> u64 {
+        self.session_tokens.load(Ordering::Relaxed)
+    }
 
-        // Always include completion tool
-        tools.push(Tool {
-            tool_type: "function".to_string(),
-            function: ToolFunction {
-                name: "complete".to_string(),
-                description: "Mark the project as completed".to_string(),
-                parameters: json!({
-                    "type": "object",
-                    "properties": {}
-                }),
-            },
-        });
+    /// Set once `preferences.max_session_tokens` is reached; every later request fails with `TokenBudgetExhausted`
+    pub fn token_budget_exhausted(&self) -> Option<TokenBudgetExhausted> {
+        let limit = self.config.preferences.max_session_tokens;
+        let used = self.session_tokens();
+        (limit > 0 && used >= limit).then_some(TokenBudgetExhausted { used, limit })
+    }
+
+    /// Whether requests should include tool definitions for the current model
+    pub fn tools_enabled(&self) -> bool {
+        self.config.openrouter.supports_tools && !self.tools_unsupported.load(Ordering::Relaxed)
+    }
 
-        tools
+    /// Full URL for an API path under the configured base URL
+    pub fn endpoint(&self, path: &str) -> String {
+        format!("{}/{}", self.config.openrouter.base_url, path.trim_start_matches('/'))
+    }
+
+    pub fn get_tools(&self) -> Vec<Tool> {
+        tool_definitions(&self.config.tools)
     }
 
     pub async fn chat_completion(
         &self,
         messages: Vec<Message>,
     ) -> Result<OpenRouterResponse, Box<dyn std::error::Error>> {
-        let request = OpenRouterRequest {
-            model: self.config.openrouter.model.clone(),
-            messages,
-            tools: self.get_tools(),
-            tool_choice: "auto".to_string(),
-        };
+        self.chat_completion_with_tool_choice(messages, ToolChoice::Auto).await
+    }
 
-        let endpoint = format!("{}/chat/completions", self.config.openrouter.base_url);
-        
-        if self.config.preferences.verbose {
-            println!();
-            println!("🔗 Sending request to: {}", endpoint);
-            println!("📊 Request: {} messages, {} tools", request.messages.len(), request.tools.len());
+    /// Chat completion asking for `openrouter.n` alternative replies, for chat turns where the user picks one.
+    /// Only the first choice is continued when it hits the token limit.
+    pub async fn chat_completion_choices(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<OpenRouterResponse, Box<dyn std::error::Error>> {
+        let n = self.config.openrouter.n.filter(|&n| n > 1);
+        self.complete(messages, ToolChoice::Auto, n).await
+    }
+
+    /// Chat completion with an explicit tool choice, e.g. to guarantee a structured tool call.
+    /// Text replies cut off by the token limit are continued and stitched together, up to `MAX_CONTINUATIONS` times.
+    /// A truncated tool call cannot be continued; its arguments no longer parse, so it fails instead of writing half a file.
+    pub async fn chat_completion_with_tool_choice(
+        &self,
+        messages: Vec<Message>,
+        tool_choice: ToolChoice,
+    ) -> Result<OpenRouterResponse, Box<dyn std::error::Error>> {
+        self.complete(messages, tool_choice, None).await
+    }
+
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tool_choice: ToolChoice,
+        n: Option<u32>,
+    ) -> Result<OpenRouterResponse, Box<dyn std::error::Error>> {
+        let mut response = self.send_chat_request(messages.clone(), tool_choice, n).await?;
+
+        for attempt in 1..=MAX_CONTINUATIONS {
+            let Some(choice) = response.choices.first_mut() else {
+                break;
+            };
+            // A cut-off reply is better than none once the budget is spent
+            if !choice.truncated() || has_tool_calls(&choice.message) || self.token_budget_exhausted().is_some() {
+                break;
+            }
+
+            println!("{}", theme::paint(ThemeRole::Dim, &format!(
+                "✂️ Reply hit the token limit; continuing ({}/{})", attempt, MAX_CONTINUATIONS
+            )));
+            let partial = choice.message.content.text();
+            let mut continuation_messages = messages.clone();
+            continuation_messages.push(Message {
+                role: "assistant".to_string(),
+                content: partial.clone().into(),
+                tool_calls: None,
+                tool_call_id: None,
+            });
+            continuation_messages.push(Message {
+                role: "user".to_string(),
+                content: CONTINUE_INSTRUCTION.into(),
+                tool_calls: None,
+                tool_call_id: None,
+            });
+
+            let continuation = self.send_chat_request(continuation_messages, ToolChoice::None, None).await?;
+            let Some(next) = continuation.choices.into_iter().next() else {
+                break;
+            };
+            choice.message.content = format!("{}{}", partial, next.message.content.text()).into();
+            choice.finish_reason = next.finish_reason;
         }
 
-        let raw_response = self
-            .client
-            .post(&endpoint)
-            .json(&request)
-            .send()
-            .await?;
+        if let Some(choice) = response.choices.first().filter(|choice| choice.truncated()) {
+            let what = if has_tool_calls(&choice.message) { "A tool call" } else { "The reply" };
+            println!("{}", theme::paint(ThemeRole::Warning, &format!(
+                "⚠️ {} was cut off by the token limit (see openrouter.max_tokens)", what
+            )));
+        }
+        Ok(response)
+    }
 
-        // Log the raw response for debugging
-        let response_text = raw_response.text().await?;
-        if self.config.preferences.verbose {
-            let max_len = min(80, response_text.len());
-            println!("\x1b[38;5;245m🐛 Raw API response: {}\x1b[0m", response_text.get(..max_len).unwrap().trim());
+    async fn send_chat_request(
+        &self,
+        messages: Vec<Message>,
+        tool_choice: ToolChoice,
+        n: Option<u32>,
+    ) -> Result<OpenRouterResponse, Box<dyn std::error::Error>> {
+        if let ToolChoice::Function(name) = &tool_choice {
+            if !self.tools_enabled() || !self.get_tools().iter().any(|tool| &tool.function.name == name) {
+                return Err(format!("Cannot force tool '{}': it is not enabled", name).into());
+            }
         }
 
-        // Try to parse as error response first
-        if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(&response_text) {
-            return Err(format!("OpenRouter API Error: {} (code: {})", 
-                error_response.error.message, error_response.error.code).into());
+        if let Some(exhausted) = self.token_budget_exhausted() {
+            return Err(exhausted.into());
         }
 
-        let response: OpenRouterResponse = serde_json::from_str(&response_text)?;
+        let endpoint = self.endpoint("chat/completions");
+        // Several choices would arrive interleaved in one stream, so those requests are never streamed
+        let mut streaming = self.config.openrouter.stream && n.is_none();
 
+        loop {
+            let use_tools = self.tools_enabled();
+            let request = if use_tools {
+                OpenRouterRequest {
+                    model: self.config.openrouter.model.clone(),
+                    messages: messages.clone(),
+                    tools: self.get_tools(),
+                    tool_choice: Some(tool_choice.clone()),
+                    parallel_tool_calls: self.config.openrouter.parallel_tool_calls.then_some(true),
+                    max_tokens: self.config.openrouter.max_tokens,
+                    seed: self.config.openrouter.seed,
+                    n,
+                    stream: streaming.then_some(true),
+                    stream_options: streaming.then(|| json!({"include_usage": true})),
+                }
+            } else {
+                let mut plain_messages = messages.clone();
+                plain_messages.push(Message {
+                    role: "system".to_string(),
+                    content: NO_TOOLS_INSTRUCTION.into(),
+                    tool_calls: None,
+                    tool_call_id: None,
+                });
+                OpenRouterRequest {
+                    model: self.config.openrouter.model.clone(),
+                    messages: plain_messages,
+                    tools: Vec::new(),
+                    tool_choice: None,
+                    parallel_tool_calls: None,
+                    max_tokens: self.config.openrouter.max_tokens,
+                    seed: self.config.openrouter.seed,
+                    n,
+                    stream: streaming.then_some(true),
+                    stream_options: streaming.then(|| json!({"include_usage": true})),
+                }
+            };
+
+            if self.config.preferences.verbose >= VERBOSE_RAW {
+                println!();
+                println!("🔗 Sending request to: {}", endpoint);
+                println!("📊 Request: {} messages, {} tools", request.messages.len(), request.tools.len());
+                println!("{}", theme::paint(ThemeRole::Dim, &format!("🐛 Raw API request: {}", serde_json::to_string(&request).unwrap_or_default())));
+            }
+
+            let span = tracing::info_span!(
+                "api_request",
+                model = %request.model,
+                messages = request.messages.len(),
+                tools = request.tools.len()
+            );
+            tracing::debug!(%endpoint, "sending chat completion request");
+
+            let spinner = Spinner::start("Waiting for model...", self.config.preferences.spinner);
+            let started = Instant::now();
+            let body = async {
+                let raw_response = self.client.post(&endpoint).json(&request).send().await?;
+                tracing::debug!(status = %raw_response.status(), "response headers received");
+                // Errors, and servers that ignore `stream`, still answer with a whole JSON body
+                let event_stream = raw_response
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.starts_with("text/event-stream"));
+                if event_stream {
+                    Ok(ResponseBody::Streamed(read_stream(raw_response).await))
+                } else {
+                    raw_response.text().await.map(ResponseBody::Text)
+                }
+            }
+            .instrument(span)
+            .await?;
+            drop(spinner);
+
+            let response_text = match body {
+                ResponseBody::Text(text) => text,
+                ResponseBody::Streamed(Ok(response)) => {
+                    tracing::debug!(elapsed_ms = started.elapsed().as_millis() as u64, "streamed chat completion received");
+                    return self.accept_response(response);
+                }
+                ResponseBody::Streamed(Err(e)) => {
+                    // Never act on a partial reply; ask again for the whole of it
+                    tracing::warn!(error = %e, "incomplete stream");
+                    println!("{}", theme::paint(ThemeRole::Warning, &format!("⚠️ Streamed reply was incomplete ({}); retrying without streaming", e)));
+                    streaming = false;
+                    continue;
+                }
+            };
+            tracing::debug!(bytes = response_text.len(), elapsed_ms = started.elapsed().as_millis() as u64, "chat completion response received");
+            if self.config.preferences.verbose >= VERBOSE_RAW {
+                println!("{}", theme::paint(ThemeRole::Dim, &format!("🐛 Raw API response: {}", response_text.trim())));
+            }
+
+            // Try to parse as error response first
+            if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(&response_text) {
+                tracing::warn!(error = %error_response.describe(), "API returned an error");
+                if use_tools && is_tools_unsupported_error(error_response.message()) {
+                    // Remember for the rest of the session and retry as plain conversation
+                    self.tools_unsupported.store(true, Ordering::Relaxed);
+                    println!("{}", theme::paint(ThemeRole::Warning, &format!(
+                        "⚠️ Model '{}' does not support tool calls; continuing without tools",
+                        self.config.openrouter.model
+                    )));
+                    continue;
+                }
+                if let Some(filtered) = error_response.moderation() {
+                    return Err(filtered.into());
+                }
+                return Err(format!("{} API Error: {}",
+                    self.config.openrouter.provider.label(), error_response.describe()).into());
+            }
+
+            return self.accept_response(serde_json::from_str(&response_text)?);
+        }
+    }
+
+    /// Count a reply's tokens against the session and check whether the content filter stopped it
+    fn accept_response(&self, response: OpenRouterResponse) -> Result<OpenRouterResponse, Box<dyn std::error::Error>> {
+        if let Some(usage) = &response.usage {
+            let used = self.session_tokens.fetch_add(usage.total(), Ordering::Relaxed) + usage.total();
+            tracing::debug!(tokens = usage.total(), session_tokens = used, "token usage");
+        }
+        if let Some(choice) = response.choices.first().filter(|choice| choice.content_filtered()) {
+            tracing::warn!("reply stopped by the provider's content filter");
+            if choice.message.content.is_empty() && !has_tool_calls(&choice.message) {
+                return Err(ContentFiltered { reason: None }.into());
+            }
+            println!("{}", theme::paint(ThemeRole::Warning, "⚠️ The provider's content filter cut this reply short"));
+        }
         Ok(response)
     }
 
-    pub async fn list_models(&self, search_term: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let endpoint = format!("{}/models", self.config.openrouter.base_url);
+    /// The models catalog, fetched at most once per session unless `refresh` is set
+    async fn fetch_models(&self, refresh: bool) -> Result<Arc<Vec<Model>>, Box<dyn std::error::Error>> {
+        if !refresh {
+            if let Some(models) = self.models.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone() {
+                return Ok(models);
+            }
+        }
+        let models = Arc::new(self.load_models(refresh).await?);
+        *self.models.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(models.clone());
+        Ok(models)
+    }
+
+    /// Load the models catalog, serving it from the disk cache unless `refresh` is set
+    async fn load_models(&self, refresh: bool) -> Result<Vec<Model>, Box<dyn std::error::Error>> {
+        let base_url = &self.config.openrouter.base_url;
+        let cache = ModelsCache::new().ok();
+
+        if !refresh {
+            if let Some(cached) = cache
+                .as_ref()
+                .and_then(|cache| cache.load(base_url, self.config.openrouter.models_cache_ttl))
+            {
+                if let Ok(models_response) = serde_json::from_str::<ModelsResponse>(&cached.response) {
+                    if self.config.preferences.verbose >= VERBOSE_SUMMARIES {
+                        println!("🗂️ Using models cached at {}", cached.fetched_at.format("%Y-%m-%d %H:%M UTC"));
+                    }
+                    return Ok(models_response.data);
+                }
+            }
+        }
+
+        let endpoint = self.endpoint("models");
         
-        if self.config.preferences.verbose {
+        if self.config.preferences.verbose >= VERBOSE_SUMMARIES {
             println!("🔗 Fetching models from: {}", endpoint);
         }
 
-        let raw_response = self
+        let fetched = async {
+            let response_text = self.request_models(&endpoint).await?;
+            if self.config.preferences.verbose >= VERBOSE_RAW {
+                println!("🐛 Raw models response: {}", log_preview(&response_text));
+            }
+            let models_response: ModelsResponse = serde_json::from_str(&response_text)?;
+            Ok::<_, Box<dyn std::error::Error>>((response_text, models_response))
+        }
+        .await;
+
+        // Local servers often lack /models or answer in their own format; carry on without a catalog
+        let (response_text, models_response) = match fetched {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                // An outdated list beats none while the server is unreachable
+                let stale = cache
+                    .as_ref()
+                    .and_then(|cache| cache.load_any_age(base_url))
+                    .and_then(|cached| Some((cached.fetched_at, serde_json::from_str::<ModelsResponse>(&cached.response).ok()?)));
+                if let Some((fetched_at, models_response)) = stale {
+                    if self.config.preferences.verbose >= VERBOSE_SUMMARIES {
+                        println!("🗂️ Models list unavailable ({}); using the list cached at {}", e, fetched_at.format("%Y-%m-%d %H:%M UTC"));
+                    }
+                    return Ok(models_response.data);
+                }
+                if self.config.openrouter.provider == Provider::OpenAiCompatible {
+                    if self.config.preferences.verbose >= VERBOSE_SUMMARIES {
+                        println!("⚠️ No models list from {}: {}", endpoint, e);
+                    }
+                    return Ok(Vec::new());
+                }
+                return Err(e);
+            }
+        };
+
+        // A failed cache write only costs the next lookup a network round trip
+        if let Some(cache) = &cache {
+            if let Err(e) = cache.store(base_url, &response_text) {
+                if self.config.preferences.verbose >= VERBOSE_SUMMARIES {
+                    println!("⚠️ Could not cache models list: {}", e);
+                }
+            }
+        }
+
+        Ok(models_response.data)
+    }
+
+    /// GET the models list, retrying connection failures, timeouts, rate limiting and server errors with
+    /// exponential backoff
+    async fn request_models(&self, endpoint: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut attempt = 1;
+        loop {
+            let error: Box<dyn std::error::Error> = match self.client.get(endpoint).send().await {
+                Ok(response) if !is_transient_status(response.status().as_u16()) => return Ok(response.text().await?),
+                Ok(response) => format!("{} answered {}", endpoint, response.status()).into(),
+                Err(e) if e.is_connect() || e.is_timeout() => e.into(),
+                Err(e) => return Err(e.into()),
+            };
+            if attempt >= MODELS_FETCH_ATTEMPTS {
+                return Err(error);
+            }
+            let delay = retry_delay(attempt);
+            tracing::warn!(%error, attempt, "models request failed, retrying");
+            if self.config.preferences.verbose >= VERBOSE_SUMMARIES {
+                println!("🔁 Models request failed ({}); retrying in {:.1}s", error, delay.as_secs_f64());
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Ask the server for its models, bypassing the cache and the provider's tolerance of a missing list.
+    /// Returns how many models it reported, or `None` if it answered in a format other than the OpenAI one.
+    pub async fn check_connectivity(&self) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+        let endpoint = self.endpoint("models");
+        let response = self
             .client
             .get(&endpoint)
             .send()
-            .await?;
+            .await
+            .map_err(|e| format!("Cannot reach {}: {}", endpoint, e))?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+        if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(&response_text) {
+            return Err(format!("{} answered {}: {}", endpoint, status, error_response.describe()).into());
+        }
+        if !status.is_success() {
+            return Err(format!("{} answered {}", endpoint, status).into());
+        }
+
+        Ok(serde_json::from_str::<ModelsResponse>(&response_text)
+            .ok()
+            .map(|models| models.data.len()))
+    }
+
+    /// Startup check that the API answers and accepts the key, so a bad key or base URL is reported
+    /// before the first message instead of after it
+    pub async fn preflight(&self) -> Result<(), Box<dyn std::error::Error>> {
+        // OpenRouter's models list is public, so the key is checked against the endpoint describing it
+        let endpoint = match self.config.openrouter.provider {
+            Provider::OpenRouter => self.endpoint("key"),
+            Provider::OpenAiCompatible => self.endpoint("models"),
+        };
+        let response = self
+            .client
+            .get(&endpoint)
+            .timeout(PREFLIGHT_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("Cannot reach {}: {}", endpoint, e))?;
 
-        let response_text = raw_response.text().await?;
-        if self.config.preferences.verbose {
-            let max_len = min(80, response_text.len());
-            println!("🐛 Raw models response: {}", response_text.get(..max_len).unwrap());
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
         }
+        let response_text = response.text().await.unwrap_or_default();
+        let detail = serde_json::from_str::<ErrorResponse>(&response_text)
+            .map(|error_response| format!(": {}", error_response.describe()))
+            .unwrap_or_default();
+        let problem = match status.as_u16() {
+            401 | 403 => "the API key was rejected",
+            _ => "the API is not answering normally",
+        };
+        Err(format!("{} ({} answered {}{})", problem, endpoint, status, detail).into())
+    }
 
-        let models_response: ModelsResponse = serde_json::from_str(&response_text)?;
+    /// Catalog entry for the model `id`, or `None` if the catalog does not list it
+    pub async fn get_model_info(&self, id: &str) -> Result<Option<ModelInfo>, Box<dyn std::error::Error>> {
+        let models = self.fetch_models(false).await?;
+        Ok(models.iter().find(|model| model.id == id).map(ModelInfo::from))
+    }
+
+    /// Check whether the configured model accepts image input
+    pub async fn model_supports_vision(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        let info = self.get_model_info(&self.config.openrouter.model).await?;
+        Ok(info.is_some_and(|info| info.supports_vision))
+    }
+
+    pub async fn list_models(&self, search_term: &str, filter: ModelFilter, refresh: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let models = self.fetch_models(refresh).await?;
         
-        let mut model_names: Vec<String> = models_response.data
-            .into_iter()
-            .map(|model| model.id)
+        let mut model_names: Vec<String> = models
+            .iter()
+            .filter(|model| filter.matches(model))
+            .map(|model| model.id.clone())
             .collect();
 
         // Filter models if search term is provided
@@ -339,4 +1086,424 @@ impl OpenRouterClient {
 
         Ok(model_names)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_content_serializes_as_string() {
+        let message = Message {
+            role: "user".to_string(),
+            content: "hello".into(),
+            tool_calls: None,
+            tool_call_id: None,
+        };
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["content"], "hello");
+    }
+
+    #[test]
+    fn test_image_parts_serialize_as_array() {
+        let content = MessageContent::Parts(vec![
+            ContentPart::Text { text: "what is this?".to_string() },
+            ContentPart::ImageUrl { image_url: ImageUrl { url: "data:image/png;base64,AA==".to_string() } },
+        ]);
+        let json = serde_json::to_value(&content).unwrap();
+        assert_eq!(json[0]["type"], "text");
+        assert_eq!(json[1]["type"], "image_url");
+        assert_eq!(json[1]["image_url"]["url"], "data:image/png;base64,AA==");
+        assert_eq!(content.text(), "what is this?");
+    }
+
+    #[test]
+    fn test_null_content_deserializes_as_empty() {
+        let message: Message = serde_json::from_str(r#"{"role": "assistant", "content": null}"#).unwrap();
+        assert!(message.content.is_empty());
+    }
+
+    #[test]
+    fn test_model_supports_vision() {
+        let models: ModelsResponse = serde_json::from_str(r#"{"data": [
+            {"id": "a", "architecture": {"modality": "text+image->text"}},
+            {"id": "b", "architecture": {"input_modalities": ["text"]}},
+            {"id": "c"}
+        ]}"#).unwrap();
+        assert!(models.data[0].supports_vision());
+        assert!(!models.data[1].supports_vision());
+        assert!(!models.data[2].supports_vision());
+    }
+
+    #[test]
+    fn test_log_preview_keeps_characters_whole() {
+        // Byte 80 falls inside the emoji, which starts at byte 78
+        let response = format!("{}🎉 done", "a".repeat(78));
+        assert_eq!(log_preview(&response), "a".repeat(78));
+        let response = format!("{}é", "a".repeat(79));
+        assert_eq!(log_preview(&response), "a".repeat(79));
+        assert_eq!(log_preview("short ✓"), "short ✓");
+        assert_eq!(log_preview(&"x".repeat(100)).len(), 80);
+    }
+
+    #[test]
+    fn test_models_retry_policy() {
+        assert!(is_transient_status(429));
+        assert!(is_transient_status(503));
+        assert!(!is_transient_status(404));
+        assert!(!is_transient_status(401));
+        assert_eq!(retry_delay(1), std::time::Duration::from_millis(500));
+        assert_eq!(retry_delay(2), std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_model_info_from_catalog() {
+        let models: ModelsResponse = serde_json::from_str(r#"{"data": [
+            {"id": "anthropic/claude-sonnet-4", "name": "Claude Sonnet 4", "context_length": 200000,
+             "pricing": {"prompt": "0.000003", "completion": "0.000015"},
+             "top_provider": {"max_completion_tokens": 64000},
+             "supported_parameters": ["tools"], "architecture": {"input_modalities": ["text", "image"]}},
+            {"id": "openrouter/auto", "pricing": {"prompt": "-1", "completion": "-1"}}
+        ]}"#).unwrap();
+
+        let info = ModelInfo::from(&models.data[0]);
+        assert_eq!(info.context_length, Some(200000));
+        assert_eq!(info.max_completion_tokens, Some(64000));
+        assert_eq!((info.prompt_price, info.completion_price), (Some(0.000003), Some(0.000015)));
+        assert!(info.supports_tools && info.supports_vision);
+
+        let info = ModelInfo::from(&models.data[1]);
+        assert_eq!((info.context_length, info.prompt_price, info.completion_price), (None, None, None));
+        assert!(!info.supports_tools && !info.supports_vision);
+    }
+
+    #[test]
+    fn test_model_filter_by_capability() {
+        let models: ModelsResponse = serde_json::from_str(r#"{"data": [
+            {"id": "tools", "supported_parameters": ["temperature", "tools", "tool_choice"]},
+            {"id": "both", "supported_parameters": ["tools"], "architecture": {"input_modalities": ["text", "image"]}},
+            {"id": "plain", "supported_parameters": ["temperature"]}
+        ]}"#).unwrap();
+
+        let matching = |filter: ModelFilter| -> Vec<&str> {
+            models.data.iter().filter(|model| filter.matches(model)).map(|model| model.id.as_str()).collect()
+        };
+        assert_eq!(matching(ModelFilter::default()), vec!["tools", "both", "plain"]);
+        assert_eq!(matching(ModelFilter { tools: true, vision: false }), vec!["tools", "both"]);
+        assert_eq!(matching(ModelFilter { tools: true, vision: true }), vec!["both"]);
+        assert_eq!(ModelFilter { tools: true, vision: true }.describe().unwrap(), "tool calling and vision");
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_ignores_trailing_slash() {
+        let mut config = Config::default();
+        config.openrouter.api_key = Some("sk-test".to_string());
+
+        config.openrouter.base_url = "https://openrouter.ai/api/v1".to_string();
+        let plain = OpenRouterClient::new(config.clone()).await.unwrap();
+        config.openrouter.base_url = "https://openrouter.ai/api/v1//".to_string();
+        let slashed = OpenRouterClient::new(config.clone()).await.unwrap();
+
+        assert_eq!(plain.endpoint("chat/completions"), "https://openrouter.ai/api/v1/chat/completions");
+        assert_eq!(plain.endpoint("chat/completions"), slashed.endpoint("chat/completions"));
+
+        config.openrouter.base_url = "openrouter.ai/api/v1".to_string();
+        assert!(OpenRouterClient::new(config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_openai_compatible_provider_without_key() {
+        let mut server = mockito::Server::new_async().await;
+        let chat = server
+            .mock("POST", "/chat/completions")
+            .match_header("x-title", mockito::Matcher::Missing)
+            .with_body(r#"{"error": "model 'llama3' not found, try pulling it first"}"#)
+            .create_async()
+            .await;
+        let _models = server.mock("GET", "/models").with_status(404).with_body("404 page not found").create_async().await;
+
+        let mut config = Config::default();
+        config.openrouter.base_url = server.url();
+        config.preferences.spinner = false;
+        if env::var_os("OPENROUTER_API_KEY").is_none() {
+            assert!(OpenRouterClient::new(config.clone()).await.is_err());
+        }
+
+        config.openrouter.provider = Provider::OpenAiCompatible;
+        let client = OpenRouterClient::new(config).await.unwrap();
+        assert!(client.list_models("", ModelFilter::default(), true).await.unwrap().is_empty());
+        assert!(client.check_connectivity().await.is_err());
+
+        let error = client.chat_completion(vec![Message {
+            role: "user".to_string(),
+            content: "hello".into(),
+            tool_calls: None,
+            tool_call_id: None,
+        }]).await.err().unwrap();
+        chat.assert_async().await;
+        assert_eq!(error.to_string(), "OpenAI-compatible API Error: model 'llama3' not found, try pulling it first");
+    }
+
+    #[test]
+    fn test_error_code_is_optional() {
+        let describe = |body: &str| serde_json::from_str::<ErrorResponse>(body).unwrap().describe();
+        assert_eq!(describe(r#"{"error": {"message": "Rate limited", "code": 429}}"#), "Rate limited (code: 429)");
+        assert_eq!(describe(r#"{"error": {"message": "Bad key", "type": "auth", "code": "invalid_api_key"}}"#), "Bad key (code: invalid_api_key)");
+        assert_eq!(describe(r#"{"error": {"message": "Overloaded", "code": null}}"#), "Overloaded");
+    }
+
+    #[tokio::test]
+    async fn test_truncated_reply_is_continued_and_stitched() {
+        let mut server = mockito::Server::new_async().await;
+        let reply = |content: &str, finish_reason: &str| {
+            json!({"choices": [{"message": {"role": "assistant", "content": content}, "finish_reason": finish_reason}]}).to_string()
+        };
+        let first = server
+            .mock("POST", "/chat/completions")
+            .with_body(reply("fn main() {\n    println!(", "length"))
+            .expect(1)
+            .create_async()
+            .await;
+        let continuation = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex("cut off by the length limit".to_string()))
+            .with_body(reply("\"hi\");\n}", "stop"))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut config = Config::default();
+        config.openrouter.api_key = Some("sk-test".to_string());
+        config.openrouter.base_url = server.url();
+        config.preferences.spinner = false;
+        let client = OpenRouterClient::new(config).await.unwrap();
+
+        let response = client.chat_completion(vec![Message {
+            role: "user".to_string(),
+            content: "Write hello world".into(),
+            tool_calls: None,
+            tool_call_id: None,
+        }]).await.unwrap();
+
+        first.assert_async().await;
+        continuation.assert_async().await;
+        assert_eq!(response.choices[0].message.content.text(), "fn main() {\n    println!(\"hi\");\n}");
+        assert!(!response.choices[0].truncated());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_reply_and_retry_after_truncated_stream() {
+        let mut server = mockito::Server::new_async().await;
+        let events = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":2}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let streamed = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::PartialJsonString(r#"{"stream": true}"#.to_string()),
+                mockito::Matcher::Regex("greet".to_string()),
+            ]))
+            .with_header("content-type", "text/event-stream")
+            .with_body(events)
+            .expect(1)
+            .create_async()
+            .await;
+        // The connection drops in the middle of a tool call's arguments
+        let truncated = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::PartialJsonString(r#"{"stream": true}"#.to_string()),
+                mockito::Matcher::Regex("write".to_string()),
+            ]))
+            .with_header("content-type", "text/event-stream")
+            .with_body("data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"1\",\"function\":{\"name\":\"write_file\",\"arguments\":\"{\\\"pa\"}}]}}]}\n\n")
+            .expect(1)
+            .create_async()
+            .await;
+        // The retry asks for the whole reply at once
+        let whole = server
+            .mock("POST", "/chat/completions")
+            .match_request(|request| {
+                let body = request.utf8_lossy_body().unwrap_or_default();
+                body.contains("write") && !body.contains("\"stream\"")
+            })
+            .with_body(json!({"choices": [{"message": {"role": "assistant", "content": "Written"}, "finish_reason": "stop"}]}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut config = Config::default();
+        config.openrouter.api_key = Some("sk-test".to_string());
+        config.openrouter.base_url = server.url();
+        config.openrouter.stream = true;
+        config.preferences.spinner = false;
+        let client = OpenRouterClient::new(config).await.unwrap();
+        let ask = |prompt: &str| vec![Message { role: "user".to_string(), content: prompt.into(), tool_calls: None, tool_call_id: None }];
+
+        let response = client.chat_completion(ask("greet")).await.unwrap();
+        assert_eq!(response.choices[0].message.content.text(), "Hello");
+        assert_eq!(client.session_tokens(), 7);
+
+        let response = client.chat_completion(ask("write")).await.unwrap();
+        assert_eq!(response.choices[0].message.content.text(), "Written");
+        assert!(response.choices[0].message.tool_calls.is_none());
+
+        streamed.assert_async().await;
+        truncated.assert_async().await;
+        whole.assert_async().await;
+    }
+
+    #[test]
+    fn test_reasoning_is_kept_apart_from_the_reply() {
+        let response: OpenRouterResponse = serde_json::from_str(r#"{"choices": [
+            {"message": {"role": "assistant", "content": "42", "reasoning": "Six times seven."}, "finish_reason": "stop"},
+            {"message": {"role": "assistant", "content": "42", "reasoning_content": "Recalled it."}},
+            {"message": {"role": "assistant", "content": null, "reasoning": "  "}},
+            {"finish_reason": "content_filter"}
+        ]}"#).unwrap();
+
+        assert_eq!(response.choices[0].message.content.text(), "42");
+        assert_eq!(response.choices[0].reasoning.as_deref(), Some("Six times seven."));
+        assert_eq!(response.choices[1].reasoning.as_deref(), Some("Recalled it."));
+        assert!(response.choices[2].reasoning.is_none());
+        assert!(response.choices[3].message.content.is_empty());
+        // The reply goes back into the conversation without the reasoning
+        let echoed = serde_json::to_value(&response.choices[0].message).unwrap();
+        assert!(echoed.get("reasoning").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_token_budget_halts_requests() {
+        let mut server = mockito::Server::new_async().await;
+        let chat = server
+            .mock("POST", "/chat/completions")
+            .with_body(r#"{"choices": [{"message": {"role": "assistant", "content": "ok"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 600, "completion_tokens": 100, "total_tokens": 700}}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let mut config = Config::default();
+        config.openrouter.api_key = Some("sk-test".to_string());
+        config.openrouter.base_url = server.url();
+        config.preferences.spinner = false;
+        config.preferences.max_session_tokens = 1000;
+        let client = OpenRouterClient::new(config.clone()).await.unwrap();
+        let ask = || vec![Message { role: "user".to_string(), content: "hi".into(), tool_calls: None, tool_call_id: None }];
+
+        client.chat_completion(ask()).await.unwrap();
+        assert_eq!(client.session_tokens(), 700);
+        assert!(client.token_budget_exhausted().is_none());
+        // The request that crosses the budget still completes; the next one is never sent
+        client.chat_completion(ask()).await.unwrap();
+        let error = client.chat_completion(ask()).await.err().unwrap();
+        assert_eq!(error.downcast_ref::<TokenBudgetExhausted>(), Some(&TokenBudgetExhausted { used: 1400, limit: 1000 }));
+
+        // Switching models does not reset the count
+        let switched = OpenRouterClient::new(config).await.unwrap().with_session_tokens_of(&client);
+        assert!(switched.chat_completion(ask()).await.is_err());
+        chat.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_content_filtered_responses() {
+        let mut server = mockito::Server::new_async().await;
+        let withheld = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex("withheld".to_string()))
+            .with_body(r#"{"id": "gen-1", "choices": [{"index": 0, "finish_reason": "content_filter",
+                "content_filter_results": {"violence": {"filtered": true, "severity": "high"}}}]}"#)
+            .create_async()
+            .await;
+        let flagged = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex("flagged".to_string()))
+            .with_status(403)
+            .with_body(r#"{"error": {"code": 403, "message": "openai/gpt-4o requires moderation on OpenRouter. Your input was flagged for \"violence\"",
+                "metadata": {"reasons": ["violence", "harassment"], "flagged_input": "…", "provider_name": "OpenAI"}}}"#)
+            .create_async()
+            .await;
+        let cut_short = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex("partial".to_string()))
+            .with_body(json!({"choices": [{"message": {"role": "assistant", "content": "Here is"}, "finish_reason": "content_filter"}]}).to_string())
+            .create_async()
+            .await;
+
+        let mut config = Config::default();
+        config.openrouter.api_key = Some("sk-test".to_string());
+        config.openrouter.base_url = server.url();
+        config.preferences.spinner = false;
+        let client = OpenRouterClient::new(config).await.unwrap();
+        let ask = |prompt: &str| vec![Message {
+            role: "user".to_string(),
+            content: prompt.into(),
+            tool_calls: None,
+            tool_call_id: None,
+        }];
+
+        let error = client.chat_completion(ask("withheld")).await.err().unwrap();
+        assert_eq!(error.downcast_ref::<ContentFiltered>(), Some(&ContentFiltered { reason: None }));
+        assert_eq!(error.to_string(), "The provider blocked this response");
+
+        let error = client.chat_completion(ask("flagged")).await.err().unwrap();
+        assert_eq!(error.to_string(), "The provider blocked this response (violence, harassment)");
+
+        // Whatever arrived before the filter stepped in is still a reply
+        let response = client.chat_completion(ask("partial")).await.unwrap();
+        assert_eq!(response.choices[0].message.content.text(), "Here is");
+        assert!(response.choices[0].content_filtered());
+
+        withheld.assert_async().await;
+        flagged.assert_async().await;
+        cut_short.assert_async().await;
+
+        let azure = r#"{"error": {"code": "content_filter", "message": "The response was filtered due to the prompt triggering content management policy."}}"#;
+        let moderation = serde_json::from_str::<ErrorResponse>(azure).unwrap().moderation().unwrap();
+        assert_eq!(moderation.reason.as_deref(), Some("The response was filtered due to the prompt triggering content management policy."));
+        assert!(serde_json::from_str::<ErrorResponse>(r#"{"error": {"message": "Rate limited", "code": 429}}"#).unwrap().moderation().is_none());
+    }
+
+    #[test]
+    fn test_tool_choice_serialization() {
+        assert_eq!(serde_json::to_value(ToolChoice::Auto).unwrap(), json!("auto"));
+        assert_eq!(serde_json::to_value(ToolChoice::None).unwrap(), json!("none"));
+        assert_eq!(
+            serde_json::to_value(ToolChoice::force("complete")).unwrap(),
+            json!({"type": "function", "function": {"name": "complete"}})
+        );
+    }
+
+    #[test]
+    fn test_tools_unsupported_detection_and_request_shape() {
+        assert!(is_tools_unsupported_error("No endpoints found that support tool use"));
+        assert!(is_tools_unsupported_error("This model does not support tools"));
+        assert!(!is_tools_unsupported_error("Rate limit exceeded"));
+
+        let request = OpenRouterRequest {
+            model: "old/model".to_string(),
+            messages: Vec::new(),
+            tools: Vec::new(),
+            tool_choice: None,
+            parallel_tool_calls: None,
+            max_tokens: None,
+            seed: None,
+            n: None,
+            stream: None,
+            stream_options: None,
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("tools").is_none());
+        assert!(json.get("tool_choice").is_none());
+        assert!(json.get("parallel_tool_calls").is_none());
+        assert!(json.get("seed").is_none());
+        assert!(json.get("n").is_none());
+        assert!(json.get("stream").is_none());
+
+        let seeded = OpenRouterRequest { seed: Some(42), n: Some(3), ..request };
+        assert_eq!(serde_json::to_value(&seeded).unwrap()["seed"], 42);
+        assert_eq!(serde_json::to_value(&seeded).unwrap()["n"], 3);
+    }
+}
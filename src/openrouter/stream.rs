@@ -0,0 +1,297 @@
+//! Assembly of streamed chat completions (`"stream": true` server-sent events, `openrouter.stream`)
+//! A tool call cut off mid-stream is caught here before its arguments reach a tool, and the request is retried
+//! without streaming
+
+use super::{Choice, Message, OpenRouterResponse, ToolCall, ToolCallFunction, Usage};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Why a stream could not be turned into a reply. Each case means the turn should be retried without streaming.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamError {
+    /// The stream ended before `[DONE]` or a finish reason arrived
+    Truncated,
+    /// The assembled arguments of a tool call are not a JSON object
+    MalformedToolCall { index: usize, name: String },
+    /// An event's payload was not a valid chunk
+    InvalidEvent(String),
+    /// The provider reported an error part-way through the stream
+    Provider(String),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::Truncated => write!(f, "stream ended before the reply was complete"),
+            StreamError::MalformedToolCall { index, name } => {
+                write!(f, "tool call {} ({}) has incomplete or invalid arguments", index, name)
+            }
+            StreamError::InvalidEvent(reason) => write!(f, "invalid stream event: {}", reason),
+            StreamError::Provider(message) => write!(f, "provider error mid-stream: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+#[derive(Deserialize)]
+struct Chunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    error: Option<Value>,
+    /// Sent with the last chunk
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: Delta,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default, alias = "reasoning_content")]
+    reasoning: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCallDelta>,
+}
+
+/// Fragment of one tool call; `index` ties the fragments of a call together, the id and name arrive once
+#[derive(Deserialize)]
+struct ToolCallDelta {
+    index: usize,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    function: Option<FunctionDelta>,
+}
+
+#[derive(Deserialize)]
+struct FunctionDelta {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+#[derive(Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Collects server-sent events into the reply they describe
+#[derive(Default)]
+pub struct StreamAccumulator {
+    /// Bytes of a line that has not been terminated yet; events can be split anywhere, even inside a character
+    pending: Vec<u8>,
+    content: String,
+    reasoning: String,
+    tool_calls: BTreeMap<usize, PartialToolCall>,
+    finish_reason: Option<String>,
+    usage: Option<Usage>,
+    done: bool,
+}
+
+impl StreamAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed bytes as they arrive from the response body
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), StreamError> {
+        self.pending.extend_from_slice(bytes);
+        while let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            self.push_line(line.trim_end_matches(['\r', '\n']))?;
+        }
+        Ok(())
+    }
+
+    fn push_line(&mut self, line: &str) -> Result<(), StreamError> {
+        // Blank lines separate events; lines starting with ':' are keep-alive comments
+        let Some(data) = line.strip_prefix("data:") else {
+            return Ok(());
+        };
+        let data = data.trim();
+        if data == "[DONE]" {
+            self.done = true;
+            return Ok(());
+        }
+
+        let chunk: Chunk = serde_json::from_str(data).map_err(|e| StreamError::InvalidEvent(e.to_string()))?;
+        if let Some(error) = chunk.error {
+            let message = error["message"].as_str().map(str::to_string).unwrap_or_else(|| error.to_string());
+            return Err(StreamError::Provider(message));
+        }
+
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage;
+        }
+        for choice in chunk.choices {
+            if let Some(content) = choice.delta.content {
+                self.content.push_str(&content);
+            }
+            if let Some(reasoning) = choice.delta.reasoning {
+                self.reasoning.push_str(&reasoning);
+            }
+            for fragment in choice.delta.tool_calls {
+                let call = self.tool_calls.entry(fragment.index).or_default();
+                if let Some(id) = fragment.id {
+                    call.id = id;
+                }
+                if let Some(function) = fragment.function {
+                    if let Some(name) = function.name {
+                        call.name.push_str(&name);
+                    }
+                    if let Some(arguments) = function.arguments {
+                        call.arguments.push_str(&arguments);
+                    }
+                }
+            }
+            if choice.finish_reason.is_some() {
+                self.finish_reason = choice.finish_reason;
+            }
+        }
+        Ok(())
+    }
+
+    /// Text received so far, for showing the reply while it streams
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// The assembled reply with the token usage reported for it, once the stream has ended
+    pub fn into_response(mut self) -> Result<OpenRouterResponse, StreamError> {
+        let usage = self.usage.take();
+        Ok(OpenRouterResponse { choices: vec![self.finish()?], usage })
+    }
+
+    /// The assembled reply, once the stream has ended. Fails rather than return tool calls whose arguments
+    /// were cut off, so malformed arguments are never executed.
+    pub fn finish(mut self) -> Result<Choice, StreamError> {
+        if !self.pending.is_empty() {
+            // An unterminated last event is fine if complete, and means the connection dropped if not
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).to_string();
+            match self.push_line(line.trim_end_matches('\r')) {
+                Err(StreamError::InvalidEvent(_)) => return Err(StreamError::Truncated),
+                result => result?,
+            }
+        }
+        if !self.done && self.finish_reason.is_none() {
+            return Err(StreamError::Truncated);
+        }
+
+        let mut tool_calls = Vec::with_capacity(self.tool_calls.len());
+        for (index, call) in self.tool_calls {
+            // Tools without parameters may stream no argument text at all
+            let arguments = if call.arguments.trim().is_empty() { "{}".to_string() } else { call.arguments };
+            if call.name.is_empty() || !matches!(serde_json::from_str::<Value>(&arguments), Ok(Value::Object(_))) {
+                return Err(StreamError::MalformedToolCall { index, name: call.name });
+            }
+            tool_calls.push(ToolCall {
+                id: call.id,
+                call_type: "function".to_string(),
+                function: ToolCallFunction { name: call.name, arguments },
+            });
+        }
+
+        Ok(Choice {
+            message: Message {
+                role: "assistant".to_string(),
+                content: self.content.into(),
+                tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                tool_call_id: None,
+            },
+            finish_reason: self.finish_reason,
+            reasoning: (!self.reasoning.trim().is_empty()).then_some(self.reasoning),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stream that says "Reading" and then calls read_file, its arguments spread over several events
+    const EVENTS: &str = concat!(
+        ": OPENROUTER PROCESSING\n\n",
+        "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"Read\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"ing\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"read_file\",\"arguments\":\"\"}}]}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"pa\"}}]}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"th\\\":\\\"src/é.rs\\\"}\"}}]}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"tool_calls\"}]}\n\n",
+        "data: [DONE]\n\n",
+    );
+
+    fn accumulate(bytes: &[u8], split: usize) -> StreamAccumulator {
+        let mut accumulator = StreamAccumulator::new();
+        for piece in bytes.chunks(split) {
+            accumulator.push_bytes(piece).unwrap();
+        }
+        accumulator
+    }
+
+    #[test]
+    fn test_stream_assembles_tool_call_fragments() {
+        // Split at every size so events, JSON and the multi-byte character are all cut somewhere
+        for split in 1..=EVENTS.len() {
+            let choice = accumulate(EVENTS.as_bytes(), split).finish().unwrap();
+            assert_eq!(choice.message.content.text(), "Reading");
+            assert_eq!(choice.finish_reason.as_deref(), Some("tool_calls"));
+            let calls = choice.message.tool_calls.unwrap();
+            assert_eq!(calls.len(), 1);
+            assert_eq!(calls[0].id, "call_1");
+            assert_eq!(calls[0].function.name, "read_file");
+            assert_eq!(calls[0].function.arguments, r#"{"path":"src/é.rs"}"#);
+        }
+    }
+
+    #[test]
+    fn test_stream_collects_reasoning_apart_from_content() {
+        let events = concat!(
+            "data: {\"choices\":[{\"delta\":{\"reasoning\":\"Two plus \"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"reasoning\":\"two.\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"4\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":6}}\n\n",
+        );
+        assert_eq!(accumulate(events.as_bytes(), 16).into_response().unwrap().usage.map(|usage| usage.total()), Some(15));
+        let choice = accumulate(events.as_bytes(), 16).finish().unwrap();
+        assert_eq!(choice.message.content.text(), "4");
+        assert_eq!(choice.reasoning.as_deref(), Some("Two plus two."));
+        assert!(accumulate(EVENTS.as_bytes(), 64).finish().unwrap().reasoning.is_none());
+    }
+
+    #[test]
+    fn test_truncated_stream_is_not_executed() {
+        // The connection drops in the middle of the tool call's arguments
+        let cut = EVENTS.find("th\\\"").unwrap();
+        let accumulator = accumulate(&EVENTS.as_bytes()[..cut], 7);
+        assert_eq!(accumulator.content(), "Reading");
+        assert_eq!(accumulator.finish().err(), Some(StreamError::Truncated));
+
+        // A finish reason with cut-off arguments, as when the token limit is hit mid-call
+        let mut accumulator = accumulate(&EVENTS.as_bytes()[..EVENTS[..cut].rfind("data: ").unwrap()], 64);
+        accumulator.push_bytes(b"data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"length\"}]}\n\n").unwrap();
+        assert_eq!(
+            accumulator.finish().err(),
+            Some(StreamError::MalformedToolCall { index: 0, name: "read_file".to_string() })
+        );
+
+        let mut accumulator = StreamAccumulator::new();
+        let error = accumulator.push_bytes(b"data: {\"error\":{\"message\":\"upstream timeout\"}}\n\n").unwrap_err();
+        assert_eq!(error, StreamError::Provider("upstream timeout".to_string()));
+    }
+}
@@ -49,6 +49,15 @@ impl fmt::Display for ActionStatus {
 }
 
 impl ActionStatus {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            ActionStatus::Pending => "⏳",
+            ActionStatus::InProgress => "🔄",
+            ActionStatus::Completed => "✅",
+            ActionStatus::Failed => "❌",
+        }
+    }
+
     fn as_str(&self) -> &str {
         match self {
             ActionStatus::Pending => " Pending",
@@ -59,6 +68,23 @@ impl ActionStatus {
     }
 }
 
+impl ActionPlan {
+    /// Drop the actions with the given ids, along with phases left empty and dependencies on them.
+    /// Returns how many actions were removed.
+    pub fn remove_actions(&mut self, ids: &[usize]) -> usize {
+        let count = |plan: &ActionPlan| plan.phases.iter().map(|phase| phase.actions.len()).sum::<usize>();
+        let before = count(self);
+        for phase in &mut self.phases {
+            phase.actions.retain(|action| !ids.contains(&action.id));
+            for action in &mut phase.actions {
+                action.dependencies.retain(|dependency| !ids.contains(dependency));
+            }
+        }
+        self.phases.retain(|phase| !phase.actions.is_empty());
+        before - count(self)
+    }
+}
+
 impl fmt::Display for ActionPlan {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "\x1b[1m\x1b[34m╔══════════════════════════════════════════════════════════════════════╗\x1b[0m")?;
@@ -126,6 +152,30 @@ impl fmt::Display for Action {
     }
 }
 
+/// Compact progress view of a plan: one numbered line per action with its status icon
+pub fn render_plan(plan: &ActionPlan) -> String {
+    let actions: Vec<&Action> = plan.phases.iter().flat_map(|p| &p.actions).collect();
+    let completed = actions.iter().filter(|a| a.status == ActionStatus::Completed).count();
+    let failed = actions.iter().filter(|a| a.status == ActionStatus::Failed).count();
+
+    let mut lines = vec![format!("\x1b[1m🎯 {}\x1b[0m", plan.title)];
+    for (index, action) in actions.iter().enumerate() {
+        let line = format!("{:>3}. {} {} ({} → {})", index + 1, action.status.icon(), action.title, action.tool, action.target);
+        lines.push(match action.status {
+            ActionStatus::InProgress => format!("\x1b[1m{}\x1b[0m", line),
+            ActionStatus::Completed => format!("\x1b[2m{}\x1b[0m", line),
+            _ => line,
+        });
+    }
+
+    let mut progress = format!("     {}", create_progress_bar(completed, actions.len(), 30));
+    if failed > 0 {
+        progress.push_str(&format!(" \x1b[31m{} failed\x1b[0m", failed));
+    }
+    lines.push(progress);
+    lines.join("\n")
+}
+
 fn wrap_text(text: &str, width: usize, indent: &str) -> String {
     let words: Vec<&str> = text.split_whitespace().collect();
     let mut lines = Vec::new();
@@ -187,4 +237,71 @@ mod tests {
         assert!(bar.contains("░"));
         assert!(bar.contains("(3/10)"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_render_plan_lists_actions_with_status() {
+        let action = |id: usize, title: &str, status: ActionStatus| Action {
+            id,
+            title: title.to_string(),
+            tool: "create_file".to_string(),
+            target: format!("file{}.rs", id),
+            operation: String::new(),
+            purpose: String::new(),
+            success_criteria: String::new(),
+            dependencies: Vec::new(),
+            status,
+        };
+        let plan = ActionPlan {
+            title: "Build it".to_string(),
+            overview: String::new(),
+            phases: vec![Phase {
+                name: "Setup".to_string(),
+                emoji: "🏗️".to_string(),
+                actions: vec![
+                    action(1, "Scaffold", ActionStatus::Completed),
+                    action(2, "Implement", ActionStatus::InProgress),
+                    action(3, "Test", ActionStatus::Failed),
+                ],
+            }],
+            expected_outcome: String::new(),
+        };
+
+        let rendered = render_plan(&plan);
+        assert!(rendered.contains("  1. ✅ Scaffold (create_file → file1.rs)"));
+        assert!(rendered.contains("  2. 🔄 Implement"));
+        assert!(rendered.contains("  3. ❌ Test"));
+        assert!(rendered.contains("(1/3)"));
+        assert!(rendered.contains("1 failed"));
+    }
+
+    #[test]
+    fn test_remove_actions_drops_empty_phases_and_dependencies() {
+        let action = |id: usize, dependencies: Vec<usize>| Action {
+            id,
+            title: format!("Action {}", id),
+            tool: "create_file".to_string(),
+            target: format!("file{}.rs", id),
+            operation: String::new(),
+            purpose: String::new(),
+            success_criteria: String::new(),
+            dependencies,
+            status: ActionStatus::Pending,
+        };
+        let phase = |name: &str, actions: Vec<Action>| Phase { name: name.to_string(), emoji: "🔧".to_string(), actions };
+        let mut plan = ActionPlan {
+            title: "Build it".to_string(),
+            overview: String::new(),
+            phases: vec![
+                phase("Setup", vec![action(1, vec![])]),
+                phase("Build", vec![action(2, vec![1]), action(3, vec![1, 2])]),
+            ],
+            expected_outcome: String::new(),
+        };
+
+        assert_eq!(plan.remove_actions(&[1, 9]), 1);
+        assert_eq!(plan.phases.len(), 1);
+        assert_eq!(plan.phases[0].actions[1].dependencies, vec![2]);
+        assert_eq!(plan.remove_actions(&[2, 3]), 2);
+        assert!(plan.phases.is_empty());
+    }
+}
@@ -7,6 +7,8 @@ impl PromptManager {
     pub fn get_system_prompt() -> String {
         "You are an expert coding assistant that creates detailed, step-by-step action plans for coding tasks. \
         You work with filesystem and command execution tools to complete user requests. \
+        When you need several independent reads or lookups, request them together in one response rather than one per turn. \
+        When a tool error says \"recoverable\": false, stop retrying it and tell the user what they need to do. \
         You always respond with valid JSON when JSON format is requested, and you follow the exact format specified in user prompts. \
         You create comprehensive plans that break down complex requests into specific, executable actions.".to_string()
     }
@@ -31,14 +33,14 @@ Your plan must include:
 
 ### 2. **Discovery Actions**
 For each discovery step, specify:
-- **Tool**: Which tool to use (Read, Grep, Glob, LS, Bash, etc.)
+- **Tool**: Which tool to use (read_file, list_directory, run_command, etc.)
 - **Target**: Specific files, directories, or patterns to examine
 - **Purpose**: What information you're seeking
 - **Expected Output**: What you expect to find
 
 ### 3. **Implementation Actions**
 For each implementation step, specify:
-- **Tool**: Which tool to use (Edit, MultiEdit, Write, Bash, etc.)
+- **Tool**: Which tool to use (write_file, create_file, run_command, etc.)
 - **Operation**: Exact changes to make
 - **Files**: Specific file paths involved
 - **Dependencies**: Prerequisites from previous steps
@@ -123,14 +125,13 @@ Each action item must follow this structure:
 
 ## Tool Selection Guide
 
-- **Read**: For examining specific files
-- **Grep**: For searching content across files
-- **Glob**: For finding files by pattern
-- **LS**: For directory exploration
-- **Edit/MultiEdit**: For modifying existing files
-- **Write**: For creating new files (use sparingly)
-- **Bash**: For running commands, tests, builds
-- **Task**: For complex multi-step operations
+- **read_file**: For examining specific files
+- **list_directory**: For directory exploration
+- **query_context**: For the project's language, framework and layout
+- **run_command**: For searching content (`grep`, `find`) and running commands, tests, builds
+- **write_file**: For modifying existing files (send the complete new content)
+- **create_file**: For creating new files (use sparingly)
+- **create_directory**: For creating directories
 
 ## Response Requirements
 
@@ -218,8 +219,8 @@ Remember: The goal is to create a plan so detailed that any LLM could execute it
             You have access to filesystem operations, command execution, and code editing tools. \
             Be systematic and thorough in your approach.\n\n\
             Important:\n\
-            - Use the Read tool to examine files before editing\n\
-            - Use appropriate tools for each operation (Edit, MultiEdit, Write, Bash, etc.)\n\
+            - Use read_file to examine files before editing\n\
+            - Use appropriate tools for each operation (write_file, create_file, run_command, etc.)\n\
             - Verify your changes after implementation\n\
             - Provide clear feedback about what you're doing",
             working_dir,
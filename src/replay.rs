@@ -0,0 +1,224 @@
+//! `loo replay`: re-run a recorded session's tool calls against another directory
+//! The model is never contacted; each result is compared with the one recorded in the session
+
+use crate::config::{Config, ConfigManager, VERBOSE_TOOL_RESULTS};
+use crate::openrouter::{Message, ToolCall};
+use crate::session::{SessionRecord, SessionStore};
+use crate::theme::{self, ThemeRole};
+use crate::tools::error::classify as classify_tool_error;
+use crate::tools::ToolExecutor;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Result fields that legitimately differ between directories
+const VOLATILE_FIELDS: &[&str] = &["absolute_path"];
+
+/// A tool call from the session together with the result recorded for it
+pub struct ReplayStep {
+    pub call: ToolCall,
+    pub recorded: Option<String>,
+}
+
+/// Load the session behind `source`: a session JSON file, a story.md (via its session ID) or a bare session ID
+pub fn resolve_session(source: &str, store: Option<&SessionStore>) -> Result<SessionRecord, Box<dyn std::error::Error>> {
+    let path = Path::new(source);
+    if !path.is_file() {
+        let store = store.ok_or_else(|| format!("No such session or story file: {}", source))?;
+        return store.load(source);
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", source, e))?;
+    if let Ok(record) = serde_json::from_str::<SessionRecord>(&content) {
+        return Ok(record);
+    }
+
+    // story.md leaves file contents out of the tool arguments, so replay the session it was written for
+    let session_id = story_session_id(&content)
+        .ok_or_else(|| format!("{} is neither a session record nor a loo story", source))?;
+    let store = store.ok_or("No session store available to look up the story's session")?;
+    store.load(session_id).map_err(|e| {
+        format!("{} was written by session {}, whose record could not be loaded: {}", source, session_id, e).into()
+    })
+}
+
+fn story_session_id(story: &str) -> Option<&str> {
+    story
+        .lines()
+        .find_map(|line| line.strip_prefix("**Session ID:** `"))
+        .and_then(|rest| rest.split_once('`'))
+        .map(|(id, _)| id)
+}
+
+/// Tool calls in the order they were made, each paired with its recorded result
+pub fn recorded_steps(messages: &[Message]) -> Vec<ReplayStep> {
+    messages
+        .iter()
+        .filter(|message| message.role == "assistant")
+        .flat_map(|message| message.tool_calls.iter().flatten())
+        .map(|call| ReplayStep {
+            call: call.clone(),
+            recorded: messages
+                .iter()
+                .find(|message| message.role == "tool" && message.tool_call_id.as_deref() == Some(call.id.as_str()))
+                .map(|message| message.content.text()),
+        })
+        .collect()
+}
+
+/// Describe how a replayed result differs from the recorded one, ignoring directory-specific fields.
+/// Errors are compared by category only, since their messages embed paths.
+pub fn divergence(recorded: &str, replayed: &str) -> Option<String> {
+    let (recorded, replayed) = (comparable(recorded), comparable(replayed));
+    if recorded == replayed {
+        return None;
+    }
+
+    let (Value::Object(recorded), Value::Object(replayed)) = (&recorded, &replayed) else {
+        return Some("result differs".to_string());
+    };
+    let mut fields: Vec<&str> = recorded
+        .keys()
+        .chain(replayed.keys())
+        .map(|key| key.as_str())
+        .filter(|key| recorded.get(*key) != replayed.get(*key))
+        .collect();
+    fields.sort_unstable();
+    fields.dedup();
+
+    Some(match (recorded.get("status"), replayed.get("status")) {
+        (Some(before), Some(after)) if before != after => format!("status {} → {}", before, after),
+        _ => format!("{} differ", fields.join(", ")),
+    })
+}
+
+fn comparable(result: &str) -> Value {
+    let Ok(Value::Object(mut fields)) = serde_json::from_str::<Value>(result) else {
+        return Value::String(result.to_string());
+    };
+    if fields.get("status").and_then(Value::as_str) == Some("error") {
+        fields.retain(|key, _| key == "status" || key == "category");
+    }
+    for field in VOLATILE_FIELDS {
+        fields.remove(*field);
+    }
+    Value::Object(fields)
+}
+
+/// Replay `source` into `target_dir`, printing one line per tool call. Fails if any call diverged.
+pub async fn run(source: &str, target_dir: &str, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let store = SessionStore::new().ok();
+    let record = resolve_session(source, store.as_ref())?;
+    let steps = recorded_steps(&record.messages);
+
+    // Replayed calls write and delete files, so they only run where nothing can be lost
+    if fs::read_dir(target_dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(format!("Refusing to replay into {}: the directory is not empty", target_dir).into());
+    }
+    fs::create_dir_all(target_dir).map_err(|e| format!("Cannot create {}: {}", target_dir, e))?;
+    let target_dir = fs::canonicalize(target_dir)?.to_string_lossy().to_string();
+    let executor = ToolExecutor::new(target_dir.clone(), config.preferences.verbose >= VERBOSE_TOOL_RESULTS)
+        .with_git_guard(config.tools.git_protected_branches.clone(), config.preferences.auto_confirm)
+        .with_command_approval(config.tools.confirm_commands)
+        .with_max_write_bytes(config.tools.max_write_bytes)
+        .with_max_output_lines(config.tools.max_output_lines)
+        .with_line_endings(config.tools.line_ending, config.tools.trailing_newline)
+        .with_format_on_write(config.tools.format_on_write)
+        .with_scaffold_dir(ConfigManager::scaffold_dir().ok())
+        .with_template_dirs(config.tools.template_dir.clone(), ConfigManager::file_templates_dir().ok())
+        .with_redaction(config.preferences.redact_secrets);
+
+    println!("🔁 Replaying {} tool call(s) from session {}", steps.len(), record.id);
+    println!("📁 Target directory: {}", target_dir);
+
+    let mut diverged = 0;
+    for (i, step) in steps.iter().enumerate() {
+        let replayed = match executor.execute_tool_call(&step.call).await {
+            Ok(result) => result,
+            Err(e) => classify_tool_error(&step.call.function.name, e.as_ref()).to_json().to_string(),
+        };
+
+        let label = format!("{:>3}. {}", i + 1, step.call.function.name);
+        match step.recorded.as_deref().map(|recorded| divergence(recorded, &replayed)) {
+            Some(None) => println!("  ✅ {}", label),
+            Some(Some(difference)) => {
+                diverged += 1;
+                println!("{}", theme::paint(ThemeRole::Warning, &format!("  ⚠️ {}: {}", label, difference)));
+            }
+            None => println!("  ❔ {} (no recorded result)", label),
+        }
+    }
+
+    if diverged > 0 {
+        return Err(format!("{} of {} tool call(s) diverged from the recording", diverged, steps.len()).into());
+    }
+    println!("✅ Replay matched the recording");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openrouter::ToolCallFunction;
+
+    fn tool_call(id: &str, name: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: ToolCallFunction { name: name.to_string(), arguments: arguments.to_string() },
+        }
+    }
+
+    fn message(role: &str, content: &str, tool_calls: Option<Vec<ToolCall>>, tool_call_id: Option<&str>) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string().into(),
+            tool_calls,
+            tool_call_id: tool_call_id.map(|id| id.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_recorded_steps_pair_calls_with_results() {
+        let messages = vec![
+            message("user", "make a file", None, None),
+            message("assistant", "", Some(vec![
+                tool_call("a", "create_file", r#"{"path":"x.txt","content":"hi"}"#),
+                tool_call("b", "read_file", r#"{"path":"x.txt"}"#),
+            ]), None),
+            message("tool", r#"{"status":"success"}"#, None, Some("a")),
+            message("assistant", "done", None, None),
+        ];
+
+        let steps = recorded_steps(&messages);
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].call.function.name, "create_file");
+        assert_eq!(steps[0].recorded.as_deref(), Some(r#"{"status":"success"}"#));
+        assert!(steps[1].recorded.is_none());
+    }
+
+    #[test]
+    fn test_divergence_ignores_paths_and_reports_changes() {
+        let recorded = r#"{"status":"success","path":"x.txt","size":2,"absolute_path":"/old/x.txt"}"#;
+        assert_eq!(divergence(recorded, r#"{"status":"success","path":"x.txt","size":2,"absolute_path":"/new/x.txt"}"#), None);
+        assert_eq!(
+            divergence(recorded, r#"{"status":"success","path":"x.txt","size":5,"absolute_path":"/new/x.txt"}"#).as_deref(),
+            Some("size differ")
+        );
+        assert_eq!(
+            divergence(recorded, r#"{"status":"error","category":"not_found","message":"/new/x.txt"}"#).as_deref(),
+            Some("status \"success\" → \"error\"")
+        );
+        assert_eq!(
+            divergence(r#"{"status":"error","category":"not_found","message":"/old"}"#, r#"{"status":"error","category":"not_found","message":"/new"}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_story_session_id() {
+        let story = "# LOO CLI Session Story\n\n**Session ID:** `1234-abcd`\n**Working Directory:** `/tmp`\n";
+        assert_eq!(story_session_id(story), Some("1234-abcd"));
+        assert_eq!(story_session_id("# notes"), None);
+    }
+}
@@ -1,14 +1,43 @@
-use crate::config::Config;
-use crate::openrouter::{Message, OpenRouterClient};
+use crate::attachments::{extract_file_references, extract_image_references, load_context_files, load_file_context, load_image_part};
+use crate::autosave::AutosaveSnapshot;
+use crate::changes::{ChangeKind, ChangeTracker};
+use crate::commands::registry::ListModelsArgs;
+use crate::commands::{execute_command, semantic_commands};
+use crate::compaction::compact_messages;
+use crate::config::{CompleteAction, Config, ToolOutput, VERBOSE_SUMMARIES, VERBOSE_TOOL_RESULTS};
+use crate::diff::{diff_stats, paint_diff, unified_diff};
+use crate::input::unescape_file_triggers;
+use crate::openrouter::{Choice, ContentFiltered, ContentPart, Message, MessageContent, OpenRouterClient, TokenBudgetExhausted, ToolChoice};
 use crate::prompts::PromptManager;
-use crate::story::StoryLogger;
-use crate::tools::ToolExecutor;
-use inquire::Autocomplete;
+use crate::session::{clip, format_session_list, format_transcript, format_work_summary, SessionRecord, SessionStore};
+use crate::spinner::ProgressLog;
+use crate::story::{format_duration, summarize_tool_result, StoryLogger, ToolUsageSummary};
+use crate::theme::{self, ThemeRole};
+use crate::tools::error::classify as classify_tool_error;
+use crate::tools::batch::CallCoalescer;
+use crate::tools::deletes::{bulk_delete_denied, DeleteGuard};
+use crate::tools::{completion_summary, is_interrupted, skipped_after_complete, skipped_after_interrupt, ToolExecutor};
+use crate::watcher::{format_change_note, FileWatcher};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::collections::VecDeque;
+use std::env;
 use std::fs;
+use std::io::IsTerminal;
 use std::path::Path;
+use std::time::Instant;
+use tracing::Instrument;
+
+/// Characters of each tool call's arguments and result included when asking for an explanation
+const EXPLAIN_ARGUMENTS_CHARS: usize = 300;
+const EXPLAIN_RESULT_CHARS: usize = 800;
+
+/// Largest file kept in memory to diff against when the model rewrites it
+const REWRITE_DIFF_MAX_BYTES: usize = 1024 * 1024;
+
+/// Diff lines printed for a rewrite; story.md gets the whole diff
+const REWRITE_DIFF_LINES: usize = 40;
 
 /// Represents conversation context and semantic understanding
 #[derive(Debug, Clone)]
@@ -23,6 +52,8 @@ pub struct ConversationContext {
     pub state: ConversationState,
     /// Working memory for ongoing tasks
     pub working_memory: Vec<String>,
+    /// Durable notes added with /remember, kept across sessions
+    pub notes: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -54,6 +85,31 @@ struct ConversationStateAnalysis {
     suggested_tools: Vec<String>,
 }
 
+impl ConversationState {
+    pub const ALL: [ConversationState; 5] = [
+        ConversationState::Conversational,
+        ConversationState::Planning,
+        ConversationState::Implementing,
+        ConversationState::Troubleshooting,
+        ConversationState::Exploring,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConversationState::Conversational => "conversational",
+            ConversationState::Planning => "planning",
+            ConversationState::Implementing => "implementing",
+            ConversationState::Troubleshooting => "troubleshooting",
+            ConversationState::Exploring => "exploring",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ConversationState> {
+        let name = name.to_lowercase();
+        Self::ALL.iter().find(|state| state.name() == name).cloned()
+    }
+}
+
 impl Default for ConversationContext {
     fn default() -> Self {
         Self {
@@ -67,6 +123,7 @@ impl Default for ConversationContext {
             ],
             state: ConversationState::Conversational,
             working_memory: Vec::new(),
+            notes: Vec::new(),
         }
     }
 }
@@ -81,13 +138,38 @@ pub struct SemanticEngine {
     pub session_id: String,
     pub messages: Vec<Message>,
     pub context: ConversationContext,
+    pub started_at: DateTime<Utc>,
+    /// Watches the working directory for external edits when `preferences.watch_files` is on
+    pub file_watcher: Option<FileWatcher>,
+    /// Files changed outside loo since the previous turn
+    pub external_changes: Vec<String>,
+    /// Tool calls made during the current turn, summarized when it ends
+    turn_tool_usage: ToolUsageSummary,
+    /// `delete_file` calls requested this turn, checked against `tools.max_deletes_per_turn`
+    delete_guard: DeleteGuard,
+    /// Files created, modified or deleted by tool calls this session
+    pub changes: ChangeTracker,
+    /// Mode forced with /mode; disables automatic state detection until `/mode auto`
+    pub pinned_state: Option<ConversationState>,
+    /// Instructions from `--append-system`, added to the end of every system message this run
+    pub system_additions: Vec<String>,
+    /// Files from `preferences.context_files` and `--context-files`, read again after /clear
+    pub context_files: Vec<String>,
+    /// Their contents, sent after the system message with every request
+    context_message: Option<Message>,
+    /// What a resumed session already did, sent after the context files until /clear
+    work_summary_message: Option<Message>,
 }
 
 impl SemanticEngine {
     pub async fn new(
         working_dir: String,
         cli_model: Option<String>,
-        cli_verbose: bool,
+        cli_max_tokens: Option<u32>,
+        cli_seed: Option<u64>,
+        cli_choices: Option<u32>,
+        cli_verbose: u8,
+        cli_no_story: bool,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         use crate::config::ConfigManager;
         use uuid::Uuid;
@@ -98,14 +180,62 @@ impl SemanticEngine {
             config.openrouter.model = model;
         }
 
-        if cli_verbose {
-            config.preferences.verbose = true;
+        if cli_max_tokens.is_some() {
+            config.openrouter.max_tokens = cli_max_tokens;
+        }
+
+        if cli_seed.is_some() {
+            config.openrouter.seed = cli_seed;
+        }
+
+        if cli_choices.is_some() {
+            config.openrouter.n = cli_choices;
+        }
+
+        config.preferences.verbose = config.preferences.verbose.max(cli_verbose);
+
+        if cli_no_story {
+            config.preferences.write_story = false;
         }
 
         let openrouter_client = OpenRouterClient::new(config.clone()).await?;
-        let tool_executor = ToolExecutor::new(working_dir.clone(), config.preferences.verbose);
+        let tool_executor = ToolExecutor::new(working_dir.clone(), config.preferences.verbose >= VERBOSE_TOOL_RESULTS)
+            .with_git_guard(config.tools.git_protected_branches.clone(), config.preferences.auto_confirm)
+            .with_command_approval(config.tools.confirm_commands)
+            .with_max_write_bytes(config.tools.max_write_bytes)
+            .with_max_output_lines(config.tools.max_output_lines)
+            .with_line_endings(config.tools.line_ending, config.tools.trailing_newline)
+            .with_format_on_write(config.tools.format_on_write)
+            .with_scaffold_dir(ConfigManager::scaffold_dir().ok())
+            .with_template_dirs(config.tools.template_dir.clone(), ConfigManager::file_templates_dir().ok())
+            .with_redaction(config.preferences.redact_secrets);
         let session_id = Uuid::new_v4().to_string();
-        let story_logger = StoryLogger::new(working_dir.clone(), session_id.clone());
+        let story_logger = StoryLogger::new(working_dir.clone(), session_id.clone())
+            .with_assistant_name(config.preferences.assistant_name.clone())
+            .with_file_output(config.preferences.write_story)
+            .with_format(config.preferences.story_format);
+
+        // Recall working memory and notes from the last session in this directory
+        let mut context = ConversationContext::default();
+        if let Some(previous) = SessionStore::new()
+            .and_then(|store| store.latest_for_dir(&working_dir))
+            .unwrap_or(None)
+        {
+            context.working_memory = previous.working_memory;
+            context.notes = previous.notes;
+        }
+
+        let file_watcher = if config.preferences.watch_files {
+            match FileWatcher::start(&working_dir) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: File watching disabled: {}", e)));
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
         Ok(Self {
             openrouter_client,
@@ -115,19 +245,482 @@ impl SemanticEngine {
             working_dir,
             session_id,
             messages: Vec::new(),
-            context: ConversationContext::default(),
+            context,
+            started_at: Utc::now(),
+            file_watcher,
+            external_changes: Vec::new(),
+            turn_tool_usage: ToolUsageSummary::new(),
+            delete_guard: DeleteGuard::default(),
+            changes: ChangeTracker::new(),
+            pinned_state: None,
+            system_additions: Vec::new(),
+            context_files: Vec::new(),
+            context_message: None,
+            work_summary_message: None,
         })
     }
 
+    /// Add instructions to the end of the system message for this run only
+    pub fn with_system_additions(mut self, additions: Vec<String>) -> Self {
+        self.system_additions = additions.into_iter().filter(|addition| !addition.trim().is_empty()).collect();
+        self
+    }
+
+    /// Preload the configured context files plus `extra` from the command line
+    pub fn with_context_files(mut self, extra: Vec<String>) -> Self {
+        let mut paths = self.config.preferences.context_files.clone();
+        for path in extra {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        self.context_files = paths;
+        let loaded = self.load_context_files();
+        if loaded > 0 {
+            println!("📚 Loaded {} context file(s): {}", loaded, self.context_files.join(", "));
+        }
+        self
+    }
+
+    /// Read the context files into the message sent with every request, returning how many were loaded
+    fn load_context_files(&mut self) -> usize {
+        let (content, warnings) = load_context_files(&self.working_dir, &self.context_files);
+        for warning in &warnings {
+            eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: {}", warning)));
+        }
+        self.context_message = content.map(|content| Message {
+            role: "user".to_string(),
+            content: content.into(),
+            tool_calls: None,
+            tool_call_id: None,
+        });
+        match self.context_message {
+            Some(_) => self.context_files.len() - warnings.len(),
+            None => 0,
+        }
+    }
+
+    /// Current session state as a persistable record
+    pub fn session_record(&self) -> SessionRecord {
+        SessionRecord {
+            id: self.session_id.clone(),
+            working_dir: self.working_dir.clone(),
+            created_at: self.started_at,
+            updated_at: Utc::now(),
+            messages: self.messages.clone(),
+            working_memory: self.context.working_memory.clone(),
+            notes: self.context.notes.clone(),
+        }
+    }
+
+    /// Persist the current session so its memory can be recalled later
+    pub fn save_session(&self) -> Result<(), Box<dyn std::error::Error>> {
+        SessionStore::new()?.save(&self.session_record())?;
+        Ok(())
+    }
+
+    /// Tell the user the provider refused the turn and record it. A prompt with no reply is dropped from
+    /// the history, since a flagged prompt would otherwise get every later request blocked too.
+    fn report_blocked_response(&mut self, filtered: &ContentFiltered) {
+        let mut message = filtered.to_string();
+        if self.messages.last().is_some_and(|message| message.role == "user") {
+            self.messages.pop();
+            self.context.important_messages.pop_back();
+            message.push_str("; the message was left out of the conversation");
+        }
+        println!("{}", theme::paint(ThemeRole::Warning, &format!(
+            "🚫 {}. Try rephrasing, or switch models with {}model.", message, self.config.preferences.command_trigger
+        )));
+        self.story_logger.log_response_blocked(&message);
+    }
+
+    /// Which of the replies requested with `openrouter.n` the turn continues with. Text replies are shown in
+    /// full and the user picks one; turns where a reply calls tools, and runs without a terminal, take the first.
+    fn pick_reply(&self, choices: &[Choice]) -> usize {
+        if choices.len() < 2
+            || choices.iter().any(Choice::calls_tools)
+            || self.config.preferences.auto_confirm
+            || !std::io::stdin().is_terminal()
+        {
+            return 0;
+        }
+
+        let mut options = Vec::new();
+        for (index, choice) in choices.iter().enumerate() {
+            let text = choice.message.content.text();
+            println!("{}", theme::paint(ThemeRole::Dim, &format!("── Reply {} of {} ──", index + 1, choices.len())));
+            println!("{}\n", text.trim());
+            let first_line = text.lines().find(|line| !line.trim().is_empty()).unwrap_or("(empty)");
+            options.push(format!("{}. {}", index + 1, clip(first_line.trim(), REPLY_PREVIEW_CHARS)));
+        }
+        match inquire::Select::new("Continue with which reply?", options).raw_prompt() {
+            Ok(picked) => picked.index,
+            Err(_) => 0,
+        }
+    }
+
+    /// Saved sessions of the working directory, newest first
+    pub fn session_history(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let records = SessionStore::new()?.list_for_dir(&self.working_dir)?;
+        Ok(format_session_list(&self.working_dir, &records, Some(&self.session_id)))
+    }
+
+    /// Prompts and replies of a saved session, found by id or id prefix
+    pub fn show_session(&self, id: &str) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(format_transcript(&SessionStore::new()?.find(id)?))
+    }
+
+    /// Continue a saved session of this directory. Its messages and working memory replace the current
+    /// ones, which are saved first so they can be resumed in turn; notes from both are kept. Only prompts are
+    /// carried in context, so the model also gets a summary of what the session's tool calls did.
+    pub fn resume_session(&mut self, id: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let store = SessionStore::new()?;
+        let record = store.find(id)?;
+        if record.id == self.session_id {
+            return Ok(format!("Already in session {}", record.short_id()));
+        }
+        if record.working_dir != self.working_dir {
+            return Err(format!("Session {} was recorded in {}; /cd there to resume it", record.short_id(), record.working_dir).into());
+        }
+        if !self.messages.is_empty() {
+            store.save(&self.session_record())?;
+        }
+
+        let mut summary = format!("▶️ Resumed session {} ({} turns): {}", record.short_id(), record.turn_count(), record.title());
+        self.work_summary_message = format_work_summary(&record).map(|content| Message {
+            role: "user".to_string(),
+            content: content.into(),
+            tool_calls: None,
+            tool_call_id: None,
+        });
+        if self.work_summary_message.is_some() {
+            summary.push_str("\n📋 The model was given a summary of the files and commands it worked with");
+        }
+        let mut notes = std::mem::take(&mut self.context.notes);
+        for note in record.notes {
+            if !notes.contains(&note) {
+                notes.push(note);
+            }
+        }
+        self.context = ConversationContext::default();
+        self.context.notes = notes;
+        self.context.working_memory = record.working_memory;
+        self.context.important_messages = record.messages.iter().filter(|message| message.role == "user").cloned().collect();
+        while self.context.important_messages.len() > 20 {
+            self.context.important_messages.pop_front();
+        }
+        if let Some(pinned) = &self.pinned_state {
+            self.context.state = pinned.clone();
+        }
+        self.session_id = record.id;
+        self.started_at = record.created_at;
+        self.messages = record.messages;
+        Ok(summary)
+    }
+
+    /// Snapshot of the session and story for the background autosaver
+    pub fn autosave_snapshot(&self) -> AutosaveSnapshot {
+        AutosaveSnapshot {
+            session: self.session_record(),
+            story: self.story_logger.clone(),
+        }
+    }
+
+    /// Handle a slash command typed in the semantic chat
+    pub async fn handle_command(&mut self, command_line: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let parts: Vec<&str> = command_line.split_whitespace().collect();
+        if parts.is_empty() {
+            return Ok(());
+        }
+
+        let command_name = parts[0];
+        let args = parts[1..].join(" ");
+
+        let result = match execute_command(command_line) {
+            Some(Err(e)) if e.to_string().starts_with("ENGINE_COMMAND:template:") => {
+                let marker = e.to_string();
+                let prompt = marker.strip_prefix("ENGINE_COMMAND:template:").unwrap_or_default();
+                semantic_commands::handle_template_command(self, prompt).await
+            }
+            Some(Err(e)) if e.to_string().starts_with("ENGINE_COMMAND:") => {
+                match command_name {
+                    "clear" => Ok(self.clear_context(args.trim() == "--keep-memory")),
+                    "compact" => semantic_commands::handle_compact_command(self, &args).await,
+                    "model" => semantic_commands::handle_model_command(self, &args).await,
+                    "list-models" => semantic_commands::handle_list_models_command(self, &args).await,
+                    "memory" => semantic_commands::handle_memory_command(self, &args).await,
+                    "remember" => semantic_commands::handle_remember_command(self, &args).await,
+                    "changes" => semantic_commands::handle_changes_command(self, &args).await,
+                    "mode" => semantic_commands::handle_mode_command(self, &args).await,
+                    "open" => semantic_commands::handle_open_command(self, &args).await,
+                    "cd" => semantic_commands::handle_cd_command(self, &args).await,
+                    "history" => semantic_commands::handle_history_command(self, &args).await,
+                    "tools" => semantic_commands::handle_tools_command(self, &args).await,
+                    "dump" => semantic_commands::handle_dump_command(self, &args).await,
+                    "explain" => semantic_commands::handle_explain_command(self, &args).await,
+                    _ => Err(format!("/{} is not available in semantic chat", command_name).into()),
+                }
+            }
+            Some(other) => other,
+            None => Err(format!("Unknown command: {}", command_name).into()),
+        };
+
+        match result {
+            Ok(output) => {
+                if !output.trim().is_empty() {
+                    println!("{}", output);
+                }
+            }
+            Err(e) => {
+                println!("{}", theme::paint(ThemeRole::Error, &format!("❌ Command error: {}", e)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describe the files changed this session, limited to `path` when given
+    pub fn changes_report(&self, with_diff: bool, path: Option<&str>) -> String {
+        match path {
+            Some(path) => {
+                let mut tracker = ChangeTracker::new();
+                for (changed, kind) in self.changes.changes().iter().filter(|(changed, _)| changed == path) {
+                    tracker.record(changed, *kind);
+                }
+                if tracker.changes().is_empty() {
+                    return format!("📝 {} was not changed in this session", path);
+                }
+                tracker.render(&self.working_dir, with_diff)
+            }
+            None => self.changes.render(&self.working_dir, with_diff),
+        }
+    }
+
+    /// Switch the directory tools, `@` references and autocomplete work in. Relative paths resolve against
+    /// the current directory and `~` against the home directory.
+    pub fn change_directory(&mut self, path: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let target = match path.strip_prefix('~') {
+            Some(rest) => {
+                let home = dirs::home_dir().ok_or("Cannot determine the home directory")?;
+                home.join(rest.trim_start_matches('/'))
+            }
+            None => Path::new(&self.working_dir).join(path),
+        };
+        let target = fs::canonicalize(&target).map_err(|e| format!("{}: {}", path, e))?;
+        if !target.is_dir() {
+            return Err(format!("{} is not a directory", target.display()).into());
+        }
+
+        let working_dir = target.to_string_lossy().to_string();
+        if working_dir == self.working_dir {
+            return Ok(format!("📁 Already in {}", working_dir));
+        }
+
+        self.changes.anchor_to(&self.working_dir);
+        self.tool_executor.set_working_dir(working_dir.clone());
+        self.working_dir = working_dir;
+        self.external_changes.clear();
+        if self.file_watcher.is_some() {
+            self.file_watcher = match FileWatcher::start(&self.working_dir) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: File watching disabled: {}", e)));
+                    None
+                }
+            };
+        }
+
+        let entries = fs::read_dir(&self.working_dir).map(|entries| entries.count()).unwrap_or(0);
+        let mut report = format!("📁 Working directory: {} ({} entries)", self.working_dir, entries);
+        match crate::tools::git::current_branch(&self.working_dir) {
+            Some(branch) => report.push_str(&format!("\n🌿 Git branch: {}", branch)),
+            None if !Path::new(&self.working_dir).join(".git").exists() => report.push_str("\n💡 Not a git repository"),
+            None => {}
+        }
+        Ok(report)
+    }
+
+    /// Launch `$VISUAL`/`$EDITOR` on `path`, or on the last file a tool created or modified
+    pub fn open_in_editor(&self, path: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+        let path = match path.or(self.changes.last_edited()) {
+            Some(path) => path,
+            None => return Err("No files edited in this session yet (usage: /open [path])".into()),
+        };
+        let target = Path::new(&self.working_dir).join(path);
+        if !target.is_file() {
+            return Err(format!("{} does not exist", path).into());
+        }
+
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .ok()
+            .filter(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+        // Editors are often configured with flags, e.g. `code --wait`
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        let status = std::process::Command::new(program)
+            .args(words)
+            .arg(&target)
+            .current_dir(&self.working_dir)
+            .status()
+            .map_err(|e| format!("Could not launch {}: {}", program, e))?;
+
+        if !status.success() {
+            return Err(format!("{} exited with {}", program, status).into());
+        }
+        Ok(format!("📂 Closed {}", path))
+    }
+
+    /// Serialize the exact message history sent to the API, relative paths resolving against the working directory
+    pub fn dump_messages(&self, path: &str) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+        let target = Path::new(&self.working_dir).join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&target, serde_json::to_string_pretty(&self.messages)?)?;
+        Ok(target)
+    }
+
+    /// Tool calls and results made since the last user message, one line per step
+    fn last_turn_transcript(&self) -> Option<String> {
+        let turn_start = self.messages.iter().rposition(|message| message.role == "user")?;
+        let mut transcript = String::new();
+
+        for message in &self.messages[turn_start + 1..] {
+            for tool_call in message.tool_calls.iter().flatten() {
+                transcript.push_str(&format!(
+                    "CALL {}({})\n",
+                    tool_call.function.name,
+                    clip(&tool_call.function.arguments, EXPLAIN_ARGUMENTS_CHARS)
+                ));
+            }
+            if message.role == "tool" {
+                transcript.push_str(&format!("RESULT {}\n", clip(&message.content.text(), EXPLAIN_RESULT_CHARS)));
+            }
+        }
+
+        (!transcript.is_empty()).then_some(transcript)
+    }
+
+    /// Ask the model for a plain-language rationale of the tool calls made in the last turn
+    pub async fn explain_last_turn(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let Some(transcript) = self.last_turn_transcript() else {
+            return Ok("💡 Nothing to explain yet: the last turn made no tool calls".to_string());
+        };
+        let request = self.messages.iter().rev().find(|message| message.role == "user").map(|message| message.content.text()).unwrap_or_default();
+
+        let messages = vec![
+            Message {
+                role: "system".to_string(),
+                content: "You explain a coding assistant's actions to someone learning from it. For the tool calls below, say in plain language why each step was taken, what it revealed, and how it led to the next. Be concise; do not call tools.".to_string().into(),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            Message {
+                role: "user".to_string(),
+                content: format!("Request:\n{}\n\nTool calls and results:\n{}", request, transcript).into(),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ];
+
+        let response = self.openrouter_client.chat_completion_with_tool_choice(messages, ToolChoice::None).await?;
+        let explanation = response.choices.first().map(|choice| choice.message.content.text()).unwrap_or_default();
+        if explanation.trim().is_empty() {
+            return Err("The model returned no explanation".into());
+        }
+        Ok(format!("💡 {}", explanation.trim()))
+    }
+
+    /// Add a durable note that stays in context for this and later sessions
+    pub fn remember(&mut self, note: &str) -> String {
+        let note = note.trim().to_string();
+        if !self.context.notes.contains(&note) {
+            self.context.notes.push(note.clone());
+        }
+        format!("🧠 Remembered: {}", note)
+    }
+
+    /// Describe the notes and working memory currently held in context
+    pub fn memory_summary(&self) -> String {
+        let mut summary = String::from("🧠 Project memory\n");
+
+        if self.context.notes.is_empty() {
+            summary.push_str("  Notes: none (add one with /remember <note>)\n");
+        } else {
+            summary.push_str("  Notes:\n");
+            for note in &self.context.notes {
+                summary.push_str(&format!("    • {}\n", note));
+            }
+        }
+
+        if self.context.working_memory.is_empty() {
+            summary.push_str("  Working memory: empty");
+        } else {
+            summary.push_str("  Working memory:\n");
+            for entry in &self.context.working_memory {
+                summary.push_str(&format!("    • {}\n", entry));
+            }
+        }
+
+        summary.trim_end().to_string()
+    }
+
+    /// Tools the model can call right now, and why any others are unavailable
+    pub fn tools_summary(&self) -> String {
+        if !self.openrouter_client.tools_enabled() {
+            return format!(
+                "🔧 No tools: model '{}' is used without tool calls (openrouter.supports_tools is off or the model rejected tools)",
+                self.config.openrouter.model
+            );
+        }
+
+        let mut summary = String::from("🔧 Active tools\n");
+        for tool in self.openrouter_client.get_tools() {
+            summary.push_str(&format!("  • {} - {}\n", tool.function.name, tool.function.description));
+        }
+
+        let disabled: Vec<&str> = [
+            ("tools.filesystem", self.config.tools.filesystem),
+            ("tools.commands", self.config.tools.commands),
+        ]
+        .into_iter()
+        .filter(|(_, enabled)| !enabled)
+        .map(|(key, _)| key)
+        .collect();
+        if !disabled.is_empty() {
+            summary.push_str(&format!("  Disabled by config: {}", disabled.join(", ")));
+        }
+
+        summary.trim_end().to_string()
+    }
+
     /// Process a conversation turn with semantic understanding
     pub async fn process_conversation(&mut self, user_input: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let span = tracing::info_span!("conversation_turn", messages = self.messages.len());
+        self.run_conversation_turn(user_input).instrument(span).await
+    }
+
+    async fn run_conversation_turn(&mut self, user_input: &str) -> Result<(), Box<dyn std::error::Error>> {
+        // Pick up edits made outside loo while the user was typing
+        self.external_changes = self.file_watcher.as_ref().map(|watcher| watcher.take_changes()).unwrap_or_default();
+        if let Some(note) = format_change_note(&self.external_changes) {
+            println!("{}", theme::paint(ThemeRole::Dim, &format!("👀 {}", note)));
+        }
+
+        self.turn_tool_usage = ToolUsageSummary::new();
+        self.delete_guard.start_turn();
+        let turn_started = Instant::now();
+
         // Analyze user intent and update conversation context
         self.analyze_and_update_context(user_input).await?;
 
-        // Add user message to conversation
+        // Add user message to conversation, attaching any referenced images
         let user_message = Message {
             role: "user".to_string(),
-            content: user_input.to_string(),
+            content: self.build_user_content(user_input).await?,
             tool_calls: None,
             tool_call_id: None,
         };
@@ -144,48 +737,135 @@ impl SemanticEngine {
 
         // Create temporary message list with adaptive context
         let mut conversation_messages = vec![system_message];
+        conversation_messages.extend(self.context_message.clone());
+        conversation_messages.extend(self.work_summary_message.clone());
         conversation_messages.extend(self.get_relevant_context());
         conversation_messages.push(self.messages.last().unwrap().clone());
 
         // Process conversation loop with semantic awareness
         loop {
-            let response = self.openrouter_client
-                .chat_completion(conversation_messages.clone())
-                .await?;
-
-            let assistant_message = &response.choices[0].message;
+            let response = match self.openrouter_client.chat_completion_choices(conversation_messages.clone()).await {
+                Ok(response) => response,
+                Err(e) => match e.downcast::<ContentFiltered>() {
+                    Ok(filtered) => {
+                        self.report_blocked_response(&filtered);
+                        break;
+                    }
+                    // The session ends after this turn; see preferences.max_session_tokens
+                    Err(e) if e.is::<TokenBudgetExhausted>() => {
+                        println!("{}", theme::paint(ThemeRole::Warning, &format!("🛑 {}", e)));
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                },
+            };
+
+            let choice = &response.choices[self.pick_reply(&response.choices)];
+            if let Some(reasoning) = &choice.reasoning {
+                if self.config.preferences.verbose >= VERBOSE_SUMMARIES {
+                    println!("{}", theme::paint(ThemeRole::Dim, &format!("💭 {}", reasoning.trim())));
+                }
+                self.story_logger.log_reasoning(reasoning);
+            }
+            let assistant_message = &choice.message;
             let response_clone = assistant_message.clone();
             conversation_messages.push(response_clone.clone());
             self.messages.push(response_clone);
 
             // Update working memory with assistant insights
             if !assistant_message.content.is_empty() {
-                self.story_logger.log_assistant_response(&assistant_message.content);
-                self.update_working_memory(&assistant_message.content);
+                let content = assistant_message.content.text();
+                self.story_logger.log_assistant_response(&content);
+                self.update_working_memory(&content);
             }
 
             // Handle tool calls with semantic awareness
             if let Some(tool_calls) = &assistant_message.tool_calls {
-                self.execute_tools_semantically(tool_calls, &mut conversation_messages).await?;
+                // Text sent along with `complete` is the model's wrap-up, and may be all the user gets this turn
+                let completes = tool_calls.iter().any(|call| call.function.name == "complete");
+                if completes && !assistant_message.content.is_empty() {
+                    println!("{} {}", self.config.preferences.assistant_label(), assistant_message.content);
+                }
+                if self.execute_tools_semantically(tool_calls, &mut conversation_messages).await? {
+                    break;
+                }
             } else {
                 // No more tool calls, conversation complete
                 if !assistant_message.content.is_empty() {
-                    println!("🤖 {}", assistant_message.content);
+                    println!("{} {}", self.config.preferences.assistant_label(), assistant_message.content);
                 }
                 break;
             }
         }
 
+        let turn_duration = turn_started.elapsed();
+        tracing::debug!(elapsed_ms = turn_duration.as_millis() as u64, "conversation turn finished");
+        self.story_logger.log_turn_completed(turn_duration);
+        if let Some(summary) = self.turn_tool_usage.render() {
+            println!("{}", theme::paint(ThemeRole::Dim, &format!("📋 {} in {}", summary, format_duration(turn_duration))));
+        }
+
+        // Changes made by our own tools this turn are already known to the model
+        if let Some(watcher) = &self.file_watcher {
+            watcher.take_changes();
+        }
+        self.external_changes.clear();
+
         Ok(())
     }
 
+    /// Build user message content, inlining `@path` files and turning `@image:path` references into image parts
+    async fn build_user_content(&self, user_input: &str) -> Result<MessageContent, Box<dyn std::error::Error>> {
+        let triggers = self.config.preferences.triggers();
+        let (text, image_paths) = extract_image_references(user_input, triggers.file);
+
+        let mut file_blocks = Vec::new();
+        for path in extract_file_references(&text, triggers.file) {
+            match load_file_context(&self.working_dir, &path) {
+                Ok(Some(block)) => {
+                    println!("📎 Attached file: {}", path);
+                    file_blocks.push(block);
+                }
+                Ok(None) => {}
+                // One unusable reference should not cost the user the whole message
+                Err(e) => eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: Not attaching {}: {}", path, e))),
+            }
+        }
+        let mut text = unescape_file_triggers(&text, triggers);
+        if !file_blocks.is_empty() {
+            text = format!("{}\n\nReferenced files:\n\n{}", text, file_blocks.join("\n\n"));
+        }
+
+        if image_paths.is_empty() {
+            return Ok(text.into());
+        }
+
+        if !self.openrouter_client.model_supports_vision().await? {
+            return Err(format!(
+                "Model '{}' does not accept images. Switch to a vision-capable model to attach files.",
+                self.config.openrouter.model
+            ).into());
+        }
+
+        let mut parts = vec![ContentPart::Text { text }];
+        for path in &image_paths {
+            parts.push(load_image_part(&self.working_dir, path)?);
+            println!("🖼️ Attached image: {}", path);
+        }
+
+        Ok(MessageContent::Parts(parts))
+    }
+
     /// Analyze user input and update conversation context using LLM
     async fn analyze_and_update_context(&mut self, user_input: &str) -> Result<(), Box<dyn std::error::Error>> {
         // Use LLM to analyze conversation state
         match self.analyze_conversation_state_with_llm(user_input).await {
             Ok(analysis) => {
-                // Parse the detected state
-                self.context.state = self.parse_conversation_state(&analysis.state);
+                // Parse the detected state unless the user pinned a mode
+                self.context.state = match &self.pinned_state {
+                    Some(pinned) => pinned.clone(),
+                    None => self.parse_conversation_state(&analysis.state),
+                };
 
                 // Update current thread/topic if provided
                 if let Some(topic) = analysis.topic {
@@ -209,7 +889,7 @@ impl SemanticEngine {
             Err(e) => {
                 // Fallback to rule-based analysis if LLM fails
                 eprintln!("Error: LLM analysis failed, using Conversational state: {}", e);
-                self.context.state = ConversationState::Conversational;
+                self.context.state = self.pinned_state.clone().unwrap_or(ConversationState::Conversational);
             }
         }
 
@@ -229,6 +909,38 @@ impl SemanticEngine {
             tools.extend_from_slice(&["run_tests", "create_test"]);
         }
 
+        if input_lower.contains("build") || input_lower.contains("test") || input_lower.contains("lint") {
+            tools.push("project_commands");
+        }
+
+        if input_lower.contains("server") || input_lower.contains("background") || input_lower.contains("watch") {
+            tools.extend_from_slice(&["run_command_background", "check_process", "stop_process"]);
+        }
+
+        if input_lower.contains("script") || input_lower.contains("executable") || input_lower.contains("chmod") {
+            tools.push("set_permissions");
+        }
+
+        if input_lower.contains("large") || input_lower.contains("token") || input_lower.contains("how big") || input_lower.contains("size") {
+            tools.push("file_stats");
+        }
+
+        if input_lower.contains("config") || input_lower.contains(".json") || input_lower.contains(".toml") || input_lower.contains("yaml") || input_lower.contains(".yml") {
+            tools.push("query_structured");
+        }
+
+        if input_lower.contains("archive") || input_lower.contains(".zip") || input_lower.contains(".tar") {
+            tools.push("inspect_archive");
+        }
+
+        if input_lower.contains("new project") || input_lower.contains("scaffold") || input_lower.contains("gitignore") {
+            tools.push("scaffold_project");
+        }
+
+        if input_lower.contains("template") || input_lower.contains("boilerplate") || input_lower.contains("component") {
+            tools.push("create_from_template");
+        }
+
         if input_lower.contains("install") || input_lower.contains("package") || input_lower.contains("dependency") {
             tools.extend_from_slice(&["package_install", "dependency_check"]);
         }
@@ -267,6 +979,16 @@ impl SemanticEngine {
 
         // Add working directory context
         content.push_str(&format!(" Working directory: {}.", self.working_dir));
+        if let Some(note) = format_change_note(&self.external_changes) {
+            content.push_str(&format!(" {} (edited outside loo; re-read them before relying on earlier contents).", note));
+        }
+        if let Some(branch) = crate::tools::git::current_branch(&self.working_dir) {
+            if self.config.tools.git_protected_branches.contains(&branch) {
+                content.push_str(&format!(" Git branch: {} (protected - commits and staging need user confirmation, prefer a feature branch).", branch));
+            } else {
+                content.push_str(&format!(" Git branch: {}.", branch));
+            }
+        }
 
         // Add conversation state-specific extensions
         match self.context.state {
@@ -281,14 +1003,14 @@ impl SemanticEngine {
                 content.push_str(" IMPLEMENTATION MODE: The user is ready to build and implement.
                     Use available tools proactively to execute the necessary steps.
                     Follow systematic approaches: Read files before editing, verify changes after implementation,
-                    and use appropriate tools for each operation (Edit, MultiEdit, Write, Bash, etc.).
+                    and use appropriate tools for each operation (write_file, create_file, run_command, etc.).
                     Be thorough in your execution and provide clear feedback about what you're doing.");
             }
             ConversationState::Troubleshooting => {
                 content.push_str(" TROUBLESHOOTING MODE: The user is experiencing difficulties.
                     Help them debug issues systematically. First, gather information about the problem,
                     examine relevant files and logs, reproduce the issue if possible,
-                    then provide specific solutions. Use discovery tools (Read, Grep, Glob, LS, Bash)
+                    then provide specific solutions. Use discovery tools (read_file, list_directory, run_command with grep or find)
                     to investigate before suggesting fixes.");
             }
             ConversationState::Exploring => {
@@ -314,6 +1036,11 @@ impl SemanticEngine {
             content.push_str(&format!(" Current focus: {}.", thread));
         }
 
+        // Add durable notes the user asked to keep
+        if !self.context.notes.is_empty() {
+            content.push_str(&format!(" Remembered notes: {}.", self.context.notes.join("; ")));
+        }
+
         // Add working memory context
         if !self.context.working_memory.is_empty() {
             let recent_context = self.context.working_memory
@@ -331,26 +1058,30 @@ impl SemanticEngine {
         match self.context.state {
             ConversationState::Planning => {
                 content.push_str(" For planning: Focus on understanding requirements first, then create structured plans.
-                     Use Read/Grep/LS tools for discovery, then provide detailed action plans.");
+                     Use read_file, list_directory and query_context for discovery, then provide detailed action plans.");
             }
             ConversationState::Implementing => {
-                content.push_str(" For implementation: Use Edit/MultiEdit for code changes, Write for new files,
-                    Bash for commands/tests. Always read files before editing.");
+                content.push_str(" For implementation: Use write_file for code changes, create_file for new files,
+                    run_command for commands/tests. Always read files before editing.");
             }
             ConversationState::Troubleshooting => {
-                content.push_str(" For troubleshooting: Start with Read/Grep to examine code, Bash to reproduce issues,
-                then use Edit tools to fix problems. Verify fixes with additional commands.");
+                content.push_str(" For troubleshooting: Start with read_file to examine code, run_command to reproduce issues,
+                then use write_file to fix problems. Verify fixes with additional commands.");
             }
             ConversationState::Exploring => {
-                content.push_str(" For exploration: Use Read, Grep, Glob, and LS extensively to understand the codebase.
+                content.push_str(" For exploration: Use read_file, list_directory and query_context extensively to understand the codebase.
                 Explain what you find and guide the user through the structure.");
             }
             _ => {}
         }
 
+        for addition in &self.system_additions {
+            content.push_str(&format!(" {}", addition.trim()));
+        }
+
         Message {
             role: "system".to_string(),
-            content,
+            content: content.into(),
             tool_calls: None,
             tool_call_id: None,
         }
@@ -408,18 +1139,21 @@ impl SemanticEngine {
         }
     }
 
-    /// Execute tools with semantic awareness
+    /// Execute tools with semantic awareness.
+    /// Returns true when the user interrupted a tool, in which case the queued calls were skipped and the turn should end.
     async fn execute_tools_semantically(
         &mut self,
         tool_calls: &[crate::openrouter::ToolCall],
         conversation_messages: &mut Vec<Message>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        if self.config.preferences.verbose || tool_calls.len() > 1 {
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.config.preferences.verbose >= VERBOSE_SUMMARIES || tool_calls.len() > 1 {
             println!("🤖 Making {} tool calls", tool_calls.len());
         }
 
-        for tool_call in tool_calls {
-            if self.config.preferences.verbose {
+        let deletes_allowed = self.delete_guard.allow(tool_calls, &self.config, &self.story_logger);
+        let mut duplicates = CallCoalescer::new();
+        for (index, tool_call) in tool_calls.iter().enumerate() {
+            if self.config.preferences.verbose >= VERBOSE_TOOL_RESULTS {
                 println!("  🔧 Executing: {}", tool_call.function.name);
             } else {
                 println!("🔧 {}", tool_call.function.name);
@@ -429,41 +1163,117 @@ impl SemanticEngine {
             let args: serde_json::Value = serde_json::from_str(&tool_call.function.arguments)
                 .unwrap_or(serde_json::Value::Null);
             self.story_logger.log_tool_execution(&tool_call.function.name, &args);
+            let changed_path = args["path"].as_str().map(|path| path.to_string());
+            let existed_before = changed_path
+                .as_ref()
+                .is_some_and(|path| Path::new(&self.working_dir).join(path).exists());
+
+            let tool_started = Instant::now();
+            let outcome = match duplicates.cached(tool_call) {
+                Some(result) => {
+                    tracing::debug!(tool = %tool_call.function.name, id = %tool_call.id, "reusing the result of an identical call");
+                    println!("  ♻️ Same as an earlier call in this response, reusing its result");
+                    Ok(result)
+                }
+                None if !deletes_allowed && tool_call.function.name == "delete_file" => {
+                    let denied = bulk_delete_denied(changed_path.as_deref().unwrap_or_default(), self.config.tools.max_deletes_per_turn);
+                    Err(denied.into())
+                }
+                None => {
+                    let detail = args["command"].as_str().or(changed_path.as_deref());
+                    let _progress = ProgressLog::start(&tool_call.function.name, detail, self.config.preferences.progress_log);
+                    self.tool_executor.execute_tool_call(tool_call).await
+                }
+            };
+            let tool_duration = tool_started.elapsed();
+            duplicates.record(tool_call, outcome.as_deref().ok());
+            for pattern in self.tool_executor.take_new_approvals() {
+                self.story_logger.log_command_approved(&pattern);
+            }
 
-            match self.tool_executor.execute_tool_call(tool_call).await {
+            match outcome {
                 Ok(result) => {
-                    if self.config.preferences.verbose {
-                        println!("  ✅ Success: {}", result);
-                    } else {
-                        println!("  ✅");
+                    match self.config.preferences.tool_output_level() {
+                        ToolOutput::Full => println!("  ✅ Success in {}: {}", format_duration(tool_duration), result),
+                        ToolOutput::Summary => println!(
+                            "  ✅ {} ({})",
+                            summarize_tool_result(&tool_call.function.name, &result),
+                            format_duration(tool_duration)
+                        ),
+                        ToolOutput::Minimal => println!("  ✅ {}", format_duration(tool_duration)),
                     }
 
                     // Update working memory based on tool results
                     self.update_memory_from_tool_result(&tool_call.function.name, &result);
 
-                    // Log tool result
-                    self.story_logger.log_tool_result(&tool_call.function.name, true, &result);
+                    // Log tool result, plus the uncapped command output when verbose
+                    self.story_logger.log_tool_result(&tool_call.function.name, true, &result, tool_duration);
+                    if let Some(output) = self.tool_executor.take_full_output().filter(|_| self.config.preferences.verbose >= VERBOSE_TOOL_RESULTS) {
+                        self.story_logger.log_full_output(&output);
+                    }
+                    self.turn_tool_usage.record(&tool_call.function.name, true, &result);
+                    if let (Some(path), Some(kind)) = (&changed_path, ChangeKind::from_tool(&tool_call.function.name, existed_before)) {
+                        self.changes.record(path, kind);
+                        if kind != ChangeKind::Deleted {
+                            self.show_rewrite_diff(path);
+                        }
+                    }
+                    if tool_call.function.name == "scaffold_project" {
+                        let scaffolded: serde_json::Value = serde_json::from_str(&result).unwrap_or_default();
+                        for path in scaffolded["created"].as_array().into_iter().flatten().filter_map(|path| path.as_str()) {
+                            self.changes.record(path, ChangeKind::Created);
+                        }
+                    }
 
                     // Create tool response message
                     let tool_message = Message {
                         role: "tool".to_string(),
-                        content: result.clone(),
+                        content: result.clone().into(),
                         tool_calls: None,
                         tool_call_id: Some(tool_call.id.clone()),
                     };
                     conversation_messages.push(tool_message.clone());
                     self.messages.push(tool_message);
+
+                    if is_interrupted(&result) {
+                        self.skip_interrupted_turn(&tool_calls[index + 1..], conversation_messages);
+                        return Ok(true);
+                    }
+                    if tool_call.function.name == "complete" {
+                        let summary = completion_summary(tool_call);
+                        match self.config.tools.on_complete {
+                            CompleteAction::EndTurn => {
+                                self.end_turn_on_complete(&tool_calls[index + 1..], conversation_messages, summary.as_deref());
+                                return Ok(true);
+                            }
+                            CompleteAction::Continue => {
+                                println!("{}", theme::paint(ThemeRole::Dim, "🏁 The model marked the task complete"));
+                                if let Some(summary) = summary {
+                                    println!("{} {}", self.config.preferences.assistant_label(), summary);
+                                }
+                            }
+                        }
+                    }
                 }
                 Err(e) => {
-                    println!("  ❌ Error: {}", e);
+                    println!("  ❌ Error after {}: {}", format_duration(tool_duration), e);
 
                     // Log tool error
-                    self.story_logger.log_tool_result(&tool_call.function.name, false, &e.to_string());
+                    self.story_logger.log_tool_result(&tool_call.function.name, false, &e.to_string(), tool_duration);
+                    self.turn_tool_usage.record(&tool_call.function.name, false, &e.to_string());
+
+                    // Failures the model cannot work around are flagged for the user
+                    let tool_error = classify_tool_error(&tool_call.function.name, e.as_ref());
+                    if !tool_error.category.recoverable() {
+                        println!("{}", theme::paint(ThemeRole::Warning, &format!(
+                            "  ✋ {} needs your attention ({})", tool_call.function.name, tool_error.category.as_str().replace('_', " ")
+                        )));
+                    }
 
                     // Create error tool response
                     let error_message = Message {
                         role: "tool".to_string(),
-                        content: serde_json::json!({"status": "error", "message": e.to_string()}).to_string(),
+                        content: tool_error.to_json().to_string().into(),
                         tool_calls: None,
                         tool_call_id: Some(tool_call.id.clone()),
                     };
@@ -473,7 +1283,83 @@ impl SemanticEngine {
             }
         }
 
-        Ok(())
+        if duplicates.coalesced > 0 {
+            println!("♻️ Reused the result of {} repeated tool {}", duplicates.coalesced, if duplicates.coalesced == 1 { "call" } else { "calls" });
+        }
+
+        Ok(false)
+    }
+
+    /// When the model writes a file it already wrote this session, show and record what the rewrite changed
+    fn show_rewrite_diff(&mut self, path: &str) {
+        let full_path = Path::new(&self.working_dir).join(path);
+        let content = fs::read_to_string(&full_path).ok().filter(|content| content.len() <= REWRITE_DIFF_MAX_BYTES);
+        let Some(current) = content.clone() else {
+            self.changes.remember_write(full_path, None);
+            return;
+        };
+        let Some(diff) = self.changes.remember_write(full_path, content).and_then(|previous| unified_diff(&previous, &current, 3)) else {
+            return;
+        };
+
+        if self.config.preferences.tool_output_level() != ToolOutput::Minimal {
+            let (added, removed) = diff_stats(&diff);
+            println!("  ✏️ Changes since the previous write of {} (+{} -{}):", path, added, removed);
+            let lines: Vec<&str> = diff.lines().collect();
+            println!("{}", paint_diff(&lines[..lines.len().min(REWRITE_DIFF_LINES)].join("\n")));
+            if lines.len() > REWRITE_DIFF_LINES {
+                println!("{}", theme::paint(ThemeRole::Dim, &format!("  … {} more diff lines", lines.len() - REWRITE_DIFF_LINES)));
+            }
+        }
+        self.story_logger.log_file_rewritten(path, &diff);
+    }
+
+    /// After Ctrl+C, answer the queued tool calls without running them so every call still has a result
+    fn skip_interrupted_turn(&mut self, remaining: &[crate::openrouter::ToolCall], conversation_messages: &mut Vec<Message>) {
+        self.story_logger.log_process_interrupted();
+
+        for tool_call in remaining {
+            let skipped = Message {
+                role: "tool".to_string(),
+                content: skipped_after_interrupt(&tool_call.function.name).into(),
+                tool_calls: None,
+                tool_call_id: Some(tool_call.id.clone()),
+            };
+            conversation_messages.push(skipped.clone());
+            self.messages.push(skipped);
+        }
+
+        let skipped = if remaining.is_empty() {
+            String::new()
+        } else {
+            format!("; skipped {} queued tool call(s)", remaining.len())
+        };
+        println!("{}", theme::paint(ThemeRole::Warning, &format!("⏹️ Turn interrupted{}", skipped)));
+    }
+
+    /// Close the turn after a `complete` call, answering the calls queued behind it without running them, and
+    /// show the call's summary
+    fn end_turn_on_complete(&mut self, remaining: &[crate::openrouter::ToolCall], conversation_messages: &mut Vec<Message>, summary: Option<&str>) {
+        for tool_call in remaining {
+            let skipped = Message {
+                role: "tool".to_string(),
+                content: skipped_after_complete(&tool_call.function.name).into(),
+                tool_calls: None,
+                tool_call_id: Some(tool_call.id.clone()),
+            };
+            conversation_messages.push(skipped.clone());
+            self.messages.push(skipped);
+        }
+
+        let skipped = if remaining.is_empty() {
+            String::new()
+        } else {
+            format!("; skipped {} tool call(s) queued after it", remaining.len())
+        };
+        println!("{}", theme::paint(ThemeRole::Success, &format!("🎉 Task complete{}", skipped)));
+        if let Some(summary) = summary {
+            println!("{} {}", self.config.preferences.assistant_label(), summary);
+        }
     }
 
     /// Analyze conversation state using LLM with structured JSON response
@@ -503,14 +1389,14 @@ Respond with ONLY the JSON object, no other text."#;
 
         let analysis_message = Message {
             role: "user".to_string(),
-            content: format!("Analyze this user input: \"{}\"", user_input),
+            content: format!("Analyze this user input: \"{}\"", user_input).into(),
             tool_calls: None,
             tool_call_id: None,
         };
 
         let system_message = Message {
             role: "system".to_string(),
-            content: system_prompt.to_string(),
+            content: system_prompt.to_string().into(),
             tool_calls: None,
             tool_call_id: None,
         };
@@ -518,10 +1404,10 @@ Respond with ONLY the JSON object, no other text."#;
         let messages = vec![system_message, analysis_message];
 
         let response = self.openrouter_client.chat_completion(messages).await?;
-        let content = &response.choices[0].message.content;
+        let content = response.choices[0].message.content.text();
 
         // Parse JSON response
-        let analysis: ConversationStateAnalysis = serde_json::from_str(content)
+        let analysis: ConversationStateAnalysis = serde_json::from_str(&content)
             .map_err(|e| format!("Failed to parse LLM response as JSON: {}. Response was: {}", e, content))?;
 
         Ok(analysis)
@@ -574,7 +1460,7 @@ Respond with ONLY the JSON object, no other text."#;
     }
 
     /// Clear conversation context (semantic equivalent of /clear)
-    pub fn clear_context(&mut self) -> String {
+    pub fn clear_context(&mut self, keep_memory: bool) -> String {
         let message_count = self.messages.len().saturating_sub(1);
 
         // Keep only system message if any
@@ -586,10 +1472,73 @@ Respond with ONLY the JSON object, no other text."#;
             self.messages.clear();
         }
 
-        // Reset conversation context
-        self.context = ConversationContext::default();
+        // Reset conversation context, keeping durable notes (and what was learned, if asked)
+        let previous = std::mem::take(&mut self.context);
+        self.context.notes = previous.notes;
+        if let Some(pinned) = &self.pinned_state {
+            self.context.state = pinned.clone();
+        }
+
+        // Context files may have been edited during the session, so they are read again
+        let reloaded = match self.load_context_files() {
+            0 => String::new(),
+            count => format!("\n📚 Reloaded {} context file(s)", count),
+        };
+        if !keep_memory {
+            self.work_summary_message = None;
+            return format!("🧹 Conversation context cleared ({} messages removed){}", message_count, reloaded);
+        }
+
+        self.context.working_memory = previous.working_memory;
+        self.context.current_thread = previous.current_thread;
+        let thread = match &self.context.current_thread {
+            Some(thread) => format!("current thread '{}'", thread),
+            None => "no current thread".to_string(),
+        };
+        format!(
+            "🧹 Cleared {} messages; kept {} working memory entries, {} and {} notes{}",
+            message_count,
+            self.context.working_memory.len(),
+            thread,
+            self.context.notes.len(),
+            reloaded
+        )
+    }
+
+    /// Pin the conversation mode, or return to automatic detection with `auto`
+    pub fn set_mode(&mut self, mode: &str) -> Result<String, String> {
+        if mode == "auto" {
+            self.pinned_state = None;
+            return Ok(format!("🎛️ Mode: {} (automatic detection resumed)", self.context.state.name()));
+        }
 
-        format!("🧹 Conversation context cleared ({} messages removed)", message_count)
+        let state = ConversationState::from_name(mode).ok_or_else(|| {
+            let names: Vec<&str> = ConversationState::ALL.iter().map(|state| state.name()).collect();
+            format!("Unknown mode: {} (use {} or auto)", mode, names.join(", "))
+        })?;
+        self.context.state = state.clone();
+        self.pinned_state = Some(state);
+        Ok(format!("🎛️ Mode: {} (pinned, /mode auto to resume detection)", self.context.state.name()))
+    }
+
+    /// Describe the active conversation mode
+    pub fn mode_summary(&self) -> String {
+        let detection = if self.pinned_state.is_some() { "pinned" } else { "auto-detected" };
+        format!("🎛️ Mode: {} ({})", self.context.state.name(), detection)
+    }
+
+    /// Drop older turns while keeping the system prompt, notes and the last `keep_turns` exchanges
+    pub fn compact_context(&mut self, keep_turns: usize) -> String {
+        let report = compact_messages(&mut self.messages, keep_turns);
+
+        if report.messages_removed == 0 {
+            return format!("🗜️ Nothing to compact (conversation has {} turns or fewer)", keep_turns);
+        }
+
+        format!(
+            "🗜️ Compacted context: {} messages removed, ~{} tokens reclaimed ({} most recent turns kept)",
+            report.messages_removed, report.tokens_reclaimed, keep_turns
+        )
     }
 
     /// Change model (semantic equivalent of /model)
@@ -600,7 +1549,7 @@ Respond with ONLY the JSON object, no other text."#;
 
         match crate::openrouter::OpenRouterClient::new(self.config.clone()).await {
             Ok(new_client) => {
-                self.openrouter_client = new_client;
+                self.openrouter_client = new_client.with_session_tokens_of(&self.openrouter_client);
                 Ok(format!("✅ Model changed from '{}' to '{}'", old_model, new_model))
             }
             Err(e) => {
@@ -611,20 +1560,21 @@ Respond with ONLY the JSON object, no other text."#;
     }
 
     /// List available models (semantic equivalent of /list-models)
-    pub async fn list_models(&self, search_term: &str) -> Result<String, Box<dyn std::error::Error>> {
-        match self.openrouter_client.list_models(search_term).await {
+    pub async fn list_models(&self, query: &ListModelsArgs) -> Result<String, Box<dyn std::error::Error>> {
+        let scope = query.scope();
+        match self.openrouter_client.list_models(&query.search_term, query.filter, query.refresh).await {
             Ok(models) => {
                 if models.is_empty() {
-                    if search_term.is_empty() {
+                    if scope.is_empty() {
                         Ok("📋 No models available".to_string())
                     } else {
-                        Ok(format!("📋 No models found matching '{}'", search_term))
+                        Ok(format!("📋 No models found{}", scope))
                     }
                 } else {
-                    let mut result = if search_term.is_empty() {
+                    let mut result = if scope.is_empty() {
                         format!("📋 Available models ({}):\n", models.len())
                     } else {
-                        format!("📋 Models matching '{}' ({}):\n", search_term, models.len())
+                        format!("📋 Models{} ({}):\n", scope, models.len())
                     };
 
                     let max_items = std::cmp::min(models.len(), 10);
@@ -644,177 +1594,5 @@ Respond with ONLY the JSON object, no other text."#;
     }
 }
 
-/// File system autocomplete for semantic engine
-#[derive(Clone)]
-pub struct CustomTextAutocomplete {
-    working_dir: String,
-}
-
-impl CustomTextAutocomplete {
-    pub fn new(working_dir: String) -> Self {
-        Self {
-            working_dir,
-        }
-    }
-}
-
-impl Autocomplete for CustomTextAutocomplete {
-    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, inquire::CustomUserError> {
-        // Handle filesystem autocomplete if '@' is present
-        if input.contains('@') {
-            let last_at = input.rfind('@').unwrap();
-            let before_at = &input[..last_at];
-            let after_at = &input[last_at + 1..];
-
-            // Check if this is a folder path that should show contents
-            if after_at.ends_with('/') && !after_at.trim_end_matches('/').is_empty() {
-                let folder_contents = self.get_folder_contents(after_at);
-
-                if !folder_contents.is_empty() {
-                    let drill_suggestions: Vec<String> = folder_contents
-                        .into_iter()
-                        .map(|item| format!("{}@{}{}", before_at, after_at, item))
-                        .collect();
-
-                    return Ok(drill_suggestions);
-                }
-            }
-
-            // Regular filesystem autocomplete
-            let suggestions = self.get_file_suggestions(after_at);
-
-            let full_suggestions: Vec<String> = suggestions
-                .into_iter()
-                .map(|suggestion| format!("{}@{}", before_at, suggestion))
-                .collect();
-
-            return Ok(full_suggestions);
-        }
-
-        // No suggestions for regular text
-        Ok(vec![])
-    }
-
-    fn get_completion(
-        &mut self,
-        _input: &str,
-        highlighted_suggestion: Option<String>,
-    ) -> Result<inquire::autocompletion::Replacement, inquire::CustomUserError> {
-        Ok(match highlighted_suggestion {
-            Some(suggestion) => inquire::autocompletion::Replacement::Some(suggestion),
-            None => inquire::autocompletion::Replacement::None,
-        })
-    }
-}
-
-impl CustomTextAutocomplete {
-    fn get_folder_contents(&self, folder_path: &str) -> Vec<String> {
-        let clean_path = folder_path.trim_end_matches('/');
-        let full_path = Path::new(&self.working_dir).join(clean_path);
-        let mut entries = Vec::new();
-
-        if let Ok(dir_entries) = fs::read_dir(&full_path) {
-            for entry in dir_entries.flatten() {
-                if let Ok(metadata) = entry.metadata() {
-                    let name = entry.file_name().to_string_lossy().to_string();
-
-                    if name.starts_with('.') && !folder_path.contains("/.") {
-                        continue;
-                    }
-
-                    if metadata.is_dir() {
-                        entries.push(format!("{}/", name));
-                    } else {
-                        entries.push(name);
-                    }
-                }
-            }
-        }
-
-        entries.sort_by(|a, b| {
-            let a_is_dir = a.ends_with('/');
-            let b_is_dir = b.ends_with('/');
-            match (a_is_dir, b_is_dir) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.cmp(b),
-            }
-        });
-
-        entries
-    }
-
-    fn get_file_suggestions(&self, partial_path: &str) -> Vec<String> {
-        if partial_path.is_empty() {
-            return self.list_directory(".");
-        }
-
-        let path = Path::new(partial_path);
-        let (dir_path, file_prefix) = if partial_path.ends_with('/') {
-            (partial_path.trim_end_matches('/').to_string(), String::new())
-        } else {
-            match path.parent() {
-                Some(parent) => {
-                    let parent_str = parent.to_string_lossy().to_string();
-                    let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-                    (parent_str, file_name)
-                }
-                None => (".".to_string(), partial_path.to_string()),
-            }
-        };
-
-        let dir_path_str = if dir_path.is_empty() { "." } else { &dir_path };
-        let entries = self.list_directory(dir_path_str);
-
-        entries
-            .into_iter()
-            .filter(|entry| entry.starts_with(&file_prefix))
-            .collect()
-    }
-
-    fn list_directory(&self, relative_path: &str) -> Vec<String> {
-        let full_path = Path::new(&self.working_dir).join(relative_path);
-        let mut entries = Vec::new();
-
-        if let Ok(dir_entries) = fs::read_dir(&full_path) {
-            for entry in dir_entries.flatten() {
-                if let Ok(metadata) = entry.metadata() {
-                    let name = entry.file_name().to_string_lossy().to_string();
-
-                    if name.starts_with('.') && !relative_path.contains("/.") {
-                        continue;
-                    }
-
-                    let entry_path = if relative_path == "." {
-                        if metadata.is_dir() {
-                            format!("{}/", name)
-                        } else {
-                            name
-                        }
-                    } else {
-                        let clean_relative_path = relative_path.trim_end_matches('/');
-                        if metadata.is_dir() {
-                            format!("{}/{}/", clean_relative_path, name)
-                        } else {
-                            format!("{}/{}", clean_relative_path, name)
-                        }
-                    };
-
-                    entries.push(entry_path);
-                }
-            }
-        }
-
-        entries.sort_by(|a, b| {
-            let a_is_dir = a.ends_with('/');
-            let b_is_dir = b.ends_with('/');
-            match (a_is_dir, b_is_dir) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.cmp(b),
-            }
-        });
-
-        entries
-    }
-}
\ No newline at end of file
+/// Longest first line of a reply shown in the `openrouter.n` picker
+const REPLY_PREVIEW_CHARS: usize = 70;
@@ -0,0 +1,439 @@
+use crate::openrouter::Message;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Persisted state of a semantic chat session
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionRecord {
+    pub id: String,
+    pub working_dir: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub messages: Vec<Message>,
+    /// Working memory accumulated during the session
+    #[serde(default)]
+    pub working_memory: Vec<String>,
+    /// Durable notes added with /remember, carried into later sessions
+    #[serde(default)]
+    pub notes: Vec<String>,
+}
+
+/// Longest session title shown in listings
+const TITLE_WIDTH: usize = 60;
+
+/// Longest message shown by `/history show`; longer ones end in `…`
+const TRANSCRIPT_MESSAGE_CHARS: usize = 500;
+
+impl SessionRecord {
+    /// Prompts the user sent
+    pub fn turn_count(&self) -> usize {
+        self.messages.iter().filter(|message| message.role == "user").count()
+    }
+
+    /// First line of the first prompt, shortened for listings
+    pub fn title(&self) -> String {
+        let prompt = self.messages.iter().find(|message| message.role == "user").map(|message| message.content.text());
+        match prompt.as_deref().and_then(|prompt| prompt.lines().map(str::trim).find(|line| !line.is_empty())) {
+            Some(line) => clip(line, TITLE_WIDTH),
+            None => "(no prompts)".to_string(),
+        }
+    }
+
+    /// First 8 characters of the id, enough to name a session in `/history show` and `resume`
+    pub fn short_id(&self) -> &str {
+        self.id.get(..8).unwrap_or(&self.id)
+    }
+}
+
+/// First `max_chars` characters of `text`, marked with `…` when cut
+pub(crate) fn clip(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Sessions of `working_dir`, newest first, one line each; `current` marks the running session
+pub fn format_session_list(working_dir: &str, records: &[SessionRecord], current: Option<&str>) -> String {
+    if records.is_empty() {
+        return format!("📜 No saved sessions for {}", working_dir);
+    }
+
+    let mut listing = format!("📜 Sessions for {} ({})", working_dir, records.len());
+    for record in records {
+        let turns = record.turn_count();
+        listing.push_str(&format!(
+            "\n  {}  {}  {:>3} {}  {}{}",
+            record.short_id(),
+            record.updated_at.format("%Y-%m-%d %H:%M UTC"),
+            turns,
+            if turns == 1 { "turn " } else { "turns" },
+            record.title(),
+            if current == Some(record.id.as_str()) { " (current)" } else { "" }
+        ));
+    }
+    listing.push_str("\n💡 /history show <id> to read one, /history resume <id> to continue it");
+    listing
+}
+
+/// Prompts, replies and tool calls of a session, without tool results
+pub fn format_transcript(record: &SessionRecord) -> String {
+    let mut transcript = format!(
+        "📜 Session {} in {}\n   Started {}, last updated {}, {} turns",
+        record.id,
+        record.working_dir,
+        record.created_at.format("%Y-%m-%d %H:%M UTC"),
+        record.updated_at.format("%Y-%m-%d %H:%M UTC"),
+        record.turn_count()
+    );
+    for message in &record.messages {
+        let text = message.content.text();
+        let text = text.trim();
+        match message.role.as_str() {
+            "user" => transcript.push_str(&format!("\n\n👤 {}", clip(text, TRANSCRIPT_MESSAGE_CHARS))),
+            "assistant" => {
+                if !text.is_empty() {
+                    transcript.push_str(&format!("\n🤖 {}", clip(text, TRANSCRIPT_MESSAGE_CHARS)));
+                }
+                for call in message.tool_calls.iter().flatten() {
+                    transcript.push_str(&format!("\n   🔧 {}", call.function.name));
+                }
+            }
+            _ => {}
+        }
+    }
+    transcript
+}
+
+/// Most entries of one kind listed in a resumed session's work summary; older ones are counted instead
+const SUMMARY_LIST_LIMIT: usize = 20;
+
+/// Longest command shown in a work summary
+const SUMMARY_COMMAND_CHARS: usize = 80;
+
+/// What a session's tool calls did, rebuilt from its messages so a resumed session can tell the model which
+/// files it already touched and which commands it ran without replaying every tool result. Paths are listed
+/// once each, in the order they were last touched; `None` when the session made no tool calls.
+pub fn format_work_summary(record: &SessionRecord) -> Option<String> {
+    let mut calls = HashMap::new();
+    for message in &record.messages {
+        for call in message.tool_calls.iter().flatten() {
+            let args: Value = serde_json::from_str(&call.function.arguments).unwrap_or_default();
+            calls.insert(call.id.as_str(), (call.function.name.as_str(), args));
+        }
+    }
+
+    let mut written = Vec::new();
+    let mut deleted = Vec::new();
+    let mut read = Vec::new();
+    let mut directories = Vec::new();
+    let mut commands = Vec::new();
+    let mut failures = Vec::new();
+    let mut other: Vec<(&str, usize)> = Vec::new();
+    for message in record.messages.iter().filter(|message| message.role == "tool") {
+        let Some((name, args)) = message.tool_call_id.as_deref().and_then(|id| calls.get(id)) else {
+            continue;
+        };
+        let result: Value = serde_json::from_str(&message.content.text()).unwrap_or_default();
+        let path = args["path"].as_str().unwrap_or_default().to_string();
+        if result["status"] == "error" {
+            let target = args["path"].as_str().or(args["command"].as_str()).map(|target| format!(" {}", clip(target, SUMMARY_COMMAND_CHARS)));
+            failures.push(format!("{}{} ({})", name, target.unwrap_or_default(), result["message"].as_str().unwrap_or("failed")));
+            continue;
+        }
+        match *name {
+            "create_file" | "write_file" | "create_from_template" => touch(&mut written, path),
+            "scaffold_project" => {
+                for created in result["created"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                    touch(&mut written, created.to_string());
+                }
+            }
+            "delete_file" => touch(&mut deleted, path),
+            "read_file" => touch(&mut read, path),
+            "create_directory" => touch(&mut directories, path),
+            "run_command" | "run_command_background" => {
+                let command = clip(args["command"].as_str().unwrap_or_default(), SUMMARY_COMMAND_CHARS);
+                let outcome = match (result["exit_code"].as_i64(), result["pid"].as_u64()) {
+                    (Some(code), _) => format!("exit {}", code),
+                    (None, Some(pid)) => format!("started as pid {}", pid),
+                    (None, None) => "interrupted".to_string(),
+                };
+                commands.push(format!("`{}` ({})", command, outcome));
+            }
+            _ => match other.iter_mut().find(|(tool, _)| tool == name) {
+                Some((_, count)) => *count += 1,
+                None => other.push((name, 1)),
+            },
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (label, entries) in [
+        ("Files created or written", written),
+        ("Files deleted", deleted),
+        ("Files read", read),
+        ("Directories created", directories),
+        ("Commands run", commands),
+        ("Failed tool calls", failures),
+    ] {
+        if let Some(list) = summary_list(&entries) {
+            lines.push(format!("- {}: {}", label, list));
+        }
+    }
+    if !other.is_empty() {
+        let counts: Vec<String> = other.iter().map(|(tool, count)| format!("{} ×{}", tool, count)).collect();
+        lines.push(format!("- Other tools: {}", counts.join(", ")));
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "This session is being resumed. Work done in it so far, rebuilt from its tool results (files may have changed since):\n{}",
+        lines.join("\n")
+    ))
+}
+
+/// Move `path` to the end of `paths`, so each path appears once where it was last touched
+fn touch(paths: &mut Vec<String>, path: String) {
+    if path.is_empty() {
+        return;
+    }
+    paths.retain(|existing| *existing != path);
+    paths.push(path);
+}
+
+/// The most recent `SUMMARY_LIST_LIMIT` entries, comma-separated
+fn summary_list(entries: &[String]) -> Option<String> {
+    let skipped = entries.len().saturating_sub(SUMMARY_LIST_LIMIT);
+    let list = entries[skipped..].join(", ");
+    match skipped {
+        _ if entries.is_empty() => None,
+        0 => Some(list),
+        _ => Some(format!("{} earlier, then {}", skipped, list)),
+    }
+}
+
+/// On-disk store for session records, one JSON file per session
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    /// Open the default store under the user's data directory
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = dirs::data_dir()
+            .ok_or("Could not find data directory")?
+            .join("loo")
+            .join("sessions");
+        Self::with_dir(dir)
+    }
+
+    /// Open a store rooted at an explicit directory
+    pub fn with_dir<P: AsRef<Path>>(dir: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn record_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn save(&self, record: &SessionRecord) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let path = self.record_path(&record.id);
+        let json = serde_json::to_string_pretty(record)?;
+        fs::write(&path, json)?;
+        Ok(path)
+    }
+
+    pub fn load(&self, id: &str) -> Result<SessionRecord, Box<dyn std::error::Error>> {
+        let path = self.record_path(id);
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read session '{}': {}", id, e))?;
+        let record: SessionRecord = serde_json::from_str(&content)?;
+        Ok(record)
+    }
+
+    /// Load a session by its id or a prefix of it that matches exactly one session
+    pub fn find(&self, id: &str) -> Result<SessionRecord, Box<dyn std::error::Error>> {
+        if id.is_empty() || id.contains(['/', '\\']) {
+            return Err(format!("Invalid session id '{}'", id).into());
+        }
+        if self.record_path(id).is_file() {
+            return self.load(id);
+        }
+
+        let matches: Vec<String> = fs::read_dir(&self.dir)?
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+            .filter(|stem| stem.starts_with(id))
+            .collect();
+        match matches.as_slice() {
+            [only] => self.load(only),
+            [] => Err(format!("No saved session matches '{}'", id).into()),
+            _ => Err(format!("'{}' matches {} sessions; use more of the id", id, matches.len()).into()),
+        }
+    }
+
+    /// List sessions recorded for a working directory, most recently updated first
+    pub fn list_for_dir(&self, working_dir: &str) -> Result<Vec<SessionRecord>, Box<dyn std::error::Error>> {
+        let mut records = Vec::new();
+
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+
+            // Skip unreadable or foreign files rather than failing the whole listing
+            let record = match fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<SessionRecord>(&content).ok())
+            {
+                Some(record) => record,
+                None => continue,
+            };
+
+            if record.working_dir == working_dir {
+                records.push(record);
+            }
+        }
+
+        records.sort_by_key(|record| std::cmp::Reverse(record.updated_at));
+        Ok(records)
+    }
+
+    /// Most recent session for a working directory, if any
+    pub fn latest_for_dir(&self, working_dir: &str) -> Result<Option<SessionRecord>, Box<dyn std::error::Error>> {
+        Ok(self.list_for_dir(working_dir)?.into_iter().next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(id: &str, working_dir: &str, updated_at: DateTime<Utc>) -> SessionRecord {
+        SessionRecord {
+            id: id.to_string(),
+            working_dir: working_dir.to_string(),
+            created_at: updated_at,
+            updated_at,
+            messages: Vec::new(),
+            working_memory: vec!["File created".to_string()],
+            notes: vec!["Use tabs".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::with_dir(temp_dir.path()).unwrap();
+
+        store.save(&record("abc", "/project", Utc::now())).unwrap();
+        let loaded = store.load("abc").unwrap();
+
+        assert_eq!(loaded.working_dir, "/project");
+        assert_eq!(loaded.notes, vec!["Use tabs".to_string()]);
+        assert_eq!(loaded.working_memory, vec!["File created".to_string()]);
+    }
+
+    #[test]
+    fn test_find_by_prefix_and_listing() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::with_dir(temp_dir.path()).unwrap();
+        let now = Utc::now();
+
+        let mut first = record("3f2a9c1e-0001", "/project", now);
+        first.messages = vec![
+            Message { role: "user".to_string(), content: "\n  Fix the failing tests\nthey time out".to_string().into(), tool_calls: None, tool_call_id: None },
+            Message { role: "assistant".to_string(), content: "Done".to_string().into(), tool_calls: None, tool_call_id: None },
+        ];
+        store.save(&first).unwrap();
+        store.save(&record("3f2a9c1e-0002", "/project", now - chrono::Duration::hours(1))).unwrap();
+
+        assert_eq!(store.find("3f2a9c1e-0001").unwrap().id, "3f2a9c1e-0001");
+        assert_eq!(store.find("3f2a9c1e-00").unwrap_err().to_string(), "'3f2a9c1e-00' matches 2 sessions; use more of the id");
+        assert!(store.find("ffff").is_err());
+        assert!(store.find("../etc").is_err());
+
+        let records = store.list_for_dir("/project").unwrap();
+        let listing = format_session_list("/project", &records, Some("3f2a9c1e-0001"));
+        assert!(listing.contains("3f2a9c1e  "));
+        assert!(listing.contains("  1 turn   Fix the failing tests (current)"));
+        assert!(listing.contains("  0 turns  (no prompts)"));
+        assert!(format_transcript(&first).contains("👤 Fix the failing tests\nthey time out\n🤖 Done"));
+    }
+
+    #[test]
+    fn test_work_summary_from_tool_results() {
+        let call = |id: &str, name: &str, arguments: Value| crate::openrouter::ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: crate::openrouter::ToolCallFunction { name: name.to_string(), arguments: arguments.to_string() },
+        };
+        let result = |id: &str, content: Value| Message { role: "tool".to_string(), content: content.to_string().into(), tool_calls: None, tool_call_id: Some(id.to_string()) };
+
+        let mut session = record("abc", "/project", Utc::now());
+        assert_eq!(format_work_summary(&session), None);
+        session.messages = vec![
+            Message { role: "user".to_string(), content: "Add a test".to_string().into(), tool_calls: None, tool_call_id: None },
+            Message {
+                role: "assistant".to_string(),
+                content: String::new().into(),
+                tool_calls: Some(vec![
+                    call("1", "write_file", serde_json::json!({"path": "src/lib.rs", "content": "..."})),
+                    call("2", "read_file", serde_json::json!({"path": "Cargo.toml"})),
+                    call("3", "run_command", serde_json::json!({"command": "cargo test"})),
+                    call("4", "write_file", serde_json::json!({"path": "tests/it.rs", "content": "..."})),
+                    call("5", "write_file", serde_json::json!({"path": "src/lib.rs", "content": "..."})),
+                    call("6", "delete_file", serde_json::json!({"path": "old.rs"})),
+                    call("7", "file_stats", serde_json::json!({"paths": "src"})),
+                    call("8", "scaffold_project", serde_json::json!({"project_type": "rust", "path": "app"})),
+                ]),
+                tool_call_id: None,
+            },
+            result("1", serde_json::json!({"status": "success"})),
+            result("2", serde_json::json!({"status": "success"})),
+            result("3", serde_json::json!({"status": "success", "exit_code": 101})),
+            result("4", serde_json::json!({"status": "success"})),
+            result("5", serde_json::json!({"status": "success"})),
+            result("6", serde_json::json!({"status": "error", "message": "file not found"})),
+            result("7", serde_json::json!({"status": "success"})),
+            result("8", serde_json::json!({"status": "success", "created": ["app/Cargo.toml", "app/src/main.rs"], "skipped": []})),
+        ];
+
+        let summary = format_work_summary(&session).unwrap();
+        assert!(summary.contains("\n- Files created or written: tests/it.rs, src/lib.rs, app/Cargo.toml, app/src/main.rs\n"));
+        assert!(summary.contains("\n- Files read: Cargo.toml\n"));
+        assert!(summary.contains("\n- Commands run: `cargo test` (exit 101)\n"));
+        assert!(summary.contains("\n- Failed tool calls: delete_file old.rs (file not found)\n"));
+        assert!(summary.ends_with("\n- Other tools: file_stats ×1"));
+        assert!(!summary.contains("Files deleted"));
+    }
+
+    #[test]
+    fn test_latest_for_dir_filters_and_orders() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::with_dir(temp_dir.path()).unwrap();
+        let now = Utc::now();
+
+        store.save(&record("old", "/project", now - chrono::Duration::hours(1))).unwrap();
+        store.save(&record("new", "/project", now)).unwrap();
+        store.save(&record("other", "/elsewhere", now + chrono::Duration::hours(1))).unwrap();
+
+        let latest = store.latest_for_dir("/project").unwrap().unwrap();
+        assert_eq!(latest.id, "new");
+        assert_eq!(store.list_for_dir("/project").unwrap().len(), 2);
+        assert!(store.latest_for_dir("/nowhere").unwrap().is_none());
+    }
+}
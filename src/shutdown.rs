@@ -0,0 +1,186 @@
+//! Clean exit when loo is stopped by a process manager, CI timeout, `kill` or `--timeout`
+//! SIGTERM, SIGINT when there is no terminal, and the run deadline kill background commands, write the story and
+//! last saved session state and restore the terminal before exiting
+
+use crate::session::{SessionRecord, SessionStore};
+use crate::story::{format_duration, StoryLogger};
+use crate::theme::{self, ThemeRole};
+use crate::tools::background::ProcessTable;
+use crossterm::{cursor, execute, terminal};
+use std::future::Future;
+use std::io::{self, IsTerminal};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Exit status when `--timeout` expires, matching coreutils `timeout`
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Exit status for a signal, following the shell convention of 128 + signal number
+fn signal_exit_code(signal_number: i32) -> i32 {
+    128 + signal_number
+}
+
+const SIGINT: i32 = 2;
+const SIGTERM: i32 = 15;
+
+/// What is saved on the way out: the story as logged so far and the session as of the last finished turn
+struct ExitState {
+    session: Mutex<Option<SessionRecord>>,
+    story: StoryLogger,
+    /// Background commands, whose process groups `std::process::exit` would otherwise leave running
+    processes: Mutex<Option<Arc<ProcessTable>>>,
+}
+
+/// Saves and exits on a termination signal or when the run deadline passes; the main loop publishes the
+/// session after each turn. SIGINT is only handled without a terminal: interactively Ctrl+C stops running
+/// commands and is counted towards leaving the chat instead.
+pub struct ShutdownHandler {
+    state: Arc<ExitState>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl ShutdownHandler {
+    pub fn new(story: StoryLogger) -> Self {
+        Self {
+            state: Arc::new(ExitState { session: Mutex::new(None), story, processes: Mutex::new(None) }),
+            task: None,
+        }
+    }
+
+    /// Kill the commands in `processes` on an early exit
+    pub fn track_processes(&self, processes: Arc<ProcessTable>) {
+        if let Ok(mut tracked) = self.state.processes.lock() {
+            *tracked = Some(processes);
+        }
+    }
+
+    /// Start handling SIGTERM, and SIGINT when stdin is not a terminal
+    pub fn listen_for_signals(&mut self) -> io::Result<()> {
+        let received = listen(!io::stdin().is_terminal())?;
+        let state = Arc::clone(&self.state);
+        self.task = Some(tokio::spawn(async move {
+            let (name, number) = received.await;
+            shut_down(&format!("🛑 Received {}, saving and exiting", name), ThemeRole::Warning, &state);
+            std::process::exit(signal_exit_code(number));
+        }));
+        Ok(())
+    }
+
+    /// End the process once `limit` has passed, with the same saving as for a signal and status 124.
+    /// A plain thread fires even while the session is blocked reading stdin or waiting on a command.
+    pub fn exit_after(&self, limit: Duration) {
+        let state = Arc::clone(&self.state);
+        std::thread::spawn(move || {
+            std::thread::sleep(limit);
+            shut_down(&format!("⏱️ Run timed out after {}", format_duration(limit)), ThemeRole::Error, &state);
+            std::process::exit(TIMEOUT_EXIT_CODE);
+        });
+    }
+
+    /// Replace the session state written on an early exit
+    pub fn publish(&self, record: SessionRecord) {
+        if let Ok(mut session) = self.state.session.lock() {
+            *session = Some(record);
+        }
+    }
+}
+
+impl Drop for ShutdownHandler {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// Resolves with the name and number of the first termination signal
+#[cfg(unix)]
+fn listen(handle_interrupt: bool) -> io::Result<impl Future<Output = (&'static str, i32)>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = if handle_interrupt { Some(signal(SignalKind::interrupt())?) } else { None };
+    Ok(async move {
+        tokio::select! {
+            _ = terminate.recv() => ("SIGTERM", SIGTERM),
+            Some(_) = async { interrupt.as_mut()?.recv().await } => ("SIGINT", SIGINT),
+        }
+    })
+}
+
+/// Without SIGTERM only Ctrl+C can be caught
+#[cfg(not(unix))]
+fn listen(handle_interrupt: bool) -> io::Result<impl Future<Output = (&'static str, i32)>> {
+    Ok(async move {
+        if !handle_interrupt {
+            std::future::pending::<()>().await;
+        }
+        let _ = tokio::signal::ctrl_c().await;
+        ("Ctrl+C", SIGINT)
+    })
+}
+
+fn shut_down(message: &str, role: ThemeRole, state: &ExitState) {
+    // The exit can come while a prompt holds raw mode or the spinner has hidden the cursor
+    let _ = terminal::disable_raw_mode();
+    let _ = execute!(io::stdout(), cursor::Show);
+    eprintln!("\n{}", theme::paint(role, message));
+
+    let processes = state.processes.lock().map(|processes| processes.clone()).unwrap_or_else(|poisoned| poisoned.into_inner().clone());
+    if let Some(processes) = processes {
+        processes.kill_all();
+    }
+    let record = state.session.lock().map(|mut session| session.take()).unwrap_or_else(|poisoned| poisoned.into_inner().take());
+    if let Some(record) = record {
+        if let Err(e) = SessionStore::new().and_then(|store| store.save(&record)) {
+            eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: Failed to save session: {}", e)));
+        }
+    }
+    match state.story.write_story_file() {
+        Ok(()) if !state.story.writes_file() => {}
+        Ok(()) => eprintln!("📝 Session story saved to story.md"),
+        Err(e) => eprintln!("{}", theme::paint(ThemeRole::Warning, &format!("Warning: Failed to write story file: {}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_exit_codes() {
+        assert_eq!(signal_exit_code(SIGTERM), 143);
+        assert_eq!(signal_exit_code(SIGINT), 130);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shut_down_kills_background_process_groups() {
+        let group_alive = |pid: u32| {
+            let output = std::process::Command::new("ps").args(["-A", "-o", "pgid=,stat="]).output().unwrap();
+            String::from_utf8_lossy(&output.stdout).lines().any(|line| {
+                let mut fields = line.split_whitespace();
+                fields.next() == Some(pid.to_string().as_str()) && fields.next().is_some_and(|stat| !stat.starts_with('Z'))
+            })
+        };
+
+        let working_dir = tempfile::TempDir::new().unwrap();
+        let processes = Arc::new(ProcessTable::default());
+        let pid = processes.spawn("sleep 30 & sleep 30", &working_dir.path().to_string_lossy()).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(group_alive(pid));
+
+        let story = StoryLogger::new(working_dir.path().to_string_lossy().to_string(), "test".to_string()).with_file_output(false);
+        let handler = ShutdownHandler::new(story);
+        handler.track_processes(Arc::clone(&processes));
+        shut_down("stopping", ThemeRole::Warning, &handler.state);
+
+        assert!(processes.list().is_empty());
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while group_alive(pid) && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(!group_alive(pid), "process group {} is still running", pid);
+    }
+}
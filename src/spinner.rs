@@ -0,0 +1,126 @@
+//! Activity indicators: the spinner shown while waiting on the model, and the progress log that replaces it
+//! for long tool calls when stderr is not a terminal
+
+use crate::config::ProgressLogFormat;
+use crate::input;
+use crate::theme::{self, ThemeRole};
+use std::io::{self, IsTerminal, Write};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+const FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often a long tool call reports that it is still running
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Running spinner; the indicator stays on screen until this value is dropped
+pub struct Spinner {
+    task: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Start a spinner with `message`, or a no-op one when disabled or stderr cannot redraw lines
+    pub fn start(message: &str, enabled: bool) -> Self {
+        if !enabled || !io::stderr().is_terminal() || input::dumb_terminal() {
+            return Self { task: None };
+        }
+
+        let message = message.to_string();
+        let started = Instant::now();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FRAME_INTERVAL);
+            for frame in FRAMES.iter().cycle() {
+                interval.tick().await;
+                let line = format!("{} {} {:.1}s", frame, message, started.elapsed().as_secs_f32());
+                let mut stderr = io::stderr();
+                let _ = write!(stderr, "\r{}", theme::paint(ThemeRole::Dim, &line));
+                let _ = stderr.flush();
+            }
+        });
+
+        Self { task: Some(task) }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+            // Erase the indicator line so the response starts on a clean line
+            let mut stderr = io::stderr();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        }
+    }
+}
+
+/// Running progress log for one tool call; reporting stops when this value is dropped
+pub struct ProgressLog {
+    task: Option<JoinHandle<()>>,
+}
+
+impl ProgressLog {
+    /// Report `tool` (described by `detail`, e.g. the command) every few seconds on stderr, but only where the
+    /// spinner cannot be drawn: scripts, CI and dumb terminals
+    pub fn start(tool: &str, detail: Option<&str>, format: ProgressLogFormat) -> Self {
+        if format == ProgressLogFormat::Off || (io::stderr().is_terminal() && !input::dumb_terminal()) {
+            return Self { task: None };
+        }
+
+        let tool = tool.to_string();
+        let detail = detail.map(|detail| detail.to_string());
+        let started = tokio::time::Instant::now();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(started + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+            loop {
+                interval.tick().await;
+                eprintln!("{}", progress_line(&tool, detail.as_deref(), started.elapsed(), format));
+            }
+        });
+
+        Self { task: Some(task) }
+    }
+}
+
+impl Drop for ProgressLog {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// One progress report in `format`
+pub fn progress_line(tool: &str, detail: Option<&str>, elapsed: Duration, format: ProgressLogFormat) -> String {
+    match format {
+        ProgressLogFormat::Json => json!({
+            "event": "progress",
+            "tool": tool,
+            "detail": detail,
+            "elapsed_secs": elapsed.as_secs(),
+        })
+        .to_string(),
+        _ => format!("⏳ Still running: {}, {}s elapsed", detail.unwrap_or(tool), elapsed.as_secs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_line_formats() {
+        let elapsed = Duration::from_secs(45);
+        assert_eq!(
+            progress_line("run_command", Some("cargo build"), elapsed, ProgressLogFormat::Text),
+            "⏳ Still running: cargo build, 45s elapsed"
+        );
+        assert_eq!(progress_line("list_directory", None, elapsed, ProgressLogFormat::Text), "⏳ Still running: list_directory, 45s elapsed");
+        assert_eq!(
+            progress_line("run_command", Some("cargo build"), elapsed, ProgressLogFormat::Json),
+            r#"{"detail":"cargo build","elapsed_secs":45,"event":"progress","tool":"run_command"}"#
+        );
+    }
+}
@@ -1,12 +1,23 @@
 use std::fs;
 use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 use chrono::{DateTime, Utc};
 use serde_json::Value;
+use crate::config::StoryFormat;
 
+/// Session story recorder; clones share the same entries, so background tasks can log and flush safely
+#[derive(Clone)]
 pub struct StoryLogger {
     working_dir: String,
-    entries: Vec<StoryEntry>,
+    entries: Arc<Mutex<Vec<StoryEntry>>>,
     session_id: String,
+    /// Names the assistant in response headings instead of "Assistant"
+    assistant_name: Option<String>,
+    /// Whether `write_story_file` touches disk
+    write_file: bool,
+    /// Emoji or text labels in headings and results
+    format: StoryFormat,
 }
 
 #[derive(Clone)]
@@ -20,41 +31,101 @@ pub struct StoryEntry {
 pub enum StoryEntryType {
     UserPrompt,
     AssistantResponse,
+    /// Thinking a reasoning model returned alongside its reply, folded away in story.md
+    Reasoning,
     ToolExecution { tool_name: String, args: Value },
-    ToolResult { success: bool, summary: String },
+    ToolResult { success: bool, summary: String, duration: Duration },
+    TurnCompleted { duration: Duration },
     ProcessInterrupted,
+    /// Complete command output when the copy sent to the model was capped
+    FullOutput,
+    /// A safety limit such as `tools.max_deletes_per_turn` was reached
+    GuardTriggered,
+    /// The user allowed a command pattern to run for the rest of the session
+    CommandApproved,
+    /// The provider's moderation blocked the request or the reply
+    ResponseBlocked,
+    /// Diff of a file the model wrote again, against its previous write in the session
+    FileRewritten { path: String },
+}
+
+/// Tally of the tool calls made during one turn, rendered as a one-line outcome
+#[derive(Default)]
+pub struct ToolUsageSummary {
+    counts: Vec<(String, usize)>,
+    exit_codes: Vec<String>,
+    failures: usize,
 }
 
 impl StoryLogger {
     pub fn new(working_dir: String, session_id: String) -> Self {
         Self {
             working_dir,
-            entries: Vec::new(),
+            entries: Arc::new(Mutex::new(Vec::new())),
             session_id,
+            assistant_name: None,
+            write_file: true,
+            format: StoryFormat::default(),
+        }
+    }
+
+    pub fn with_format(mut self, format: StoryFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// `icon`, or its text label such as `[TOOL]` in the plain format
+    fn label(&self, icon: &'static str, plain: &'static str) -> &'static str {
+        match self.format {
+            StoryFormat::Markdown => icon,
+            StoryFormat::Plain => plain,
         }
     }
 
-    pub fn log_user_prompt(&mut self, prompt: &str) {
-        self.entries.push(StoryEntry {
+    pub fn with_assistant_name(mut self, name: Option<String>) -> Self {
+        self.assistant_name = name;
+        self
+    }
+
+    /// Keep the story in memory only, so story.md is never written
+    pub fn with_file_output(mut self, enabled: bool) -> Self {
+        self.write_file = enabled;
+        self
+    }
+
+    pub fn writes_file(&self) -> bool {
+        self.write_file
+    }
+
+    pub fn log_user_prompt(&self, prompt: &str) {
+        self.lock_entries().push(StoryEntry {
             timestamp: Utc::now(),
             entry_type: StoryEntryType::UserPrompt,
             content: prompt.to_string(),
         });
     }
 
-    pub fn log_assistant_response(&mut self, response: &str) {
-        self.entries.push(StoryEntry {
+    pub fn log_assistant_response(&self, response: &str) {
+        self.lock_entries().push(StoryEntry {
             timestamp: Utc::now(),
             entry_type: StoryEntryType::AssistantResponse,
             content: response.to_string(),
         });
     }
 
-    pub fn log_tool_execution(&mut self, tool_name: &str, args: &Value) {
+    pub fn log_reasoning(&self, reasoning: &str) {
+        self.lock_entries().push(StoryEntry {
+            timestamp: Utc::now(),
+            entry_type: StoryEntryType::Reasoning,
+            content: reasoning.to_string(),
+        });
+    }
+
+    pub fn log_tool_execution(&self, tool_name: &str, args: &Value) {
         // Filter out file content from args for logging
         let filtered_args = self.filter_content_from_args(args.clone());
         
-        self.entries.push(StoryEntry {
+        self.lock_entries().push(StoryEntry {
             timestamp: Utc::now(),
             entry_type: StoryEntryType::ToolExecution {
                 tool_name: tool_name.to_string(),
@@ -64,27 +135,81 @@ impl StoryLogger {
         });
     }
 
-    pub fn log_tool_result(&mut self, tool_name: &str, success: bool, result: &str) {
-        let summary = self.create_result_summary(tool_name, result);
+    pub fn log_tool_result(&self, tool_name: &str, success: bool, result: &str, duration: Duration) {
+        let summary = summarize_tool_result(tool_name, result);
         
-        self.entries.push(StoryEntry {
+        self.lock_entries().push(StoryEntry {
             timestamp: Utc::now(),
             entry_type: StoryEntryType::ToolResult {
                 success,
                 summary,
+                duration,
             },
             content: String::new(),
         });
     }
 
-    pub fn log_process_interrupted(&mut self) {
-        self.entries.push(StoryEntry {
+    pub fn log_full_output(&self, output: &str) {
+        self.lock_entries().push(StoryEntry {
+            timestamp: Utc::now(),
+            entry_type: StoryEntryType::FullOutput,
+            content: output.to_string(),
+        });
+    }
+
+    pub fn log_turn_completed(&self, duration: Duration) {
+        self.lock_entries().push(StoryEntry {
+            timestamp: Utc::now(),
+            entry_type: StoryEntryType::TurnCompleted { duration },
+            content: String::new(),
+        });
+    }
+
+    pub fn log_process_interrupted(&self) {
+        self.lock_entries().push(StoryEntry {
             timestamp: Utc::now(),
             entry_type: StoryEntryType::ProcessInterrupted,
             content: "Process was interrupted by user (Ctrl-C)".to_string(),
         });
     }
 
+    pub fn log_guard_triggered(&self, message: &str) {
+        self.lock_entries().push(StoryEntry {
+            timestamp: Utc::now(),
+            entry_type: StoryEntryType::GuardTriggered,
+            content: message.to_string(),
+        });
+    }
+
+    pub fn log_command_approved(&self, pattern: &str) {
+        self.lock_entries().push(StoryEntry {
+            timestamp: Utc::now(),
+            entry_type: StoryEntryType::CommandApproved,
+            content: pattern.to_string(),
+        });
+    }
+
+    pub fn log_file_rewritten(&self, path: &str, diff: &str) {
+        self.lock_entries().push(StoryEntry {
+            timestamp: Utc::now(),
+            entry_type: StoryEntryType::FileRewritten { path: path.to_string() },
+            content: diff.to_string(),
+        });
+    }
+
+    pub fn log_response_blocked(&self, message: &str) {
+        self.lock_entries().push(StoryEntry {
+            timestamp: Utc::now(),
+            entry_type: StoryEntryType::ResponseBlocked,
+            content: message.to_string(),
+        });
+    }
+
+    // A writer that panicked mid-push cannot leave a half-written entry, so poisoning is ignored
+    fn lock_entries(&self) -> MutexGuard<'_, Vec<StoryEntry>> {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
     fn filter_content_from_args(&self, mut args: Value) -> Value {
         if let Value::Object(ref mut map) = args {
             // Remove 'content' field if it exists
@@ -95,62 +220,10 @@ impl StoryLogger {
         args
     }
 
-    fn create_result_summary(&self, tool_name: &str, result: &str) -> String {
-        match tool_name {
-            "create_file" | "write_file" => {
-                if let Ok(json) = serde_json::from_str::<Value>(result) {
-                    if let (Some(path), Some(size)) = (json["path"].as_str(), json["size"].as_u64()) {
-                        return format!("File {} ({} bytes)", path, size);
-                    }
-                }
-                "File operation completed".to_string()
-            },
-            "read_file" => {
-                if let Ok(json) = serde_json::from_str::<Value>(result) {
-                    if let (Some(path), Some(size)) = (json["path"].as_str(), json["size"].as_u64()) {
-                        return format!("Read file {} ({} bytes)", path, size);
-                    }
-                }
-                "File read completed".to_string()
-            },
-            "delete_file" => {
-                if let Ok(json) = serde_json::from_str::<Value>(result) {
-                    if let Some(path) = json["path"].as_str() {
-                        return format!("Deleted file {}", path);
-                    }
-                }
-                "File deleted".to_string()
-            },
-            "create_directory" => {
-                if let Ok(json) = serde_json::from_str::<Value>(result) {
-                    if let Some(path) = json["path"].as_str() {
-                        return format!("Created directory {}", path);
-                    }
-                }
-                "Directory created".to_string()
-            },
-            "list_directory" => {
-                if let Ok(json) = serde_json::from_str::<Value>(result) {
-                    if let (Some(path), Some(entries)) = (json["path"].as_str(), json["entries"].as_array()) {
-                        return format!("Listed directory {} ({} items)", path, entries.len());
-                    }
-                }
-                "Directory listed".to_string()
-            },
-            "run_command" => {
-                if let Ok(json) = serde_json::from_str::<Value>(result) {
-                    if let (Some(command), Some(success)) = (json["command"].as_str(), json["success"].as_bool()) {
-                        let status = if success { "✓" } else { "✗" };
-                        return format!("{} Command: {}", status, command);
-                    }
-                }
-                "Command executed".to_string()
-            },
-            _ => format!("{} completed", tool_name)
-        }
-    }
-
     pub fn write_story_file(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.write_file {
+            return Ok(());
+        }
         let story_path = Path::new(&self.working_dir).join("story.md");
         let content = self.generate_markdown();
         fs::write(story_path, content)?;
@@ -168,7 +241,7 @@ impl StoryLogger {
         markdown.push_str("---\n\n");
 
         // Entries
-        for (i, entry) in self.entries.iter().enumerate() {
+        for (i, entry) in self.lock_entries().iter().enumerate() {
             let timestamp = entry.timestamp.format("%H:%M:%S");
             
             match &entry.entry_type {
@@ -178,24 +251,61 @@ impl StoryLogger {
                     markdown.push_str(&format!("```\n{}\n```\n\n", entry.content));
                 },
                 StoryEntryType::AssistantResponse => {
-                    markdown.push_str(&format!("### Assistant Response\n"));
+                    markdown.push_str(&format!("### {} Response\n", self.assistant_name.as_deref().unwrap_or("Assistant")));
                     markdown.push_str(&format!("**Time:** {}\n\n", timestamp));
                     markdown.push_str(&format!("{}\n\n", entry.content));
                 },
+                StoryEntryType::Reasoning => {
+                    markdown.push_str(&format!(
+                        "<details><summary>{} Reasoning</summary>\n\n{}\n\n</details>\n\n",
+                        self.label("💭", "[REASONING]"), entry.content.trim()
+                    ));
+                },
                 StoryEntryType::ToolExecution { tool_name, args } => {
-                    markdown.push_str(&format!("### 🔧 Tool: `{}`\n", tool_name));
+                    markdown.push_str(&format!("### {} Tool: `{}`\n", self.label("🔧", "[TOOL]"), tool_name));
                     markdown.push_str(&format!("**Time:** {}\n\n", timestamp));
                     if args != &Value::Null {
                         markdown.push_str(&format!("**Arguments:**\n```json\n{}\n```\n\n", 
                             serde_json::to_string_pretty(args).unwrap_or_else(|_| "Invalid JSON".to_string())));
                     }
                 },
-                StoryEntryType::ToolResult { success, summary } => {
-                    let status_icon = if *success { "✅" } else { "❌" };
-                    markdown.push_str(&format!("**Result:** {} {}\n\n", status_icon, summary));
+                StoryEntryType::ToolResult { success, summary, duration } => {
+                    let status_icon = if *success { self.label("✅", "[OK]") } else { self.label("❌", "[FAIL]") };
+                    // Command summaries carry the command's own outcome as ✓ or ✗
+                    let summary = match self.format {
+                        StoryFormat::Markdown => summary.clone(),
+                        StoryFormat::Plain => summary.replacen('✓', "[PASS]", 1).replacen('✗', "[EXIT FAIL]", 1),
+                    };
+                    markdown.push_str(&format!("**Result:** {} {} (ran in {})\n\n", status_icon, summary, format_duration(*duration)));
+                },
+                StoryEntryType::FullOutput => {
+                    markdown.push_str(&format!("<details><summary>Full output</summary>\n\n```\n{}\n```\n</details>\n\n", entry.content.trim_end()));
+                },
+                StoryEntryType::TurnCompleted { duration } => {
+                    markdown.push_str(&format!("_Turn completed in {} at {}_\n\n", format_duration(*duration), timestamp));
                 },
                 StoryEntryType::ProcessInterrupted => {
-                    markdown.push_str(&format!("### ⚠️ Process Interrupted\n"));
+                    markdown.push_str(&format!("### {} Process Interrupted\n", self.label("⚠️", "[INTERRUPTED]")));
+                    markdown.push_str(&format!("**Time:** {}\n\n", timestamp));
+                    markdown.push_str(&format!("{}\n\n", entry.content));
+                },
+                StoryEntryType::GuardTriggered => {
+                    markdown.push_str(&format!("### {} Guard Triggered\n", self.label("🛡️", "[GUARD]")));
+                    markdown.push_str(&format!("**Time:** {}\n\n", timestamp));
+                    markdown.push_str(&format!("{}\n\n", entry.content));
+                },
+                StoryEntryType::CommandApproved => {
+                    markdown.push_str(&format!("### {} Command Approved\n", self.label("👍", "[APPROVED]")));
+                    markdown.push_str(&format!("**Time:** {}\n\n", timestamp));
+                    markdown.push_str(&format!("`{} {}` may run for the rest of the session\n\n", entry.content, self.label("…", "...")));
+                },
+                StoryEntryType::FileRewritten { path } => {
+                    markdown.push_str(&format!("### {} Rewrote `{}`\n", self.label("✏️", "[EDIT]"), path));
+                    markdown.push_str(&format!("**Time:** {}\n\n", timestamp));
+                    markdown.push_str(&format!("Changes since the previous write:\n```diff\n{}\n```\n\n", entry.content.trim_end()));
+                },
+                StoryEntryType::ResponseBlocked => {
+                    markdown.push_str(&format!("### {} Response Blocked\n", self.label("🚫", "[BLOCKED]")));
                     markdown.push_str(&format!("**Time:** {}\n\n", timestamp));
                     markdown.push_str(&format!("{}\n\n", entry.content));
                 },
@@ -204,4 +314,402 @@ impl StoryLogger {
 
         markdown
     }
-}
\ No newline at end of file
+}
+
+impl ToolUsageSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, tool_name: &str, success: bool, result: &str) {
+        if !success {
+            self.failures += 1;
+            return;
+        }
+
+        match self.counts.iter_mut().find(|(name, _)| name == tool_name) {
+            Some((_, count)) => *count += 1,
+            None => self.counts.push((tool_name.to_string(), 1)),
+        }
+
+        if tool_name == "run_command" {
+            let exit_code = serde_json::from_str::<Value>(result)
+                .ok()
+                .map(|json| match json["exit_code"].as_i64() {
+                    Some(code) => code.to_string(),
+                    None => "interrupted".to_string(),
+                })
+                .unwrap_or_else(|| "?".to_string());
+            if !self.exit_codes.contains(&exit_code) {
+                self.exit_codes.push(exit_code);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty() && self.failures == 0
+    }
+
+    /// e.g. "3 files created, 1 command run (exit 0), 1 file read"
+    pub fn render(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut parts: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(tool_name, count)| {
+                let part = describe_tool_count(tool_name, *count);
+                if tool_name == "run_command" {
+                    format!("{} (exit {})", part, self.exit_codes.join(", "))
+                } else {
+                    part
+                }
+            })
+            .collect();
+        if self.failures > 0 {
+            parts.push(format!("{} failed", self.failures));
+        }
+
+        Some(parts.join(", "))
+    }
+}
+
+/// One-line description of a tool result, e.g. `Read file src/main.rs (2048 bytes)`
+pub fn summarize_tool_result(tool_name: &str, result: &str) -> String {
+    match tool_name {
+        "create_file" | "write_file" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(path), Some(size)) = (json["path"].as_str(), json["size"].as_u64()) {
+                    let formatting = &json["formatting"];
+                    return match (formatting["formatter"].as_str(), formatting["outcome"].as_str()) {
+                        (Some(formatter), Some("formatted")) => format!(
+                            "File {} ({} bytes, formatted by {}: +{} -{} lines)",
+                            path, size, formatter, formatting["lines_added"], formatting["lines_removed"]
+                        ),
+                        (Some(formatter), Some("failed")) => format!("File {} ({} bytes, {} failed)", path, size, formatter),
+                        _ => format!("File {} ({} bytes)", path, size),
+                    };
+                }
+            }
+            "File operation completed".to_string()
+        },
+        "read_file" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(path), Some(size)) = (json["path"].as_str(), json["size"].as_u64()) {
+                    return format!("Read file {} ({} bytes)", path, size);
+                }
+            }
+            "File read completed".to_string()
+        },
+        "delete_file" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let Some(path) = json["path"].as_str() {
+                    return format!("Deleted file {}", path);
+                }
+            }
+            "File deleted".to_string()
+        },
+        "create_directory" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let Some(path) = json["path"].as_str() {
+                    return format!("Created directory {}", path);
+                }
+            }
+            "Directory created".to_string()
+        },
+        "list_directory" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(path), Some(entries)) = (json["path"].as_str(), json["entries"].as_array()) {
+                    return format!("Listed directory {} ({} items)", path, entries.len());
+                }
+            }
+            "Directory listed".to_string()
+        },
+        "file_stats" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(files), Some(tokens)) = (json["total"]["files"].as_u64(), json["total"]["tokens"].as_u64()) {
+                    return format!("Measured {} {} (~{} tokens)", files, if files == 1 { "file" } else { "files" }, tokens);
+                }
+            }
+            "File sizes measured".to_string()
+        },
+        "run_command" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(command), Some(success)) = (json["command"].as_str(), json["success"].as_bool()) {
+                    let status = if success { "✓" } else { "✗" };
+                    return format!("{} Command: {}", status, command);
+                }
+            }
+            "Command executed".to_string()
+        },
+        "run_command_background" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(command), Some(pid)) = (json["command"].as_str(), json["pid"].as_u64()) {
+                    return format!("Started in background: {} (pid {})", command, pid);
+                }
+            }
+            "Background command started".to_string()
+        },
+        "check_process" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let Some(processes) = json["processes"].as_array() {
+                    return format!("Listed background commands ({} running)", processes.len());
+                }
+                if let (Some(command), Some(running)) = (json["command"].as_str(), json["running"].as_bool()) {
+                    return match (running, json["exit_code"].as_i64()) {
+                        (true, _) => format!("Checked {}: still running", command),
+                        (false, Some(code)) => format!("Checked {}: exited with code {}", command, code),
+                        (false, None) => format!("Checked {}: killed by a signal", command),
+                    };
+                }
+            }
+            "Background command checked".to_string()
+        },
+        "stop_process" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let Some(command) = json["command"].as_str() {
+                    return format!("Stopped background command: {}", command);
+                }
+            }
+            "Background command stopped".to_string()
+        },
+        "set_permissions" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(path), Some(mode)) = (json["path"].as_str(), json["mode"].as_str()) {
+                    return format!("Set mode {} on {}", mode, path);
+                }
+            }
+            "Permissions changed".to_string()
+        },
+        "query_structured" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(path), Some(query)) = (json["path"].as_str(), json["query"].as_str()) {
+                    if query.is_empty() {
+                        return format!("Read all of {}", path);
+                    }
+                    return format!("Read {} from {}", query, path);
+                }
+            }
+            "Structured file queried".to_string()
+        },
+        "inspect_archive" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(path), Some(entry)) = (json["path"].as_str(), json["entry"].as_str()) {
+                    return format!("Read {} from archive {}", entry, path);
+                }
+                if let (Some(path), Some(count)) = (json["path"].as_str(), json["entry_count"].as_u64()) {
+                    return format!("Listed archive {} ({} entries)", path, count);
+                }
+            }
+            "Archive inspected".to_string()
+        },
+        "create_from_template" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(path), Some(template), Some(size)) = (json["path"].as_str(), json["template"].as_str(), json["size"].as_u64()) {
+                    return format!("Created {} from template {} ({} bytes)", path, template, size);
+                }
+            }
+            "File created from template".to_string()
+        },
+        "scaffold_project" => {
+            if let Ok(json) = serde_json::from_str::<Value>(result) {
+                if let (Some(project_type), Some(created)) = (json["project_type"].as_str(), json["created"].as_array()) {
+                    return format!("Scaffolded {} project ({} files created)", project_type, created.len());
+                }
+            }
+            "Project scaffolded".to_string()
+        },
+        _ => format!("{} completed", tool_name)
+    }
+}
+
+/// Human-readable duration such as `2.3s` or `1m 05s`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 60.0 {
+        return format!("{:.1}s", secs);
+    }
+    let whole = duration.as_secs();
+    format!("{}m {:02}s", whole / 60, whole % 60)
+}
+
+/// Counted form of a tool call, matching the wording of the story summaries
+fn describe_tool_count(tool_name: &str, count: usize) -> String {
+    let files = if count == 1 { "file" } else { "files" };
+    let directories = if count == 1 { "directory" } else { "directories" };
+    match tool_name {
+        "create_file" => format!("{} {} created", count, files),
+        "write_file" => format!("{} {} written", count, files),
+        "read_file" => format!("{} {} read", count, files),
+        "delete_file" => format!("{} {} deleted", count, files),
+        "create_directory" => format!("{} {} created", count, directories),
+        "list_directory" => format!("{} {} listed", count, directories),
+        "file_stats" => format!("{} file size {}", count, if count == 1 { "check" } else { "checks" }),
+        "run_command" => format!("{} {} run", count, if count == 1 { "command" } else { "commands" }),
+        "run_command_background" => format!("{} background {} started", count, if count == 1 { "command" } else { "commands" }),
+        "check_process" => format!("{} process {}", count, if count == 1 { "check" } else { "checks" }),
+        "stop_process" => format!("{} background {} stopped", count, if count == 1 { "command" } else { "commands" }),
+        "set_permissions" => format!("{} permission {}", count, if count == 1 { "change" } else { "changes" }),
+        "query_structured" => format!("{} structured {}", count, if count == 1 { "query" } else { "queries" }),
+        "inspect_archive" => format!("{} {} inspected", count, if count == 1 { "archive" } else { "archives" }),
+        "create_from_template" => format!("{} {} created from templates", count, files),
+        "scaffold_project" => format!("{} {} scaffolded", count, if count == 1 { "project" } else { "projects" }),
+        _ => format!("{} {} {}", count, tool_name, if count == 1 { "call" } else { "calls" }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_usage_summary() {
+        let mut summary = ToolUsageSummary::new();
+        assert_eq!(summary.render(), None);
+
+        for path in ["a.rs", "b.rs", "c.rs"] {
+            summary.record("create_file", true, &format!(r#"{{"path":"{}","size":1}}"#, path));
+        }
+        summary.record("run_command", true, r#"{"command":"cargo build","exit_code":0,"success":true}"#);
+        summary.record("read_file", true, r#"{"path":"a.rs","size":1}"#);
+        summary.record("delete_file", false, "not found");
+
+        assert_eq!(
+            summary.render().unwrap(),
+            "3 files created, 1 command run (exit 0), 1 file read, 1 failed"
+        );
+    }
+
+    #[test]
+    fn test_summarize_tool_result() {
+        assert_eq!(
+            summarize_tool_result("read_file", r#"{"path":"src/main.rs","size":2048,"content":"..."}"#),
+            "Read file src/main.rs (2048 bytes)"
+        );
+        assert_eq!(summarize_tool_result("read_file", "not json"), "File read completed");
+        assert_eq!(summarize_tool_result("search_files", "{}"), "search_files completed");
+    }
+
+    #[test]
+    fn test_durations_in_story() {
+        assert_eq!(format_duration(Duration::from_millis(2340)), "2.3s");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m 05s");
+
+        let logger = StoryLogger::new(".".to_string(), "session".to_string());
+        logger.log_tool_result("run_command", true, r#"{"command":"make","success":true}"#, Duration::from_millis(2300));
+        logger.log_turn_completed(Duration::from_secs(4));
+
+        let markdown = logger.generate_markdown();
+        assert!(markdown.contains("✓ Command: make (ran in 2.3s)"));
+        assert!(markdown.contains("_Turn completed in 4.0s"));
+    }
+
+    #[test]
+    fn test_full_output_in_story() {
+        let logger = StoryLogger::new(".".to_string(), "session".to_string());
+        logger.log_full_output("line 1\nline 2\n");
+
+        let markdown = logger.generate_markdown();
+        assert!(markdown.contains("<details><summary>Full output</summary>\n\n```\nline 1\nline 2\n```\n</details>"));
+    }
+
+    #[test]
+    fn test_guard_trigger_in_story() {
+        let logger = StoryLogger::new(".".to_string(), "session".to_string());
+        logger.log_guard_triggered("Model attempted 7 deletions in one turn");
+
+        let markdown = logger.generate_markdown();
+        assert!(markdown.contains("### 🛡️ Guard Triggered\n"));
+        assert!(markdown.contains("Model attempted 7 deletions in one turn\n"));
+
+        logger.log_file_rewritten("src/lib.rs", "@@ -1,1 +1,1 @@\n-old\n+new\n");
+        assert!(logger.generate_markdown().contains("### ✏️ Rewrote `src/lib.rs`\n"));
+        assert!(logger.generate_markdown().contains("```diff\n@@ -1,1 +1,1 @@\n-old\n+new\n```"));
+
+        logger.log_response_blocked("The provider blocked this response (violence)");
+        assert!(logger.generate_markdown().contains("### 🚫 Response Blocked\n"));
+
+        logger.log_command_approved("cargo build");
+        assert!(logger.generate_markdown().contains("### 👍 Command Approved\n"));
+        assert!(logger.generate_markdown().contains("`cargo build …` may run for the rest of the session"));
+    }
+
+    #[test]
+    fn test_plain_story_has_text_labels() {
+        let logger = StoryLogger::new(".".to_string(), "session".to_string()).with_format(StoryFormat::Plain);
+        logger.log_tool_execution("run_command", &serde_json::json!({"command": "make"}));
+        logger.log_tool_result("run_command", true, r#"{"command":"make","success":false}"#, Duration::from_millis(2300));
+        logger.log_tool_result("read_file", false, "not found", Duration::from_millis(10));
+        logger.log_guard_triggered("Model attempted 7 deletions in one turn");
+        logger.log_command_approved("cargo build");
+
+        let markdown = logger.generate_markdown();
+        assert!(markdown.contains("### [TOOL] Tool: `run_command`\n"));
+        assert!(markdown.contains("**Result:** [OK] [EXIT FAIL] Command: make (ran in 2.3s)"));
+        assert!(markdown.contains("**Result:** [FAIL] File read completed"));
+        assert!(markdown.contains("### [GUARD] Guard Triggered\n"));
+        assert!(markdown.contains("`cargo build ...` may run for the rest of the session"));
+        assert!(!markdown.chars().any(|c| matches!(c, '🔧' | '✅' | '❌' | '🛡' | '👍' | '✓' | '✗' | '…')));
+    }
+
+    #[test]
+    fn test_reasoning_is_folded_in_story() {
+        let logger = StoryLogger::new(".".to_string(), "session".to_string());
+        logger.log_reasoning("The user wants a list, so sort first.\n");
+        logger.log_assistant_response("Sorted.");
+
+        let markdown = logger.generate_markdown();
+        assert!(markdown.contains("<details><summary>💭 Reasoning</summary>\n\nThe user wants a list, so sort first.\n\n</details>"));
+        assert!(markdown.find("Reasoning").unwrap() < markdown.find("Sorted.").unwrap());
+        assert!(logger.with_format(StoryFormat::Plain).generate_markdown().contains("<summary>[REASONING] Reasoning</summary>"));
+    }
+
+    #[test]
+    fn test_assistant_name_in_story() {
+        let logger = StoryLogger::new(".".to_string(), "session".to_string());
+        logger.log_assistant_response("Done");
+        assert!(logger.generate_markdown().contains("### Assistant Response"));
+
+        let logger = logger.with_assistant_name(Some("Ada".to_string()));
+        assert!(logger.generate_markdown().contains("### Ada Response"));
+    }
+
+    #[test]
+    fn test_story_without_file_output_stays_in_memory() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let working_dir = temp_dir.path().to_string_lossy().to_string();
+
+        let logger = StoryLogger::new(working_dir.clone(), "session".to_string()).with_file_output(false);
+        logger.log_user_prompt("Fix the build");
+        logger.write_story_file().unwrap();
+        assert!(!temp_dir.path().join("story.md").exists());
+        assert!(logger.generate_markdown().contains("Fix the build"));
+
+        StoryLogger::new(working_dir, "session".to_string()).write_story_file().unwrap();
+        assert!(temp_dir.path().join("story.md").exists());
+    }
+
+    #[test]
+    fn test_concurrent_logging_keeps_every_entry() {
+        let logger = StoryLogger::new(".".to_string(), "session".to_string());
+
+        let handles: Vec<_> = (0..8)
+            .map(|worker| {
+                let logger = logger.clone();
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        logger.log_user_prompt(&format!("{}-{}", worker, i));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(logger.lock_entries().len(), 400);
+        assert_eq!(logger.generate_markdown().matches("User Request").count(), 400);
+    }
+}
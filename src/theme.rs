@@ -0,0 +1,259 @@
+//! Terminal color theme
+//! Maps semantic output roles to colors from the `[theme]` config section
+
+use crate::config::ThemeConfig;
+use crate::input;
+use crossterm::style::{Color, Stylize};
+use std::env;
+use std::sync::OnceLock;
+
+/// Built-in presets accepted by `theme.preset`
+pub const PRESETS: &[&str] = &["dark", "light", "none"];
+
+/// Semantic role of a piece of terminal output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeRole {
+    Prompt,
+    Warning,
+    Selection,
+    Dim,
+    Success,
+    Error,
+}
+
+impl ThemeRole {
+    pub const ALL: [ThemeRole; 6] = [
+        ThemeRole::Prompt,
+        ThemeRole::Warning,
+        ThemeRole::Selection,
+        ThemeRole::Dim,
+        ThemeRole::Success,
+        ThemeRole::Error,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThemeRole::Prompt => "prompt",
+            ThemeRole::Warning => "warning",
+            ThemeRole::Selection => "selection",
+            ThemeRole::Dim => "dim",
+            ThemeRole::Success => "success",
+            ThemeRole::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ThemeRole> {
+        Self::ALL.iter().copied().find(|role| role.name() == name)
+    }
+}
+
+/// Resolved colors for every role; `None` means print without styling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub prompt: Option<Color>,
+    pub warning: Option<Color>,
+    pub selection: Option<Color>,
+    pub dim: Option<Color>,
+    pub success: Option<Color>,
+    pub error: Option<Color>,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            prompt: Some(Color::Cyan),
+            warning: Some(Color::Yellow),
+            selection: Some(Color::Cyan),
+            dim: Some(Color::DarkGrey),
+            success: Some(Color::Green),
+            error: Some(Color::Red),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            prompt: Some(Color::DarkBlue),
+            warning: Some(Color::DarkYellow),
+            selection: Some(Color::DarkMagenta),
+            dim: Some(Color::DarkGrey),
+            success: Some(Color::DarkGreen),
+            error: Some(Color::DarkRed),
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            prompt: None,
+            warning: None,
+            selection: None,
+            dim: None,
+            success: None,
+            error: None,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "none" => Some(Self::none()),
+            _ => None,
+        }
+    }
+
+    /// Build a theme from config, applying per-role overrides on top of the preset.
+    /// `no_color` wins over everything, following https://no-color.org
+    pub fn from_config(config: &ThemeConfig, no_color: bool) -> Self {
+        if no_color {
+            return Self::none();
+        }
+
+        let mut theme = Self::preset(&config.preset).unwrap_or_else(Self::dark);
+        for role in ThemeRole::ALL {
+            if let Some(color) = config.role(role).and_then(parse_color) {
+                *theme.slot(role) = Some(color);
+            }
+        }
+        theme
+    }
+
+    pub fn color(&self, role: ThemeRole) -> Option<Color> {
+        match role {
+            ThemeRole::Prompt => self.prompt,
+            ThemeRole::Warning => self.warning,
+            ThemeRole::Selection => self.selection,
+            ThemeRole::Dim => self.dim,
+            ThemeRole::Success => self.success,
+            ThemeRole::Error => self.error,
+        }
+    }
+
+    fn slot(&mut self, role: ThemeRole) -> &mut Option<Color> {
+        match role {
+            ThemeRole::Prompt => &mut self.prompt,
+            ThemeRole::Warning => &mut self.warning,
+            ThemeRole::Selection => &mut self.selection,
+            ThemeRole::Dim => &mut self.dim,
+            ThemeRole::Success => &mut self.success,
+            ThemeRole::Error => &mut self.error,
+        }
+    }
+
+    /// Style text for a role, leaving it untouched when the role has no color
+    pub fn paint(&self, role: ThemeRole, text: &str) -> String {
+        match self.color(role) {
+            Some(color) => text.with(color).to_string(),
+            None => text.to_string(),
+        }
+    }
+
+    /// Matching inquire render config so prompts follow the same theme
+    pub fn render_config(&self) -> inquire::ui::RenderConfig<'static> {
+        use inquire::ui::{RenderConfig, StyleSheet, Styled};
+
+        if *self == Self::none() {
+            return RenderConfig::empty();
+        }
+
+        let mut render_config = RenderConfig::default_colored();
+        if let Some(color) = self.prompt.map(to_inquire_color) {
+            render_config.prompt_prefix = Styled::new("?").with_fg(color);
+        }
+        if let Some(color) = self.selection.map(to_inquire_color) {
+            render_config.highlighted_option_prefix = Styled::new(">").with_fg(color);
+            render_config.selected_option = Some(StyleSheet::new().with_fg(color));
+            render_config.answer = StyleSheet::new().with_fg(color);
+        }
+        if let Some(color) = self.dim.map(to_inquire_color) {
+            render_config.help_message = StyleSheet::new().with_fg(color);
+            render_config.placeholder = StyleSheet::new().with_fg(color);
+        }
+        if let Some(color) = self.error.map(to_inquire_color) {
+            render_config.error_message = render_config.error_message.with_prefix(Styled::new("#").with_fg(color));
+        }
+        render_config
+    }
+}
+
+/// Parse a color name such as `cyan`, `dark_grey` or `dark-blue`
+pub fn parse_color(name: &str) -> Option<Color> {
+    let normalized = name.trim().to_lowercase().replace('-', "_");
+    Color::try_from(normalized.as_str()).ok()
+}
+
+/// True when the `NO_COLOR` convention asks us not to emit colors, or the terminal cannot show them
+pub fn no_color_requested() -> bool {
+    env::var("NO_COLOR").map(|value| !value.is_empty()).unwrap_or(false) || input::dumb_terminal()
+}
+
+fn to_inquire_color(color: Color) -> inquire::ui::Color {
+    use inquire::ui::Color as InquireColor;
+
+    match color {
+        Color::Black => InquireColor::Black,
+        Color::DarkGrey => InquireColor::DarkGrey,
+        Color::Red => InquireColor::LightRed,
+        Color::DarkRed => InquireColor::DarkRed,
+        Color::Green => InquireColor::LightGreen,
+        Color::DarkGreen => InquireColor::DarkGreen,
+        Color::Yellow => InquireColor::LightYellow,
+        Color::DarkYellow => InquireColor::DarkYellow,
+        Color::Blue => InquireColor::LightBlue,
+        Color::DarkBlue => InquireColor::DarkBlue,
+        Color::Magenta => InquireColor::LightMagenta,
+        Color::DarkMagenta => InquireColor::DarkMagenta,
+        Color::Cyan => InquireColor::LightCyan,
+        Color::DarkCyan => InquireColor::DarkCyan,
+        Color::White => InquireColor::White,
+        Color::Grey => InquireColor::Grey,
+        Color::Rgb { r, g, b } => InquireColor::Rgb { r, g, b },
+        Color::AnsiValue(value) => InquireColor::AnsiValue(value),
+        Color::Reset => InquireColor::White,
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Install the theme for this process and apply it to inquire prompts
+pub fn init(config: &ThemeConfig) {
+    let theme = THEME.get_or_init(|| Theme::from_config(config, no_color_requested()));
+    inquire::set_global_render_config(theme.render_config());
+}
+
+/// The active theme, falling back to the dark preset before `init` runs
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(|| Theme::from_config(&ThemeConfig::default(), no_color_requested()))
+}
+
+/// Style text for a role using the active theme
+pub fn paint(role: ThemeRole, text: &str) -> String {
+    current().paint(role, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_apply_on_top_of_preset() {
+        let config = ThemeConfig {
+            preset: "light".to_string(),
+            warning: Some("dark-red".to_string()),
+            error: Some("not-a-color".to_string()),
+            ..ThemeConfig::default()
+        };
+
+        let theme = Theme::from_config(&config, false);
+        assert_eq!(theme.warning, Some(Color::DarkRed));
+        assert_eq!(theme.error, Some(Color::DarkRed));
+        assert_eq!(theme.prompt, Some(Color::DarkBlue));
+    }
+
+    #[test]
+    fn test_no_color_disables_styling() {
+        let theme = Theme::from_config(&ThemeConfig::default(), true);
+        assert_eq!(theme, Theme::none());
+        assert_eq!(theme.paint(ThemeRole::Error, "boom"), "boom");
+        assert_ne!(Theme::dark().paint(ThemeRole::Error, "boom"), "boom");
+    }
+}
@@ -0,0 +1,234 @@
+//! Session approvals for `run_command` and `run_command_background` (`tools.confirm_commands`)
+//! A command is reduced to patterns such as `cargo build` or `rm -rf target`; once the user approves a
+//! pattern, later commands matching it run without asking again for the rest of the session
+
+use std::collections::BTreeSet;
+use std::sync::{Mutex, MutexGuard};
+
+/// Programs whose first non-flag argument is a subcommand. Approving one of their commands covers the
+/// subcommand with any arguments; every other command is only approved with exactly the same arguments.
+const SUBCOMMAND_PROGRAMS: &[&str] = &[
+    "cargo", "git", "go", "npm", "yarn", "pnpm", "bun", "pip", "pip3", "poetry", "uv", "make", "docker", "kubectl",
+    "dotnet", "mvn", "gradle",
+];
+
+/// Pattern of each command in a shell command line. For a program with subcommands it is the program and
+/// subcommand, so `cargo build --release` and `cargo build -p loo` share `cargo build`; otherwise it is the
+/// whole command, so approving `rm -rf target` does not allow `rm -rf target /` and approving `find .` does
+/// not allow `find . -delete`. Leading `VAR=value` assignments and redirections to files stay in the pattern,
+/// so approving `cargo build` does not allow `LD_PRELOAD=x cargo build` and approving `echo done` does not
+/// allow `echo done > ~/.bashrc`; only `2>&1`-style redirections and `/dev/null` are left out.
+pub fn command_patterns(command: &str) -> Vec<String> {
+    let mut patterns = Vec::new();
+    for segment in segments(command) {
+        let (assignments, words, redirects) = split_command(segment);
+        let mut pattern = assignments;
+        if let Some((program, arguments)) = words.split_first() {
+            pattern.push(program.to_string());
+            if SUBCOMMAND_PROGRAMS.contains(program) {
+                pattern.extend(arguments.iter().find(|word| !word.starts_with('-')).map(|word| word.to_string()));
+            } else {
+                pattern.extend(arguments.iter().map(|word| word.to_string()));
+            }
+        }
+        if pattern.is_empty() && redirects.is_empty() {
+            continue;
+        }
+        pattern.extend(redirects);
+        let pattern = pattern.join(" ");
+        if !patterns.contains(&pattern) {
+            patterns.push(pattern);
+        }
+    }
+    patterns
+}
+
+/// Whether `command` runs commands that `command_patterns` cannot see: substitutions like `$(…)`, backticks
+/// and `<(…)`, subshells `( … )` and groups `{ … }`. No saved approval covers such a command.
+pub fn runs_nested_commands(command: &str) -> bool {
+    command.contains(['`', '(', '{'])
+}
+
+/// How an approved pattern reads in the prompt: `'cargo build …'` when it covers other arguments too
+pub fn describe_pattern(pattern: &str) -> String {
+    match pattern.split_whitespace().find(|word| !is_assignment(word)) {
+        Some(program) if SUBCOMMAND_PROGRAMS.contains(&program) => format!("'{} …'", pattern),
+        _ => format!("'{}'", pattern),
+    }
+}
+
+/// Leading assignments, program and arguments, and redirections of one command. Redirections keep their
+/// target as `> out.txt`, except those to another descriptor or `/dev/null`, which are dropped.
+fn split_command(segment: &str) -> (Vec<String>, Vec<&str>, Vec<String>) {
+    // `done>out.txt` is the word `done` followed by the redirection `>out.txt`
+    let mut words = segment
+        .split_whitespace()
+        .flat_map(|word| match word.find(['>', '<']) {
+            Some(i) if !word[..i].chars().all(|c| c.is_ascii_digit() || c == '&') => vec![&word[..i], &word[i..]],
+            _ => vec![word],
+        })
+        .peekable();
+    let mut assignments = Vec::new();
+    while let Some(word) = words.next_if(|word| is_assignment(word)) {
+        assignments.push(word.to_string());
+    }
+    let mut arguments = Vec::new();
+    let mut redirects = Vec::new();
+    while let Some(word) = words.next() {
+        if !is_redirect(word) {
+            arguments.push(word);
+            continue;
+        }
+        // `> out.txt` names its target in the next word, `>out.txt` and `2>&1` do not
+        let operator_len = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '&').trim_start_matches(['>', '<']).len();
+        let (operator, target) = word.split_at(operator_len);
+        let target = if target.is_empty() { words.next().unwrap_or_default() } else { target };
+        if !target.starts_with('&') && target != "/dev/null" {
+            redirects.push(format!("{} {}", operator, target));
+        }
+    }
+    (assignments, arguments, redirects)
+}
+
+/// Commands of a shell command line, split at `;`, `|`, `&` and line breaks but not at redirections like `2>&1`
+fn segments(command: &str) -> Vec<&str> {
+    let bytes = command.as_bytes();
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        let separator = match byte {
+            b';' | b'|' | b'\n' => true,
+            b'&' => !(i > 0 && matches!(bytes[i - 1], b'>' | b'<')) && bytes.get(i + 1) != Some(&b'>'),
+            _ => false,
+        };
+        if separator {
+            segments.push(&command[start..i]);
+            start = i + 1;
+        }
+    }
+    segments.push(&command[start..]);
+    segments
+}
+
+/// A redirection such as `>out.txt`, `2>/dev/null` or `2>&1`
+fn is_redirect(word: &str) -> bool {
+    word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '&').starts_with(['>', '<'])
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=')
+        .is_some_and(|(name, _)| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+/// Patterns approved this session
+#[derive(Default)]
+pub struct CommandApprovals {
+    approved: Mutex<BTreeSet<String>>,
+    /// Approved since the last `take_new`, for the story
+    new: Mutex<Vec<String>>,
+}
+
+impl CommandApprovals {
+    /// Patterns of `command` that have not been approved yet; a command running nested commands is never
+    /// approved and is its own pattern
+    pub fn unapproved(&self, command: &str) -> Vec<String> {
+        if runs_nested_commands(command) {
+            return vec![command.trim().to_string()];
+        }
+        let approved = lock(&self.approved);
+        command_patterns(command).into_iter().filter(|pattern| !approved.contains(pattern)).collect()
+    }
+
+    pub fn approve(&self, patterns: &[String]) {
+        let mut approved = lock(&self.approved);
+        let mut new = lock(&self.new);
+        for pattern in patterns {
+            if approved.insert(pattern.clone()) {
+                new.push(pattern.clone());
+            }
+        }
+    }
+
+    pub fn take_new(&self) -> Vec<String> {
+        std::mem::take(&mut *lock(&self.new))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_patterns() {
+        assert_eq!(command_patterns("cargo build --release"), vec!["cargo build"]);
+        assert_eq!(command_patterns("cargo -q build"), vec!["cargo build"]);
+        assert_eq!(command_patterns("ls  -la"), vec!["ls -la"]);
+        assert_eq!(command_patterns("RUST_LOG=debug cargo test -- --nocapture"), vec!["RUST_LOG=debug cargo test"]);
+        assert_eq!(command_patterns("PATH=/tmp/bin:$PATH; cargo build"), vec!["PATH=/tmp/bin:$PATH", "cargo build"]);
+        assert_eq!(command_patterns("rm -rf target"), vec!["rm -rf target"]);
+        assert_eq!(command_patterns("rm -rf target /"), vec!["rm -rf target /"]);
+        assert_eq!(command_patterns("find . -delete"), vec!["find . -delete"]);
+        assert_eq!(
+            command_patterns("cargo fmt && cargo build 2>&1 | tail -5; cargo build"),
+            vec!["cargo fmt", "cargo build", "tail -5"]
+        );
+        assert_eq!(command_patterns("make 2>/dev/null >build.log check &"), vec!["make check > build.log"]);
+        assert_eq!(command_patterns("echo done > out.txt"), vec!["echo done > out.txt"]);
+        assert_eq!(command_patterns("cat <notes.txt 2>>err.log"), vec!["cat < notes.txt 2>> err.log"]);
+        assert_eq!(command_patterns("cargo test >&2"), vec!["cargo test"]);
+        assert_eq!(describe_pattern("cargo build"), "'cargo build …'");
+        assert_eq!(describe_pattern("RUST_LOG=debug cargo test"), "'RUST_LOG=debug cargo test …'");
+        assert_eq!(describe_pattern("find ."), "'find .'");
+    }
+
+    #[test]
+    fn test_approvals_cover_later_commands() {
+        let approvals = CommandApprovals::default();
+        assert_eq!(approvals.unapproved("cargo build"), vec!["cargo build"]);
+        approvals.approve(&approvals.unapproved("cargo build"));
+        assert!(approvals.unapproved("cargo build --release").is_empty());
+        assert_eq!(approvals.unapproved("cargo build && npm install"), vec!["npm install"]);
+        assert_eq!(approvals.take_new(), vec!["cargo build"]);
+        assert!(approvals.take_new().is_empty());
+
+        approvals.approve(&approvals.unapproved("find ."));
+        assert!(approvals.unapproved("find .").is_empty());
+        assert_eq!(approvals.unapproved("find . -delete"), vec!["find . -delete"]);
+    }
+
+    #[test]
+    fn test_approvals_do_not_cover_assignments_or_redirections() {
+        let approvals = CommandApprovals::default();
+        approvals.approve(&approvals.unapproved("cargo build"));
+        approvals.approve(&approvals.unapproved("echo done"));
+        assert!(approvals.unapproved("cargo build 2>&1 | echo done >/dev/null").is_empty());
+        assert_eq!(approvals.unapproved("echo done > ~/.bashrc"), vec!["echo done > ~/.bashrc"]);
+        assert_eq!(approvals.unapproved("echo done>>~/.bashrc"), vec!["echo done >> ~/.bashrc"]);
+        assert_eq!(approvals.unapproved("echo done 2>~/.bashrc"), vec!["echo done 2> ~/.bashrc"]);
+        assert_eq!(approvals.unapproved("LD_PRELOAD=x cargo build"), vec!["LD_PRELOAD=x cargo build"]);
+        assert_eq!(approvals.unapproved("PATH=/tmp/evil:$PATH cargo build"), vec!["PATH=/tmp/evil:$PATH cargo build"]);
+        assert_eq!(approvals.unapproved("export PATH=/tmp/evil; cargo build"), vec!["export PATH=/tmp/evil"]);
+    }
+
+    #[test]
+    fn test_nested_commands_are_never_approved() {
+        let approvals = CommandApprovals::default();
+        approvals.approve(&approvals.unapproved("cargo build"));
+        for command in [
+            "cargo build $(rm -rf ~)",
+            "cargo build `rm -rf ~`",
+            "cargo build <(rm -rf ~)",
+            "cargo build >(rm -rf ~)",
+            "(rm -rf ~) && cargo build",
+            "{ rm -rf ~; } && cargo build",
+        ] {
+            assert!(runs_nested_commands(command), "{}", command);
+            assert_eq!(approvals.unapproved(command), vec![command], "{}", command);
+        }
+        assert!(!runs_nested_commands("cargo build 2>&1 | tail -5"));
+    }
+}
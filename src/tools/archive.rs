@@ -0,0 +1,401 @@
+//! Read-only access to zip and tar archives for `inspect_archive`
+//! Zip central directories and tar headers are parsed here; gzip and deflate data go through the system
+//! `gzip` and `unzip`, the same way git operations shell out to `git`
+
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Largest archive, after gunzipping a `.tar.gz`, that will be loaded
+pub const MAX_ARCHIVE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Largest entry whose content is returned to the model
+pub const MAX_ENTRY_BYTES: u64 = 1024 * 1024;
+
+/// Entries listed before the listing is cut short
+pub const MAX_LISTED_ENTRIES: usize = 1000;
+
+const ZIP_END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP_CENTRAL_DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const TAR_BLOCK: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Archive format implied by the file name
+    pub fn from_path(path: &Path) -> Option<ArchiveKind> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if [".zip", ".jar", ".whl", ".war"].iter().any(|extension| name.ends_with(extension)) {
+            Some(ArchiveKind::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub name: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Where an entry's data lives inside the loaded archive
+struct Located {
+    entry: ArchiveEntry,
+    offset: usize,
+    compressed_size: usize,
+    /// Zip compression method; 0 means stored
+    method: u16,
+}
+
+fn malformed(kind: &str, reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("not a valid {} archive: {}", kind, reason))
+}
+
+fn load(path: &Path, kind: ArchiveKind) -> io::Result<Vec<u8>> {
+    let size = fs::metadata(path)?.len();
+    if size > MAX_ARCHIVE_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("archive is {} bytes, over the {} byte limit", size, MAX_ARCHIVE_BYTES)));
+    }
+    if kind != ArchiveKind::TarGz {
+        return fs::read(path);
+    }
+
+    let mut gzip = Command::new("gzip");
+    gzip.arg("-dc").arg(path);
+    capped_output(gzip, MAX_ARCHIVE_BYTES, "archive", "gzip")
+}
+
+/// Stdout of `command`, read no further than `limit` bytes so a decompression bomb cannot exhaust memory.
+/// The process is killed as soon as it writes past the limit.
+fn capped_output(mut command: Command, limit: u64, what: &str, kind: &str) -> io::Result<Vec<u8>> {
+    let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let mut stdout = Vec::new();
+    child.stdout.take().expect("stdout is piped").take(limit + 1).read_to_end(&mut stdout)?;
+    if stdout.len() as u64 > limit {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} expands past the {} byte limit", what, limit)));
+    }
+
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    if !child.wait()?.success() {
+        return Err(malformed(kind, stderr.trim()));
+    }
+    Ok(stdout)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn zip_entries(data: &[u8]) -> io::Result<Vec<Located>> {
+    // The end record sits in the last 22 bytes plus an optional comment of up to 64KiB
+    let search_start = data.len().saturating_sub(22 + u16::MAX as usize);
+    let end = (search_start..=data.len().saturating_sub(22))
+        .rev()
+        .find(|&offset| u32_at(data, offset) == Some(ZIP_END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| malformed("zip", "end of central directory not found"))?;
+    let count = u16_at(data, end + 10).ok_or_else(|| malformed("zip", "truncated end record"))?;
+    let mut offset = u32_at(data, end + 16).ok_or_else(|| malformed("zip", "truncated end record"))? as usize;
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let truncated = || malformed("zip", "truncated central directory");
+        if u32_at(data, offset) != Some(ZIP_CENTRAL_DIRECTORY_ENTRY) {
+            return Err(truncated());
+        }
+        let method = u16_at(data, offset + 10).ok_or_else(truncated)?;
+        let compressed_size = u32_at(data, offset + 20).ok_or_else(truncated)? as usize;
+        let size = u32_at(data, offset + 24).ok_or_else(truncated)? as u64;
+        let name_len = u16_at(data, offset + 28).ok_or_else(truncated)? as usize;
+        let extra_len = u16_at(data, offset + 30).ok_or_else(truncated)? as usize;
+        let comment_len = u16_at(data, offset + 32).ok_or_else(truncated)? as usize;
+        let local_header = u32_at(data, offset + 42).ok_or_else(truncated)? as usize;
+        let name = data.get(offset + 46..offset + 46 + name_len).ok_or_else(truncated)?;
+        let name = String::from_utf8_lossy(name).to_string();
+
+        entries.push(Located {
+            entry: ArchiveEntry { is_dir: name.ends_with('/'), name, size },
+            offset: local_header,
+            compressed_size,
+            method,
+        });
+        offset += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+/// Numeric tar header field: octal text, or base-256 when the high bit is set
+fn tar_number(field: &[u8]) -> Option<u64> {
+    if field.first().is_some_and(|byte| byte & 0x80 != 0) {
+        return Some(field[1..].iter().fold(0, |value, byte| (value << 8) | *byte as u64));
+    }
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+fn tar_string(field: &[u8]) -> String {
+    let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Sum of the header bytes with the checksum field itself counted as spaces
+fn tar_checksum(header: &[u8]) -> u64 {
+    header.iter().enumerate().map(|(i, byte)| if (148..156).contains(&i) { b' ' as u64 } else { *byte as u64 }).sum()
+}
+
+fn tar_entries(data: &[u8]) -> io::Result<Vec<Located>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    let mut long_name = None;
+
+    while offset < data.len() {
+        let header = data.get(offset..offset + TAR_BLOCK).ok_or_else(|| malformed("tar", "truncated header"))?;
+        if header.iter().all(|byte| *byte == 0) {
+            break;
+        }
+        if tar_number(&header[148..156]) != Some(tar_checksum(header)) {
+            return Err(malformed("tar", "header checksum mismatch"));
+        }
+        let size = tar_number(&header[124..136]).ok_or_else(|| malformed("tar", "unreadable entry size"))?;
+        let data_offset = offset + TAR_BLOCK;
+        let data_end = data_offset.checked_add(size as usize).filter(|end| *end <= data.len())
+            .ok_or_else(|| malformed("tar", "entry runs past the end of the archive"))?;
+        offset = data_offset + (size as usize).div_ceil(TAR_BLOCK) * TAR_BLOCK;
+
+        let type_flag = header[156];
+        // GNU long names arrive as a pseudo entry holding the name of the entry that follows
+        if type_flag == b'L' {
+            long_name = Some(tar_string(&data[data_offset..data_end]));
+            continue;
+        }
+        if matches!(type_flag, b'x' | b'g') {
+            continue;
+        }
+
+        let name = long_name.take().unwrap_or_else(|| {
+            let name = tar_string(&header[0..100]);
+            let prefix = tar_string(&header[345..500]);
+            if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                format!("{}/{}", prefix, name)
+            } else {
+                name
+            }
+        });
+        let is_dir = type_flag == b'5' || name.ends_with('/');
+        entries.push(Located {
+            entry: ArchiveEntry { name, size, is_dir },
+            offset: data_offset,
+            compressed_size: size as usize,
+            method: 0,
+        });
+    }
+    Ok(entries)
+}
+
+fn entries_of(data: &[u8], kind: ArchiveKind) -> io::Result<Vec<Located>> {
+    match kind {
+        ArchiveKind::Zip => zip_entries(data),
+        ArchiveKind::Tar | ArchiveKind::TarGz => tar_entries(data),
+    }
+}
+
+/// Entries of the archive at `path` in the order they are stored, without extracting anything
+pub fn list(path: &Path, kind: ArchiveKind) -> io::Result<Vec<ArchiveEntry>> {
+    let data = load(path, kind)?;
+    Ok(entries_of(&data, kind)?.into_iter().map(|located| located.entry).collect())
+}
+
+/// Text content of the entry called `name`. Binary and oversized entries are refused.
+pub fn read_entry(path: &Path, kind: ArchiveKind, name: &str) -> io::Result<String> {
+    let data = load(path, kind)?;
+    let located = entries_of(&data, kind)?
+        .into_iter()
+        .find(|located| located.entry.name == name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no entry named '{}' in the archive", name)))?;
+    if located.entry.is_dir {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is a directory", name)));
+    }
+    if located.entry.size > MAX_ENTRY_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+            "'{}' is {} bytes, over the {} byte limit for reading entries", name, located.entry.size, MAX_ENTRY_BYTES
+        )));
+    }
+
+    let bytes = match (kind, located.method) {
+        (ArchiveKind::Zip, 0) => {
+            let local = located.offset;
+            if u32_at(&data, local) != Some(ZIP_LOCAL_HEADER) {
+                return Err(malformed("zip", "missing local file header"));
+            }
+            let name_len = u16_at(&data, local + 26).unwrap_or_default() as usize;
+            let extra_len = u16_at(&data, local + 28).unwrap_or_default() as usize;
+            let start = local + 30 + name_len + extra_len;
+            data.get(start..start + located.compressed_size)
+                .ok_or_else(|| malformed("zip", "entry runs past the end of the archive"))?
+                .to_vec()
+        }
+        (ArchiveKind::Zip, _) => {
+            // The size in the central directory is only a claim, so the output is capped as well
+            let mut unzip = Command::new("unzip");
+            unzip.arg("-p").arg(path).arg(name);
+            capped_output(unzip, MAX_ENTRY_BYTES, &format!("'{}'", name), "zip").map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => io::Error::new(e.kind(), format!("unzip is needed to read compressed zip entries: {}", e)),
+                _ => e,
+            })?
+        }
+        _ => data[located.offset..located.offset + located.compressed_size].to_vec(),
+    };
+
+    String::from_utf8(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("'{}' is binary, only text entries can be read", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_header(name: &str, size: usize, type_flag: u8) -> Vec<u8> {
+        let mut header = vec![0u8; TAR_BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}\0", size);
+        header[124..136].copy_from_slice(size.as_bytes());
+        header[156] = type_flag;
+        let checksum = format!("{:06o}\0 ", tar_checksum(&header));
+        header[148..156].copy_from_slice(checksum.as_bytes());
+        header
+    }
+
+    #[test]
+    fn test_tar_entries_and_content() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut tar = tar_header("docs/", 0, b'5');
+        tar.extend(tar_header("docs/notes.txt", 5, b'0'));
+        let mut content = b"hello".to_vec();
+        content.resize(TAR_BLOCK, 0);
+        tar.extend(content);
+        tar.extend(vec![0u8; TAR_BLOCK * 2]);
+        let path = dir.path().join("bundle.tar");
+        fs::write(&path, &tar).unwrap();
+
+        assert_eq!(
+            list(&path, ArchiveKind::Tar).unwrap(),
+            vec![
+                ArchiveEntry { name: "docs/".to_string(), size: 0, is_dir: true },
+                ArchiveEntry { name: "docs/notes.txt".to_string(), size: 5, is_dir: false },
+            ]
+        );
+        assert_eq!(read_entry(&path, ArchiveKind::Tar, "docs/notes.txt").unwrap(), "hello");
+        assert_eq!(read_entry(&path, ArchiveKind::Tar, "missing").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    /// Single stored (uncompressed) entry, as written by `zip -0`
+    fn stored_zip(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut zip = ZIP_LOCAL_HEADER.to_le_bytes().to_vec();
+        zip.extend([0u8; 14]);
+        zip.extend((content.len() as u32).to_le_bytes());
+        zip.extend((content.len() as u32).to_le_bytes());
+        zip.extend((name.len() as u16).to_le_bytes());
+        zip.extend(0u16.to_le_bytes());
+        zip.extend(name.as_bytes());
+        zip.extend(content);
+
+        let central_directory = zip.len();
+        zip.extend(ZIP_CENTRAL_DIRECTORY_ENTRY.to_le_bytes());
+        zip.extend([0u8; 16]);
+        zip.extend((content.len() as u32).to_le_bytes());
+        zip.extend((content.len() as u32).to_le_bytes());
+        zip.extend((name.len() as u16).to_le_bytes());
+        zip.extend([0u8; 12]);
+        zip.extend(0u32.to_le_bytes());
+        zip.extend(name.as_bytes());
+        let central_directory_size = zip.len() - central_directory;
+
+        zip.extend(ZIP_END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        zip.extend([0u8; 4]);
+        zip.extend(1u16.to_le_bytes());
+        zip.extend(1u16.to_le_bytes());
+        zip.extend((central_directory_size as u32).to_le_bytes());
+        zip.extend((central_directory as u32).to_le_bytes());
+        zip.extend(0u16.to_le_bytes());
+        zip
+    }
+
+    #[test]
+    fn test_zip_entries_and_content() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("bundle.zip");
+        fs::write(&path, stored_zip("config/app.json", b"{\"debug\":true}")).unwrap();
+
+        assert_eq!(
+            list(&path, ArchiveKind::Zip).unwrap(),
+            vec![ArchiveEntry { name: "config/app.json".to_string(), size: 14, is_dir: false }]
+        );
+        assert_eq!(read_entry(&path, ArchiveKind::Zip, "config/app.json").unwrap(), "{\"debug\":true}");
+    }
+
+    #[test]
+    fn test_decompressor_output_is_capped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("bomb.tar.gz");
+        let mut gzip = Command::new("gzip").arg("-c").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        let mut input = gzip.stdin.take().unwrap();
+        let writer = std::thread::spawn(move || io::Write::write_all(&mut input, &vec![0u8; 64 * 1024]));
+        let compressed = gzip.wait_with_output().unwrap().stdout;
+        writer.join().unwrap().unwrap();
+        fs::write(&path, compressed).unwrap();
+
+        let mut gunzip = Command::new("gzip");
+        gunzip.arg("-dc").arg(&path);
+        let err = capped_output(gunzip, 1024, "archive", "gzip").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("expands past the 1024 byte limit"));
+
+        let mut gunzip = Command::new("gzip");
+        gunzip.arg("-dc").arg(&path);
+        assert_eq!(capped_output(gunzip, 64 * 1024, "archive", "gzip").unwrap().len(), 64 * 1024);
+    }
+
+    #[test]
+    fn test_malformed_archives_are_invalid_data() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("broken.zip");
+        fs::write(&path, b"definitely not a zip").unwrap();
+        assert_eq!(list(&path, ArchiveKind::Zip).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let path = dir.path().join("broken.tar");
+        fs::write(&path, tar_header("big.bin", 4096, b'0')).unwrap();
+        assert_eq!(list(&path, ArchiveKind::Tar).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::write(&path, [b'x'; TAR_BLOCK * 2]).unwrap();
+        assert_eq!(list(&path, ArchiveKind::Tar).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(ArchiveKind::from_path(Path::new("a/b.TGZ")), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::from_path(Path::new("notes.txt")), None);
+    }
+}
@@ -0,0 +1,224 @@
+//! Commands started with `run_command_background`, such as dev servers, polled with `check_process` and
+//! ended with `stop_process`. Each command leads its own process group so stopping it also stops whatever
+//! the shell started, and anything still running is killed when the session ends.
+
+use std::collections::BTreeMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::process::{Child, Command};
+use tokio::signal;
+use tokio::task::JoinHandle;
+
+/// Most commands running in the background at once
+pub const MAX_BACKGROUND_PROCESSES: usize = 8;
+
+/// Longest `check_process` waits for a command to exit
+pub const MAX_WAIT_SECS: u64 = 300;
+
+/// How long `run_command_background` watches a new command, so typos and busy ports show up in its result
+pub const STARTUP_CHECK: Duration = Duration::from_millis(500);
+
+/// Time a command gets to exit after SIGTERM before it is killed
+const STOP_GRACE: Duration = Duration::from_secs(5);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time allowed for the output readers to catch up once a command has exited
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+struct BackgroundProcess {
+    command: String,
+    child: Child,
+    started: Instant,
+    stdout: Arc<Mutex<String>>,
+    stderr: Arc<Mutex<String>>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl BackgroundProcess {
+    /// State of the command, with the output written since the previous report
+    fn report(&mut self, pid: u32, exit_code: Option<Option<i32>>) -> ProcessReport {
+        ProcessReport {
+            pid,
+            command: self.command.clone(),
+            running: exit_code.is_none(),
+            exit_code: exit_code.flatten(),
+            stdout: std::mem::take(&mut *lock(&self.stdout)),
+            stderr: std::mem::take(&mut *lock(&self.stderr)),
+            elapsed: self.started.elapsed(),
+        }
+    }
+
+    /// Give the readers a moment to collect the last output of an exited command
+    async fn drain(&mut self) {
+        for reader in self.readers.drain(..) {
+            // A grandchild may still hold the pipe open, so the reader is abandoned rather than awaited forever
+            let abort = reader.abort_handle();
+            if tokio::time::timeout(DRAIN_TIMEOUT, reader).await.is_err() {
+                abort.abort();
+            }
+        }
+    }
+}
+
+/// A background command as seen by `check_process` or `stop_process`
+#[derive(Debug)]
+pub struct ProcessReport {
+    pub pid: u32,
+    pub command: String,
+    pub running: bool,
+    /// `None` while running, and after exiting on a signal
+    pub exit_code: Option<i32>,
+    /// Output written since the previous report
+    pub stdout: String,
+    pub stderr: String,
+    pub elapsed: Duration,
+}
+
+/// Background commands of one session, by pid
+#[derive(Default)]
+pub struct ProcessTable {
+    processes: Mutex<BTreeMap<u32, BackgroundProcess>>,
+}
+
+impl ProcessTable {
+    fn processes(&self) -> MutexGuard<'_, BTreeMap<u32, BackgroundProcess>> {
+        lock(&self.processes)
+    }
+
+    /// Start `command` through `sh -c` in `working_dir` and return its pid
+    pub fn spawn(&self, command: &str, working_dir: &str) -> Result<u32, String> {
+        let mut processes = self.processes();
+        if processes.len() >= MAX_BACKGROUND_PROCESSES {
+            return Err(format!(
+                "{} background commands are already running; stop one with stop_process first",
+                MAX_BACKGROUND_PROCESSES
+            ));
+        }
+
+        let mut shell = Command::new("sh");
+        shell
+            .arg("-c")
+            .arg(command)
+            .current_dir(working_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        shell.process_group(0);
+
+        let mut child = shell.spawn().map_err(|e| format!("failed to start '{}': {}", command, e))?;
+        let pid = child.id().ok_or_else(|| format!("'{}' exited before it could be tracked", command))?;
+        let stdout = Arc::new(Mutex::new(String::new()));
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let readers = vec![
+            tokio::spawn(super::capture_lines(child.stdout.take(), stdout.clone(), None)),
+            tokio::spawn(super::capture_lines(child.stderr.take(), stderr.clone(), None)),
+        ];
+
+        processes.insert(pid, BackgroundProcess {
+            command: command.to_string(),
+            child,
+            started: Instant::now(),
+            stdout,
+            stderr,
+            readers,
+        });
+        Ok(pid)
+    }
+
+    /// Pids and commands of everything started this session that has not been reported as exited
+    pub fn list(&self) -> Vec<(u32, String)> {
+        self.processes().iter().map(|(pid, process)| (*pid, process.command.clone())).collect()
+    }
+
+    /// Report on `pid`, first waiting up to `wait` for it to exit (Ctrl+C ends the wait early).
+    /// An exited command is reported once and then forgotten. `None` if `pid` is not a background command.
+    pub async fn check(&self, pid: u32, wait: Duration) -> Option<ProcessReport> {
+        let deadline = Instant::now() + wait;
+        loop {
+            let exit_code = {
+                let mut processes = self.processes();
+                exit_code(&mut processes.get_mut(&pid)?.child)
+            };
+            if let Some(exit_code) = exit_code {
+                let mut process = self.processes().remove(&pid)?;
+                process.drain().await;
+                return Some(process.report(pid, Some(exit_code)));
+            }
+            if Instant::now() >= deadline {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = signal::ctrl_c() => break,
+            }
+        }
+        Some(self.processes().get_mut(&pid)?.report(pid, None))
+    }
+
+    /// Stop `pid` with SIGTERM, then SIGKILL if it is still running after a grace period
+    pub async fn stop(&self, pid: u32) -> Option<ProcessReport> {
+        let mut process = self.processes().remove(&pid)?;
+        let deadline = Instant::now() + STOP_GRACE;
+        let mut exit = exit_code(&mut process.child);
+        if exit.is_none() {
+            terminate(pid, &mut process.child, false);
+        }
+        while exit.is_none() && Instant::now() < deadline {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            exit = exit_code(&mut process.child);
+        }
+        if exit.is_none() {
+            terminate(pid, &mut process.child, true);
+            exit = Some(process.child.wait().await.ok().and_then(|status| status.code()));
+        }
+        process.drain().await;
+        Some(process.report(pid, exit))
+    }
+
+    /// Kill the process group of every command still running and forget them all, for when the session ends
+    /// without dropping the table, such as on SIGTERM or `--timeout`
+    pub fn kill_all(&self) {
+        let processes = std::mem::take(&mut *self.processes());
+        for (pid, mut process) in processes {
+            if exit_code(&mut process.child).is_none() {
+                terminate(pid, &mut process.child, true);
+            }
+        }
+    }
+}
+
+impl Drop for ProcessTable {
+    fn drop(&mut self) {
+        self.kill_all();
+    }
+}
+
+/// `Some(exit code)` once the command has exited
+fn exit_code(child: &mut Child) -> Option<Option<i32>> {
+    child.try_wait().ok().flatten().map(|status| status.code())
+}
+
+/// Signal the command's whole process group; the negative pid addresses the group it leads
+#[cfg(unix)]
+fn terminate(pid: u32, _child: &mut Child, force: bool) {
+    let _ = std::process::Command::new("kill")
+        .arg(if force { "-KILL" } else { "-TERM" })
+        .arg("--")
+        .arg(format!("-{}", pid))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32, child: &mut Child, _force: bool) {
+    let _ = child.start_kill();
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,60 @@
+use crate::openrouter::ToolCall;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Tools that only inspect the project, so repeating one with the same arguments gives the same result.
+/// Only these are coalesced; `check_process` is not among them because its answer changes as the process runs.
+pub fn is_read_only(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "read_file" | "list_directory" | "file_stats" | "query_context" | "project_commands" | "query_structured" | "inspect_archive"
+    )
+}
+
+/// Shares results between identical read-only calls (same tool and arguments) in one batch of tool calls,
+/// since weaker models sometimes repeat a call within a response. Every other call is executed each time it
+/// appears and empties the cache, as reads after it could differ.
+#[derive(Debug, Default)]
+pub struct CallCoalescer {
+    results: HashMap<(String, String), String>,
+    /// Calls answered from an earlier identical call instead of being executed
+    pub coalesced: usize,
+}
+
+impl CallCoalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Result of an identical read-only call made earlier in the batch
+    pub fn cached(&mut self, tool_call: &ToolCall) -> Option<String> {
+        if !is_read_only(&tool_call.function.name) {
+            return None;
+        }
+        let result = self.results.get(&key(tool_call)).cloned();
+        if result.is_some() {
+            self.coalesced += 1;
+        }
+        result
+    }
+
+    /// Remember a read-only call's result for reuse, or forget everything if the call may have changed the
+    /// project. Failed calls are not remembered, so a repeat of one is executed again.
+    pub fn record(&mut self, tool_call: &ToolCall, result: Option<&str>) {
+        if !is_read_only(&tool_call.function.name) {
+            self.results.clear();
+            return;
+        }
+        if let Some(result) = result {
+            self.results.entry(key(tool_call)).or_insert_with(|| result.to_string());
+        }
+    }
+}
+
+// Arguments are compared as parsed JSON so key order and whitespace do not matter
+fn key(tool_call: &ToolCall) -> (String, String) {
+    let arguments = serde_json::from_str::<Value>(&tool_call.function.arguments)
+        .map(|arguments| arguments.to_string())
+        .unwrap_or_else(|_| tool_call.function.arguments.clone());
+    (tool_call.function.name.clone(), arguments)
+}
@@ -0,0 +1,102 @@
+//! `tools.max_deletes_per_turn`: a model deleting more files in one turn than the limit is stopped until the
+//! user allows it, which they are asked once per turn
+
+use super::error::{ToolError, ToolErrorCategory};
+use crate::config::Config;
+use crate::openrouter::ToolCall;
+use crate::story::StoryLogger;
+use crate::theme::{self, ThemeRole};
+use serde_json::Value;
+use std::io::IsTerminal;
+
+/// Paths the `delete_file` calls among `tool_calls` would remove
+pub fn delete_targets(tool_calls: &[ToolCall]) -> Vec<String> {
+    tool_calls
+        .iter()
+        .filter(|call| call.function.name == "delete_file")
+        .map(|call| {
+            serde_json::from_str::<Value>(&call.function.arguments)
+                .ok()
+                .and_then(|args| args["path"].as_str().map(str::to_string))
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Error returned in place of a `delete_file` call held back by `tools.max_deletes_per_turn`
+pub fn bulk_delete_denied(path: &str, limit: usize) -> ToolError {
+    ToolError::new(
+        "delete_file",
+        ToolErrorCategory::PermissionDenied,
+        format!("deleting more than {} files in one turn was not confirmed (tools.max_deletes_per_turn); ask the user before deleting more", limit),
+    )
+    .with_target(path)
+}
+
+/// Deletions requested so far in the current turn and the user's answer once they were asked
+pub struct DeleteGuard {
+    deletes: usize,
+    allowed: Option<bool>,
+    /// Whether there is a terminal to ask on; without one, deletions past the limit are refused
+    interactive: bool,
+}
+
+impl Default for DeleteGuard {
+    fn default() -> Self {
+        Self { deletes: 0, allowed: None, interactive: std::io::stdin().is_terminal() }
+    }
+}
+
+impl DeleteGuard {
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    /// Forget the previous turn's deletions and answer
+    pub fn start_turn(&mut self) {
+        self.deletes = 0;
+        self.allowed = None;
+    }
+
+    /// Count a response's deletions against `tools.max_deletes_per_turn`, asking once per turn whether the model
+    /// may go past the limit. Returns whether this response's `delete_file` calls may run.
+    pub fn allow(&mut self, tool_calls: &[ToolCall], config: &Config, story_logger: &StoryLogger) -> bool {
+        let targets = delete_targets(tool_calls);
+        if targets.is_empty() {
+            return true;
+        }
+        self.deletes += targets.len();
+        let limit = config.tools.max_deletes_per_turn;
+        if limit == 0 || self.deletes <= limit {
+            return true;
+        }
+        if let Some(allowed) = self.allowed {
+            return allowed;
+        }
+
+        println!("{}", theme::paint(ThemeRole::Warning, &format!(
+            "🛡️ The model wants to delete {} files this turn, more than tools.max_deletes_per_turn ({})", self.deletes, limit
+        )));
+        for target in &targets {
+            println!("   - {}", target);
+        }
+        // Without a terminal there is nobody to ask, so the guard holds
+        let allowed = config.preferences.auto_confirm
+            || (self.interactive
+                && inquire::Confirm::new("Allow the model to keep deleting files this turn?")
+                    .with_default(false)
+                    .prompt()
+                    .unwrap_or(false));
+
+        story_logger.log_guard_triggered(&format!(
+            "Model requested {} deletions in one turn (tools.max_deletes_per_turn = {}): {}. Pending: {}",
+            self.deletes,
+            limit,
+            if allowed { "allowed" } else { "blocked" },
+            targets.join(", ")
+        ));
+        self.allowed = Some(allowed);
+        allowed
+    }
+}
@@ -0,0 +1,129 @@
+use serde_json::{json, Value};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Broad class of a tool failure, reported to the model so it can pick a recovery
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolErrorCategory {
+    NotFound,
+    PermissionDenied,
+    AlreadyExists,
+    InvalidArguments,
+    CommandFailed,
+    TooLarge,
+    /// The file changed on disk since the model read it
+    Conflict,
+    Io,
+}
+
+impl ToolErrorCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolErrorCategory::NotFound => "not_found",
+            ToolErrorCategory::PermissionDenied => "permission_denied",
+            ToolErrorCategory::AlreadyExists => "already_exists",
+            ToolErrorCategory::InvalidArguments => "invalid_arguments",
+            ToolErrorCategory::CommandFailed => "command_failed",
+            ToolErrorCategory::TooLarge => "too_large",
+            ToolErrorCategory::Conflict => "conflict",
+            ToolErrorCategory::Io => "io",
+        }
+    }
+
+    /// Whether the model can work around the failure itself, e.g. by creating a missing file or fixing
+    /// its arguments. Permission and unexpected IO failures need the user instead.
+    pub fn recoverable(&self) -> bool {
+        !matches!(self, ToolErrorCategory::PermissionDenied | ToolErrorCategory::Io)
+    }
+
+    fn from_io_kind(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::NotFound => ToolErrorCategory::NotFound,
+            io::ErrorKind::PermissionDenied => ToolErrorCategory::PermissionDenied,
+            io::ErrorKind::AlreadyExists => ToolErrorCategory::AlreadyExists,
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => ToolErrorCategory::InvalidArguments,
+            _ => ToolErrorCategory::Io,
+        }
+    }
+}
+
+/// A tool failure with enough context for the model to act on it
+#[derive(Debug, Clone)]
+pub struct ToolError {
+    pub tool: String,
+    /// Resolved path or command the tool was operating on
+    pub target: Option<String>,
+    pub category: ToolErrorCategory,
+    pub message: String,
+}
+
+impl ToolError {
+    pub fn new(tool: &str, category: ToolErrorCategory, message: impl Into<String>) -> Self {
+        Self {
+            tool: tool.to_string(),
+            target: None,
+            category,
+            message: message.into(),
+        }
+    }
+
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Wrap an IO error, describing what the tool was doing to which path
+    pub fn io(tool: &str, action: &str, path: &Path, error: io::Error) -> Self {
+        let category = ToolErrorCategory::from_io_kind(error.kind());
+        let summary = match category {
+            ToolErrorCategory::NotFound => "not found".to_string(),
+            ToolErrorCategory::PermissionDenied => "permission denied".to_string(),
+            ToolErrorCategory::AlreadyExists => "already exists".to_string(),
+            _ => "failed".to_string(),
+        };
+        let target = path.to_string_lossy().to_string();
+
+        Self::new(tool, category, format!("{} {} {}: {}", summary, action, target, error)).with_target(target)
+    }
+
+    pub fn missing_parameter(tool: &str, parameter: &str) -> Self {
+        Self::new(tool, ToolErrorCategory::InvalidArguments, format!("Missing '{}' parameter", parameter))
+    }
+
+    /// JSON error envelope sent back to the model as the tool result
+    pub fn to_json(&self) -> Value {
+        json!({
+            "status": "error",
+            "tool": self.tool,
+            "category": self.category.as_str(),
+            "recoverable": self.category.recoverable(),
+            "target": self.target,
+            "message": self.message
+        })
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+/// View any error returned by `execute_tool_call` as a `ToolError`, whose `to_json` is the envelope sent to the model
+pub fn classify(tool: &str, error: &(dyn std::error::Error + 'static)) -> ToolError {
+    if let Some(tool_error) = error.downcast_ref::<ToolError>() {
+        return tool_error.clone();
+    }
+
+    let category = if let Some(io_error) = error.downcast_ref::<io::Error>() {
+        ToolErrorCategory::from_io_kind(io_error.kind())
+    } else if error.is::<serde_json::Error>() {
+        ToolErrorCategory::InvalidArguments
+    } else {
+        ToolErrorCategory::Io
+    };
+    ToolError::new(tool, category, error.to_string())
+}
@@ -0,0 +1,177 @@
+//! Sizes of files for `file_stats`, so the model can tell what it can afford to read into context
+//! Paths may be globs: `*` and `?` match within one path component and `**` matches any number of them
+
+use crate::compaction::CHARS_PER_TOKEN;
+use crate::watcher::IGNORED_DIRS;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Files reported by one call before the rest are left out
+pub const MAX_MATCHED_FILES: usize = 500;
+
+/// Largest file whose lines are counted; bigger files only get a size-based token estimate
+const MAX_COUNTED_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Leading bytes checked for a NUL byte when deciding whether a file is binary
+const BINARY_PROBE_BYTES: usize = 8192;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct FileStats {
+    /// Relative to the working directory, with `/` separators
+    pub path: String,
+    pub bytes: u64,
+    /// `None` for binary files and files too large to count
+    pub lines: Option<usize>,
+    /// Estimated tokens the content would take up in context, `None` for binary files
+    pub tokens: Option<usize>,
+    pub binary: bool,
+}
+
+/// Whether `path` (relative, `/`-separated) matches the glob `pattern`
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty() && *part != ".").collect();
+    let path: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    match_components(&pattern, &path)
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                let pattern: Vec<char> = first.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                match_name(&pattern, &name) && match_components(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_name(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_name(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_name(rest, &name[1..]),
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Files under `root` matching `pattern`, a path or glob relative to `root`. A directory stands for every
+/// file below it. At most `limit` files are returned.
+pub fn matching_files(root: &Path, pattern: &str, limit: usize) -> io::Result<Vec<PathBuf>> {
+    let pattern = pattern.trim_start_matches("./");
+    if !is_glob(pattern) {
+        let path = root.join(pattern);
+        if !fs::metadata(&path)?.is_dir() {
+            return Ok(vec![path]);
+        }
+        let mut files = Vec::new();
+        walk(&path, &mut |file| push_limited(&mut files, file, limit))?;
+        return Ok(files);
+    }
+
+    // Only the directory named by the pattern's leading literal components needs walking
+    let literal: Vec<&str> = pattern.split('/').take_while(|part| !is_glob(part)).collect();
+    let start = root.join(literal.join("/"));
+    if !start.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    walk(&start, &mut |file| {
+        let relative = relative_path(root, file);
+        if glob_matches(pattern, &relative) {
+            push_limited(&mut files, file, limit)
+        } else {
+            true
+        }
+    })?;
+    Ok(files)
+}
+
+/// Add `file` unless `files` is full; returns whether to keep walking
+fn push_limited(files: &mut Vec<PathBuf>, file: &Path, limit: usize) -> bool {
+    if files.len() < limit {
+        files.push(file.to_path_buf());
+    }
+    files.len() < limit
+}
+
+/// Visit files below `dir` in name order, skipping `IGNORED_DIRS` and symlinked directories, until `visit`
+/// returns false
+fn walk(dir: &Path, visit: &mut dyn FnMut(&Path) -> bool) -> io::Result<bool> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if IGNORED_DIRS.iter().any(|ignored| entry.file_name() == *ignored) {
+                continue;
+            }
+            if !walk(&path, visit)? {
+                return Ok(false);
+            }
+        } else if path.is_file() && !visit(&path) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// `path` relative to `root` with `/` separators
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// Line count, size and token estimate of the file at `path`
+pub fn stats(root: &Path, path: &Path) -> io::Result<FileStats> {
+    let bytes = fs::metadata(path)?.len();
+    let mut probe = Vec::with_capacity(BINARY_PROBE_BYTES);
+    fs::File::open(path)?.take(BINARY_PROBE_BYTES as u64).read_to_end(&mut probe)?;
+    let binary = probe.contains(&0);
+
+    let lines = if binary || bytes > MAX_COUNTED_BYTES {
+        None
+    } else {
+        let content = fs::read(path)?;
+        let breaks = content.iter().filter(|byte| **byte == b'\n').count();
+        // A last line without a final newline still counts
+        Some(breaks + usize::from(content.last().is_some_and(|byte| *byte != b'\n')))
+    };
+
+    Ok(FileStats {
+        path: relative_path(root, path),
+        bytes,
+        lines,
+        tokens: (!binary).then(|| (bytes as usize).div_ceil(CHARS_PER_TOKEN)),
+        binary,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("src/*.rs", "src/main.rs"));
+        assert!(!glob_matches("src/*.rs", "src/tools/mod.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/main.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/tools/mod.rs"));
+        assert!(glob_matches("**/Cargo.toml", "Cargo.toml"));
+        assert!(glob_matches("./docs/?.md", "docs/a.md"));
+        assert!(!glob_matches("docs/?.md", "docs/ab.md"));
+        assert!(!glob_matches("*.rs", "src/main.rs"));
+    }
+}
@@ -0,0 +1,148 @@
+//! File templates for `create_from_template`, with `{{var}}` placeholders
+//! Templates are looked up in the project's `tools.template_dir`, then `<config dir>/loo/templates/`, then the built-ins
+
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Built-in templates as (name, content)
+pub const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "react_component",
+        "export interface {{name}}Props {\n  className?: string;\n}\n\nexport function {{name}}({ className }: {{name}}Props) {\n  return <div className={className}></div>;\n}\n\nexport default {{name}};\n",
+    ),
+    (
+        "rust_test",
+        "use {{crate_name}}::{{module}};\n\n#[test]\nfn test_{{module}}() {\n    todo!(\"test {{module}}\");\n}\n",
+    ),
+    (
+        "python_test",
+        "import pytest\n\nfrom {{module}} import *\n\n\ndef test_{{module}}():\n    pytest.skip(\"not written yet\")\n",
+    ),
+];
+
+/// Variable filled in from the target path when the caller does not set it
+pub const FILE_STEM_VARIABLE: &str = "file_stem";
+
+/// A template's content and where it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub content: String,
+    /// `project`, `user` or `builtin`
+    pub source: &'static str,
+}
+
+/// Directories searched before the built-ins, most specific first
+#[derive(Debug, Clone, Default)]
+pub struct TemplateDirs {
+    pub project: Option<PathBuf>,
+    pub user: Option<PathBuf>,
+}
+
+impl TemplateDirs {
+    fn searched(&self) -> impl Iterator<Item = (&Path, &'static str)> {
+        let project = self.project.as_deref().map(|dir| (dir, "project"));
+        let user = self.user.as_deref().map(|dir| (dir, "user"));
+        project.into_iter().chain(user)
+    }
+
+    /// Find `name`: a file of that name in a template directory, or a built-in.
+    /// Names with path separators are rejected so templates cannot be read from elsewhere.
+    pub fn find(&self, name: &str) -> Option<Template> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return None;
+        }
+        for (dir, source) in self.searched() {
+            if let Ok(content) = fs::read_to_string(dir.join(name)) {
+                return Some(Template { content, source });
+            }
+        }
+        BUILTIN_TEMPLATES
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, content)| Template { content: content.to_string(), source: "builtin" })
+    }
+
+    /// Names of every template that can be found, sorted
+    pub fn available(&self) -> Vec<String> {
+        let mut names: BTreeSet<String> = BUILTIN_TEMPLATES.iter().map(|(name, _)| name.to_string()).collect();
+        for (dir, _) in self.searched() {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten().filter(|entry| entry.path().is_file()) {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with('.') {
+                    names.insert(name);
+                }
+            }
+        }
+        names.into_iter().collect()
+    }
+}
+
+/// Substitute `{{var}}` (spaces inside the braces allowed) with `variables`; strings are inserted as-is and
+/// other JSON values in their JSON form. Returns the names of placeholders without a value when any are left.
+pub fn render(template: &str, variables: &Map<String, Value>) -> Result<String, Vec<String>> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut missing = BTreeSet::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}").map(|end| start + 2 + end) else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..end].trim();
+        match variables.get(name) {
+            Some(Value::String(value)) => rendered.push_str(value),
+            Some(value) => rendered.push_str(&value.to_string()),
+            None => {
+                missing.insert(name.to_string());
+            }
+        }
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+
+    if missing.is_empty() {
+        Ok(rendered)
+    } else {
+        Err(missing.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn variables(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_render_substitutes_and_reports_missing() {
+        let vars = variables(json!({"name": "Button", "count": 2}));
+        assert_eq!(render("<{{name}} n={{ count }}/>", &vars), Ok("<Button n=2/>".to_string()));
+        assert_eq!(render("{{name}} {{kind}} {{other}} {{kind}}", &vars), Err(vec!["kind".to_string(), "other".to_string()]));
+        assert_eq!(render("no placeholders {{ unclosed", &vars), Ok("no placeholders {{ unclosed".to_string()));
+    }
+
+    #[test]
+    fn test_find_prefers_project_then_user_templates() {
+        let project = tempfile::TempDir::new().unwrap();
+        let user = tempfile::TempDir::new().unwrap();
+        fs::write(project.path().join("rust_test"), "ours").unwrap();
+        fs::write(user.path().join("rust_test"), "mine").unwrap();
+        fs::write(user.path().join("handler.rs"), "fn {{name}}() {}").unwrap();
+        let dirs = TemplateDirs { project: Some(project.path().to_path_buf()), user: Some(user.path().to_path_buf()) };
+
+        assert_eq!(dirs.find("rust_test").unwrap().source, "project");
+        assert_eq!(dirs.find("handler.rs").unwrap().source, "user");
+        assert_eq!(dirs.find("react_component").unwrap().source, "builtin");
+        assert!(dirs.find("../handler.rs").is_none());
+        assert!(dirs.available().contains(&"handler.rs".to_string()));
+        assert!(TemplateDirs::default().available().contains(&"python_test".to_string()));
+    }
+}
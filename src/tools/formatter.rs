@@ -0,0 +1,167 @@
+//! Formatting of files written by `create_file` and `write_file` (`tools.format_on_write`)
+//! The formatter is picked by extension and given the content on stdin, so only the written file changes
+//! (rustfmt run on a path would also reformat the `mod` files it declares). Files are left as written when
+//! the formatter is not installed, fails or times out.
+
+use crate::diff::{diff_stats, unified_diff};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest formatter error kept in a tool result
+const MAX_ERROR_CHARS: usize = 300;
+
+/// How long a formatter may run before it is killed and the file kept as written
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Formatter command for a file extension, reading the source on stdin and writing the result to stdout
+struct Formatter {
+    program: &'static str,
+    args: &'static [&'static str],
+    /// Flag naming the file being formatted, so the formatter can pick its parser and config
+    path_flag: Option<&'static str>,
+}
+
+fn formatter_for(path: &Path) -> Option<Formatter> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    let formatter = match extension.as_str() {
+        // rustfmt defaults to the 2015 edition, which rejects async code; rustfmt.toml still applies
+        "rs" => Formatter { program: "rustfmt", args: &["--edition", "2021", "--emit", "stdout"], path_flag: None },
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "css" | "scss" | "html" | "vue" => {
+            Formatter { program: "prettier", args: &["--log-level", "warn"], path_flag: Some("--stdin-filepath") }
+        }
+        "py" => Formatter { program: "black", args: &["--quiet", "-"], path_flag: Some("--stdin-filename") },
+        "go" => Formatter { program: "gofmt", args: &[], path_flag: None },
+        _ => return None,
+    };
+    Some(formatter)
+}
+
+/// What formatting did to a written file, reported in the tool result
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct FormatReport {
+    pub formatter: &'static str,
+    /// `formatted`, `unchanged`, `not_installed` or `failed`
+    pub outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_added: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_removed: Option<usize>,
+    /// The formatter's complaint, e.g. a syntax error, when it failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What a formatter run ended with
+enum RunOutcome {
+    Formatted(String),
+    Failed(String),
+    TimedOut,
+}
+
+/// Feed `input` to `command` and collect its stdout, killing it once `timeout` has passed
+fn run_with_timeout(mut command: Command, input: &str, timeout: Duration) -> io::Result<RunOutcome> {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    // Pipes are drained on their own threads so a formatter blocked on a full pipe cannot stall the deadline
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let stdout_reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stderr_reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stderr.read_to_end(&mut output);
+        output
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(RunOutcome::TimedOut);
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    let _ = writer.join();
+    let stdout = stdout_reader.join().unwrap_or_else(|_| Ok(Vec::new()))?;
+    let stderr = stderr_reader.join().unwrap_or_default();
+    if !status.success() {
+        return Ok(RunOutcome::Failed(String::from_utf8_lossy(&stderr).trim().chars().take(MAX_ERROR_CHARS).collect()));
+    }
+    match String::from_utf8(stdout) {
+        Ok(formatted) => Ok(RunOutcome::Formatted(formatted)),
+        Err(_) => Ok(RunOutcome::Failed("formatter output is not UTF-8".to_string())),
+    }
+}
+
+/// Format `written`, the content just written to `path`, and write the result back. `None` when no
+/// formatter handles the file type. Returns the report and the file's new content when the formatter changed it.
+pub fn format_file(path: &Path, written: &str) -> Option<(FormatReport, Option<String>)> {
+    let formatter = formatter_for(path)?;
+    let report = |outcome, error| FormatReport { formatter: formatter.program, outcome, lines_added: None, lines_removed: None, error };
+
+    let mut command = Command::new(formatter.program);
+    command.args(formatter.args).current_dir(path.parent().unwrap_or(Path::new(".")));
+    if let Some(flag) = formatter.path_flag {
+        command.arg(flag).arg(path);
+    }
+    let formatted = match run_with_timeout(command, written, FORMAT_TIMEOUT) {
+        Ok(RunOutcome::Formatted(formatted)) => formatted,
+        Ok(RunOutcome::Failed(error)) => return Some((report("failed", Some(error)), None)),
+        Ok(RunOutcome::TimedOut) => {
+            return Some((report("failed", Some(format!("timed out after {}s", FORMAT_TIMEOUT.as_secs()))), None))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Some((report("not_installed", None), None)),
+        Err(e) => return Some((report("failed", Some(e.to_string())), None)),
+    };
+    if formatted == written {
+        return Some((report("unchanged", None), None));
+    }
+    if let Err(e) = fs::write(path, &formatted) {
+        return Some((report("failed", Some(e.to_string())), None));
+    }
+    // Changes to line endings alone show up as a formatted file with no lines changed
+    let (added, removed) = unified_diff(written, &formatted, 0).map(|diff| diff_stats(&diff)).unwrap_or((0, 0));
+    let report = FormatReport { lines_added: Some(added), lines_removed: Some(removed), ..report("formatted", None) };
+    Some((report, Some(formatted)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatter_by_extension() {
+        assert_eq!(formatter_for(Path::new("src/main.rs")).map(|f| f.program), Some("rustfmt"));
+        assert_eq!(formatter_for(Path::new("web/App.TSX")).map(|f| f.program), Some("prettier"));
+        assert_eq!(formatter_for(Path::new("tool.py")).map(|f| f.program), Some("black"));
+        assert!(formatter_for(Path::new("README.md")).is_none());
+        assert!(formatter_for(Path::new("Makefile")).is_none());
+    }
+
+    #[test]
+    fn test_formatter_is_killed_after_timeout() {
+        let mut sleep = Command::new("sleep");
+        sleep.arg("5");
+        let started = Instant::now();
+        assert!(matches!(run_with_timeout(sleep, "", Duration::from_millis(100)), Ok(RunOutcome::TimedOut)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let cat = Command::new("cat");
+        assert!(matches!(run_with_timeout(cat, "as written", FORMAT_TIMEOUT), Ok(RunOutcome::Formatted(out)) if out == "as written"));
+    }
+}
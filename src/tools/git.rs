@@ -0,0 +1,109 @@
+use std::process::Command;
+
+/// Branches treated as protected when the config does not list any
+pub fn default_protected_branches() -> Vec<String> {
+    vec!["main".to_string(), "master".to_string()]
+}
+
+/// Name of the branch checked out in `working_dir`, or `None` outside a repo or on a detached HEAD
+pub fn current_branch(working_dir: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--short", "-q", "HEAD"])
+        .current_dir(working_dir)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if branch.is_empty() {
+        return None;
+    }
+    Some(branch)
+}
+
+/// Run `git init` in `dir` unless it is already inside a work tree. Returns whether a repository was created.
+pub fn init_unless_tracked(dir: &std::path::Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let inside = Command::new("git")
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("git is not available: {}", e))?;
+    if inside.status.success() {
+        return Ok(false);
+    }
+
+    let output = Command::new("git").args(["init", "--quiet"]).current_dir(dir).output()?;
+    if !output.status.success() {
+        return Err(format!("git init failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(true)
+}
+
+/// Diff of `path` against HEAD, or `None` outside a git repository.
+/// Untracked files are diffed against an empty file so new files show their full content.
+pub fn diff_against_head(working_dir: &str, path: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["diff", "--no-color", "HEAD", "--", path])
+        .current_dir(working_dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let diff = String::from_utf8_lossy(&output.stdout).to_string();
+    if !diff.is_empty() || !std::path::Path::new(working_dir).join(path).exists() {
+        return Some(diff);
+    }
+
+    // `--no-index` exits with 1 when the files differ, so only the output matters
+    let output = Command::new("git")
+        .args(["diff", "--no-color", "--no-index", "--", "/dev/null", path])
+        .current_dir(working_dir)
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// The first branch-modifying git operation (`commit` or `add`) in a shell command line
+pub fn guarded_git_operation(command: &str) -> Option<&'static str> {
+    command
+        .split(['&', '|', ';', '\n'])
+        .filter_map(|segment| {
+            let mut words = segment.split_whitespace();
+            if words.next()? != "git" {
+                return None;
+            }
+            // Skip global options such as `-C dir` or `--no-pager`
+            let mut subcommand = words.next()?;
+            while subcommand.starts_with('-') {
+                if subcommand == "-C" || subcommand == "-c" {
+                    words.next()?;
+                }
+                subcommand = words.next()?;
+            }
+            match subcommand {
+                "commit" => Some("commit"),
+                "add" => Some("add"),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guarded_git_operation() {
+        assert_eq!(guarded_git_operation("git commit -m 'fix'"), Some("commit"));
+        assert_eq!(guarded_git_operation("cargo fmt && git add -A"), Some("add"));
+        assert_eq!(guarded_git_operation("git -C repo --no-pager commit"), Some("commit"));
+        assert_eq!(guarded_git_operation("git status; git log"), None);
+        assert_eq!(guarded_git_operation("echo git commit"), None);
+    }
+}
@@ -1,20 +1,359 @@
+pub mod approval;
+pub mod archive;
+pub mod background;
+pub mod batch;
+pub mod deletes;
+pub mod error;
+pub mod file_stats;
+pub mod file_templates;
+pub mod formatter;
+pub mod git;
+pub mod newlines;
+pub mod project_commands;
+pub mod registry;
+pub mod scaffold;
+pub mod structured;
+
 use crate::openrouter::ToolCall;
+use crate::redaction::{redact_tool_output, REDACTED};
+use crate::theme::{self, ThemeRole};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use error::{ToolError, ToolErrorCategory};
+use newlines::{LineEnding, TrailingNewline};
 use tokio::signal;
 use tokio::process::Command as TokioCommand;
 use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::Instrument;
+
+/// Default cap on the content a single `create_file`/`write_file` call may write
+pub const DEFAULT_MAX_WRITE_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Default number of `run_command` output lines kept per stream
+pub const DEFAULT_MAX_OUTPUT_LINES: usize = 400;
 
 pub struct ToolExecutor {
     working_dir: String,
     verbose: bool,
+    /// Branches where `git commit`/`git add` need confirmation
+    protected_branches: Vec<String>,
+    auto_confirm: bool,
+    /// Whether there is a terminal to ask on; without one, anything that needs confirming is refused
+    interactive: bool,
+    /// Ask before running a command whose pattern has not been approved this session
+    confirm_commands: bool,
+    approvals: approval::CommandApprovals,
+    max_write_bytes: u64,
+    /// Mask secrets in tool results before they reach the model and the story
+    redact_secrets: bool,
+    /// Lines of `run_command` stdout/stderr kept in the result, split between head and tail (0 = unlimited)
+    max_output_lines: usize,
+    /// Uncapped stdout/stderr of the last `run_command` whose output was capped
+    full_output: Mutex<Option<String>>,
+    /// Directory whose `<type>/` subdirectories replace the built-in `scaffold_project` templates
+    scaffold_dir: Option<PathBuf>,
+    /// `create_from_template` template directories: the project's, relative to the working directory, and the user's
+    template_dir: Option<String>,
+    user_template_dir: Option<PathBuf>,
+    /// Commands started with `run_command_background`, killed when the executor is dropped or on shutdown
+    background: Arc<background::ProcessTable>,
+    /// Line endings and final newline of content written by `create_file`/`write_file`
+    line_ending: LineEnding,
+    trailing_newline: TrailingNewline,
+    /// Run rustfmt, prettier, black or gofmt on files written by `create_file`/`write_file`/`create_from_template`
+    format_on_write: bool,
+    /// Content hash of each file as the model last saw it, by read or by its own write, to catch
+    /// writes that would overwrite changes made on disk in between
+    known_content: Mutex<HashMap<PathBuf, u64>>,
 }
 
 impl ToolExecutor {
     pub fn new(working_dir: String, verbose: bool) -> Self {
-        Self { working_dir, verbose }
+        Self {
+            working_dir,
+            verbose,
+            protected_branches: git::default_protected_branches(),
+            auto_confirm: false,
+            interactive: std::io::stdin().is_terminal(),
+            confirm_commands: false,
+            approvals: approval::CommandApprovals::default(),
+            max_write_bytes: DEFAULT_MAX_WRITE_BYTES,
+            redact_secrets: true,
+            max_output_lines: DEFAULT_MAX_OUTPUT_LINES,
+            full_output: Mutex::new(None),
+            scaffold_dir: None,
+            template_dir: None,
+            user_template_dir: None,
+            background: Arc::new(background::ProcessTable::default()),
+            line_ending: LineEnding::default(),
+            trailing_newline: TrailingNewline::default(),
+            format_on_write: false,
+            known_content: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_line_endings(mut self, line_ending: LineEnding, trailing_newline: TrailingNewline) -> Self {
+        self.line_ending = line_ending;
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    pub fn with_format_on_write(mut self, format_on_write: bool) -> Self {
+        self.format_on_write = format_on_write;
+        self
+    }
+
+    pub fn with_scaffold_dir(mut self, scaffold_dir: Option<PathBuf>) -> Self {
+        self.scaffold_dir = scaffold_dir;
+        self
+    }
+
+    pub fn with_template_dirs(mut self, template_dir: Option<String>, user_template_dir: Option<PathBuf>) -> Self {
+        self.template_dir = template_dir;
+        self.user_template_dir = user_template_dir;
+        self
+    }
+
+    /// Point every tool at a new directory, e.g. after `/cd`
+    pub fn set_working_dir(&mut self, working_dir: String) {
+        self.working_dir = working_dir;
+    }
+
+    pub fn with_redaction(mut self, redact_secrets: bool) -> Self {
+        self.redact_secrets = redact_secrets;
+        self
+    }
+
+    pub fn with_max_write_bytes(mut self, max_write_bytes: u64) -> Self {
+        self.max_write_bytes = max_write_bytes;
+        self
+    }
+
+    pub fn with_max_output_lines(mut self, max_output_lines: usize) -> Self {
+        self.max_output_lines = max_output_lines;
+        self
+    }
+
+    /// Commands started with `run_command_background`, for the shutdown handler to kill on an early exit
+    pub fn background_processes(&self) -> Arc<background::ProcessTable> {
+        Arc::clone(&self.background)
+    }
+
+    /// Complete output of the last tool call if the copy in its result was capped, for the verbose story
+    pub fn take_full_output(&self) -> Option<String> {
+        self.full_output.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take()
+    }
+
+    /// Reject writes larger than `tools.max_write_bytes` before touching the disk
+    fn check_write_size(&self, tool: &str, path: &Path, content: &str) -> Result<(), ToolError> {
+        let size = content.len() as u64;
+        if size <= self.max_write_bytes {
+            return Ok(());
+        }
+
+        Err(ToolError::new(
+            tool,
+            ToolErrorCategory::TooLarge,
+            format!("refusing to write {} bytes to {}: exceeds the {} byte limit (tools.max_write_bytes)", size, path.display(), self.max_write_bytes),
+        )
+        .with_target(path.to_string_lossy()))
+    }
+
+    /// Refuse content carrying the redaction placeholder, which would replace real secrets the model never saw
+    fn check_not_redacted(&self, tool: &str, path: &Path, content: &str) -> Result<(), ToolError> {
+        if !self.redact_secrets || !content.contains(REDACTED) {
+            return Ok(());
+        }
+
+        Err(ToolError::new(
+            tool,
+            ToolErrorCategory::InvalidArguments,
+            format!(
+                "refusing to write {} to {}: secrets were redacted from what you read, so write the file without the placeholder or leave those lines unchanged",
+                REDACTED,
+                path.display()
+            ),
+        )
+        .with_target(path.to_string_lossy()))
+    }
+
+    /// Remember `content` as what the model last saw of `path`
+    fn remember_content(&self, path: &Path, content: &[u8]) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.known_content.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(path, content_hash(content));
+    }
+
+    /// Refuse to overwrite a file that changed on disk since the model read or wrote it, unless the user
+    /// confirms. Files the model has not seen this session are not checked.
+    fn check_unchanged_on_disk(&self, tool: &str, path: &Path) -> Result<(), ToolError> {
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let Some(known) = self.known_content.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get(&key).copied() else {
+            return Ok(());
+        };
+        // A file deleted in the meantime has no changes to lose
+        let Ok(current) = fs::read(path) else {
+            return Ok(());
+        };
+        if content_hash(&current) == known {
+            return Ok(());
+        }
+
+        println!("{}", theme::paint(ThemeRole::Warning, &format!("  ⚠️ {} changed on disk since it was last read", path.display())));
+        if self.auto_confirm {
+            return Ok(());
+        }
+        let conflict = || {
+            ToolError::new(
+                tool,
+                ToolErrorCategory::Conflict,
+                format!("{} changed on disk since it was last read; read it again and reapply the change", path.display()),
+            )
+            .with_target(path.to_string_lossy())
+        };
+
+        // Without a terminal there is nobody to ask, so the external changes are kept
+        if !self.interactive {
+            return Err(conflict());
+        }
+
+        let confirmed = inquire::Confirm::new(&format!("Overwrite the changes made to {} outside loo?", path.display()))
+            .with_default(false)
+            .with_help_message("The model has not seen these changes")
+            .prompt()
+            .unwrap_or(false);
+
+        if confirmed {
+            Ok(())
+        } else {
+            Err(conflict())
+        }
+    }
+
+    /// Configure which branches are protected and whether guarded operations are auto-confirmed
+    pub fn with_git_guard(mut self, protected_branches: Vec<String>, auto_confirm: bool) -> Self {
+        self.protected_branches = protected_branches;
+        self.auto_confirm = auto_confirm;
+        self
+    }
+
+    /// Whether confirmations can be asked for; defaults to whether stdin is a terminal
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    /// Confirm each new command pattern before `run_command` runs it (`tools.confirm_commands`); skipped when
+    /// guarded operations are auto-confirmed
+    pub fn with_command_approval(mut self, confirm_commands: bool) -> Self {
+        self.confirm_commands = confirm_commands;
+        self
+    }
+
+    /// Command patterns the user approved since the last call, for the story
+    pub fn take_new_approvals(&self) -> Vec<String> {
+        self.approvals.take_new()
+    }
+
+    /// `path` relative to the working directory with symlinks resolved, refused unless it stays inside the
+    /// working directory
+    fn resolve_inside_working_dir(&self, tool: &str, path: &str) -> Result<PathBuf, ToolError> {
+        let full_path = Path::new(&self.working_dir).join(path);
+        let resolved = full_path.canonicalize().map_err(|e| ToolError::io(tool, "resolving", &full_path, e))?;
+        let root = Path::new(&self.working_dir).canonicalize().map_err(|e| ToolError::io(tool, "resolving", Path::new(&self.working_dir), e))?;
+        if !resolved.starts_with(&root) {
+            return Err(outside_working_dir(tool).with_target(path));
+        }
+        Ok(resolved)
+    }
+
+    /// Refuse a command with patterns the user has not approved this session unless they approve them now
+    fn check_command_approved(&self, tool: &str, command: &str) -> Result<(), ToolError> {
+        if !self.confirm_commands || self.auto_confirm {
+            return Ok(());
+        }
+        let unapproved = self.approvals.unapproved(command);
+        if unapproved.is_empty() {
+            return Ok(());
+        }
+
+        let denied = |reason: &str| {
+            ToolError::new(tool, ToolErrorCategory::PermissionDenied, format!("'{}' was not run: {}", command, reason))
+                .with_target(command)
+        };
+
+        // Without a terminal there is nobody to ask, so only approved commands run
+        if !self.interactive {
+            return Err(denied(
+                "there is no terminal to approve it (set tools.confirm_commands to false or preferences.auto_confirm to true)",
+            ));
+        }
+
+        let nested = approval::runs_nested_commands(command);
+        let help = if nested {
+            "It runs nested commands or subshells, so it is confirmed every time".to_string()
+        } else {
+            let patterns = unapproved.iter().map(|pattern| approval::describe_pattern(pattern)).collect::<Vec<_>>().join(", ");
+            format!("Yes also allows {} for the rest of the session", patterns)
+        };
+        let confirmed = inquire::Confirm::new(&format!("Run '{}'?", command))
+            .with_default(false)
+            .with_help_message(&help)
+            .prompt()
+            .unwrap_or(false);
+
+        if confirmed {
+            if !nested {
+                self.approvals.approve(&unapproved);
+            }
+            Ok(())
+        } else {
+            Err(denied("the user declined it"))
+        }
+    }
+
+    /// Refuse `git commit`/`git add` on a protected branch unless the user confirms it
+    fn check_protected_branch(&self, command: &str) -> Result<(), ToolError> {
+        let Some(operation) = git::guarded_git_operation(command) else {
+            return Ok(());
+        };
+        let Some(branch) = git::current_branch(&self.working_dir) else {
+            return Ok(());
+        };
+        if !self.protected_branches.contains(&branch) || self.auto_confirm {
+            return Ok(());
+        }
+
+        let denied = || {
+            ToolError::new(
+                "run_command",
+                ToolErrorCategory::PermissionDenied,
+                format!("git {} on protected branch '{}' was not confirmed; create a feature branch first", operation, branch),
+            )
+            .with_target(command)
+        };
+
+        // Without a terminal there is nobody to ask, so the guard holds
+        if !self.interactive {
+            return Err(denied());
+        }
+
+        let confirmed = inquire::Confirm::new(&format!("Run 'git {}' on protected branch '{}'?", operation, branch))
+            .with_default(false)
+            .with_help_message(command)
+            .prompt()
+            .unwrap_or(false);
+
+        if confirmed {
+            Ok(())
+        } else {
+            Err(denied())
+        }
     }
 
     pub async fn execute_tool_call(
@@ -22,77 +361,136 @@ impl ToolExecutor {
         tool_call: &ToolCall,
     ) -> Result<String, Box<dyn std::error::Error>> {
         let args: Value = serde_json::from_str(&tool_call.function.arguments)?;
+        self.take_full_output();
         
         if self.verbose {
             println!("  🔧 Executing: {}", tool_call.function.name);
             println!("     Args: {}", tool_call.function.arguments);
         }
 
-        match tool_call.function.name.as_str() {
-            "create_file" => self.handle_create_file(&args),
-            "read_file" => self.handle_read_file(&args),
-            "write_file" => self.handle_write_file(&args),
-            "delete_file" => self.handle_delete_file(&args),
-            "create_directory" => self.handle_create_directory(&args),
-            "list_directory" => self.handle_list_directory(&args),
-            "run_command" => self.handle_run_command(&args).await,
-            "query_context" => self.handle_query_context(&args),
-            "complete" => self.handle_complete(),
-            _ => Ok(json!({"status": "error", "message": format!("Unknown tool: {}", tool_call.function.name)}).to_string()),
+        let span = tracing::info_span!("tool", name = %tool_call.function.name, id = %tool_call.id);
+        tracing::debug!(tool = %tool_call.function.name, arguments = %tool_call.function.arguments, "executing tool");
+        let result = match registry::find_tool(&tool_call.function.name) {
+            Some(tool) => tool.execute(self, &args).instrument(span).await?,
+            None => {
+                tracing::warn!(tool = %tool_call.function.name, "model called an unknown tool");
+                json!({"status": "error", "message": format!("Unknown tool: {}", tool_call.function.name)}).to_string()
+            }
+        };
+        tracing::debug!(tool = %tool_call.function.name, bytes = result.len(), "tool finished");
+
+        if !self.redact_secrets {
+            return Ok(result);
+        }
+
+        let mut full_output = self.full_output.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(output) = full_output.as_mut() {
+            *output = redact_tool_output(output).0;
         }
+        drop(full_output);
+
+        let (redacted, count) = redact_tool_output(&result);
+        if count > 0 {
+            println!("{}", theme::paint(ThemeRole::Warning, &format!(
+                "  🔒 Redacted {} secret(s) from {} output", count, tool_call.function.name
+            )));
+        }
+        Ok(redacted)
     }
 
     fn handle_create_file(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
-        let path = args["path"].as_str().ok_or("Missing 'path' parameter")?;
-        let content = args["content"].as_str().unwrap_or("");
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("create_file", "path"))?;
         let full_path = Path::new(&self.working_dir).join(path);
+        let content = self.normalized_content(&full_path, args["content"].as_str().unwrap_or(""));
+        self.check_write_size("create_file", &full_path, &content)?;
+        self.check_not_redacted("create_file", &full_path, &content)?;
+        self.check_unchanged_on_disk("create_file", &full_path)?;
 
         if let Some(parent) = full_path.parent() {
-            fs::create_dir_all(parent)?;
+            fs::create_dir_all(parent).map_err(|e| ToolError::io("create_file", "creating directory", parent, e))?;
         }
 
-        fs::write(&full_path, content)?;
-        Ok(json!({
+        fs::write(&full_path, &content).map_err(|e| ToolError::io("create_file", "writing", &full_path, e))?;
+        let (content, formatting) = self.format_written(&full_path, content);
+        self.remember_content(&full_path, content.as_bytes());
+        let mut result = json!({
             "status": "success",
             "path": path,
             "size": content.len(),
             "absolute_path": full_path.to_string_lossy()
-        }).to_string())
+        });
+        if let Some(formatting) = formatting {
+            result["formatting"] = json!(formatting);
+        }
+        Ok(result.to_string())
+    }
+
+    /// Apply `tools.format_on_write` to a file just written with `content`, returning what is now on disk and
+    /// the formatter's report
+    fn format_written(&self, full_path: &Path, content: String) -> (String, Option<formatter::FormatReport>) {
+        if !self.format_on_write {
+            return (content, None);
+        }
+        match formatter::format_file(full_path, &content) {
+            Some((report, Some(formatted))) => (formatted, Some(report)),
+            Some((report, None)) => (content, Some(report)),
+            None => (content, None),
+        }
+    }
+
+    /// `content` as it will be written to `full_path`, following `tools.line_ending` and `tools.trailing_newline`
+    fn normalized_content(&self, full_path: &Path, content: &str) -> String {
+        let existing = match self.line_ending {
+            LineEnding::Auto => fs::read_to_string(full_path).ok(),
+            _ => None,
+        };
+        newlines::normalize(content, existing.as_deref(), self.line_ending, self.trailing_newline)
     }
 
     fn handle_read_file(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
-        let path = args["path"].as_str().ok_or("Missing 'path' parameter")?;
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("read_file", "path"))?;
         let full_path = Path::new(&self.working_dir).join(path);
-        let content = fs::read_to_string(&full_path)?;
-        
+        let content = fs::read_to_string(&full_path).map_err(|e| ToolError::io("read_file", "reading", &full_path, e))?;
+        self.remember_content(&full_path, content.as_bytes());
+
         Ok(json!({
             "status": "success",
             "path": path,
             "content": content,
             "size": content.len(),
+            "mode": fs::metadata(&full_path).ok().as_ref().and_then(file_mode),
             "absolute_path": full_path.to_string_lossy()
         }).to_string())
     }
 
     fn handle_write_file(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
-        let path = args["path"].as_str().ok_or("Missing 'path' parameter")?;
-        let content = args["content"].as_str().unwrap_or("");
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("write_file", "path"))?;
         let full_path = Path::new(&self.working_dir).join(path);
+        let content = self.normalized_content(&full_path, args["content"].as_str().unwrap_or(""));
+        self.check_write_size("write_file", &full_path, &content)?;
+        self.check_not_redacted("write_file", &full_path, &content)?;
+        self.check_unchanged_on_disk("write_file", &full_path)?;
 
-        fs::write(&full_path, content)?;
-        Ok(json!({
+        fs::write(&full_path, &content).map_err(|e| ToolError::io("write_file", "writing", &full_path, e))?;
+        let (content, formatting) = self.format_written(&full_path, content);
+        self.remember_content(&full_path, content.as_bytes());
+        let mut result = json!({
             "status": "success",
             "path": path,
             "size": content.len(),
             "absolute_path": full_path.to_string_lossy()
-        }).to_string())
+        });
+        if let Some(formatting) = formatting {
+            result["formatting"] = json!(formatting);
+        }
+        Ok(result.to_string())
     }
 
     fn handle_delete_file(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
-        let path = args["path"].as_str().ok_or("Missing 'path' parameter")?;
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("delete_file", "path"))?;
         let full_path = Path::new(&self.working_dir).join(path);
 
-        fs::remove_file(&full_path)?;
+        fs::remove_file(&full_path).map_err(|e| ToolError::io("delete_file", "deleting", &full_path, e))?;
         Ok(json!({
             "status": "success",
             "path": path,
@@ -102,10 +500,10 @@ impl ToolExecutor {
     }
 
     fn handle_create_directory(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
-        let path = args["path"].as_str().ok_or("Missing 'path' parameter")?;
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("create_directory", "path"))?;
         let full_path = Path::new(&self.working_dir).join(path);
 
-        fs::create_dir_all(&full_path)?;
+        fs::create_dir_all(&full_path).map_err(|e| ToolError::io("create_directory", "creating", &full_path, e))?;
         Ok(json!({
             "status": "success",
             "path": path,
@@ -118,7 +516,7 @@ impl ToolExecutor {
         let path = args["path"].as_str().unwrap_or(".");
         let full_path = Path::new(&self.working_dir).join(path);
 
-        let entries = fs::read_dir(&full_path)?;
+        let entries = fs::read_dir(&full_path).map_err(|e| ToolError::io("list_directory", "listing", &full_path, e))?;
         let files: Result<Vec<_>, _> = entries
             .map(|entry| {
                 entry.map(|e| {
@@ -127,12 +525,13 @@ impl ToolExecutor {
                         "name": e.file_name().to_string_lossy(),
                         "is_dir": metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false),
                         "size": metadata.as_ref().and_then(|m| if m.is_file() { Some(m.len()) } else { None }),
+                        "mode": metadata.as_ref().and_then(file_mode),
                     })
                 })
             })
             .collect();
 
-        let file_list = files?;
+        let file_list = files.map_err(|e| ToolError::io("list_directory", "listing", &full_path, e))?;
         Ok(json!({
             "status": "success",
             "path": path,
@@ -141,9 +540,168 @@ impl ToolExecutor {
         }).to_string())
     }
 
+    fn handle_set_permissions(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("set_permissions", "path"))?;
+        let mode = args["mode"].as_str().ok_or_else(|| ToolError::missing_parameter("set_permissions", "mode"))?;
+        let bits = parse_mode(mode).ok_or_else(|| {
+            ToolError::new("set_permissions", ToolErrorCategory::InvalidArguments, format!("'{}' is not an octal mode such as 755; setuid, setgid and sticky bits cannot be set", mode))
+        })?;
+
+        let full_path = Path::new(&self.working_dir).join(path);
+        let resolved = self.resolve_inside_working_dir("set_permissions", path)?;
+
+        let previous = fs::metadata(&resolved).ok().as_ref().and_then(file_mode);
+        set_mode(&resolved, bits).map_err(|e| ToolError::io("set_permissions", "changing permissions of", &full_path, e))?;
+        Ok(json!({
+            "status": "success",
+            "path": path,
+            "previous_mode": previous,
+            "mode": format!("{:o}", bits),
+            "absolute_path": full_path.to_string_lossy()
+        }).to_string())
+    }
+
+    fn handle_inspect_archive(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("inspect_archive", "path"))?;
+        let full_path = Path::new(&self.working_dir).join(path);
+        let kind = archive::ArchiveKind::from_path(&full_path).ok_or_else(|| {
+            ToolError::new("inspect_archive", ToolErrorCategory::InvalidArguments, "unsupported archive type, expected .zip, .jar, .tar, .tar.gz or .tgz")
+                .with_target(path)
+        })?;
+
+        let resolved = self.resolve_inside_working_dir("inspect_archive", path)?;
+
+        if let Some(entry) = args["entry"].as_str() {
+            let content = archive::read_entry(&resolved, kind, entry).map_err(|e| ToolError::io("inspect_archive", "reading", &full_path, e))?;
+            return Ok(json!({
+                "status": "success",
+                "path": path,
+                "entry": entry,
+                "size": content.len(),
+                "content": content
+            }).to_string());
+        }
+
+        let entries = archive::list(&resolved, kind).map_err(|e| ToolError::io("inspect_archive", "listing", &full_path, e))?;
+        let total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+        Ok(json!({
+            "status": "success",
+            "path": path,
+            "entry_count": entries.len(),
+            "total_size": total_size,
+            "entries": &entries[..entries.len().min(archive::MAX_LISTED_ENTRIES)],
+            "truncated": entries.len() > archive::MAX_LISTED_ENTRIES
+        }).to_string())
+    }
+
+    fn handle_query_structured(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("query_structured", "path"))?;
+        let query = args["query"].as_str().unwrap_or("");
+        let full_path = Path::new(&self.working_dir).join(path);
+        let format = match args["format"].as_str() {
+            Some(name) => structured::Format::from_name(name).ok_or_else(|| {
+                ToolError::new("query_structured", ToolErrorCategory::InvalidArguments, format!("unknown format '{}', expected json, toml or yaml", name))
+            })?,
+            None => structured::Format::from_path(&full_path).ok_or_else(|| {
+                ToolError::new("query_structured", ToolErrorCategory::InvalidArguments, "cannot tell the format from the extension, pass format as json, toml or yaml")
+                    .with_target(path)
+            })?,
+        };
+        let segments = structured::parse_query(query)
+            .map_err(|e| ToolError::new("query_structured", ToolErrorCategory::InvalidArguments, e))?;
+
+        let resolved = self.resolve_inside_working_dir("query_structured", path)?;
+        let size = fs::metadata(&resolved).map_err(|e| ToolError::io("query_structured", "reading", &full_path, e))?.len();
+        if size > structured::MAX_STRUCTURED_BYTES {
+            return Err(ToolError::new("query_structured", ToolErrorCategory::TooLarge, format!("file is {} bytes, the limit is {}", size, structured::MAX_STRUCTURED_BYTES))
+                .with_target(path)
+                .into());
+        }
+        let content = fs::read_to_string(&resolved).map_err(|e| ToolError::io("query_structured", "reading", &full_path, e))?;
+        let document = structured::parse(&content, format).map_err(|e| {
+            ToolError::new("query_structured", ToolErrorCategory::InvalidArguments, format!("could not parse as {}: {}", format.name().to_uppercase(), e))
+                .with_target(path)
+        })?;
+
+        let value = structured::lookup(&document, &segments).map_err(|e| match e {
+            structured::LookupError::Missing(message) => ToolError::new("query_structured", ToolErrorCategory::NotFound, message).with_target(path),
+            structured::LookupError::NotContainer(message) => ToolError::new("query_structured", ToolErrorCategory::InvalidArguments, message).with_target(path),
+        })?;
+        Ok(json!({
+            "status": "success",
+            "path": path,
+            "format": format.name(),
+            "query": query,
+            "type": structured::type_name(value),
+            "value": value
+        }).to_string())
+    }
+
+    fn handle_file_stats(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let patterns: Vec<&str> = match &args["paths"] {
+            Value::Array(paths) => paths.iter().filter_map(Value::as_str).collect(),
+            Value::String(path) => vec![path.as_str()],
+            _ => Vec::new(),
+        };
+        if patterns.is_empty() {
+            return Err(ToolError::missing_parameter("file_stats", "paths").into());
+        }
+        let root = self.resolve_inside_working_dir("file_stats", ".")?;
+
+        // One file past the limit is collected to tell whether anything was left out
+        let mut files: Vec<PathBuf> = Vec::new();
+        let mut unmatched = Vec::new();
+        for pattern in &patterns {
+            // Checked before walking so a glob cannot wander through the rest of the filesystem
+            if Path::new(pattern).is_absolute() || pattern.split('/').any(|part| part == "..") {
+                return Err(outside_working_dir("file_stats").with_target(*pattern).into());
+            }
+            let remaining = file_stats::MAX_MATCHED_FILES + 1 - files.len();
+            let found = match file_stats::matching_files(&root, pattern, remaining) {
+                Ok(found) => found,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(ToolError::io("file_stats", "reading", &root.join(pattern), e).into()),
+            };
+            if found.is_empty() {
+                unmatched.push(*pattern);
+            }
+            for path in found {
+                self.resolve_inside_working_dir("file_stats", &path.to_string_lossy())?;
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+            if files.len() > file_stats::MAX_MATCHED_FILES {
+                break;
+            }
+        }
+        let truncated = files.len() > file_stats::MAX_MATCHED_FILES;
+        files.truncate(file_stats::MAX_MATCHED_FILES);
+
+        let stats = files
+            .iter()
+            .map(|path| file_stats::stats(&root, path).map_err(|e| ToolError::io("file_stats", "reading", path, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(json!({
+            "status": "success",
+            "files": stats,
+            "total": {
+                "files": stats.len(),
+                "bytes": stats.iter().map(|file| file.bytes).sum::<u64>(),
+                "lines": stats.iter().filter_map(|file| file.lines).sum::<usize>(),
+                "tokens": stats.iter().filter_map(|file| file.tokens).sum::<usize>()
+            },
+            "unmatched": unmatched,
+            "truncated": truncated
+        }).to_string())
+    }
+
     async fn handle_run_command(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
-        let command = args["command"].as_str().ok_or("Missing 'command' parameter")?;
+        let command = args["command"].as_str().ok_or_else(|| ToolError::missing_parameter("run_command", "command"))?;
         
+        self.check_command_approved("run_command", command)?;
+        self.check_protected_branch(command)?;
+
         println!("  🚀 Running: {} (Press Ctrl+C to interrupt)", command);
         
         let mut child = TokioCommand::new("sh")
@@ -153,79 +711,139 @@ impl ToolExecutor {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .stdin(Stdio::null())
-            .spawn()?;
+            .spawn()
+            .map_err(|e| {
+                ToolError::new("run_command", ToolErrorCategory::CommandFailed, format!("failed to start '{}': {}", command, e))
+                    .with_target(command)
+            })?;
 
-        let mut stdout_output = String::new();
-        let mut stderr_output = String::new();
-        let mut _interrupted = false;
+        // Drain both pipes while the command runs so a chatty command cannot fill them and stall
+        let stdout_output = Arc::new(Mutex::new(String::new()));
+        let stderr_output = Arc::new(Mutex::new(String::new()));
+        let readers = [
+            tokio::spawn(capture_lines(child.stdout.take(), stdout_output.clone(), self.verbose.then_some(false))),
+            tokio::spawn(capture_lines(child.stderr.take(), stderr_output.clone(), self.verbose.then_some(true))),
+        ];
 
         // Set up Ctrl+C handling
-        tokio::select! {
+        let status = tokio::select! {
             result = child.wait() => {
-                match result {
-                    Ok(status) => {
-                        // Read any remaining output
-                        if let Some(stdout) = child.stdout.take() {
-                            let mut reader = BufReader::new(stdout);
-                            let mut line = String::new();
-                            while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
-                                stdout_output.push_str(&line);
-                                if self.verbose {
-                                    print!("    {}", line);
-                                }
-                                line.clear();
-                            }
-                        }
-                        
-                        if let Some(stderr) = child.stderr.take() {
-                            let mut reader = BufReader::new(stderr);
-                            let mut line = String::new();
-                            while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
-                                stderr_output.push_str(&line);
-                                if self.verbose {
-                                    eprint!("    {}", line);
-                                }
-                                line.clear();
-                            }
-                        }
-
-                        let success = status.success();
-                        let result = json!({
-                            "status": if success { "success" } else { "warning" },
-                            "command": command,
-                            "stdout": stdout_output,
-                            "stderr": stderr_output,
-                            "exit_code": status.code(),
-                            "success": success,
-                            "interrupted": false
-                        });
-
-                        Ok(result.to_string())
-                    }
-                    Err(e) => Err(format!("Failed to wait for command: {}", e).into())
+                let status = result.map_err(|e| {
+                    ToolError::new("run_command", ToolErrorCategory::CommandFailed, format!("Failed to wait for command: {}", e))
+                        .with_target(command)
+                })?;
+                // Let the readers reach the end of the output
+                for reader in readers {
+                    let _ = reader.await;
                 }
+                Some(status)
             }
             _ = signal::ctrl_c() => {
                 println!("  ⚠️  Ctrl+C detected, terminating process...");
-                _interrupted = true;
                 
-                // Kill the child process
+                // Kill the child process; anything it spawned may still hold the pipes open
                 let _ = child.kill().await;
-                
-                let result = json!({
-                    "status": "interrupted",
-                    "command": command,
-                    "stdout": stdout_output,
-                    "stderr": stderr_output,
-                    "exit_code": null,
-                    "success": false,
-                    "interrupted": true,
-                    "message": "Process was interrupted by user (Ctrl+C)"
-                });
-
-                Ok(result.to_string())
+                for reader in &readers {
+                    reader.abort();
+                }
+                None
             }
+        };
+
+        let stdout_output = std::mem::take(&mut *stdout_output.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        let stderr_output = std::mem::take(&mut *stderr_output.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+
+        // The model only sees the head and tail of long output; the full text is kept for the story
+        let capped_stdout = cap_lines(&stdout_output, self.max_output_lines);
+        let capped_stderr = cap_lines(&stderr_output, self.max_output_lines);
+        if capped_stdout.len() != stdout_output.len() || capped_stderr.len() != stderr_output.len() {
+            let full = if stderr_output.is_empty() {
+                stdout_output
+            } else {
+                format!("{}\n--- stderr ---\n{}", stdout_output, stderr_output)
+            };
+            *self.full_output.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(full);
         }
+
+        let result = match status {
+            Some(status) => json!({
+                "status": if status.success() { "success" } else { "warning" },
+                "command": command,
+                "stdout": capped_stdout,
+                "stderr": capped_stderr,
+                "exit_code": status.code(),
+                "success": status.success(),
+                "interrupted": false
+            }),
+            None => json!({
+                "status": "interrupted",
+                "command": command,
+                "stdout": capped_stdout,
+                "stderr": capped_stderr,
+                "exit_code": null,
+                "success": false,
+                "interrupted": true,
+                "message": "Process was interrupted by user (Ctrl+C)"
+            }),
+        };
+        Ok(result.to_string())
+    }
+
+    async fn handle_run_command_background(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let command = args["command"].as_str().ok_or_else(|| ToolError::missing_parameter("run_command_background", "command"))?;
+
+        self.check_command_approved("run_command_background", command)?;
+        self.check_protected_branch(command)?;
+
+        let pid = self.background.spawn(command, &self.working_dir).map_err(|e| {
+            ToolError::new("run_command_background", ToolErrorCategory::CommandFailed, e).with_target(command)
+        })?;
+        println!("  🚀 Started in background: {} (pid {})", command, pid);
+
+        // Watch briefly so a typo or a port already in use shows up right away
+        let report = self.background.check(pid, background::STARTUP_CHECK).await
+            .ok_or_else(|| unknown_process("run_command_background", pid))?;
+        Ok(self.process_result(report).to_string())
+    }
+
+    async fn handle_check_process(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let Some(pid) = args["pid"].as_u64() else {
+            let processes: Vec<Value> = self.background.list()
+                .into_iter()
+                .map(|(pid, command)| json!({"pid": pid, "command": command}))
+                .collect();
+            return Ok(json!({"status": "success", "processes": processes}).to_string());
+        };
+        let wait_secs = args["wait_secs"].as_u64().unwrap_or(0).min(background::MAX_WAIT_SECS);
+
+        let report = self.background.check(pid as u32, std::time::Duration::from_secs(wait_secs)).await
+            .ok_or_else(|| unknown_process("check_process", pid as u32))?;
+        Ok(self.process_result(report).to_string())
+    }
+
+    async fn handle_stop_process(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let pid = args["pid"].as_u64().ok_or_else(|| ToolError::missing_parameter("stop_process", "pid"))? as u32;
+
+        let report = self.background.stop(pid).await.ok_or_else(|| unknown_process("stop_process", pid))?;
+        println!("  🛑 Stopped background command: {} (pid {})", report.command, pid);
+        let mut result = self.process_result(report);
+        result["stopped"] = json!(true);
+        Ok(result.to_string())
+    }
+
+    /// Result of the background process tools; output is capped like `run_command` output
+    fn process_result(&self, report: background::ProcessReport) -> Value {
+        let healthy = report.running || report.exit_code == Some(0);
+        json!({
+            "status": if healthy { "success" } else { "warning" },
+            "pid": report.pid,
+            "command": report.command,
+            "running": report.running,
+            "exit_code": report.exit_code,
+            "elapsed_secs": report.elapsed.as_secs(),
+            "stdout": cap_lines(&report.stdout, self.max_output_lines),
+            "stderr": cap_lines(&report.stderr, self.max_output_lines)
+        })
     }
 
     fn handle_query_context(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
@@ -250,6 +868,10 @@ impl ToolExecutor {
                 // Check if it's a git repo
                 let is_git = Path::new(&self.working_dir).join(".git").exists();
                 context.insert("is_git_repo".to_string(), json!(is_git));
+                if let Some(branch) = git::current_branch(&self.working_dir) {
+                    context.insert("git_branch".to_string(), json!(branch));
+                    context.insert("git_branch_protected".to_string(), json!(self.protected_branches.contains(&branch)));
+                }
 
                 // Get current working directory absolute path
                 context.insert("working_directory".to_string(), json!(self.working_dir));
@@ -291,10 +913,242 @@ impl ToolExecutor {
         }
     }
 
-    fn handle_complete(&self) -> Result<String, Box<dyn std::error::Error>> {
+    fn handle_project_commands(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let commands = project_commands::discover(Path::new(&self.working_dir));
+        Ok(json!({
+            "status": "success",
+            "commands": commands
+        }).to_string())
+    }
+
+    fn handle_scaffold_project(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let project_type = args["project_type"].as_str().ok_or_else(|| ToolError::missing_parameter("scaffold_project", "project_type"))?;
+        if !scaffold::PROJECT_TYPES.contains(&project_type) {
+            return Err(ToolError::new("scaffold_project", ToolErrorCategory::InvalidArguments, format!(
+                "unknown project type '{}', expected one of: {}", project_type, scaffold::PROJECT_TYPES.join(", ")
+            )).into());
+        }
+
+        let path = args["path"].as_str().unwrap_or(".");
+        let root = Path::new(&self.working_dir).join(path);
+        fs::create_dir_all(&root).map_err(|e| ToolError::io("scaffold_project", "creating directory", &root, e))?;
+        let name = match args["name"].as_str() {
+            Some(name) => name.to_string(),
+            None => root.canonicalize().ok()
+                .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().to_string()))
+                .unwrap_or_else(|| "project".to_string()),
+        };
+        let description = args["description"].as_str().unwrap_or("");
+
+        // Existing files are never overwritten, so scaffolding an established project is harmless
+        let mut created = Vec::new();
+        let mut skipped = Vec::new();
+        for (template, file) in scaffold::TEMPLATE_FILES {
+            let relative = Path::new(path).join(file).to_string_lossy().trim_start_matches("./").to_string();
+            let target = root.join(file);
+            if target.exists() {
+                skipped.push(relative);
+                continue;
+            }
+            let content = scaffold::render(project_type, template, self.scaffold_dir.as_deref(), &name, description);
+            fs::write(&target, content).map_err(|e| ToolError::io("scaffold_project", "writing", &target, e))?;
+            created.push(relative);
+        }
+
+        let git_initialized = args["git_init"].as_bool().unwrap_or(true)
+            && git::init_unless_tracked(&root).map_err(|e| ToolError::new("scaffold_project", ToolErrorCategory::CommandFailed, e.to_string()))?;
+
         Ok(json!({
+            "status": "success",
+            "path": path,
+            "project_type": project_type,
+            "created": created,
+            "skipped": skipped,
+            "git_initialized": git_initialized
+        }).to_string())
+    }
+
+    fn handle_create_from_template(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let name = args["template"].as_str().ok_or_else(|| ToolError::missing_parameter("create_from_template", "template"))?;
+        let path = args["path"].as_str().ok_or_else(|| ToolError::missing_parameter("create_from_template", "path"))?;
+        let full_path = Path::new(&self.working_dir).join(path);
+
+        let dirs = file_templates::TemplateDirs {
+            project: self.template_dir.as_ref().map(|dir| Path::new(&self.working_dir).join(dir)),
+            user: self.user_template_dir.clone(),
+        };
+        let template = dirs.find(name).ok_or_else(|| ToolError::new("create_from_template", ToolErrorCategory::NotFound, format!(
+            "no template named '{}'; available: {}", name, dirs.available().join(", ")
+        )))?;
+        // Templates are for new files; changing an existing one is write_file's job
+        if full_path.exists() {
+            return Err(ToolError::new("create_from_template", ToolErrorCategory::AlreadyExists, "file already exists; use write_file to change it")
+                .with_target(path).into());
+        }
+
+        let mut variables = args["variables"].as_object().cloned().unwrap_or_default();
+        if let Some(stem) = full_path.file_stem() {
+            variables.entry(file_templates::FILE_STEM_VARIABLE).or_insert_with(|| json!(stem.to_string_lossy()));
+        }
+        let rendered = file_templates::render(&template.content, &variables).map_err(|missing| {
+            ToolError::new("create_from_template", ToolErrorCategory::InvalidArguments, format!(
+                "template '{}' needs variables: {}", name, missing.join(", ")
+            ))
+        })?;
+
+        let content = self.normalized_content(&full_path, &rendered);
+        self.check_write_size("create_from_template", &full_path, &content)?;
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| ToolError::io("create_from_template", "creating directory", parent, e))?;
+        }
+        fs::write(&full_path, &content).map_err(|e| ToolError::io("create_from_template", "writing", &full_path, e))?;
+        let (content, formatting) = self.format_written(&full_path, content);
+        self.remember_content(&full_path, content.as_bytes());
+
+        let mut result = json!({
+            "status": "success",
+            "path": path,
+            "template": name,
+            "source": template.source,
+            "size": content.len(),
+            "absolute_path": full_path.to_string_lossy()
+        });
+        if let Some(formatting) = formatting {
+            result["formatting"] = json!(formatting);
+        }
+        Ok(result.to_string())
+    }
+
+    fn handle_complete(&self, args: &Value) -> Result<String, Box<dyn std::error::Error>> {
+        let mut result = json!({
             "status": "completed",
             "message": "Project marked as complete"
-        }).to_string())
+        });
+        if let Some(summary) = args["summary"].as_str().map(str::trim).filter(|summary| !summary.is_empty()) {
+            result["summary"] = json!(summary);
+        }
+        Ok(result.to_string())
+    }
+}
+
+/// Keep the first and last `max_lines / 2` lines of `output`, replacing the middle with a marker (0 = unlimited)
+pub fn cap_lines(output: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    if max_lines == 0 || lines.len() <= max_lines {
+        return output.to_string();
+    }
+
+    let head = max_lines.div_ceil(2);
+    let tail = max_lines - head;
+    let mut capped = lines[..head].join("\n");
+    capped.push_str(&format!("\n... {} lines omitted ...\n", lines.len() - max_lines));
+    capped.push_str(&lines[lines.len() - tail..].join("\n"));
+    if output.ends_with('\n') {
+        capped.push('\n');
+    }
+    capped
+}
+
+/// Append each line from `stream` to `output`, echoing it to stdout (`Some(false)`) or stderr (`Some(true)`) as it arrives
+async fn capture_lines<R: tokio::io::AsyncRead + Unpin>(stream: Option<R>, output: Arc<Mutex<String>>, echo: Option<bool>) {
+    let Some(stream) = stream else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+        match echo {
+            Some(false) => print!("    {}", line),
+            Some(true) => eprint!("    {}", line),
+            None => {}
+        }
+        output.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push_str(&line);
+        line.clear();
     }
-}
\ No newline at end of file
+}
+
+/// Whether a tool result reports that the user interrupted it with Ctrl+C
+pub fn is_interrupted(result: &str) -> bool {
+    serde_json::from_str::<Value>(result)
+        .map(|value| value["interrupted"].as_bool().unwrap_or(false))
+        .unwrap_or(false)
+}
+
+/// Result sent for a queued tool call that was never run because an earlier call in the turn was interrupted.
+/// Every tool call needs a matching result for the conversation to stay valid.
+pub fn skipped_after_interrupt(tool: &str) -> String {
+    json!({
+        "status": "interrupted",
+        "tool": tool,
+        "interrupted": true,
+        "message": "Not run: the user interrupted an earlier tool call in this turn"
+    }).to_string()
+}
+
+/// The `summary` a `complete` call gives for the user, if any
+pub fn completion_summary(tool_call: &ToolCall) -> Option<String> {
+    let arguments: Value = serde_json::from_str(&tool_call.function.arguments).ok()?;
+    arguments["summary"].as_str().map(str::trim).filter(|summary| !summary.is_empty()).map(str::to_string)
+}
+
+/// Result sent for a tool call queued after `complete` when `tools.on_complete` ends the turn there
+pub fn skipped_after_complete(tool: &str) -> String {
+    json!({
+        "status": "skipped",
+        "tool": tool,
+        "message": "Not run: an earlier complete call in this response ended the turn"
+    }).to_string()
+}
+
+fn content_hash(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A pid that was never started in the background, or whose exit was already reported
+fn unknown_process(tool: &str, pid: u32) -> ToolError {
+    ToolError::new(
+        tool,
+        ToolErrorCategory::NotFound,
+        format!("no background command with pid {}; it may have exited and already been reported", pid),
+    )
+    .with_target(pid.to_string())
+}
+
+/// A path that resolves outside the working directory, which tools reading or changing files may not touch
+fn outside_working_dir(tool: &str) -> ToolError {
+    ToolError::new(tool, ToolErrorCategory::PermissionDenied, "only files inside the working directory can be used")
+}
+
+/// Octal permission bits such as `755` or `0644`. Setuid, setgid and sticky bits (`4755`) are refused.
+fn parse_mode(mode: &str) -> Option<u32> {
+    let mode = mode.trim();
+    if mode.is_empty() || mode.len() > 4 {
+        return None;
+    }
+    u32::from_str_radix(mode, 8).ok().filter(|bits| *bits <= 0o777)
+}
+
+/// Permission bits of a file in octal, e.g. `"755"`; `None` where unix modes do not exist
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(format!("{:o}", metadata.permissions().mode() & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, bits: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(bits))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _bits: u32) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "file modes are only supported on unix"))
+}
@@ -0,0 +1,119 @@
+//! Line ending and trailing newline normalization for `create_file` and `write_file`
+//! Models emit whatever line endings they like, which leaves files with a mix of CRLF and LF
+
+use serde::{Deserialize, Serialize};
+
+/// Line endings written by the file tools (`tools.line_ending`)
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// The existing file's dominant ending, or the content's own for new files
+    #[default]
+    Auto,
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub const NAMES: [&'static str; 3] = ["auto", "lf", "crlf"];
+
+    pub fn from_name(name: &str) -> Option<LineEnding> {
+        match name {
+            "auto" => Some(LineEnding::Auto),
+            "lf" => Some(LineEnding::Lf),
+            "crlf" => Some(LineEnding::Crlf),
+            _ => None,
+        }
+    }
+}
+
+/// Whether written files end with a newline (`tools.trailing_newline`)
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingNewline {
+    /// Leave the end of the content as the model wrote it
+    #[default]
+    Keep,
+    /// Add a final newline when missing; extra blank lines at the end are left alone
+    Ensure,
+    /// Drop all trailing newlines
+    Strip,
+}
+
+impl TrailingNewline {
+    pub const NAMES: [&'static str; 3] = ["keep", "ensure", "strip"];
+
+    pub fn from_name(name: &str) -> Option<TrailingNewline> {
+        match name {
+            "keep" => Some(TrailingNewline::Keep),
+            "ensure" => Some(TrailingNewline::Ensure),
+            "strip" => Some(TrailingNewline::Strip),
+            _ => None,
+        }
+    }
+}
+
+/// `Some(true)` if CRLF is the more common ending in `text`, `None` when it has no line breaks
+fn dominant_is_crlf(text: &str) -> Option<bool> {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    match (crlf, lf) {
+        (0, 0) => None,
+        (crlf, lf) => Some(crlf > lf),
+    }
+}
+
+/// `content` with every line break written as the configured ending. `existing` is the current content of
+/// the file being replaced, which decides the ending in `auto` mode.
+pub fn normalize(content: &str, existing: Option<&str>, line_ending: LineEnding, trailing: TrailingNewline) -> String {
+    let crlf = match line_ending {
+        LineEnding::Lf => Some(false),
+        LineEnding::Crlf => Some(true),
+        LineEnding::Auto => existing.and_then(dominant_is_crlf).or_else(|| dominant_is_crlf(content)),
+    };
+    let newline = if crlf == Some(true) { "\r\n" } else { "\n" };
+
+    let mut normalized = match crlf {
+        Some(_) => content.replace("\r\n", "\n").replace('\n', newline),
+        None => content.to_string(),
+    };
+    match trailing {
+        TrailingNewline::Keep => {}
+        TrailingNewline::Ensure => {
+            if !normalized.is_empty() && !normalized.ends_with('\n') {
+                normalized.push_str(newline);
+            }
+        }
+        TrailingNewline::Strip => {
+            let end = normalized.trim_end_matches(['\r', '\n']).len();
+            normalized.truncate(end);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_line_endings() {
+        let mixed = "a\r\nb\nc\n";
+        assert_eq!(normalize(mixed, None, LineEnding::Lf, TrailingNewline::Keep), "a\nb\nc\n");
+        assert_eq!(normalize(mixed, None, LineEnding::Crlf, TrailingNewline::Keep), "a\r\nb\r\nc\r\n");
+
+        // auto follows the file being replaced, then the content itself
+        assert_eq!(normalize(mixed, Some("x\r\ny\r\n"), LineEnding::Auto, TrailingNewline::Keep), "a\r\nb\r\nc\r\n");
+        assert_eq!(normalize(mixed, Some("one line"), LineEnding::Auto, TrailingNewline::Keep), "a\nb\nc\n");
+        assert_eq!(normalize("no breaks", None, LineEnding::Auto, TrailingNewline::Keep), "no breaks");
+    }
+
+    #[test]
+    fn test_normalize_trailing_newline() {
+        assert_eq!(normalize("a\r\nb", None, LineEnding::Auto, TrailingNewline::Ensure), "a\r\nb\r\n");
+        assert_eq!(normalize("a\n\n", None, LineEnding::Auto, TrailingNewline::Ensure), "a\n\n");
+        assert_eq!(normalize("", None, LineEnding::Lf, TrailingNewline::Ensure), "");
+        assert_eq!(normalize("a\r\n\r\n", None, LineEnding::Auto, TrailingNewline::Strip), "a");
+        assert_eq!(normalize("a", Some("x\r\n"), LineEnding::Auto, TrailingNewline::Ensure), "a\r\n");
+    }
+}
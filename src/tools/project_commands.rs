@@ -0,0 +1,158 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// A runnable command defined by the project itself
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ProjectCommand {
+    /// What to type to run it, e.g. `npm run test` or `make build`
+    pub name: String,
+    /// What it runs underneath
+    pub command: String,
+    /// File the command was discovered in
+    pub source: String,
+}
+
+/// Collect npm/yarn/pnpm scripts, cargo aliases, Makefile targets and justfile recipes under `dir`
+pub fn discover(dir: &Path) -> Vec<ProjectCommand> {
+    let mut commands = Vec::new();
+    commands.extend(package_scripts(dir));
+    commands.extend(cargo_aliases(dir));
+    commands.extend(makefile_targets(dir));
+    commands.extend(just_recipes(dir));
+    commands
+}
+
+fn package_scripts(dir: &Path) -> Vec<ProjectCommand> {
+    let Some(package) = fs::read_to_string(dir.join("package.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+    else {
+        return Vec::new();
+    };
+
+    let runner = if dir.join("pnpm-lock.yaml").exists() {
+        "pnpm run"
+    } else if dir.join("yarn.lock").exists() {
+        "yarn"
+    } else {
+        "npm run"
+    };
+
+    package["scripts"]
+        .as_object()
+        .map(|scripts| {
+            scripts
+                .iter()
+                .filter_map(|(name, script)| {
+                    Some(ProjectCommand {
+                        name: format!("{} {}", runner, name),
+                        command: script.as_str()?.to_string(),
+                        source: "package.json".to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn cargo_aliases(dir: &Path) -> Vec<ProjectCommand> {
+    for source in [".cargo/config.toml", ".cargo/config"] {
+        let Some(config) = fs::read_to_string(dir.join(source))
+            .ok()
+            .and_then(|content| content.parse::<toml::Value>().ok())
+        else {
+            continue;
+        };
+
+        let Some(aliases) = config.get("alias").and_then(|alias| alias.as_table()) else {
+            return Vec::new();
+        };
+
+        return aliases
+            .iter()
+            .filter_map(|(name, value)| {
+                let command = match value {
+                    toml::Value::String(command) => command.clone(),
+                    toml::Value::Array(parts) => parts.iter().filter_map(|part| part.as_str()).collect::<Vec<_>>().join(" "),
+                    _ => return None,
+                };
+                Some(ProjectCommand {
+                    name: format!("cargo {}", name),
+                    command: format!("cargo {}", command),
+                    source: source.to_string(),
+                })
+            })
+            .collect();
+    }
+    Vec::new()
+}
+
+fn makefile_targets(dir: &Path) -> Vec<ProjectCommand> {
+    let Some((source, content)) = ["GNUmakefile", "makefile", "Makefile"]
+        .iter()
+        .find_map(|name| fs::read_to_string(dir.join(name)).ok().map(|content| (*name, content)))
+    else {
+        return Vec::new();
+    };
+
+    // `target: deps` but not variable assignments such as `CC := gcc`
+    let target = Regex::new(r"^([A-Za-z0-9_][A-Za-z0-9_./-]*)\s*:([^=]|$)").unwrap();
+    recipes(&content, &target)
+        .into_iter()
+        .map(|(name, body)| ProjectCommand {
+            name: format!("make {}", name),
+            command: body,
+            source: source.to_string(),
+        })
+        .collect()
+}
+
+fn just_recipes(dir: &Path) -> Vec<ProjectCommand> {
+    let Some((source, content)) = ["justfile", "Justfile", ".justfile"]
+        .iter()
+        .find_map(|name| fs::read_to_string(dir.join(name)).ok().map(|content| (*name, content)))
+    else {
+        return Vec::new();
+    };
+
+    // `recipe arg1 arg2: deps`, skipping settings, aliases, exports and `:=` assignments
+    let recipe = Regex::new(r"^@?([A-Za-z0-9_][A-Za-z0-9_-]*)(\s[^:]*)?:([^=]|$)").unwrap();
+    recipes(&content, &recipe)
+        .into_iter()
+        .filter(|(name, _)| !matches!(name.as_str(), "set" | "alias" | "export" | "import" | "mod"))
+        .map(|(name, body)| ProjectCommand {
+            name: format!("just {}", name),
+            command: body,
+            source: source.to_string(),
+        })
+        .collect()
+}
+
+/// Pair each header matched by `header` with its indented body lines joined by ` && `
+fn recipes(content: &str, header: &Regex) -> Vec<(String, String)> {
+    let mut recipes: Vec<(String, Vec<String>)> = Vec::new();
+    let mut in_recipe = false;
+
+    for line in content.lines() {
+        if line.starts_with([' ', '\t']) {
+            let step = line.trim();
+            if in_recipe && !step.is_empty() && !step.starts_with('#') {
+                if let Some((_, body)) = recipes.last_mut() {
+                    body.push(step.trim_start_matches('@').to_string());
+                }
+            }
+            continue;
+        }
+
+        in_recipe = false;
+        if let Some(captures) = header.captures(line) {
+            recipes.push((captures[1].to_string(), Vec::new()));
+            in_recipe = true;
+        }
+    }
+
+    recipes.into_iter().map(|(name, body)| (name, body.join(" && "))).collect()
+}
@@ -0,0 +1,409 @@
+//! Registry of the tools offered to the model
+//! Each tool carries its own schema and implementation, so the advertised list and the dispatcher cannot drift apart
+
+use super::ToolExecutor;
+use crate::config::ToolsConfig;
+use crate::openrouter::{Tool, ToolFunction};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock, RwLock};
+
+pub type ToolResult = Result<String, Box<dyn std::error::Error>>;
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = ToolResult> + 'a>>;
+
+/// The `[tools]` config switch that enables a tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolGroup {
+    Filesystem,
+    Commands,
+    /// Offered regardless of configuration
+    Always,
+}
+
+impl ToolGroup {
+    pub fn enabled(&self, config: &ToolsConfig) -> bool {
+        match self {
+            ToolGroup::Filesystem => config.filesystem,
+            ToolGroup::Commands => config.commands,
+            ToolGroup::Always => true,
+        }
+    }
+}
+
+/// A tool the model can call: its schema plus the code that runs it
+pub trait ToolHandler: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    /// JSON schema of the arguments object
+    fn parameters(&self) -> Value;
+    fn group(&self) -> ToolGroup;
+    fn execute<'a>(&'a self, executor: &'a ToolExecutor, args: &'a Value) -> ToolFuture<'a>;
+
+    /// Definition sent to the model in the `tools` field
+    fn definition(&self) -> Tool {
+        Tool {
+            tool_type: "function".to_string(),
+            function: ToolFunction {
+                name: self.name().to_string(),
+                description: self.description().to_string(),
+                parameters: self.parameters(),
+            },
+        }
+    }
+}
+
+/// Tool backed by one of the executor's built-in handlers
+struct BuiltinTool {
+    name: &'static str,
+    description: &'static str,
+    group: ToolGroup,
+    parameters: fn() -> Value,
+    run: for<'a> fn(&'a ToolExecutor, &'a Value) -> ToolFuture<'a>,
+}
+
+impl ToolHandler for BuiltinTool {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn parameters(&self) -> Value {
+        (self.parameters)()
+    }
+
+    fn group(&self) -> ToolGroup {
+        self.group
+    }
+
+    fn execute<'a>(&'a self, executor: &'a ToolExecutor, args: &'a Value) -> ToolFuture<'a> {
+        (self.run)(executor, args)
+    }
+}
+
+/// Registered tools in the order they are advertised
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn ToolHandler>>,
+}
+
+impl ToolRegistry {
+    /// A registry of the built-in tools, separate from the global one
+    pub fn with_builtins() -> Self {
+        let mut registry = Self { tools: Vec::new() };
+        for tool in builtin_tools() {
+            registry.register(Arc::new(tool));
+        }
+        registry
+    }
+
+    /// Add a tool, replacing any registered tool with the same name
+    pub fn register(&mut self, tool: Arc<dyn ToolHandler>) {
+        match self.tools.iter().position(|existing| existing.name() == tool.name()) {
+            Some(index) => self.tools[index] = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ToolHandler>> {
+        self.tools.iter().find(|tool| tool.name() == name).cloned()
+    }
+
+    /// Definitions of the tools enabled by `config`
+    pub fn definitions(&self, config: &ToolsConfig) -> Vec<Tool> {
+        self.tools
+            .iter()
+            .filter(|tool| tool.group().enabled(config))
+            .map(|tool| tool.definition())
+            .collect()
+    }
+}
+
+static TOOL_REGISTRY: OnceLock<RwLock<ToolRegistry>> = OnceLock::new();
+
+fn registry() -> &'static RwLock<ToolRegistry> {
+    TOOL_REGISTRY.get_or_init(|| RwLock::new(ToolRegistry::with_builtins()))
+}
+
+/// Add a tool to the global registry so it is both advertised and executable
+#[allow(dead_code)]
+pub fn register_tool(tool: Arc<dyn ToolHandler>) {
+    registry().write().unwrap_or_else(|poisoned| poisoned.into_inner()).register(tool);
+}
+
+/// Look up a registered tool by name
+pub fn find_tool(name: &str) -> Option<Arc<dyn ToolHandler>> {
+    registry().read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(name)
+}
+
+/// Definitions of every registered tool enabled by `config`
+pub fn tool_definitions(config: &ToolsConfig) -> Vec<Tool> {
+    registry().read().unwrap_or_else(|poisoned| poisoned.into_inner()).definitions(config)
+}
+
+fn builtin_tools() -> Vec<BuiltinTool> {
+    vec![
+        BuiltinTool {
+            name: "create_file",
+            description: "Create a new file with specified content",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path to create"},
+                    "content": {"type": "string", "description": "File content"}
+                },
+                "required": ["path", "content"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_create_file(args) }),
+        },
+        BuiltinTool {
+            name: "read_file",
+            description: "Read the contents of a file",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path to read"}
+                },
+                "required": ["path"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_read_file(args) }),
+        },
+        BuiltinTool {
+            name: "write_file",
+            description: "Write content to an existing file",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path to write to"},
+                    "content": {"type": "string", "description": "Content to write"}
+                },
+                "required": ["path", "content"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_write_file(args) }),
+        },
+        BuiltinTool {
+            name: "delete_file",
+            description: "Delete a file",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path to delete"}
+                },
+                "required": ["path"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_delete_file(args) }),
+        },
+        BuiltinTool {
+            name: "create_directory",
+            description: "Create a directory and any necessary parent directories",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "Directory path to create"}
+                },
+                "required": ["path"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_create_directory(args) }),
+        },
+        BuiltinTool {
+            name: "list_directory",
+            description: "List contents of a directory",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "Directory path to list (defaults to current directory)"}
+                }
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_list_directory(args) }),
+        },
+        BuiltinTool {
+            name: "file_stats",
+            description: "Line count, byte size and estimated token count of files, to check what is affordable to read before reading it. Accepts paths, directories and globs such as src/**/*.rs",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "paths": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Files, directories or globs relative to the working directory (* and ? match within a path component, ** across them)"
+                    }
+                },
+                "required": ["paths"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_file_stats(args) }),
+        },
+        BuiltinTool {
+            name: "query_context",
+            description: "Query project context and current state",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "type": {"type": "string", "enum": ["full", "directory"], "description": "Type of context query"}
+                }
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_query_context(args) }),
+        },
+        BuiltinTool {
+            name: "project_commands",
+            description: "List the project's own build/test/run commands (npm scripts, cargo aliases, Makefile targets, just recipes). Use these instead of guessing",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {}
+            }),
+            run: |executor, _args| Box::pin(async move { executor.handle_project_commands() }),
+        },
+        BuiltinTool {
+            name: "set_permissions",
+            description: "Set a file's unix permission bits, e.g. mode \"755\" to make a generated script executable",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path inside the working directory"},
+                    "mode": {"type": "string", "description": "Octal mode such as \"755\" or \"644\"; setuid, setgid and sticky bits are not allowed"}
+                },
+                "required": ["path", "mode"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_set_permissions(args) }),
+        },
+        BuiltinTool {
+            name: "query_structured",
+            description: "Read one value from a JSON, TOML or YAML file by path query (such as dependencies.serde.version or jobs.build.steps[0].run) instead of reading the whole file",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path inside the working directory"},
+                    "query": {"type": "string", "description": "Dotted keys with [n] array indexes, optionally starting with $; use [\"a.b\"] for keys containing dots. Empty returns the whole document"},
+                    "format": {"type": "string", "enum": super::structured::Format::NAMES, "description": "File format, when the extension does not tell"}
+                },
+                "required": ["path", "query"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_query_structured(args) }),
+        },
+        BuiltinTool {
+            name: "inspect_archive",
+            description: "List the entries (names and sizes) of a .zip, .jar, .tar, .tar.gz or .tgz archive without extracting it, or read one text entry by passing its name",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "Archive path inside the working directory"},
+                    "entry": {"type": "string", "description": "Entry name, exactly as listed, whose text content to return"}
+                },
+                "required": ["path"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_inspect_archive(args) }),
+        },
+        BuiltinTool {
+            name: "scaffold_project",
+            description: "Set up a new project in one call: writes a .gitignore and starter README for the project type (never overwriting existing files) and runs git init unless already in a repository",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "project_type": {"type": "string", "enum": super::scaffold::PROJECT_TYPES, "description": "Language or ecosystem of the project"},
+                    "path": {"type": "string", "description": "Project directory, created if missing (defaults to the working directory)"},
+                    "name": {"type": "string", "description": "Project name for the README (defaults to the directory name)"},
+                    "description": {"type": "string", "description": "One-line description for the README"},
+                    "git_init": {"type": "boolean", "description": "Initialize a git repository (default true)"}
+                },
+                "required": ["project_type"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_scaffold_project(args) }),
+        },
+        BuiltinTool {
+            name: "create_from_template",
+            description: "Create a new file from a template, replacing {{var}} placeholders with the given variables. Built-in templates: react_component (name), rust_test (crate_name, module), python_test (module); the user may have more, which an unknown template name lists. {{file_stem}} defaults to the target file name without extension. Never overwrites an existing file",
+            group: ToolGroup::Filesystem,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "template": {"type": "string", "description": "Template name"},
+                    "path": {"type": "string", "description": "Path of the file to create"},
+                    "variables": {"type": "object", "description": "Placeholder values, e.g. {\"name\": \"Button\"}", "additionalProperties": {"type": "string"}}
+                },
+                "required": ["template", "path"]
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_create_from_template(args) }),
+        },
+        BuiltinTool {
+            name: "run_command",
+            description: "Execute a shell command",
+            group: ToolGroup::Commands,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string", "description": "Command to execute"}
+                },
+                "required": ["command"]
+            }),
+            run: |executor, args| Box::pin(executor.handle_run_command(args)),
+        },
+        BuiltinTool {
+            name: "run_command_background",
+            description: "Start a long-running shell command such as a dev server without waiting for it. Returns its pid and first output; use check_process to read more output and stop_process to end it",
+            group: ToolGroup::Commands,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string", "description": "Command to start"}
+                },
+                "required": ["command"]
+            }),
+            run: |executor, args| Box::pin(executor.handle_run_command_background(args)),
+        },
+        BuiltinTool {
+            name: "check_process",
+            description: "Report whether a background command is still running, with the output it wrote since the last check. Without a pid, list the background commands",
+            group: ToolGroup::Commands,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "pid": {"type": "integer", "description": "Pid returned by run_command_background"},
+                    "wait_secs": {"type": "integer", "description": "Wait up to this many seconds (max 300) for the command to exit (default 0)"}
+                }
+            }),
+            run: |executor, args| Box::pin(executor.handle_check_process(args)),
+        },
+        BuiltinTool {
+            name: "stop_process",
+            description: "Stop a background command and everything it started, returning its remaining output",
+            group: ToolGroup::Commands,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "pid": {"type": "integer", "description": "Pid returned by run_command_background"}
+                },
+                "required": ["pid"]
+            }),
+            run: |executor, args| Box::pin(executor.handle_stop_process(args)),
+        },
+        BuiltinTool {
+            name: "complete",
+            description: "Mark the project as completed",
+            group: ToolGroup::Always,
+            parameters: || json!({
+                "type": "object",
+                "properties": {
+                    "summary": {
+                        "type": "string",
+                        "description": "One or two sentences on what was done, shown to the user"
+                    }
+                }
+            }),
+            run: |executor, args| Box::pin(async move { executor.handle_complete(args) }),
+        },
+    ]
+}
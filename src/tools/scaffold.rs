@@ -0,0 +1,67 @@
+//! Starter files for new projects: `.gitignore` and README templates per project type
+//! Built-in templates can be replaced by files in `<config dir>/loo/scaffold/<type>/`
+
+use std::fs;
+use std::path::Path;
+
+/// Project types with built-in templates
+pub const PROJECT_TYPES: &[&str] = &["rust", "node", "python", "go", "generic"];
+
+/// Template files written by `scaffold_project`, as (template name, destination file)
+pub const TEMPLATE_FILES: &[(&str, &str)] = &[("gitignore", ".gitignore"), ("README.md", "README.md")];
+
+const COMMON_IGNORES: &str = ".DS_Store\n.env\n.env.*\n*.log\n.idea/\n.vscode/\n";
+
+fn builtin_gitignore(project_type: &str) -> String {
+    let specific = match project_type {
+        "rust" => "/target/\n**/*.rs.bk\n",
+        "node" => "node_modules/\ndist/\nbuild/\ncoverage/\nnpm-debug.log*\n",
+        "python" => "__pycache__/\n*.py[cod]\n.venv/\nvenv/\n*.egg-info/\ndist/\nbuild/\n.pytest_cache/\n",
+        "go" => "/bin/\n*.exe\n*.test\n*.out\n",
+        _ => "",
+    };
+    format!("{}{}", specific, COMMON_IGNORES)
+}
+
+fn builtin_readme(project_type: &str) -> String {
+    let commands = match project_type {
+        "rust" => "```sh\ncargo build\ncargo test\n```\n",
+        "node" => "```sh\nnpm install\nnpm test\n```\n",
+        "python" => "```sh\npython -m venv .venv\n. .venv/bin/activate\npip install -e .\n```\n",
+        "go" => "```sh\ngo build ./...\ngo test ./...\n```\n",
+        _ => "",
+    };
+    let mut readme = "# {name}\n\n{description}\n".to_string();
+    if !commands.is_empty() {
+        readme.push_str(&format!("\n## Getting started\n\n{}", commands));
+    }
+    readme
+}
+
+/// Content of `template` for `project_type`, preferring `override_dir/<type>/<template>` over the built-in.
+/// `{name}` and `{description}` are filled in either way.
+pub fn render(project_type: &str, template: &str, override_dir: Option<&Path>, name: &str, description: &str) -> String {
+    let content = override_dir
+        .and_then(|dir| fs::read_to_string(dir.join(project_type).join(template)).ok())
+        .unwrap_or_else(|| match template {
+            "gitignore" => builtin_gitignore(project_type),
+            _ => builtin_readme(project_type),
+        });
+    content.replace("{name}", name).replace("{description}", description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prefers_override() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("rust")).unwrap();
+        fs::write(dir.path().join("rust").join("README.md"), "# {name} (ours)\n").unwrap();
+
+        assert_eq!(render("rust", "README.md", Some(dir.path()), "demo", ""), "# demo (ours)\n");
+        assert!(render("rust", "gitignore", Some(dir.path()), "demo", "").starts_with("/target/\n"));
+        assert!(render("node", "README.md", None, "web", "A site").starts_with("# web\n\nA site\n"));
+    }
+}
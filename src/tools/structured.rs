@@ -0,0 +1,709 @@
+//! Parsing of JSON, TOML and YAML files and path queries into them for `query_structured`, so one value can
+//! be read from a large config file without the rest of it taking up context.
+//! YAML is read by a small parser covering the block style config files use (mappings, sequences, quoted
+//! and plain scalars, `|`/`>` block scalars and single-line flow collections); anchors, aliases, tags and
+//! multiple documents are reported as unsupported rather than misread.
+
+use serde_json::{Map, Number, Value};
+use std::path::Path;
+
+/// Largest file `query_structured` will parse
+pub const MAX_STRUCTURED_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    pub const NAMES: [&'static str; 3] = ["json", "toml", "yaml"];
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Format named by the file extension
+    pub fn from_path(path: &Path) -> Option<Format> {
+        Format::from_name(&path.extension()?.to_string_lossy().to_lowercase())
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Yaml => "yaml",
+        }
+    }
+}
+
+/// Parse `content` as `format` into a JSON value
+pub fn parse(content: &str, format: Format) -> Result<Value, String> {
+    match format {
+        Format::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        Format::Toml => content.parse::<toml::Table>().map(|table| toml_to_json(toml::Value::Table(table))).map_err(|e| e.to_string().trim().to_string()),
+        Format::Yaml => parse_yaml(content),
+    }
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(text) => Value::String(text),
+        toml::Value::Integer(number) => Value::from(number),
+        toml::Value::Float(number) => Number::from_f64(number).map(Value::Number).unwrap_or(Value::Null),
+        toml::Value::Boolean(flag) => Value::Bool(flag),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect()),
+    }
+}
+
+/// One step of a query: a key of an object or an index into an array
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse a dotted path such as `dependencies.serde.version` or `jobs.build.steps[0].run`. A leading `$` or
+/// `$.` (as in JSONPath) is optional, and `["key.with.dots"]` selects keys that contain dots or brackets.
+/// An empty query, or just `$`, selects the whole document.
+pub fn parse_query(query: &str) -> Result<Vec<Segment>, String> {
+    let query = query.trim();
+    let mut rest = query.strip_prefix('$').unwrap_or(query);
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(bracketed) = rest.strip_prefix('[') {
+            let end = bracketed.find(']').ok_or_else(|| format!("unclosed '[' in query '{}'", query))?;
+            let inner = bracketed[..end].trim();
+            let quoted = inner.len() >= 2 && (inner.starts_with('"') && inner.ends_with('"') || inner.starts_with('\'') && inner.ends_with('\''));
+            if quoted {
+                segments.push(Segment::Key(inner[1..inner.len() - 1].to_string()));
+            } else {
+                let index = inner.parse().map_err(|_| format!("'[{}]' in query '{}' is not an array index or a quoted key", inner, query))?;
+                segments.push(Segment::Index(index));
+            }
+            rest = &bracketed[end + 1..];
+        } else {
+            let key = rest.strip_prefix('.').unwrap_or(rest);
+            let end = key.find(['.', '[']).unwrap_or(key.len());
+            if end == 0 {
+                return Err(format!("empty key in query '{}'", query));
+            }
+            segments.push(Segment::Key(key[..end].to_string()));
+            rest = &key[end..];
+        }
+    }
+    Ok(segments)
+}
+
+/// Why a query selected nothing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupError {
+    /// The key or index does not exist
+    Missing(String),
+    /// A key or index was applied to a value that has none
+    NotContainer(String),
+}
+
+/// The value `segments` select in `document`
+pub fn lookup<'a>(document: &'a Value, segments: &[Segment]) -> Result<&'a Value, LookupError> {
+    let mut current = document;
+    let mut walked = String::from("$");
+    for segment in segments {
+        current = match (segment, current) {
+            (Segment::Key(key), Value::Object(map)) => map.get(key).ok_or_else(|| {
+                let mut keys: Vec<&str> = map.keys().map(String::as_str).take(20).collect();
+                if map.len() > keys.len() {
+                    keys.push("...");
+                }
+                LookupError::Missing(format!("no key '{}' in {} (keys: {})", key, walked, keys.join(", ")))
+            })?,
+            (Segment::Index(index), Value::Array(items)) => items.get(*index).ok_or_else(|| {
+                LookupError::Missing(format!("index {} is past the end of {}, which has {} items", index, walked, items.len()))
+            })?,
+            (segment, value) => {
+                let wanted = match segment {
+                    Segment::Key(key) => format!("key '{}'", key),
+                    Segment::Index(index) => format!("index {}", index),
+                };
+                return Err(LookupError::NotContainer(format!("{} is {}, so it has no {}", walked, type_name(value), wanted)));
+            }
+        };
+        match segment {
+            Segment::Key(key) if key.contains(['.', '[', ']']) => walked.push_str(&format!("[\"{}\"]", key)),
+            Segment::Key(key) => walked.push_str(&format!(".{}", key)),
+            Segment::Index(index) => walked.push_str(&format!("[{}]", index)),
+        }
+    }
+    Ok(current)
+}
+
+/// JSON type of `value`, as reported to the model
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+struct YamlLine<'a> {
+    number: usize,
+    indent: usize,
+    /// The line after its indentation, comments included
+    raw: &'a str,
+}
+
+struct YamlParser<'a> {
+    lines: Vec<YamlLine<'a>>,
+    pos: usize,
+}
+
+fn parse_yaml(content: &str) -> Result<Value, String> {
+    let mut lines = Vec::new();
+    let mut documents = 0;
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let body = line.trim_start_matches(' ');
+        if body.starts_with('\t') {
+            return Err(format!("line {}: tabs cannot indent YAML", number));
+        }
+        if line.trim_end() == "---" || line.starts_with("--- ") {
+            documents += 1;
+            if documents > 1 || !lines.iter().all(|line: &YamlLine| is_blank(line.raw)) {
+                return Err(format!("line {}: files with several YAML documents are not supported", number));
+            }
+            continue;
+        }
+        if line.trim_end() == "..." {
+            break;
+        }
+        lines.push(YamlLine { number, indent: line.len() - body.len(), raw: body });
+    }
+
+    let mut parser = YamlParser { lines, pos: 0 };
+    parser.skip_blank();
+    if parser.pos == parser.lines.len() {
+        return Ok(Value::Null);
+    }
+    let indent = parser.lines[parser.pos].indent;
+    let value = parser.node(indent)?;
+    parser.skip_blank();
+    match parser.lines.get(parser.pos) {
+        Some(line) => Err(format!("line {}: unexpected indentation", line.number)),
+        None => Ok(value),
+    }
+}
+
+/// A line holding only whitespace or a comment
+fn is_blank(raw: &str) -> bool {
+    let text = raw.trim();
+    text.is_empty() || text.starts_with('#')
+}
+
+/// `text` without a trailing comment; `#` starts a comment at the start or after whitespace, outside quotes
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, c) in text.char_indices() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && (previous == ' ' || previous == '[' || previous == '{' || previous == ',' || index == 0) => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return text[..index].trim_end(),
+            None => {}
+        }
+        previous = c;
+    }
+    text.trim_end()
+}
+
+/// Split a `key: value` line into its key and the text after the colon
+fn split_entry(text: &str) -> Option<(String, &str)> {
+    if text.starts_with('"') || text.starts_with('\'') {
+        let quote = text.chars().next()?;
+        let end = text[1..].find(quote)? + 1;
+        let rest = text[end + 1..].trim_start();
+        let value = rest.strip_prefix(':')?;
+        if !value.is_empty() && !value.starts_with(' ') {
+            return None;
+        }
+        return Some((text[1..end].to_string(), value.trim()));
+    }
+    if is_sequence_item(text) || text.starts_with(['[', '{']) {
+        return None;
+    }
+    let colon = text.char_indices().find(|(index, c)| *c == ':' && text[index + 1..].chars().next().is_none_or(|next| next == ' '))?.0;
+    let key = text[..colon].trim();
+    if key.is_empty() {
+        return None;
+    }
+    Some((key.to_string(), text[colon + 1..].trim()))
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+impl YamlParser<'_> {
+    fn skip_blank(&mut self) {
+        while self.pos < self.lines.len() && is_blank(self.lines[self.pos].raw) {
+            self.pos += 1;
+        }
+    }
+
+    /// Indentation of the next non-blank line, if any
+    fn next_indent(&mut self) -> Option<usize> {
+        self.skip_blank();
+        self.lines.get(self.pos).map(|line| line.indent)
+    }
+
+    /// The block starting at the current line, which is indented by `indent`
+    fn node(&mut self, indent: usize) -> Result<Value, String> {
+        self.skip_blank();
+        let line = &self.lines[self.pos];
+        let text = strip_comment(line.raw);
+        if is_sequence_item(text) {
+            self.sequence(indent)
+        } else if split_entry(text).is_some() {
+            self.mapping(indent)
+        } else {
+            let number = line.number;
+            self.pos += 1;
+            inline_value(text).map_err(|e| format!("line {}: {}", number, e))
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+        while self.next_indent() == Some(indent) && is_sequence_item(strip_comment(self.lines[self.pos].raw)) {
+            let line = &self.lines[self.pos];
+            let item = line.raw[1..].trim_start_matches(' ');
+            let item_indent = indent + (line.raw.len() - item.len());
+            if is_blank(item) {
+                self.pos += 1;
+                items.push(match self.next_indent() {
+                    Some(child) if child > indent => self.node(child)?,
+                    _ => Value::Null,
+                });
+            } else {
+                // The item's content is parsed as if it started its own line, so `- key: value` opens a
+                // mapping that continues on the lines below at the same column
+                let number = line.number;
+                self.lines[self.pos] = YamlLine { number, indent: item_indent, raw: item };
+                items.push(self.value_at(item_indent, indent)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+        while self.next_indent() == Some(indent) {
+            let line = &self.lines[self.pos];
+            let number = line.number;
+            let Some((key, rest)) = split_entry(strip_comment(line.raw)) else {
+                break;
+            };
+            if rest.starts_with(['&', '*', '!']) {
+                return Err(format!("line {}: anchors, aliases and tags are not supported", number));
+            }
+            self.pos += 1;
+            let value = if rest.is_empty() {
+                match self.next_indent() {
+                    Some(child) if child > indent => self.node(child)?,
+                    // A sequence may sit at the same indentation as the key that holds it
+                    Some(child) if child == indent && is_sequence_item(strip_comment(self.lines[self.pos].raw)) => self.sequence(indent)?,
+                    _ => Value::Null,
+                }
+            } else if rest.starts_with(['|', '>']) {
+                self.block_scalar(indent, rest, number)?
+            } else {
+                inline_value(rest).map_err(|e| format!("line {}: {}", number, e))?
+            };
+            map.insert(key, value);
+        }
+        if let Some(line) = self.lines.get(self.pos).filter(|line| line.indent > indent) {
+            return Err(format!("line {}: unexpected indentation", line.number));
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// A sequence item's content at column `item_indent` of a sequence indented by `parent`
+    fn value_at(&mut self, item_indent: usize, parent: usize) -> Result<Value, String> {
+        let line = &self.lines[self.pos];
+        let text = strip_comment(line.raw);
+        if text.starts_with(['|', '>']) {
+            let number = line.number;
+            let header = text.to_string();
+            self.pos += 1;
+            return self.block_scalar(parent, &header, number);
+        }
+        self.node(item_indent)
+    }
+
+    /// The text of a `|` (literal) or `>` (folded) block scalar whose header follows a key at `indent`
+    fn block_scalar(&mut self, indent: usize, header: &str, number: usize) -> Result<Value, String> {
+        let chomping = header[1..].trim();
+        if !matches!(chomping, "" | "-" | "+") {
+            return Err(format!("line {}: block scalar header '{}' is not supported", number, header));
+        }
+        let mut body: Vec<String> = Vec::new();
+        let mut block_indent = None;
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.raw.trim().is_empty() {
+                body.push(String::new());
+                self.pos += 1;
+                continue;
+            }
+            if line.indent <= indent {
+                break;
+            }
+            let block = *block_indent.get_or_insert(line.indent);
+            if line.indent < block {
+                break;
+            }
+            // Indentation beyond the block's own is part of the text
+            body.push(format!("{}{}", " ".repeat(line.indent - block), line.raw));
+            self.pos += 1;
+        }
+        // Blank lines belong to the block only when more of it follows
+        let trailing_blank = body.iter().rev().take_while(|line| line.is_empty()).count();
+        let kept = body.len() - trailing_blank;
+        // Step back over trailing blank lines so the caller sees them
+        self.pos -= trailing_blank;
+
+        let lines = &body[..kept];
+        let mut text = if header.starts_with('|') {
+            lines.join("\n")
+        } else {
+            fold(lines)
+        };
+        match chomping {
+            "-" => {}
+            "+" => {
+                text.push('\n');
+                text.push_str(&"\n".repeat(trailing_blank));
+            }
+            _ if !text.is_empty() => text.push('\n'),
+            _ => {}
+        }
+        Ok(Value::String(text))
+    }
+}
+
+/// Folded block scalar text: single line breaks become spaces, blank lines become line breaks
+fn fold(lines: &[String]) -> String {
+    let mut text = String::new();
+    let mut pending_break = false;
+    for line in lines {
+        if line.is_empty() {
+            text.push('\n');
+            pending_break = false;
+        } else {
+            if pending_break {
+                text.push(' ');
+            }
+            text.push_str(line);
+            pending_break = true;
+        }
+    }
+    text
+}
+
+/// A value written on one line: a flow collection, a quoted string or a plain scalar
+fn inline_value(text: &str) -> Result<Value, String> {
+    if text.starts_with(['&', '*', '!']) {
+        return Err("anchors, aliases and tags are not supported".to_string());
+    }
+    if text.starts_with(['[', '{']) {
+        let mut flow = Flow { text, pos: 0 };
+        let value = flow.value()?;
+        flow.skip_spaces();
+        if flow.pos != text.len() {
+            return Err(format!("unexpected '{}' after a flow collection", &text[flow.pos..]));
+        }
+        return Ok(value);
+    }
+    if text.starts_with(['"', '\'']) {
+        let mut flow = Flow { text, pos: 0 };
+        let value = flow.quoted()?;
+        flow.skip_spaces();
+        if flow.pos != text.len() {
+            return Err(format!("unexpected '{}' after a quoted string", &text[flow.pos..]));
+        }
+        return Ok(value);
+    }
+    Ok(plain_scalar(text))
+}
+
+/// Resolve an unquoted scalar to null, a boolean, a number or a string
+fn plain_scalar(text: &str) -> Value {
+    let text = text.trim();
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+        "true" | "True" | "TRUE" => return Value::Bool(true),
+        "false" | "False" | "FALSE" => return Value::Bool(false),
+        _ => {}
+    }
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+    if let Some(hex) = digits.strip_prefix("0x") {
+        if let Ok(number) = i64::from_str_radix(hex, 16) {
+            return Value::from(if text.starts_with('-') { -number } else { number });
+        }
+    }
+    if let Some(octal) = digits.strip_prefix("0o") {
+        if let Ok(number) = i64::from_str_radix(octal, 8) {
+            return Value::from(if text.starts_with('-') { -number } else { number });
+        }
+    }
+    if digits.chars().next().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+        if let Ok(number) = text.parse::<i64>() {
+            return Value::from(number);
+        }
+        if let Some(number) = text.parse::<f64>().ok().and_then(Number::from_f64) {
+            return Value::Number(number);
+        }
+    }
+    Value::String(text.to_string())
+}
+
+/// Single-line flow collections such as `[a, "b", {c: 1}]`
+struct Flow<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Flow<'_> {
+    fn skip_spaces(&mut self) {
+        while self.text[self.pos..].starts_with(' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_spaces();
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.separator(']')?;
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut map = Map::new();
+                loop {
+                    self.skip_spaces();
+                    if self.peek() == Some('}') {
+                        self.pos += 1;
+                        return Ok(Value::Object(map));
+                    }
+                    let key = match self.value()? {
+                        Value::String(key) => key,
+                        other => other.to_string(),
+                    };
+                    self.skip_spaces();
+                    let value = if self.peek() == Some(':') {
+                        self.pos += 1;
+                        self.value()?
+                    } else {
+                        Value::Null
+                    };
+                    map.insert(key, value);
+                    self.separator('}')?;
+                }
+            }
+            Some('"') | Some('\'') => self.quoted(),
+            Some(_) => {
+                let rest = &self.text[self.pos..];
+                let end = rest
+                    .char_indices()
+                    .find(|(index, c)| matches!(c, ',' | ']' | '}') || *c == ':' && rest[index + 1..].chars().next().is_none_or(|next| next == ' '))
+                    .map(|(index, _)| index)
+                    .unwrap_or(rest.len());
+                self.pos += end;
+                Ok(plain_scalar(&rest[..end]))
+            }
+            None => Err("unclosed flow collection; flow collections must fit on one line".to_string()),
+        }
+    }
+
+    /// Expect a comma or the collection's closing bracket
+    fn separator(&mut self, close: char) -> Result<(), String> {
+        self.skip_spaces();
+        match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) if c == close => Ok(()),
+            Some(c) => Err(format!("expected ',' or '{}' but found '{}'", close, c)),
+            None => Err("unclosed flow collection; flow collections must fit on one line".to_string()),
+        }
+    }
+
+    fn quoted(&mut self) -> Result<Value, String> {
+        let quote = self.peek().unwrap_or('"');
+        let start = self.pos + 1;
+        let mut text = String::new();
+        let mut chars = self.text[start..].char_indices();
+        while let Some((index, c)) = chars.next() {
+            if c == quote {
+                if quote == '\'' && self.text[start + index + 1..].starts_with('\'') {
+                    // '' is an escaped single quote
+                    chars.next();
+                    text.push('\'');
+                    continue;
+                }
+                self.pos = start + index + 1;
+                return Ok(Value::String(text));
+            }
+            if c == '\\' && quote == '"' {
+                let Some((_, escaped)) = chars.next() else {
+                    break;
+                };
+                match escaped {
+                    'n' => text.push('\n'),
+                    't' => text.push('\t'),
+                    'r' => text.push('\r'),
+                    '0' => text.push('\0'),
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let code = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or_else(|| format!("invalid escape '\\u{}'", hex))?;
+                        text.push(code);
+                    }
+                    other => text.push(other),
+                }
+                continue;
+            }
+            text.push(c);
+        }
+        Err("unterminated quoted string; quoted strings must fit on one line".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(parse_query("").unwrap(), vec![]);
+        assert_eq!(parse_query("$").unwrap(), vec![]);
+        assert_eq!(
+            parse_query("$.jobs.build.steps[2].run").unwrap(),
+            vec![
+                Segment::Key("jobs".to_string()),
+                Segment::Key("build".to_string()),
+                Segment::Key("steps".to_string()),
+                Segment::Index(2),
+                Segment::Key("run".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_query("tool[\"black.toml\"].line-length").unwrap(),
+            vec![Segment::Key("tool".to_string()), Segment::Key("black.toml".to_string()), Segment::Key("line-length".to_string())]
+        );
+        assert!(parse_query("a..b").is_err());
+        assert!(parse_query("a[x]").is_err());
+        assert!(parse_query("a[0").is_err());
+    }
+
+    #[test]
+    fn test_lookup_reports_where_it_stopped() {
+        let document = json!({"package": {"name": "loo", "authors": ["a"]}});
+        let query = parse_query("package.authors[0]").unwrap();
+        assert_eq!(lookup(&document, &query), Ok(&json!("a")));
+
+        let missing = lookup(&document, &parse_query("package.version").unwrap()).unwrap_err();
+        assert_eq!(missing, LookupError::Missing("no key 'version' in $.package (keys: authors, name)".to_string()));
+        let scalar = lookup(&document, &parse_query("package.name.first").unwrap()).unwrap_err();
+        assert_eq!(scalar, LookupError::NotContainer("$.package.name is a string, so it has no key 'first'".to_string()));
+    }
+
+    #[test]
+    fn test_parse_yaml_block_style() {
+        let yaml = r#"
+# CI workflow
+name: CI
+on: [push, pull_request]
+env:
+  RUST_LOG: "debug"   # quoted
+  RETRIES: 3
+  RATIO: 0.5
+  EMPTY:
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - name: Test
+        run: |
+          cargo build
+          cargo test --all
+
+      - name: 'It''s done'
+        with: {fetch-depth: 0, submodules: true}
+tags:
+- one
+- two # comment
+url: http://example.com:8080/path
+folded: >
+  one
+  two
+"#;
+        let value = parse(yaml, Format::Yaml).unwrap();
+        assert_eq!(value["name"], "CI");
+        assert_eq!(value["on"], json!(["push", "pull_request"]));
+        assert_eq!(value["env"], json!({"RUST_LOG": "debug", "RETRIES": 3, "RATIO": 0.5, "EMPTY": null}));
+        let steps = &value["jobs"]["build"]["steps"];
+        assert_eq!(steps[0], json!({"uses": "actions/checkout@v4"}));
+        assert_eq!(steps[1]["run"], "cargo build\ncargo test --all\n");
+        assert_eq!(steps[2], json!({"name": "It's done", "with": {"fetch-depth": 0, "submodules": true}}));
+        assert_eq!(value["tags"], json!(["one", "two"]));
+        assert_eq!(value["url"], "http://example.com:8080/path");
+        assert_eq!(value["folded"], "one two\n");
+    }
+
+    #[test]
+    fn test_parse_yaml_rejects_unsupported_features() {
+        assert!(parse("base: &base\n  a: 1\n", Format::Yaml).unwrap_err().contains("anchors"));
+        assert!(parse("a: 1\n---\nb: 2\n", Format::Yaml).unwrap_err().contains("several YAML documents"));
+        assert!(parse("a: 1\n    b: 2\n", Format::Yaml).unwrap_err().contains("line 2"));
+        assert!(parse("a: [1, 2\n", Format::Yaml).unwrap_err().contains("one line"));
+    }
+
+    #[test]
+    fn test_parse_toml_and_json() {
+        let toml = "[package]\nname = \"loo\"\nedition = \"2021\"\n\n[dependencies]\nserde = { version = \"1.0\", features = [\"derive\"] }\n";
+        let value = parse(toml, Format::Toml).unwrap();
+        assert_eq!(value["dependencies"]["serde"]["features"], json!(["derive"]));
+        assert!(parse("[package\nname = 1", Format::Toml).is_err());
+        assert!(parse("{\"a\": }", Format::Json).is_err());
+        assert_eq!(Format::from_path(Path::new("config/app.YML")), Some(Format::Yaml));
+        assert_eq!(Format::from_path(Path::new("Cargo.lock")), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! Background watcher for files changed outside loo
+//! Collects paths modified under the working directory between turns
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Directories whose churn is never worth reporting, and that globs in `file_stats` do not descend into
+pub const IGNORED_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// Most paths listed in a change note before summarizing the rest
+const MAX_LISTED_CHANGES: usize = 10;
+
+pub struct FileWatcher {
+    root: PathBuf,
+    changes: Arc<Mutex<BTreeSet<PathBuf>>>,
+    // Dropping the watcher stops the background thread
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    /// Start watching `root` recursively
+    pub fn start<P: AsRef<Path>>(root: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let root = root.as_ref().canonicalize()?;
+        let changes = Arc::new(Mutex::new(BTreeSet::new()));
+
+        let sink = Arc::clone(&changes);
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            let Ok(event) = result else { return };
+            if matches!(event.kind, EventKind::Access(_) | EventKind::Other) {
+                return;
+            }
+            if let Ok(mut changes) = sink.lock() {
+                changes.extend(event.paths);
+            }
+        })?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+
+        Ok(Self { root, changes, _watcher: watcher })
+    }
+
+    /// Paths changed since the last call, relative to the watched root
+    pub fn take_changes(&self) -> Vec<String> {
+        let changed = match self.changes.lock() {
+            Ok(mut changes) => std::mem::take(&mut *changes),
+            Err(_) => return Vec::new(),
+        };
+
+        changed
+            .into_iter()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(&self.root).ok()?.to_path_buf();
+                let ignored = relative
+                    .components()
+                    .any(|component| IGNORED_DIRS.iter().any(|dir| component.as_os_str() == *dir));
+                if ignored || relative.as_os_str().is_empty() {
+                    None
+                } else {
+                    Some(relative.to_string_lossy().to_string())
+                }
+            })
+            .collect()
+    }
+}
+
+/// Short context note listing externally changed files
+pub fn format_change_note(changes: &[String]) -> Option<String> {
+    if changes.is_empty() {
+        return None;
+    }
+
+    let mut note = format!(
+        "Files changed since last turn: {}",
+        changes.iter().take(MAX_LISTED_CHANGES).cloned().collect::<Vec<_>>().join(", ")
+    );
+    if changes.len() > MAX_LISTED_CHANGES {
+        note.push_str(&format!(" (+{} more)", changes.len() - MAX_LISTED_CHANGES));
+    }
+    Some(note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_format_change_note() {
+        assert_eq!(format_change_note(&[]), None);
+        assert_eq!(
+            format_change_note(&["src/main.rs".to_string(), "README.md".to_string()]).unwrap(),
+            "Files changed since last turn: src/main.rs, README.md"
+        );
+
+        let many: Vec<String> = (0..12).map(|i| format!("f{}.rs", i)).collect();
+        assert!(format_change_note(&many).unwrap().ends_with("(+2 more)"));
+    }
+
+    #[test]
+    fn test_watcher_reports_external_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let watcher = FileWatcher::start(temp_dir.path()).unwrap();
+
+        std::fs::create_dir(temp_dir.path().join(".git")).unwrap();
+        std::fs::write(temp_dir.path().join(".git").join("index"), "x").unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "hello").unwrap();
+
+        // Events arrive on a background thread
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut seen = Vec::new();
+        while Instant::now() < deadline && !seen.contains(&"notes.txt".to_string()) {
+            std::thread::sleep(Duration::from_millis(50));
+            seen.extend(watcher.take_changes());
+        }
+
+        assert!(seen.contains(&"notes.txt".to_string()));
+        assert!(seen.iter().all(|path| !path.starts_with(".git")));
+    }
+}
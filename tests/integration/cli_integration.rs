@@ -1,5 +1,7 @@
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
 use std::fs;
+use std::path::Path;
 use tempfile::TempDir;
 
 #[test]
@@ -29,6 +31,66 @@ fn test_cli_version_output() {
     assert!(stdout.contains("0.1.0"));
 }
 
+#[test]
+fn test_version_subcommand_verbose() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+
+    let output = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "version", "--verbose"])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .env("OPENROUTER_API_KEY", "sk-test-secret")
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("loo 0.1.0"));
+    assert!(stdout.contains("Git commit:"));
+    assert!(stdout.contains("Rustc:"));
+    assert!(stdout.contains("Default model:"));
+    assert!(stdout.contains("API key: detected"));
+    assert!(!stdout.contains("sk-test-secret"));
+}
+
+#[test]
+fn test_explicit_config_file() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config_file = temp_dir.path().join("accounts").join("work.toml");
+    let config_arg = config_file.to_string_lossy().to_string();
+
+    let output = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "--config", &config_arg, "config", "set", "openrouter.model", "test/model"])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let content = fs::read_to_string(&config_file).expect("Explicit config file should be written");
+    assert!(content.contains("test/model"));
+    assert!(!temp_dir.path().join("loo").join("config.toml").exists());
+
+    // A key given in the environment is used but never written to the file
+    let output = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "--config", &config_arg, "config", "set", "preferences.verbose", "1"])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .env("OPENROUTER_API_KEY", "sk-from-env")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(!fs::read_to_string(&config_file).unwrap().contains("sk-from-env"));
+
+    let output = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "config", "get", "--config", &config_arg])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .output()
+        .expect("Failed to execute command");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("test/model"));
+}
+
 #[test]
 fn test_config_init_command() {
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
@@ -119,6 +181,128 @@ fn test_config_set_invalid_key() {
     assert!(!output.status.success());
 }
 
+#[test]
+fn test_max_tokens_must_be_positive() {
+    let output = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "--max-tokens", "0"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--max-tokens"));
+}
+
+#[test]
+fn test_timeout_must_be_positive() {
+    let output = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "--timeout", "0"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--timeout"));
+}
+
+#[test]
+fn test_log_file_receives_diagnostics() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let log_path = temp_dir.path().join("loo.log");
+
+    let output = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "--log-file", log_path.to_str().unwrap(), "config", "get"])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .env_remove("RUST_LOG")
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let log = fs::read_to_string(&log_path).expect("log file should be created");
+    assert!(log.contains("config"));
+    // Diagnostics go to the file, not the user's terminal
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("DEBUG"));
+}
+
+#[test]
+fn test_append_system_reaches_system_message() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut server = mockito::Server::new();
+    let reply = r#"{"choices":[{"message":{"role":"assistant","content":"ok"},"finish_reason":"stop"}]}"#;
+    let appended = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("Always respond in TypeScript. Keep answers short.".to_string()))
+        .with_body(reply)
+        .expect_at_least(1)
+        .create();
+    let _other = server.mock("POST", "/chat/completions").with_body(reply).create();
+
+    let set = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "config", "set", "openrouter.base_url", &server.url()])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .output()
+        .expect("Failed to execute command");
+    assert!(set.status.success());
+
+    let mut child = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "--dir", temp_dir.path().to_str().unwrap(), "--no-story", "--skip-preflight",
+            "--append-system", "Always respond in TypeScript.", "--append-system", "Keep answers short."])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .env("OPENROUTER_API_KEY", "sk-test")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute command");
+    child.stdin.take().unwrap().write_all(b"hello\n").unwrap();
+    child.wait_with_output().expect("Failed to wait for loo");
+
+    appended.assert();
+}
+
+#[test]
+fn test_preflight_rejects_bad_api_key() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut server = mockito::Server::new();
+    let key_check = server
+        .mock("GET", "/key")
+        .with_status(401)
+        .with_body(r#"{"error":{"message":"No auth credentials found","code":401}}"#)
+        .create();
+    let chat = server.mock("POST", "/chat/completions").expect(0).create();
+
+    let set = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "config", "set", "openrouter.base_url", &server.url()])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .output()
+        .expect("Failed to execute command");
+    assert!(set.status.success());
+
+    let output = Command::new("cargo")
+        .args(&["run", "--bin", "loo", "--", "--dir", temp_dir.path().to_str().unwrap(), "--no-story"])
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("APPDATA", temp_dir.path())
+        .env("OPENROUTER_API_KEY", "sk-revoked")
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the API key was rejected"));
+    assert!(stderr.contains("No auth credentials found"));
+    assert!(stderr.contains("--skip-preflight"));
+    // Nothing of the session was shown
+    assert!(!stdout.contains("Starting LOO"));
+    key_check.assert();
+    chat.assert();
+}
+
 #[test]
 fn test_config_validate_without_api_key() {
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
@@ -313,4 +497,98 @@ fn test_working_directory_flag() {
     // Will fail due to network, but should not fail on directory parsing
     let stderr = String::from_utf8_lossy(&output.stderr);
     assert!(!stderr.contains("No such file or directory") && !stderr.contains("cannot find"));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_replay_session_into_fresh_directory() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let session = |size: usize| serde_json::json!({
+        "id": "recorded",
+        "working_dir": "/original/project",
+        "created_at": "2025-01-01T00:00:00Z",
+        "updated_at": "2025-01-01T00:00:00Z",
+        "messages": [
+            {"role": "user", "content": "make a greeting"},
+            {"role": "assistant", "content": "", "tool_calls": [
+                {"id": "1", "type": "function", "function": {"name": "create_file", "arguments": "{\"path\":\"hello.txt\",\"content\":\"hi\"}"}},
+                {"id": "2", "type": "function", "function": {"name": "read_file", "arguments": "{\"path\":\"missing.txt\"}"}}
+            ]},
+            {"role": "tool", "tool_call_id": "1", "content": format!("{{\"status\":\"success\",\"path\":\"hello.txt\",\"size\":{},\"absolute_path\":\"/original/project/hello.txt\"}}", size)},
+            {"role": "tool", "tool_call_id": "2", "content": "{\"status\":\"error\",\"category\":\"not_found\",\"message\":\"/original/project/missing.txt\"}"}
+        ]
+    });
+    let replay = |name: &str, size: usize| {
+        let session_file = temp_dir.path().join(format!("{}.json", name));
+        fs::write(&session_file, session(size).to_string()).unwrap();
+        Command::new("cargo")
+            .args(&["run", "--bin", "loo", "--", "replay", session_file.to_str().unwrap(),
+                "--dir", temp_dir.path().join(name).to_str().unwrap()])
+            .env("XDG_CONFIG_HOME", temp_dir.path())
+            .env("XDG_DATA_HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let matching = replay("matching", 2);
+    let stdout = String::from_utf8_lossy(&matching.stdout);
+    assert!(matching.status.success(), "{}", stdout);
+    assert!(stdout.contains("Replay matched the recording"));
+    assert_eq!(fs::read_to_string(temp_dir.path().join("matching/hello.txt")).unwrap(), "hi");
+
+    let diverging = replay("diverging", 5);
+    assert!(!diverging.status.success());
+    assert!(String::from_utf8_lossy(&diverging.stdout).contains("size differ"));
+    assert!(String::from_utf8_lossy(&diverging.stderr).contains("1 of 2 tool call(s) diverged"));
+
+    // The matching replay left hello.txt behind
+    let again = replay("matching", 2);
+    assert!(!again.status.success());
+    assert!(String::from_utf8_lossy(&again.stderr).contains("the directory is not empty"));
+}
+
+#[test]
+fn test_export_session_formats() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let session_file = temp_dir.path().join("session.json");
+    fs::write(&session_file, serde_json::json!({
+        "id": "recorded",
+        "working_dir": "/original/project",
+        "created_at": "2025-01-01T00:00:00Z",
+        "updated_at": "2025-01-01T00:00:00Z",
+        "messages": [
+            {"role": "system", "content": "You are loo"},
+            {"role": "user", "content": "read it"},
+            {"role": "assistant", "content": "", "tool_calls": [
+                {"id": "1", "type": "function", "function": {"name": "read_file", "arguments": "{\"path\":\"a.txt\"}"}}
+            ]},
+            {"role": "tool", "tool_call_id": "1", "content": "{\"status\":\"success\",\"content\":\"hi\"}"}
+        ]
+    }).to_string()).unwrap();
+    let export = |format: &str, output: &Path| {
+        Command::new("cargo")
+            .args(&["run", "--bin", "loo", "--", "export", session_file.to_str().unwrap(),
+                "--format", format, "--output", output.to_str().unwrap()])
+            .env("XDG_CONFIG_HOME", temp_dir.path())
+            .env("XDG_DATA_HOME", temp_dir.path())
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let anthropic_file = temp_dir.path().join("anthropic.json");
+    let result = export("anthropic", &anthropic_file);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let anthropic: serde_json::Value = serde_json::from_str(&fs::read_to_string(&anthropic_file).unwrap()).unwrap();
+    assert_eq!(anthropic["system"], "You are loo");
+    assert_eq!(anthropic["messages"][1]["content"][0]["type"], "tool_use");
+    assert_eq!(anthropic["messages"][2]["content"][0]["tool_use_id"], "1");
+
+    let openai_file = temp_dir.path().join("openai.json");
+    assert!(export("openai", &openai_file).status.success());
+    let openai: serde_json::Value = serde_json::from_str(&fs::read_to_string(&openai_file).unwrap()).unwrap();
+    assert_eq!(openai["messages"][2]["tool_calls"][0]["function"]["name"], "read_file");
+    assert_eq!(openai["messages"][3]["tool_call_id"], "1");
+
+    let unknown = export("gemini", &temp_dir.path().join("gemini.json"));
+    assert!(!unknown.status.success());
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("expected one of: openai, anthropic"));
+}
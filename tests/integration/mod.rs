@@ -1 +1,2 @@
-pub mod cli_integration;
\ No newline at end of file
+pub mod cli_integration;
+pub mod working_dir;
@@ -0,0 +1,156 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+const REPLY: &str = r#"{"choices":[{"message":{"role":"assistant","content":"ok"},"finish_reason":"stop"}]}"#;
+
+/// Run loo from `cwd` with config and data kept under `home`, sending one chat message
+fn run_chat(home: &Path, cwd: &Path, args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_loo"))
+        .args(args)
+        .arg("--skip-preflight")
+        .current_dir(cwd)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home)
+        .env("XDG_DATA_HOME", home)
+        .env("APPDATA", home)
+        .env("OPENROUTER_API_KEY", "sk-test")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute loo");
+    child.stdin.take().unwrap().write_all(b"hello\n").unwrap();
+    child.wait_with_output().expect("Failed to wait for loo")
+}
+
+fn set_config(home: &Path, key: &str, value: &str) {
+    let output = Command::new(env!("CARGO_BIN_EXE_loo"))
+        .args(["config", "set", key, value])
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home)
+        .env("APPDATA", home)
+        .output()
+        .expect("Failed to execute loo");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+/// A config home pointing at a mock API that answers every chat request
+fn mock_home(server: &mockito::ServerGuard) -> TempDir {
+    let home = TempDir::new().expect("Failed to create temp dir");
+    set_config(home.path(), "openrouter.base_url", &server.url());
+    set_config(home.path(), "preferences.intent_recognition", "false");
+    home
+}
+
+fn canonical(path: &Path) -> String {
+    fs::canonicalize(path).unwrap().to_string_lossy().to_string()
+}
+
+#[test]
+fn test_nonexistent_dir_is_reported() {
+    let home = TempDir::new().expect("Failed to create temp dir");
+    let output = run_chat(home.path(), home.path(), &["--dir", "no/such/project"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Cannot start a session: --dir 'no/such/project' does not exist"), "{}", stderr);
+    assert!(!stderr.contains("panicked"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Starting LOO"));
+}
+
+#[test]
+fn test_dir_that_is_a_file_is_reported() {
+    let home = TempDir::new().expect("Failed to create temp dir");
+    fs::write(home.path().join("notes.txt"), "not a project").unwrap();
+    let output = run_chat(home.path(), home.path(), &["--dir", "notes.txt"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--dir 'notes.txt' is not a directory"), "{}", stderr);
+}
+
+#[test]
+fn test_relative_dir_writes_story_to_canonical_path() {
+    let mut server = mockito::Server::new();
+    let _chat = server.mock("POST", "/chat/completions").with_body(REPLY).create();
+    let home = mock_home(&server);
+    let workspace = TempDir::new().expect("Failed to create temp dir");
+    fs::create_dir_all(workspace.path().join("project/src")).unwrap();
+
+    // `src/..` only names the project once resolved
+    let output = run_chat(home.path(), workspace.path(), &["--dir", "project/src/.."]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let project = canonical(&workspace.path().join("project"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("Working directory: {}", project)));
+    assert!(workspace.path().join("project/story.md").exists());
+    assert!(!workspace.path().join("project/src/story.md").exists());
+    assert!(!workspace.path().join("story.md").exists());
+}
+
+#[test]
+fn test_default_directory_from_config() {
+    let mut server = mockito::Server::new();
+    let _chat = server.mock("POST", "/chat/completions").with_body(REPLY).create();
+    let home = mock_home(&server);
+    let project = TempDir::new().expect("Failed to create temp dir");
+    let elsewhere = TempDir::new().expect("Failed to create temp dir");
+    set_config(home.path(), "preferences.default_directory", project.path().to_str().unwrap());
+
+    let output = run_chat(home.path(), elsewhere.path(), &[]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("Working directory: {}", canonical(project.path()))));
+    assert!(project.path().join("story.md").exists());
+    assert!(!elsewhere.path().join("story.md").exists());
+}
+
+#[test]
+fn test_dotenv_in_working_dir_reaches_config() {
+    let mut server = mockito::Server::new();
+    let chat = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex(r#""model":"dotenv/model""#.to_string()))
+        .with_body(REPLY)
+        .expect_at_least(1)
+        .create();
+    let home = mock_home(&server);
+    let project = TempDir::new().expect("Failed to create temp dir");
+    fs::write(project.path().join(".env"), "OPENROUTER_MODEL=\"dotenv/model\" # for this project\n").unwrap();
+
+    let output = run_chat(home.path(), home.path(), &["--dir", project.path().to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    chat.assert();
+}
+
+#[test]
+fn test_dir_overrides_default_directory() {
+    let mut server = mockito::Server::new();
+    let _chat = server.mock("POST", "/chat/completions").with_body(REPLY).create();
+    let home = mock_home(&server);
+    let configured = TempDir::new().expect("Failed to create temp dir");
+    let requested = TempDir::new().expect("Failed to create temp dir");
+    set_config(home.path(), "preferences.default_directory", configured.path().to_str().unwrap());
+
+    let output = run_chat(home.path(), home.path(), &["--dir", requested.path().to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(requested.path().join("story.md").exists());
+    assert!(!configured.path().join("story.md").exists());
+}
+
+#[test]
+fn test_missing_default_directory_names_the_setting() {
+    let home = TempDir::new().expect("Failed to create temp dir");
+    set_config(home.path(), "preferences.default_directory", "/no/such/loo/project");
+
+    let output = run_chat(home.path(), home.path(), &[]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("preferences.default_directory '/no/such/loo/project' does not exist"), "{}", stderr);
+}
@@ -2,6 +2,7 @@ use loo_cli::config::*;
 use tempfile::TempDir;
 use std::fs;
 use std::env;
+use std::sync::Mutex;
 
 #[test]
 fn test_config_default_values() {
@@ -11,7 +12,7 @@ fn test_config_default_values() {
     assert_eq!(config.openrouter.base_url, "https://openrouter.ai/api/v1");
     assert_eq!(config.openrouter.api_key, None);
     
-    assert_eq!(config.preferences.verbose, false);
+    assert_eq!(config.preferences.verbose, 0);
     assert_eq!(config.preferences.auto_confirm, false);
     assert_eq!(config.preferences.default_directory, None);
     
@@ -58,7 +59,7 @@ command_timeout = 600
     assert_eq!(config.openrouter.model, "anthropic/claude-3.5-sonnet");
     assert_eq!(config.openrouter.base_url, "https://api.example.com");
     
-    assert_eq!(config.preferences.verbose, true);
+    assert_eq!(config.preferences.verbose, 3);
     assert_eq!(config.preferences.auto_confirm, true);
     assert_eq!(config.preferences.default_directory, Some("/home/user/projects".to_string()));
     
@@ -68,6 +69,93 @@ command_timeout = 600
     assert_eq!(config.tools.command_timeout, 600);
 }
 
+#[test]
+fn test_config_verbosity_levels() {
+    let with_verbose = |level: &str| -> Config {
+        let toml_content = format!(
+            "[openrouter]\nmodel = \"m\"\nbase_url = \"https://openrouter.ai/api/v1\"\n\n[preferences]\nverbose = {}\nauto_confirm = false\n\n[tools]\nfilesystem = true\ncommands = true\ngit = true\ncommand_timeout = 300\n",
+            level
+        );
+        toml::from_str(&toml_content).unwrap()
+    };
+
+    assert_eq!(with_verbose("0").preferences.tool_output_level(), ToolOutput::Minimal);
+    assert_eq!(with_verbose("1").preferences.tool_output_level(), ToolOutput::Summary);
+    assert_eq!(with_verbose("2").preferences.tool_output_level(), ToolOutput::Full);
+    assert_eq!(with_verbose("9").preferences.verbose, VERBOSE_RAW);
+    assert_eq!(with_verbose("false").preferences.verbose, 0);
+
+    // -v never hides detail a configured tool_output already shows
+    let mut config = with_verbose("1");
+    config.preferences.tool_output = ToolOutput::Full;
+    assert_eq!(config.preferences.tool_output_level(), ToolOutput::Full);
+}
+
+#[test]
+fn test_config_theme_section() {
+    let toml_content = r#"
+[openrouter]
+model = "anthropic/claude-3.5-sonnet"
+base_url = "https://openrouter.ai/api/v1"
+
+[preferences]
+verbose = false
+auto_confirm = false
+
+[tools]
+filesystem = true
+commands = true
+git = true
+command_timeout = 300
+
+[theme]
+preset = "light"
+warning = "dark_red"
+"#;
+
+    let config: Config = toml::from_str(toml_content).unwrap();
+
+    assert_eq!(config.theme.preset, "light");
+    assert_eq!(config.theme.warning, Some("dark_red".to_string()));
+    assert_eq!(config.theme.prompt, None);
+
+    // Configs written before the theme section existed fall back to the dark preset
+    assert_eq!(Config::default().theme.preset, "dark");
+}
+
+#[test]
+fn test_config_prompt_section() {
+    let toml_content = r#"
+[openrouter]
+model = "anthropic/claude-3.5-sonnet"
+base_url = "https://openrouter.ai/api/v1"
+
+[preferences]
+verbose = false
+auto_confirm = false
+
+[preferences.prompt]
+label = ">"
+help = ""
+
+[tools]
+filesystem = true
+commands = true
+git = true
+command_timeout = 300
+"#;
+
+    let config: Config = toml::from_str(toml_content).unwrap();
+
+    assert_eq!(config.preferences.prompt.label(), ">");
+    assert_eq!(config.preferences.prompt.help("Type your message"), "");
+
+    let defaults = Config::default().preferences.prompt;
+    assert_eq!(defaults.label(), PromptConfig::DEFAULT_LABEL);
+    assert_eq!(defaults.help("Type your message"), "Type your message");
+    assert!(toml::to_string(&Config::default()).is_ok());
+}
+
 #[test]
 fn test_config_manager_save_and_load() -> Result<(), Box<dyn std::error::Error>> {
     let temp_dir = TempDir::new()?;
@@ -82,7 +170,7 @@ fn test_config_manager_save_and_load() -> Result<(), Box<dyn std::error::Error>>
     let mut test_config = Config::default();
     test_config.openrouter.api_key = Some("test-api-key".to_string());
     test_config.openrouter.model = "test-model".to_string();
-    test_config.preferences.verbose = true;
+    test_config.preferences.verbose = 2;
     
     // Save the config
     ConfigManager::save_config(&test_config)?;
@@ -92,7 +180,7 @@ fn test_config_manager_save_and_load() -> Result<(), Box<dyn std::error::Error>>
     
     assert_eq!(loaded_config.openrouter.api_key, Some("test-api-key".to_string()));
     assert_eq!(loaded_config.openrouter.model, "test-model");
-    assert_eq!(loaded_config.preferences.verbose, true);
+    assert_eq!(loaded_config.preferences.verbose, 2);
     
     // Restore original XDG_CONFIG_HOME
     match original_xdg {
@@ -164,7 +252,7 @@ fn test_config_manager_set_value() -> Result<(), Box<dyn std::error::Error>> {
     // Load and verify changes
     let config = ConfigManager::load_config()?;
     assert_eq!(config.openrouter.model, "new-model");
-    assert_eq!(config.preferences.verbose, true);
+    assert_eq!(config.preferences.verbose, 3);
     assert_eq!(config.tools.command_timeout, 600);
     
     // Test invalid key
@@ -184,6 +272,155 @@ fn test_config_manager_set_value() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Held by the tests below while XDG_CONFIG_HOME points at their own directory
+static CONFIG_HOME: Mutex<()> = Mutex::new(());
+
+/// Run `test` against an empty config directory, restoring XDG_CONFIG_HOME afterwards
+fn with_config_home(test: impl FnOnce() -> Result<(), Box<dyn std::error::Error>>) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = CONFIG_HOME.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let temp_dir = TempDir::new()?;
+    let original_xdg = env::var("XDG_CONFIG_HOME").ok();
+    env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    let result = test();
+
+    match original_xdg {
+        Some(val) => env::set_var("XDG_CONFIG_HOME", val),
+        None => env::remove_var("XDG_CONFIG_HOME"),
+    }
+    result
+}
+
+#[test]
+fn test_set_verbose_level() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        assert!(ConfigManager::set_config_value("preferences.verbose", "4").is_err());
+        ConfigManager::set_config_value("preferences.verbose", "1")?;
+        assert_eq!(ConfigManager::load_config()?.preferences.verbose, 1);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_confirm_commands() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("tools.confirm_commands", "false")?;
+        assert!(!ConfigManager::load_config()?.tools.confirm_commands);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_on_complete() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("tools.on_complete", "continue")?;
+        assert_eq!(ConfigManager::load_config()?.tools.on_complete, CompleteAction::Continue);
+        assert!(ConfigManager::set_config_value("tools.on_complete", "stop").is_err());
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_format_on_write() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("tools.format_on_write", "true")?;
+        assert!(ConfigManager::load_config()?.tools.format_on_write);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_template_dir() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("tools.template_dir", ".loo/templates")?;
+        assert_eq!(ConfigManager::load_config()?.tools.template_dir.as_deref(), Some(".loo/templates"));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_choice_count() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("openrouter.n", "3")?;
+        assert_eq!(ConfigManager::load_config()?.openrouter.n, Some(3));
+        assert!(ConfigManager::set_config_value("openrouter.n", "0").is_err());
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_stream() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("openrouter.stream", "true")?;
+        assert!(ConfigManager::load_config()?.openrouter.stream);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_intent_recognition() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("preferences.intent_recognition", "false")?;
+        assert!(!ConfigManager::load_config()?.preferences.intent_recognition);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_session_limits() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("preferences.max_turns", "20")?;
+        ConfigManager::set_config_value("preferences.max_session_tokens", "200000")?;
+        let config = ConfigManager::load_config()?;
+        assert_eq!(config.preferences.max_turns, 20);
+        assert_eq!(config.preferences.max_session_tokens, 200000);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_story_format() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("preferences.story_format", "plain")?;
+        assert_eq!(ConfigManager::load_config()?.preferences.story_format, StoryFormat::Plain);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_stack_request_delay() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("preferences.stack_request_delay_ms", "0")?;
+        ConfigManager::set_config_value("preferences.stack_request_jitter_ms", "250")?;
+        let config = ConfigManager::load_config()?;
+        assert_eq!((config.preferences.stack_request_delay_ms, config.preferences.stack_request_jitter_ms), (0, 250));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_context_files() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("preferences.context_files", "CONVENTIONS.md, docs/architecture.md,")?;
+        assert_eq!(ConfigManager::load_config()?.preferences.context_files, vec!["CONVENTIONS.md", "docs/architecture.md"]);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_set_autocomplete_triggers() -> Result<(), Box<dyn std::error::Error>> {
+    with_config_home(|| {
+        ConfigManager::set_config_value("preferences.file_trigger", "#")?;
+        let config = ConfigManager::load_config()?;
+        assert_eq!((config.preferences.file_trigger, config.preferences.command_trigger), ('#', '/'));
+
+        // Triggers are single symbols and must not collide
+        assert!(ConfigManager::set_config_value("preferences.command_trigger", "#").is_err());
+        assert!(ConfigManager::set_config_value("preferences.command_trigger", "ab").is_err());
+        Ok(())
+    })
+}
+
 #[test]
 fn test_config_validation() -> Result<(), Box<dyn std::error::Error>> {
     let temp_dir = TempDir::new()?;
@@ -275,7 +512,7 @@ verbose = true
     let config = ConfigManager::load_config()?;
     
     assert_eq!(config.openrouter.model, "custom-model");
-    assert_eq!(config.preferences.verbose, true);
+    assert_eq!(config.preferences.verbose, 3);
     // Should use defaults for missing values
     assert_eq!(config.tools.filesystem, true);
     assert_eq!(config.tools.command_timeout, 300);
@@ -288,4 +525,12 @@ verbose = true
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_normalize_base_url() {
+    assert_eq!(normalize_base_url("https://openrouter.ai/api/v1/").unwrap(), "https://openrouter.ai/api/v1");
+    assert_eq!(normalize_base_url("http://localhost:8080").unwrap(), "http://localhost:8080");
+    assert!(normalize_base_url("openrouter.ai/api/v1").is_err());
+    assert!(normalize_base_url("https://").is_err());
+}
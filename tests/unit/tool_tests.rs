@@ -1,4 +1,10 @@
-use loo_cli::tools::ToolExecutor;
+use loo_cli::config::Config;
+use loo_cli::tools::batch::CallCoalescer;
+use loo_cli::tools::registry::{find_tool, tool_definitions, ToolFuture, ToolGroup, ToolHandler, ToolRegistry};
+use loo_cli::tools::deletes::{bulk_delete_denied, delete_targets};
+use loo_cli::tools::{cap_lines, completion_summary, is_interrupted, skipped_after_complete, skipped_after_interrupt, ToolExecutor};
+use loo_cli::tools::error::{classify, ToolError, ToolErrorCategory};
+use loo_cli::tools::newlines::{LineEnding, TrailingNewline};
 use loo_cli::openrouter::{ToolCall, ToolCallFunction};
 use serde_json::{json, Value};
 use tempfile::TempDir;
@@ -266,6 +272,41 @@ async fn test_run_command_with_error() -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
+#[tokio::test]
+async fn test_run_command_output_is_capped() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let executor = ToolExecutor::new(temp_dir.path().to_string_lossy().to_string(), false)
+        .with_max_output_lines(4);
+
+    // Far more than a pipe buffer holds, so the output must be drained while the command runs
+    let tool_call = create_test_tool_call("run_command", json!({
+        "command": "seq 1 100000"
+    }));
+
+    let result = executor.execute_tool_call(&tool_call).await?;
+    let result_json: Value = serde_json::from_str(&result)?;
+
+    assert_eq!(result_json["stdout"], "1\n2\n... 99996 lines omitted ...\n99999\n100000\n");
+    assert_eq!(result_json["exit_code"], 0);
+    let full_output = executor.take_full_output().unwrap();
+    assert_eq!(full_output.lines().count(), 100000);
+    assert!(executor.take_full_output().is_none());
+
+    let short = create_test_tool_call("run_command", json!({ "command": "seq 1 4" }));
+    let result_json: Value = serde_json::from_str(&executor.execute_tool_call(&short).await?)?;
+    assert_eq!(result_json["stdout"], "1\n2\n3\n4\n");
+    assert!(executor.take_full_output().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_cap_lines_keeps_head_and_tail() {
+    assert_eq!(cap_lines("a\nb\nc\nd\ne", 3), "a\nb\n... 2 lines omitted ...\ne");
+    assert_eq!(cap_lines("a\nb\n", 2), "a\nb\n");
+    assert_eq!(cap_lines("a\nb\nc\n", 0), "a\nb\nc\n");
+}
+
 #[tokio::test]
 async fn test_query_context_full() -> Result<(), Box<dyn std::error::Error>> {
     let temp_dir = TempDir::new()?;
@@ -348,6 +389,13 @@ async fn test_complete_tool() -> Result<(), Box<dyn std::error::Error>> {
     
     assert_eq!(result_json["status"], "completed");
     assert!(result_json["message"].as_str().unwrap().contains("complete"));
+    assert!(result_json.get("summary").is_none());
+
+    let tool_call = create_test_tool_call("complete", json!({"summary": " Added a /health endpoint and its test. "}));
+    let result_json: Value = serde_json::from_str(&executor.execute_tool_call(&tool_call).await?)?;
+    assert_eq!(result_json["summary"], "Added a /health endpoint and its test.");
+    assert_eq!(completion_summary(&tool_call).as_deref(), Some("Added a /health endpoint and its test."));
+    assert_eq!(completion_summary(&create_test_tool_call("complete", json!({"summary": "  "}))), None);
     
     Ok(())
 }
@@ -406,4 +454,725 @@ async fn test_verbose_mode() -> Result<(), Box<dyn std::error::Error>> {
     assert_eq!(result_json["status"], "success");
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_not_found_error_envelope() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let executor = ToolExecutor::new(temp_dir.path().to_string_lossy().to_string(), false);
+
+    let tool_call = create_test_tool_call("read_file", json!({
+        "path": "missing/notes.txt"
+    }));
+
+    let error = executor.execute_tool_call(&tool_call).await.unwrap_err();
+    let envelope = classify("read_file", error.as_ref()).to_json();
+
+    let expected_target = temp_dir.path().join("missing/notes.txt").to_string_lossy().to_string();
+    assert_eq!(envelope["status"], "error");
+    assert_eq!(envelope["tool"], "read_file");
+    assert_eq!(envelope["category"], "not_found");
+    assert_eq!(envelope["recoverable"], true);
+    assert_eq!(envelope["target"], expected_target);
+    assert!(envelope["message"].as_str().unwrap().starts_with(&format!("not found reading {}", expected_target)));
+
+    Ok(())
+}
+
+#[test]
+fn test_permission_denied_error_message() {
+    let path = std::path::Path::new("/abs/path/config.toml");
+    let error = ToolError::io("write_file", "writing", path, std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+
+    assert_eq!(error.category, ToolErrorCategory::PermissionDenied);
+    assert_eq!(error.target.as_deref(), Some("/abs/path/config.toml"));
+    assert!(error.to_string().starts_with("permission denied writing /abs/path/config.toml"));
+    assert_eq!(error.to_json()["category"], "permission_denied");
+    assert_eq!(error.to_json()["recoverable"], false);
+}
+
+#[tokio::test]
+async fn test_already_exists_error_is_recoverable() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    fs::write(temp_dir.path().join("build"), "not a directory")?;
+    let executor = ToolExecutor::new(temp_dir.path().to_string_lossy().to_string(), false);
+
+    let tool_call = create_test_tool_call("create_directory", json!({"path": "build"}));
+    let error = executor.execute_tool_call(&tool_call).await.unwrap_err();
+    let envelope = classify("create_directory", error.as_ref()).to_json();
+
+    assert_eq!(envelope["category"], "already_exists");
+    assert_eq!(envelope["recoverable"], true);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_permission_denied_write() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new()?;
+    let locked_dir = temp_dir.path().join("locked");
+    fs::create_dir(&locked_dir)?;
+    fs::set_permissions(&locked_dir, fs::Permissions::from_mode(0o555))?;
+
+    // Privileged users (e.g. root in containers) bypass directory permissions
+    if fs::write(locked_dir.join("probe"), "").is_ok() {
+        return Ok(());
+    }
+
+    let executor = ToolExecutor::new(temp_dir.path().to_string_lossy().to_string(), false);
+    let tool_call = create_test_tool_call("write_file", json!({
+        "path": "locked/out.txt",
+        "content": "data"
+    }));
+
+    let error = executor.execute_tool_call(&tool_call).await.unwrap_err();
+    let envelope = classify("write_file", error.as_ref()).to_json();
+
+    assert_eq!(envelope["category"], "permission_denied");
+    assert!(envelope["message"].as_str().unwrap().contains("permission denied writing"));
+
+    fs::set_permissions(&locked_dir, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_git_guard_allows_unprotected_or_confirmed_branches() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let working_dir = temp_dir.path().to_string_lossy().to_string();
+    let init = std::process::Command::new("git")
+        .args(["init", "-q", "-b", "main"])
+        .current_dir(temp_dir.path())
+        .status();
+    if !matches!(init, Ok(status) if status.success()) {
+        // git is not available in this environment
+        return Ok(());
+    }
+    fs::write(temp_dir.path().join("a.txt"), "a")?;
+
+    let tool_call = create_test_tool_call("run_command", json!({ "command": "git add a.txt" }));
+
+    let executor = ToolExecutor::new(working_dir.clone(), false)
+        .with_git_guard(vec!["release".to_string()], false);
+    let result_json: Value = serde_json::from_str(&executor.execute_tool_call(&tool_call).await?)?;
+    assert_eq!(result_json["status"], "success");
+
+    let executor = ToolExecutor::new(working_dir.clone(), false)
+        .with_git_guard(vec!["main".to_string()], true);
+    let result_json: Value = serde_json::from_str(&executor.execute_tool_call(&tool_call).await?)?;
+    assert_eq!(result_json["status"], "success");
+
+    let context = create_test_tool_call("query_context", json!({ "type": "full" }));
+    let context_json: Value = serde_json::from_str(&executor.execute_tool_call(&context).await?)?;
+    assert_eq!(context_json["context"]["git_branch"], "main");
+    assert_eq!(context_json["context"]["git_branch_protected"], true);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unapproved_commands_need_confirmation() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let working_dir = temp_dir.path().to_string_lossy().to_string();
+    let tool_call = create_test_tool_call("run_command", json!({ "command": "echo approved > out.txt" }));
+
+    let executor = ToolExecutor::new(working_dir.clone(), false).with_command_approval(true).with_interactive(false);
+    let error = executor.execute_tool_call(&tool_call).await.unwrap_err();
+    let error = error.downcast_ref::<ToolError>().unwrap();
+    assert_eq!(error.category, ToolErrorCategory::PermissionDenied);
+    assert!(!temp_dir.path().join("out.txt").exists());
+    assert!(executor.take_new_approvals().is_empty());
+
+    // auto_confirm runs commands without asking, so nothing is recorded as approved
+    let executor = ToolExecutor::new(working_dir, false)
+        .with_git_guard(Vec::new(), true)
+        .with_command_approval(true);
+    let result_json: Value = serde_json::from_str(&executor.execute_tool_call(&tool_call).await?)?;
+    assert_eq!(result_json["status"], "success");
+    assert!(executor.take_new_approvals().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_write_guards_against_external_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let working_dir = temp_dir.path().to_string_lossy().to_string();
+    let path = temp_dir.path().join("notes.md");
+    fs::write(&path, "draft")?;
+
+    let read = create_test_tool_call("read_file", json!({ "path": "notes.md" }));
+    let write = create_test_tool_call("write_file", json!({ "path": "notes.md", "content": "model edit" }));
+
+    // Reading and then writing, twice, needs no confirmation: the second write follows loo's own write
+    let executor = ToolExecutor::new(working_dir.clone(), false);
+    executor.execute_tool_call(&read).await?;
+    executor.execute_tool_call(&write).await?;
+    executor.execute_tool_call(&write).await?;
+
+    fs::write(&path, "edited in another editor")?;
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        let error = executor.execute_tool_call(&write).await.unwrap_err();
+        let error = error.downcast_ref::<ToolError>().unwrap();
+        assert_eq!(error.category, ToolErrorCategory::Conflict);
+        assert!(error.category.recoverable());
+        assert_eq!(fs::read_to_string(&path)?, "edited in another editor");
+    }
+
+    // Reading again shows the model the new content, so the write goes ahead
+    executor.execute_tool_call(&read).await?;
+    executor.execute_tool_call(&write).await?;
+    assert_eq!(fs::read_to_string(&path)?, "model edit");
+
+    // auto_confirm overwrites with only a warning
+    let executor = ToolExecutor::new(working_dir, false).with_git_guard(Vec::new(), true);
+    executor.execute_tool_call(&read).await?;
+    fs::write(&path, "edited again")?;
+    executor.execute_tool_call(&write).await?;
+    assert_eq!(fs::read_to_string(&path)?, "model edit");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_write_size_limit() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let working_dir = temp_dir.path().to_string_lossy().to_string();
+    let executor = ToolExecutor::new(working_dir, false).with_max_write_bytes(4);
+
+    let small = create_test_tool_call("create_file", json!({ "path": "small.txt", "content": "abcd" }));
+    executor.execute_tool_call(&small).await?;
+
+    let large = create_test_tool_call("write_file", json!({ "path": "large.txt", "content": "abcde" }));
+    let error = executor.execute_tool_call(&large).await.unwrap_err();
+    let envelope = classify("write_file", error.as_ref()).to_json();
+    assert_eq!(envelope["category"], "too_large");
+    assert!(!temp_dir.path().join("large.txt").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_writes_refuse_the_redaction_placeholder() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let working_dir = temp_dir.path().to_string_lossy().to_string();
+    let env = temp_dir.path().join(".env");
+    fs::write(&env, "API_KEY=abc123\nmax_tokens = 4096\n")?;
+    let executor = ToolExecutor::new(working_dir.clone(), false);
+
+    let read = create_test_tool_call("read_file", json!({ "path": ".env" }));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&read).await?)?;
+    assert_eq!(result["content"], "API_KEY=[REDACTED]\nmax_tokens = 4096\n");
+
+    let write = create_test_tool_call("write_file", json!({ "path": ".env", "content": "API_KEY=[REDACTED]\nmax_tokens = 8192\n" }));
+    let error = executor.execute_tool_call(&write).await.unwrap_err();
+    assert_eq!(classify("write_file", error.as_ref()).to_json()["category"], "invalid_arguments");
+    assert_eq!(fs::read_to_string(&env)?, "API_KEY=abc123\nmax_tokens = 4096\n");
+
+    // With redaction off the model saw the real file, so the placeholder is ordinary text
+    let executor = ToolExecutor::new(working_dir, false).with_redaction(false);
+    let create = create_test_tool_call("create_file", json!({ "path": "notes.md", "content": "[REDACTED]" }));
+    executor.execute_tool_call(&create).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_project_commands_discovery() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path();
+    fs::write(root.join("package.json"), r#"{"scripts": {"test": "jest --ci"}}"#)?;
+    fs::write(root.join("yarn.lock"), "")?;
+    fs::write(root.join("Makefile"), ".PHONY: build\nCC := gcc\nbuild: deps\n\t@cargo build --release\n\tstrip target/release/app\n")?;
+    fs::write(root.join("justfile"), "set shell := [\"bash\", \"-c\"]\nlint target='.':\n    cargo clippy\n")?;
+    fs::create_dir(root.join(".cargo"))?;
+    fs::write(root.join(".cargo/config.toml"), "[alias]\nxtask = \"run --package xtask --\"\nci = [\"test\", \"--all\"]\n")?;
+
+    let executor = ToolExecutor::new(root.to_string_lossy().to_string(), false);
+    let tool_call = create_test_tool_call("project_commands", json!({}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&tool_call).await?)?;
+
+    let commands: Vec<(String, String)> = result["commands"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|command| (command["name"].as_str().unwrap().to_string(), command["command"].as_str().unwrap().to_string()))
+        .collect();
+    let expected = [
+        ("yarn test", "jest --ci"),
+        ("cargo ci", "cargo test --all"),
+        ("cargo xtask", "cargo run --package xtask --"),
+        ("make build", "cargo build --release && strip target/release/app"),
+        ("just lint", "cargo clippy"),
+    ];
+    assert_eq!(commands, expected.map(|(name, command)| (name.to_string(), command.to_string())));
+
+    Ok(())
+}
+
+
+#[test]
+fn test_identical_reads_in_a_batch_are_coalesced() {
+    let mut reads = CallCoalescer::new();
+    let read = create_test_tool_call("read_file", json!({"path": "lib.rs"}));
+    let listing = create_test_tool_call("list_directory", json!({"path": "."}));
+    // The same arguments with different key spacing are the same call
+    let reformatted = ToolCall {
+        function: ToolCallFunction {
+            name: "read_file".to_string(),
+            arguments: r#"{ "path" : "lib.rs" }"#.to_string(),
+        },
+        ..create_test_tool_call("read_file", json!({}))
+    };
+    let write = create_test_tool_call("write_file", json!({"path": "lib.rs", "content": "new"}));
+
+    assert_eq!(reads.cached(&read), None);
+    reads.record(&read, Some("old"));
+    reads.record(&listing, Some("lib.rs"));
+    assert_eq!(reads.cached(&reformatted).as_deref(), Some("old"));
+    assert_eq!(reads.cached(&listing).as_deref(), Some("lib.rs"));
+
+    // Reads after a write could differ, so they run again
+    reads.record(&write, Some("written"));
+    assert_eq!(reads.cached(&read), None);
+    reads.record(&read, None);
+    assert_eq!(reads.cached(&read), None, "a failed read is not reused");
+    assert_eq!(reads.coalesced, 2);
+}
+
+#[tokio::test]
+async fn test_file_writes_normalize_line_endings() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let working_dir = temp_dir.path().to_string_lossy().to_string();
+    fs::write(temp_dir.path().join("windows.txt"), "one\r\ntwo\r\n")?;
+
+    // auto keeps the ending of the file being replaced
+    let executor = ToolExecutor::new(working_dir.clone(), false);
+    let write = create_test_tool_call("write_file", json!({"path": "windows.txt", "content": "one\ntwo\nthree\n"}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&write).await?)?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("windows.txt"))?, "one\r\ntwo\r\nthree\r\n");
+    assert_eq!(result["size"], 17);
+
+    let executor = ToolExecutor::new(working_dir, false).with_line_endings(LineEnding::Lf, TrailingNewline::Ensure);
+    executor.execute_tool_call(&write).await?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("windows.txt"))?, "one\ntwo\nthree\n");
+    let create = create_test_tool_call("create_file", json!({"path": "new.txt", "content": "a\r\nb"}));
+    executor.execute_tool_call(&create).await?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("new.txt"))?, "a\nb\n");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_format_on_write() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let working_dir = temp_dir.path().to_string_lossy().to_string();
+    let messy = "fn main(){println!(\"hi\");}\n";
+
+    let create = create_test_tool_call("create_file", json!({"path": "main.rs", "content": messy}));
+    let result: Value = serde_json::from_str(&ToolExecutor::new(working_dir.clone(), false).execute_tool_call(&create).await?)?;
+    assert!(result.get("formatting").is_none());
+
+    let executor = ToolExecutor::new(working_dir, false).with_format_on_write(true);
+    let write = create_test_tool_call("write_file", json!({"path": "main.rs", "content": messy}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&write).await?)?;
+    assert_eq!(result["formatting"]["formatter"], "rustfmt");
+    let on_disk = fs::read_to_string(temp_dir.path().join("main.rs"))?;
+    match result["formatting"]["outcome"].as_str() {
+        Some("formatted") => {
+            assert_eq!(on_disk, "fn main() {\n    println!(\"hi\");\n}\n");
+            assert_eq!(result["size"], on_disk.len());
+            assert_eq!(result["formatting"]["lines_removed"], 1);
+        }
+        outcome => {
+            assert_eq!(outcome, Some("not_installed"));
+            assert_eq!(on_disk, messy);
+        }
+    }
+    // The formatted content is what the executor remembers, so rewriting is not an external modification
+    executor.execute_tool_call(&write).await?;
+
+    // Modules the file declares are not reformatted along with it
+    let module = "pub fn helper( ){}\n";
+    fs::write(temp_dir.path().join("helper.rs"), module)?;
+    let lib = create_test_tool_call("create_file", json!({"path": "lib.rs", "content": "mod helper;\nfn f( ){}\n"}));
+    executor.execute_tool_call(&lib).await?;
+    assert_eq!(fs::read_to_string(temp_dir.path().join("helper.rs"))?, module);
+
+    let text = create_test_tool_call("create_file", json!({"path": "notes.txt", "content": "a  b\n"}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&text).await?)?;
+    assert!(result.get("formatting").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_only_read_only_calls_are_coalesced() {
+    let mut duplicates = CallCoalescer::new();
+    let status = create_test_tool_call("check_process", json!({"pid": 4242}));
+    let create = create_test_tool_call("create_file", json!({"path": "a.txt", "content": "x"}));
+
+    // A running process answers differently each time it is checked
+    duplicates.record(&status, Some("running"));
+    assert_eq!(duplicates.cached(&status), None);
+
+    // Writes run every time they appear, and the repeat fails or succeeds on its own
+    duplicates.record(&create, Some("created"));
+    assert_eq!(duplicates.cached(&create), None);
+
+    let stats = create_test_tool_call("file_stats", json!({"paths": ["a.txt"]}));
+    duplicates.record(&stats, Some("1 byte"));
+    assert_eq!(duplicates.cached(&stats).as_deref(), Some("1 byte"));
+    duplicates.record(&stats, Some("1 byte"));
+    duplicates.record(&create, Some("created"));
+    assert_eq!(duplicates.cached(&stats), None);
+    assert_eq!(duplicates.coalesced, 1);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_set_permissions_tool() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new()?;
+    let workspace = temp_dir.path().join("workspace");
+    fs::create_dir(&workspace)?;
+    fs::write(workspace.join("run.sh"), "#!/bin/sh\necho hi\n")?;
+    fs::write(temp_dir.path().join("outside.sh"), "")?;
+    let executor = ToolExecutor::new(workspace.to_string_lossy().to_string(), false);
+
+    let tool_call = create_test_tool_call("set_permissions", json!({"path": "run.sh", "mode": "755"}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&tool_call).await?)?;
+    assert_eq!(result["mode"], "755");
+
+    let listing = create_test_tool_call("list_directory", json!({"path": "."}));
+    let listing: Value = serde_json::from_str(&executor.execute_tool_call(&listing).await?)?;
+    assert_eq!(listing["entries"][0]["mode"], "755");
+
+    for mode in ["u+x", "4755", "2755", "1777"] {
+        let invalid = create_test_tool_call("set_permissions", json!({"path": "run.sh", "mode": mode}));
+        let error = executor.execute_tool_call(&invalid).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::InvalidArguments, "{}", mode);
+    }
+    assert_eq!(fs::metadata(workspace.join("run.sh"))?.permissions().mode() & 0o7777, 0o755);
+
+    let tool_call = create_test_tool_call("set_permissions", json!({"path": "run.sh", "mode": "0644"}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&tool_call).await?)?;
+    assert_eq!(result["mode"], "644");
+
+    let escape = create_test_tool_call("set_permissions", json!({"path": "../outside.sh", "mode": "777"}));
+    let error = executor.execute_tool_call(&escape).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::PermissionDenied);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_scaffold_project_tool() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let overrides = temp_dir.path().join("scaffold");
+    fs::create_dir_all(overrides.join("rust"))?;
+    fs::write(overrides.join("rust").join("README.md"), "# {name}\n\n{description}\n")?;
+    let workspace = temp_dir.path().join("workspace");
+    fs::create_dir_all(workspace.join("cli"))?;
+    fs::write(workspace.join("cli").join("README.md"), "keep me")?;
+    let executor = ToolExecutor::new(workspace.to_string_lossy().to_string(), false).with_scaffold_dir(Some(overrides));
+
+    let tool_call = create_test_tool_call("scaffold_project", json!({
+        "project_type": "rust", "path": "cli", "description": "A tool", "git_init": false
+    }));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&tool_call).await?)?;
+    assert_eq!(result["created"], json!(["cli/.gitignore"]));
+    assert_eq!(result["skipped"], json!(["cli/README.md"]));
+    assert_eq!(result["git_initialized"], false);
+    assert!(fs::read_to_string(workspace.join("cli").join(".gitignore"))?.contains("/target/"));
+    assert_eq!(fs::read_to_string(workspace.join("cli").join("README.md"))?, "keep me");
+
+    let fresh = create_test_tool_call("scaffold_project", json!({"project_type": "rust", "path": "fresh", "git_init": false}));
+    executor.execute_tool_call(&fresh).await?;
+    assert_eq!(fs::read_to_string(workspace.join("fresh").join("README.md"))?, "# fresh\n\n\n");
+
+    let unknown = create_test_tool_call("scaffold_project", json!({"project_type": "cobol"}));
+    let error = executor.execute_tool_call(&unknown).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::InvalidArguments);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_inspect_archive_tool() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let workspace = temp_dir.path().join("workspace");
+    fs::create_dir_all(workspace.join("assets"))?;
+    fs::write(workspace.join("assets").join("readme.txt"), "bundled notes\n")?;
+    fs::write(workspace.join("assets").join("logo.bin"), [0u8, 159, 146, 150])?;
+    let status = std::process::Command::new("tar")
+        .args(["-czf", "assets.tar.gz", "assets"])
+        .current_dir(&workspace)
+        .status()?;
+    assert!(status.success());
+    fs::write(workspace.join("broken.zip"), "not a zip")?;
+    fs::write(temp_dir.path().join("outside.tar"), "")?;
+    let executor = ToolExecutor::new(workspace.to_string_lossy().to_string(), false);
+
+    let listing = create_test_tool_call("inspect_archive", json!({"path": "assets.tar.gz"}));
+    let listing: Value = serde_json::from_str(&executor.execute_tool_call(&listing).await?)?;
+    assert_eq!(listing["entry_count"], 3);
+    assert!(listing["entries"].as_array().unwrap().iter().any(|entry| entry["name"] == "assets/readme.txt" && entry["size"] == 14));
+
+    let read = create_test_tool_call("inspect_archive", json!({"path": "assets.tar.gz", "entry": "assets/readme.txt"}));
+    let read: Value = serde_json::from_str(&executor.execute_tool_call(&read).await?)?;
+    assert_eq!(read["content"], "bundled notes\n");
+
+    for (args, category) in [
+        (json!({"path": "assets.tar.gz", "entry": "assets/logo.bin"}), ToolErrorCategory::InvalidArguments),
+        (json!({"path": "broken.zip"}), ToolErrorCategory::InvalidArguments),
+        (json!({"path": "assets/readme.txt"}), ToolErrorCategory::InvalidArguments),
+        (json!({"path": "../outside.tar"}), ToolErrorCategory::PermissionDenied),
+    ] {
+        let call = create_test_tool_call("inspect_archive", args);
+        let error = executor.execute_tool_call(&call).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, category);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_file_stats_tool() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let workspace = temp_dir.path().join("workspace");
+    fs::create_dir_all(workspace.join("src").join("tools"))?;
+    fs::create_dir_all(workspace.join("target"))?;
+    fs::write(workspace.join("src").join("main.rs"), "fn main() {\n    run();\n}\n")?;
+    fs::write(workspace.join("src").join("tools").join("mod.rs"), "pub fn run() {}")?;
+    fs::write(workspace.join("target").join("build.rs"), "// generated\n")?;
+    fs::write(workspace.join("logo.png"), [137u8, 80, 78, 71, 0, 0, 0, 13])?;
+    let executor = ToolExecutor::new(workspace.to_string_lossy().to_string(), false);
+
+    let call = create_test_tool_call("file_stats", json!({"paths": ["src/**/*.rs", "logo.png", "docs/*.md"]}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&call).await?)?;
+    let files = result["files"].as_array().unwrap();
+    assert_eq!(files.len(), 3);
+    assert_eq!(files[0], json!({"path": "src/main.rs", "bytes": 25, "lines": 3, "tokens": 7, "binary": false}));
+    assert_eq!(files[1]["path"], "src/tools/mod.rs");
+    assert_eq!(files[1]["lines"], 1);
+    assert_eq!(files[2], json!({"path": "logo.png", "bytes": 8, "lines": null, "tokens": null, "binary": true}));
+    assert_eq!(result["total"], json!({"files": 3, "bytes": 48, "lines": 4, "tokens": 11}));
+    assert_eq!(result["unmatched"], json!(["docs/*.md"]));
+    assert_eq!(result["truncated"], false);
+
+    // A directory covers the files below it, except ignored build output when globbing
+    let call = create_test_tool_call("file_stats", json!({"paths": ["**/*.rs"]}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&call).await?)?;
+    assert_eq!(result["total"]["files"], 2);
+    let call = create_test_tool_call("file_stats", json!({"paths": "src"}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&call).await?)?;
+    assert_eq!(result["total"]["files"], 2);
+
+    let outside = create_test_tool_call("file_stats", json!({"paths": ["../*"]}));
+    let error = executor.execute_tool_call(&outside).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::PermissionDenied);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_structured_tool() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    fs::write(temp_dir.path().join("Cargo.toml"), "[package]\nname = \"demo\"\n\n[dependencies]\nserde = { version = \"1.0\" }\n")?;
+    fs::write(temp_dir.path().join("ci.yml"), "jobs:\n  test:\n    steps:\n      - run: cargo test\n")?;
+    fs::write(temp_dir.path().join("package.json"), "{\"scripts\": {\"build\": \"tsc\"}")?;
+    let executor = ToolExecutor::new(temp_dir.path().to_string_lossy().to_string(), false);
+
+    let call = create_test_tool_call("query_structured", json!({"path": "Cargo.toml", "query": "dependencies.serde.version"}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&call).await?)?;
+    assert_eq!(result["value"], "1.0");
+    assert_eq!(result["format"], "toml");
+
+    let call = create_test_tool_call("query_structured", json!({"path": "ci.yml", "query": "$.jobs.test.steps[0]"}));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&call).await?)?;
+    assert_eq!(result["value"], json!({"run": "cargo test"}));
+
+    let missing = create_test_tool_call("query_structured", json!({"path": "Cargo.toml", "query": "package.version"}));
+    let error = executor.execute_tool_call(&missing).await.unwrap_err();
+    let error = error.downcast_ref::<ToolError>().unwrap();
+    assert_eq!(error.category, ToolErrorCategory::NotFound);
+    assert!(error.to_string().contains("keys: name"));
+
+    let broken = create_test_tool_call("query_structured", json!({"path": "package.json", "query": "scripts.build"}));
+    let error = executor.execute_tool_call(&broken).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::InvalidArguments);
+    assert!(error.to_string().contains("could not parse as JSON"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_from_template_tool() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    fs::create_dir_all(temp_dir.path().join(".loo/templates"))?;
+    fs::write(temp_dir.path().join(".loo/templates/handler.rs"), "pub fn {{file_stem}}() -> &'static str {\n    \"{{ route }}\"\n}\n")?;
+    let executor = ToolExecutor::new(temp_dir.path().to_string_lossy().to_string(), false)
+        .with_template_dirs(Some(".loo/templates".to_string()), None);
+
+    let call = create_test_tool_call("create_from_template", json!({
+        "template": "react_component", "path": "src/components/Button.tsx", "variables": {"name": "Button"}
+    }));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&call).await?)?;
+    assert_eq!(result["source"], "builtin");
+    let component = fs::read_to_string(temp_dir.path().join("src/components/Button.tsx"))?;
+    assert!(component.contains("export function Button({ className }: ButtonProps)"));
+    assert!(!component.contains("{{"));
+
+    let call = create_test_tool_call("create_from_template", json!({
+        "template": "handler.rs", "path": "src/health.rs", "variables": {"route": "/health"}
+    }));
+    let result: Value = serde_json::from_str(&executor.execute_tool_call(&call).await?)?;
+    assert_eq!(result["source"], "project");
+    assert_eq!(fs::read_to_string(temp_dir.path().join("src/health.rs"))?, "pub fn health() -> &'static str {\n    \"/health\"\n}\n");
+
+    // Existing files are left alone
+    let error = executor.execute_tool_call(&call).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::AlreadyExists);
+
+    let call = create_test_tool_call("create_from_template", json!({"template": "rust_test", "path": "tests/parser.rs"}));
+    let error = executor.execute_tool_call(&call).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::InvalidArguments);
+    assert!(error.to_string().contains("needs variables: crate_name, module"));
+    assert!(!temp_dir.path().join("tests/parser.rs").exists());
+
+    let call = create_test_tool_call("create_from_template", json!({"template": "vue_page", "path": "Page.vue"}));
+    let error = executor.execute_tool_call(&call).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::NotFound);
+    assert!(error.to_string().contains("handler.rs, python_test, react_component, rust_test"));
+
+    Ok(())
+}
+
+#[test]
+fn test_delete_targets_for_bulk_delete_guard() {
+    let calls = vec![
+        create_test_tool_call("delete_file", json!({"path": "a.txt"})),
+        create_test_tool_call("read_file", json!({"path": "b.txt"})),
+        create_test_tool_call("delete_file", json!({"path": "c/d.txt"})),
+    ];
+    assert_eq!(delete_targets(&calls), vec!["a.txt".to_string(), "c/d.txt".to_string()]);
+
+    let denied = classify("delete_file", &bulk_delete_denied("a.txt", 5)).to_json();
+    assert_eq!(denied["category"], "permission_denied");
+    assert_eq!(denied["recoverable"], false);
+    assert_eq!(denied["target"], "a.txt");
+}
+
+struct EchoTool;
+
+impl ToolHandler for EchoTool {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn description(&self) -> &str {
+        "Return the given text"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({"type": "object", "properties": {"text": {"type": "string"}}})
+    }
+
+    fn group(&self) -> ToolGroup {
+        ToolGroup::Always
+    }
+
+    fn execute<'a>(&'a self, _executor: &'a ToolExecutor, args: &'a Value) -> ToolFuture<'a> {
+        Box::pin(async move { Ok(json!({"status": "success", "text": args["text"]}).to_string()) })
+    }
+}
+
+#[tokio::test]
+async fn test_registered_tools_are_advertised_and_executable() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let executor = ToolExecutor::new(temp_dir.path().to_string_lossy().to_string(), false);
+
+    let mut config = Config::default();
+    let all: Vec<String> = tool_definitions(&config.tools).into_iter().map(|tool| tool.function.name).collect();
+    assert!(all.contains(&"run_command".to_string()));
+    config.tools.commands = false;
+    let without_commands: Vec<String> = tool_definitions(&config.tools).into_iter().map(|tool| tool.function.name).collect();
+    assert!(!without_commands.contains(&"run_command".to_string()));
+
+    // A local registry keeps the test tool out of the one every other test dispatches through
+    let mut registry = ToolRegistry::with_builtins();
+    registry.register(std::sync::Arc::new(EchoTool));
+    assert!(registry.definitions(&config.tools).iter().any(|tool| tool.function.name == "echo"));
+    assert!(registry.get("run_command").is_some());
+    assert!(find_tool("echo").is_none());
+
+    let echo = registry.get("echo").unwrap();
+    let result: Value = serde_json::from_str(&echo.execute(&executor, &json!({"text": "hi"})).await?)?;
+    assert_eq!(result["text"], "hi");
+
+    Ok(())
+}
+
+#[test]
+fn test_skipped_tool_calls_read_as_interrupted() {
+    let skipped = skipped_after_interrupt("write_file");
+    let value: Value = serde_json::from_str(&skipped).unwrap();
+
+    assert!(is_interrupted(&skipped));
+    assert_eq!(value["status"], "interrupted");
+    assert_eq!(value["tool"], "write_file");
+    assert!(!is_interrupted(r#"{"status":"success","interrupted":false}"#));
+    assert!(!is_interrupted("plain text"));
+}
+
+#[test]
+fn test_calls_skipped_after_complete_are_not_interrupts() {
+    let skipped = skipped_after_complete("run_command");
+    let value: Value = serde_json::from_str(&skipped).unwrap();
+
+    assert!(!is_interrupted(&skipped));
+    assert_eq!(value["status"], "skipped");
+    assert_eq!(value["tool"], "run_command");
+}
+
+#[tokio::test]
+async fn test_background_process_tools() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let executor = ToolExecutor::new(temp_dir.path().to_string_lossy().to_string(), false);
+
+    let start = create_test_tool_call("run_command_background", json!({"command": "echo ready; sleep 30"}));
+    let started: Value = serde_json::from_str(&executor.execute_tool_call(&start).await?)?;
+    assert_eq!(started["running"], true);
+    assert_eq!(started["stdout"], "ready\n");
+    let pid = started["pid"].as_u64().unwrap();
+
+    let list = create_test_tool_call("check_process", json!({}));
+    let listed: Value = serde_json::from_str(&executor.execute_tool_call(&list).await?)?;
+    assert_eq!(listed["processes"], json!([{"pid": pid, "command": "echo ready; sleep 30"}]));
+
+    let stop = create_test_tool_call("stop_process", json!({"pid": pid}));
+    let stopped: Value = serde_json::from_str(&executor.execute_tool_call(&stop).await?)?;
+    assert_eq!(stopped["running"], false);
+    assert_eq!(stopped["stopped"], true);
+    assert!(stopped["elapsed_secs"].as_u64().unwrap() < 10);
+
+    let check = create_test_tool_call("check_process", json!({"pid": pid}));
+    let error = executor.execute_tool_call(&check).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolError>().unwrap().category, ToolErrorCategory::NotFound);
+
+    // An exited command is reported with its exit code and last output
+    let failing = create_test_tool_call("run_command_background", json!({"command": "sleep 1; echo done; exit 3"}));
+    let pid = serde_json::from_str::<Value>(&executor.execute_tool_call(&failing).await?)?["pid"].as_u64().unwrap();
+    let wait = create_test_tool_call("check_process", json!({"pid": pid, "wait_secs": 10}));
+    let exited: Value = serde_json::from_str(&executor.execute_tool_call(&wait).await?)?;
+    assert_eq!(exited["running"], false);
+    assert_eq!(exited["exit_code"], 3);
+    assert_eq!(exited["status"], "warning");
+    assert_eq!(exited["stdout"], "done\n");
+
+    Ok(())
+}